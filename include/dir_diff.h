@@ -0,0 +1,128 @@
+#ifndef DIR_DIFF_H
+#define DIR_DIFF_H
+
+/* Generated by cbindgen from src/capi.rs; do not edit. */
+
+#include <stdbool.h>
+#include <stddef.h>
+
+// [`DifferenceKind::OnlyInLeft`].
+#define DIR_DIFF_KIND_ONLY_IN_LEFT 0
+
+// [`DifferenceKind::OnlyInRight`].
+#define DIR_DIFF_KIND_ONLY_IN_RIGHT 1
+
+// [`DifferenceKind::FileType`].
+#define DIR_DIFF_KIND_FILE_TYPE 2
+
+// [`DifferenceKind::Content`].
+#define DIR_DIFF_KIND_CONTENT 3
+
+// [`DifferenceKind::SymlinkEscape`].
+#define DIR_DIFF_KIND_SYMLINK_ESCAPE 4
+
+// [`DifferenceKind::Volatile`].
+#define DIR_DIFF_KIND_VOLATILE 5
+
+// [`DifferenceKind::RootName`].
+#define DIR_DIFF_KIND_ROOT_NAME 6
+
+// [`DifferenceKind::Timeout`].
+#define DIR_DIFF_KIND_TIMEOUT 7
+
+// [`DifferenceKind::Permissions`].
+#define DIR_DIFF_KIND_PERMISSIONS 8
+
+// [`DifferenceKind::Modified`].
+#define DIR_DIFF_KIND_MODIFIED 9
+
+// [`DifferenceKind::Acl`].
+#define DIR_DIFF_KIND_ACL 10
+
+// [`DifferenceKind::Encoding`].
+#define DIR_DIFF_KIND_ENCODING 11
+
+// [`DifferenceKind::BrokenSymlink`], on either side.
+#define DIR_DIFF_KIND_BROKEN_SYMLINK 12
+
+// A [`DiffReport`], with its paths ready to be handed out as C strings.
+typedef struct DirDiffReport DirDiffReport;
+
+// Are the contents of two directories different?
+//
+// Returns 1 if they are, 0 if they aren't, and -1 if they couldn't be compared.
+//
+// # Safety
+//
+// `a_base` and `b_base` must be NUL-terminated strings.
+int dir_diff_is_different(const char *a_base, const char *b_base);
+
+// Find every difference between the contents of two directories.
+//
+// Returns `NULL` if they couldn't be compared.  Free the report with [`dir_diff_report_free`].
+//
+// # Safety
+//
+// `a_base` and `b_base` must be NUL-terminated strings.
+DirDiffReport *dir_diff_report_new(const char *a_base, const char *b_base);
+
+// Free a report from [`dir_diff_report_new`].  Does nothing given `NULL`.
+//
+// # Safety
+//
+// `report` must be `NULL`, or a report from [`dir_diff_report_new`] that isn't used after.
+void dir_diff_report_free(DirDiffReport *report);
+
+// Whether the report holds any differences.
+//
+// # Safety
+//
+// `report` must be a report from [`dir_diff_report_new`].
+bool dir_diff_report_is_different(const DirDiffReport *report);
+
+// The number of differences in the report.
+//
+// # Safety
+//
+// `report` must be a report from [`dir_diff_report_new`].
+size_t dir_diff_report_len(const DirDiffReport *report);
+
+// The relative path of the difference at `index`, or `NULL` past the end.
+//
+// The string belongs to the report, and lives as long as it.
+//
+// # Safety
+//
+// `report` must be a report from [`dir_diff_report_new`].
+const char *dir_diff_report_path(const DirDiffReport *report, size_t index);
+
+// The kind of the difference at `index`, as one of the `DIR_DIFF_KIND_*` constants, or -1
+// past the end.
+//
+// # Safety
+//
+// `report` must be a report from [`dir_diff_report_new`].
+int dir_diff_report_kind(const DirDiffReport *report, size_t index);
+
+// The report rendered as a tree, as by [`DiffReport::render`], without colors.
+//
+// Free the string with [`dir_diff_string_free`].
+//
+// # Safety
+//
+// `report` must be a report from [`dir_diff_report_new`].
+char *dir_diff_report_render(const DirDiffReport *report);
+
+// Free a string from [`dir_diff_report_render`].  Does nothing given `NULL`.
+//
+// # Safety
+//
+// `string` must be `NULL`, or a string from [`dir_diff_report_render`] that isn't used after.
+void dir_diff_string_free(char *string);
+
+// Why the last function that failed on this thread did, or `NULL` if none has.
+//
+// The string lives until another function fails on this thread.
+const char *dir_diff_last_error(void);
+
+#endif  /* DIR_DIFF_H */
@@ -0,0 +1,135 @@
+//! Reading the access control lists of local files, for
+//! [`DirDiffOptions::compare_acls`][crate::DirDiffOptions::compare_acls].
+//!
+//! POSIX ACLs are read on Linux, and DACLs on Windows.  Elsewhere, every entry reads as having
+//! the same, empty, ACL.
+
+use std::path::Path;
+
+/// The ACLs of the entry at `path`, in a form only meant to be compared with another entry's.
+///
+/// Entries without ACLs beyond their permission bits, or on file systems without ACLs, have an
+/// empty one.  Symlinks aren't followed.
+#[cfg(target_os = "linux")]
+pub(crate) fn read(path: &Path) -> std::io::Result<Vec<u8>> {
+    let mut acls = Vec::new();
+    // The default ACL is only found on directories, for what is created inside them.
+    for name in ["system.posix_acl_access", "system.posix_acl_default"] {
+        let value = read_xattr(path, name)?;
+        acls.extend((value.len() as u64).to_le_bytes());
+        acls.extend(value);
+    }
+    Ok(acls)
+}
+
+/// The value of the extended attribute `name`, or nothing if the entry doesn't have it.
+#[cfg(target_os = "linux")]
+fn read_xattr(path: &Path, name: &str) -> std::io::Result<Vec<u8>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt as _;
+
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let name = CString::new(name)?;
+    loop {
+        // SAFETY: both strings are NUL-terminated, and a null buffer of size 0 only asks for the
+        // value's size.
+        let len = unsafe { libc::lgetxattr(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0) };
+        if len < 0 {
+            let err = std::io::Error::last_os_error();
+            return match err.raw_os_error() {
+                Some(libc::ENODATA | libc::EOPNOTSUPP) => Ok(Vec::new()),
+                _ => Err(err),
+            };
+        }
+        let mut value = vec![0_u8; len as usize];
+        // SAFETY: `value` has room for the `value.len()` bytes the kernel writes at most.
+        let read = unsafe {
+            libc::lgetxattr(
+                path.as_ptr(),
+                name.as_ptr(),
+                value.as_mut_ptr().cast(),
+                value.len(),
+            )
+        };
+        if read >= 0 {
+            value.truncate(read as usize);
+            return Ok(value);
+        }
+        let err = std::io::Error::last_os_error();
+        match err.raw_os_error() {
+            // The ACL grew since its size was read, so read it again.
+            Some(libc::ERANGE) => {}
+            Some(libc::ENODATA | libc::EOPNOTSUPP) => return Ok(Vec::new()),
+            _ => return Err(err),
+        }
+    }
+}
+
+/// The DACL of the entry at `path`, in the security descriptor definition language.
+///
+/// Inherited entries are included, as they also decide who has access.
+#[cfg(windows)]
+pub(crate) fn read(path: &Path) -> std::io::Result<Vec<u8>> {
+    use std::os::windows::ffi::OsStrExt as _;
+
+    use windows_sys::Win32::Foundation::{LocalFree, ERROR_SUCCESS};
+    use windows_sys::Win32::Security::Authorization::{
+        ConvertSecurityDescriptorToStringSecurityDescriptorW, GetNamedSecurityInfoW,
+        SDDL_REVISION_1, SE_FILE_OBJECT,
+    };
+    use windows_sys::Win32::Security::{DACL_SECURITY_INFORMATION, PSECURITY_DESCRIPTOR};
+
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain([0]).collect();
+    let mut descriptor: PSECURITY_DESCRIPTOR = std::ptr::null_mut();
+    // SAFETY: `wide` is NUL-terminated, and only the descriptor, freed below, is asked for.
+    let result = unsafe {
+        GetNamedSecurityInfoW(
+            wide.as_ptr(),
+            SE_FILE_OBJECT,
+            DACL_SECURITY_INFORMATION,
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            std::ptr::null_mut(),
+            &mut descriptor,
+        )
+    };
+    if result != ERROR_SUCCESS {
+        return Err(std::io::Error::from_raw_os_error(result as i32));
+    }
+
+    let mut sddl = std::ptr::null_mut();
+    let mut len = 0;
+    // SAFETY: `descriptor` is the valid descriptor just read, and the string, freed below, is
+    // `len` characters long.
+    let converted = unsafe {
+        ConvertSecurityDescriptorToStringSecurityDescriptorW(
+            descriptor,
+            SDDL_REVISION_1,
+            DACL_SECURITY_INFORMATION,
+            &mut sddl,
+            &mut len,
+        )
+    };
+    let acl = if converted == 0 {
+        Err(std::io::Error::last_os_error())
+    } else {
+        // SAFETY: the string is `len` characters long, and freed only after being copied.
+        let chars = unsafe { std::slice::from_raw_parts(sddl, len as usize) };
+        let acl = String::from_utf16_lossy(chars)
+            .trim_end_matches('\0')
+            .as_bytes()
+            .to_vec();
+        // SAFETY: freeing the string allocated by the conversion.
+        unsafe { LocalFree(sddl.cast()) };
+        Ok(acl)
+    };
+    // SAFETY: freeing the descriptor allocated by `GetNamedSecurityInfoW`.
+    unsafe { LocalFree(descriptor) };
+    acl
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+pub(crate) fn read(_path: &Path) -> std::io::Result<Vec<u8>> {
+    Ok(Vec::new())
+}
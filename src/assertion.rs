@@ -0,0 +1,195 @@
+//! Asserting that directories are the same, for use in tests.
+
+use std::fmt::Write as _;
+use std::path::Path;
+
+use crate::compare::check_overlap;
+use crate::render::ColorChoice;
+use crate::report::{DiffReport, DifferenceKind, Side};
+use crate::snapshot::{copy_tree, Snapshot};
+use crate::source::FsSource;
+use crate::textdiff;
+use crate::Error;
+
+/// The environment variable that makes [`assert_dir_snapshot!`] update the expected directory.
+const UPDATE_VAR: &str = "DIR_DIFF_UPDATE";
+
+/// Unchanged lines shown around each change in a failure report.
+const CONTEXT_LINES: usize = 3;
+
+/// The largest file, in bytes, whose changes are shown in a failure report.
+const MAX_TEXT_DIFF_LEN: u64 = 64 * 1024;
+
+/// Assert that two directories have the same contents.
+///
+/// On failure, panics with the differences rendered as a tree, as by [`DiffReport::render`],
+/// followed by a line diff of each changed text file.  Like [`assert_eq!`], a message can be
+/// added after the directories, with the same arguments as [`format!`].  Use
+/// [`assert_dir_snapshot!`] to update the expected directory instead of failing.
+///
+/// # Examples
+///
+/// ```no_run
+/// #[test]
+/// fn generates_site() {
+///     generate("target/site");
+///     dir_diff::assert_dir_eq!("target/site", "tests/golden/site");
+///     dir_diff::assert_dir_eq!("target/site", "tests/golden/site", "for the {} theme", "dark");
+/// }
+/// # fn generate(_: &str) {}
+/// ```
+#[macro_export]
+macro_rules! assert_dir_eq {
+    ($actual:expr, $expected:expr $(,)?) => {
+        $crate::__assert_dir_eq(&$actual, &$expected, false, ::std::option::Option::None)
+    };
+    ($actual:expr, $expected:expr, $($arg:tt)+) => {
+        $crate::__assert_dir_eq(
+            &$actual,
+            &$expected,
+            false,
+            ::std::option::Option::Some(::std::format_args!($($arg)+)),
+        )
+    };
+}
+
+/// Assert that a directory has the same contents as a snapshot of it, or update the snapshot
+/// when the `DIR_DIFF_UPDATE` environment variable is set.
+///
+/// Fails like [`assert_dir_eq!`] does.  With `DIR_DIFF_UPDATE` set to anything but `0` or an
+/// empty string, the expected directory is replaced with a copy of the actual one instead, so
+/// running `DIR_DIFF_UPDATE=1 cargo test` accepts every change.  The directories must not be
+/// inside each other.
+///
+/// # Examples
+///
+/// ```no_run
+/// #[test]
+/// fn generates_site() {
+///     generate("target/site");
+///     dir_diff::assert_dir_snapshot!("target/site", "tests/snapshots/site");
+/// }
+/// # fn generate(_: &str) {}
+/// ```
+#[macro_export]
+macro_rules! assert_dir_snapshot {
+    ($actual:expr, $expected:expr $(,)?) => {
+        $crate::__assert_dir_eq(&$actual, &$expected, true, ::std::option::Option::None)
+    };
+    ($actual:expr, $expected:expr, $($arg:tt)+) => {
+        $crate::__assert_dir_eq(
+            &$actual,
+            &$expected,
+            true,
+            ::std::option::Option::Some(::std::format_args!($($arg)+)),
+        )
+    };
+}
+
+/// What [`assert_dir_eq!`] and [`assert_dir_snapshot!`] expand to.
+#[doc(hidden)]
+#[track_caller]
+pub fn __assert_dir_eq<A: AsRef<Path> + ?Sized, E: AsRef<Path> + ?Sized>(
+    actual: &A,
+    expected: &E,
+    snapshot: bool,
+    message: Option<std::fmt::Arguments<'_>>,
+) {
+    let (actual, expected) = (actual.as_ref(), expected.as_ref());
+    if snapshot && update_requested() {
+        if let Err(err) = update(actual, expected) {
+            panic!(
+                "failed to update `{}` from `{}`: {err}",
+                expected.display(),
+                actual.display()
+            );
+        }
+        return;
+    }
+
+    let report = match crate::diff(actual, expected) {
+        Ok(report) => report,
+        Err(err) => panic!(
+            "failed to compare `{}` with `{}`: {err}",
+            actual.display(),
+            expected.display()
+        ),
+    };
+    if !report.is_different() {
+        return;
+    }
+    let mut out = String::from("assertion failed: directories differ");
+    if let Some(message) = message {
+        let _ = write!(out, ": {message}");
+    }
+    out.push('\n');
+    out.push_str(&failure_report(&report));
+    if snapshot {
+        let _ = write!(
+            out,
+            "\nset {UPDATE_VAR}=1 to update `{}`\n",
+            expected.display()
+        );
+    }
+    panic!("{out}");
+}
+
+fn update_requested() -> bool {
+    matches!(std::env::var(UPDATE_VAR).as_deref(), Ok(value) if !value.is_empty() && value != "0")
+}
+
+/// Replace `expected` with a copy of `actual`.
+fn update(actual: &Path, expected: &Path) -> Result<(), Error> {
+    check_overlap(&FsSource::new(actual), &FsSource::new(expected))?;
+    if let Err(err) = std::fs::remove_dir_all(expected) {
+        if err.kind() != std::io::ErrorKind::NotFound {
+            return Err(writing(err, expected));
+        }
+    }
+    std::fs::create_dir_all(expected).map_err(|err| writing(err, expected))?;
+    copy_tree(actual, expected, Snapshot::Copy, Side::Left)
+}
+
+/// The differences as a tree, followed by a line diff of each changed text file small enough.
+fn failure_report(report: &DiffReport) -> String {
+    let mut out = report.render(ColorChoice::Never);
+    for difference in report.iter() {
+        if difference.kind() != DifferenceKind::Content {
+            continue;
+        }
+        let left = report.resolve(difference.path(), Side::Left);
+        let right = report.resolve(difference.path(), Side::Right);
+        let (Some(old), Some(new)) = (read_text(&left), read_text(&right)) else {
+            continue;
+        };
+        let hunks = textdiff::unified(&old, &new, CONTEXT_LINES);
+        if !hunks.is_empty() {
+            let _ = write!(
+                out,
+                "\n--- {}\n+++ {}\n{hunks}",
+                left.display(),
+                right.display()
+            );
+        }
+    }
+    out
+}
+
+/// The contents of the file at `path` as text, if it is small enough to diff.
+fn read_text(path: &Path) -> Option<String> {
+    let metadata = std::fs::metadata(path).ok()?;
+    if MAX_TEXT_DIFF_LEN < metadata.len() {
+        return None;
+    }
+    let contents = std::fs::read_to_string(path).ok()?;
+    (!contents.contains('\0')).then_some(contents)
+}
+
+/// An error writing the updated snapshot at `path`.
+fn writing(err: std::io::Error, path: &Path) -> Error {
+    Error::Io {
+        source: err,
+        phase: None,
+        path: Some(path.to_owned()),
+    }
+}
@@ -0,0 +1,87 @@
+//! Comparing many pairs of directories at once.
+
+use std::path::PathBuf;
+
+use crate::cache::HashCache;
+use crate::options::{DirDiffOptions, Settings};
+use crate::report::DiffReport;
+use crate::snapshot::local_trees;
+use crate::Error;
+
+/// Produce a full [`DiffReport`] for each pair of directories.
+///
+/// The pairs are spread across a pool of threads sized to the available parallelism.  With
+/// [`DirDiffOptions::cache_dir`], file contents are hashed at most once for the whole batch, so
+/// a fixture that takes part in several pairs is only read once; otherwise each pair compares
+/// its files as [`DirDiffOptions::diff`] would, stopping at the first chunk that differs.
+///
+/// Results are returned in the same order as `pairs`.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let reports = dir_diff::compare_many(vec![
+///     ("out/a".into(), "golden/a".into()),
+///     ("out/b".into(), "golden/b".into()),
+/// ]);
+/// for report in reports {
+///     assert!(!report.unwrap().is_different());
+/// }
+/// ```
+pub fn compare_many<I>(pairs: I) -> Vec<Result<DiffReport, Error>>
+where
+    I: IntoIterator<Item = (PathBuf, PathBuf)>,
+{
+    DirDiffOptions::new()
+        .compare_many(pairs)
+        .expect("the default options are valid")
+}
+
+impl DirDiffOptions {
+    /// Produce a full [`DiffReport`] for each pair of directories.
+    ///
+    /// See [`crate::compare_many`].  Fails without comparing anything if the options are invalid.
+    pub fn compare_many<I>(&self, pairs: I) -> Result<Vec<Result<DiffReport, Error>>, Error>
+    where
+        I: IntoIterator<Item = (PathBuf, PathBuf)>,
+    {
+        let pairs: Vec<_> = pairs.into_iter().collect();
+        self.run(|settings, cache| Ok(compare_pairs(&pairs, &settings, cache)))
+    }
+}
+
+fn compare_pairs(
+    pairs: &[(PathBuf, PathBuf)],
+    settings: &Settings,
+    cache: Option<&HashCache>,
+) -> Vec<Result<DiffReport, Error>> {
+    crate::pool::map(pairs, |(a, b)| {
+        let settings = settings.clone().for_pair();
+        local_trees(a, b, &settings)
+            .and_then(|(left, right)| crate::diff_with(left, right, settings, cache))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::Check;
+
+    #[test]
+    fn test_explain_pairs_apart() {
+        let settings = DirDiffOptions::new().explain(true).settings().unwrap();
+        let first = settings.clone().for_pair();
+        let second = settings.for_pair();
+        first
+            .hooks()
+            .explain(std::path::Path::new("a.txt"), Check::Name, true);
+        let explained = |settings: &Settings| {
+            let explanation = settings.hooks().take_explanation().unwrap();
+            explanation.entries().len()
+        };
+        assert_eq!(explained(&second), 0);
+        assert_eq!(explained(&first), 1);
+    }
+}
@@ -0,0 +1,223 @@
+//! Compare two directories from the command line.
+//!
+//! Exit codes follow `diff -r`, see [`Status`].
+
+use std::io::Write as _;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Parser;
+use dir_diff::{DiffReport, Difference, DirDiffOptions};
+
+/// Compare the contents of two directories
+///
+/// Exits with 0 if the directories are the same, 1 if they differ and 2 if the comparison could
+/// not be completed.
+#[derive(Parser, Debug)]
+#[command(about, long_about, version)]
+struct Args {
+    /// The left-hand directory
+    left: PathBuf,
+
+    /// The right-hand directory
+    right: PathBuf,
+
+    /// Skip paths matching this glob, relative to the roots (may be repeated)
+    #[arg(long, value_name = "GLOB")]
+    exclude: Vec<String>,
+
+    /// Allow paths matching this glob to differ, relative to the roots (may be repeated)
+    #[arg(long = "allow", value_name = "GLOB")]
+    allow_different: Vec<String>,
+
+    /// Measure how similar differing text files are, by the share of lines they have in common
+    #[arg(long)]
+    similarity: bool,
+
+    /// Also report the directories themselves having different names
+    #[arg(long)]
+    root_names: bool,
+
+    /// Print the full report as JSON
+    #[arg(long, conflicts_with = "brief")]
+    json: bool,
+
+    /// Write the full report in a binary format, `cbor` or `msgpack`
+    #[cfg(any(feature = "cbor", feature = "msgpack"))]
+    #[arg(
+        long,
+        value_name = "FORMAT",
+        value_parser = compact_format,
+        conflicts_with_all = ["json", "ndjson", "diff_rq", "brief", "first_only", "baseline"]
+    )]
+    compact: Option<dir_diff::CompactFormat>,
+
+    /// Print each difference as a line of JSON as soon as it is found
+    #[arg(long, conflicts_with_all = ["json", "diff_rq", "brief", "first_only", "baseline"])]
+    ndjson: bool,
+
+    /// Print the differences as `diff -rq` does
+    #[arg(long, conflicts_with_all = ["json", "brief", "first_only", "baseline"])]
+    diff_rq: bool,
+
+    /// Only report whether the directories differ
+    #[arg(short = 'q', long)]
+    brief: bool,
+
+    /// Stop at the first difference found and only report that one
+    #[arg(long, conflicts_with = "brief")]
+    first_only: bool,
+
+    /// Only report differences that aren't in this earlier JSON report
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["brief", "first_only"])]
+    baseline: Option<PathBuf>,
+}
+
+/// How the comparison ended, as reported through the exit code.
+///
+/// Usage errors are reported by `clap` with the same code as [`Status::Trouble`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Status {
+    /// The directories have the same contents.
+    Same = 0,
+    /// The directories differ.
+    Different = 1,
+    /// The comparison could not be completed.
+    Trouble = 2,
+}
+
+impl Status {
+    fn from_different(different: bool) -> Self {
+        if different {
+            Self::Different
+        } else {
+            Self::Same
+        }
+    }
+}
+
+impl From<Status> for ExitCode {
+    fn from(status: Status) -> Self {
+        ExitCode::from(status as u8)
+    }
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    let status = run(&args).unwrap_or_else(|err| {
+        let _ = writeln!(std::io::stderr(), "dir-diff: {err}");
+        Status::Trouble
+    });
+    status.into()
+}
+
+fn run(args: &Args) -> Result<Status, Box<dyn std::error::Error>> {
+    let mut options = DirDiffOptions::new();
+    for glob in &args.exclude {
+        options = options.exclude(glob);
+    }
+    for glob in &args.allow_different {
+        options = options.allow_different(glob);
+    }
+    options = options
+        .compare_root_names(args.root_names)
+        .similarity(args.similarity);
+
+    let mut stdout = std::io::stdout().lock();
+    if args.brief {
+        let different = options.is_different(&args.left, &args.right)?;
+        if different {
+            writeln!(
+                stdout,
+                "Directories {} and {} differ",
+                args.left.display(),
+                args.right.display()
+            )?;
+        }
+        return Ok(Status::from_different(different));
+    }
+
+    if args.first_only {
+        let difference = options.first_difference(&args.left, &args.right)?;
+        if args.json {
+            serde_json::to_writer_pretty(&mut stdout, &difference)?;
+            writeln!(stdout)?;
+        } else if let Some(difference) = &difference {
+            write_difference(&mut stdout, difference)?;
+        }
+        return Ok(Status::from_different(difference.is_some()));
+    }
+
+    if args.ndjson {
+        let count = options
+            .differences(&args.left, &args.right)
+            .write_ndjson(&mut stdout)?;
+        return Ok(Status::from_different(count > 0));
+    }
+
+    let report = options.diff(&args.left, &args.right)?;
+    if let Some(baseline) = &args.baseline {
+        let baseline: DiffReport = serde_json::from_slice(&std::fs::read(baseline)?)?;
+        let delta = report.compare_to_baseline(&baseline);
+        if args.json {
+            serde_json::to_writer_pretty(&mut stdout, &delta)?;
+            writeln!(stdout)?;
+        } else {
+            for difference in delta.introduced() {
+                write_difference(&mut stdout, difference)?;
+            }
+        }
+        return Ok(Status::from_different(delta.has_regressions()));
+    }
+    #[cfg(any(feature = "cbor", feature = "msgpack"))]
+    if let Some(format) = args.compact {
+        report.write_compact(format, &mut stdout)?;
+        return Ok(Status::from_different(report.is_different()));
+    }
+    if args.json {
+        serde_json::to_writer_pretty(&mut stdout, &report)?;
+        writeln!(stdout)?;
+    } else if args.diff_rq {
+        write!(stdout, "{}", report.render_diff_rq())?;
+    } else {
+        write_text(&mut stdout, &report)?;
+    }
+    Ok(Status::from_different(report.is_different()))
+}
+
+#[cfg(any(feature = "cbor", feature = "msgpack"))]
+fn compact_format(name: &str) -> Result<dir_diff::CompactFormat, String> {
+    match name {
+        #[cfg(feature = "cbor")]
+        "cbor" => Ok(dir_diff::CompactFormat::Cbor),
+        #[cfg(feature = "msgpack")]
+        "msgpack" => Ok(dir_diff::CompactFormat::MessagePack),
+        _ => Err(format!("unsupported format `{name}`")),
+    }
+}
+
+fn write_text(out: &mut dyn std::io::Write, report: &DiffReport) -> std::io::Result<()> {
+    for difference in report.differences() {
+        write_difference(out, difference)?;
+    }
+    Ok(())
+}
+
+fn write_difference(out: &mut dyn std::io::Write, difference: &Difference) -> std::io::Result<()> {
+    let marker = difference.kind().marker();
+    // Differences between the roots themselves have an empty path.
+    let path = if difference.path().as_os_str().is_empty() {
+        std::path::Path::new(".")
+    } else {
+        difference.path().as_path()
+    };
+    match difference.similarity() {
+        Some(similarity) => writeln!(
+            out,
+            "{marker} {} ({:.0}% similar)",
+            path.display(),
+            similarity.ratio() * 100.0
+        ),
+        None => writeln!(out, "{marker} {}", path.display()),
+    }
+}
@@ -0,0 +1,248 @@
+//! Masking the parts of compiled artifacts that differ between builds of the same sources.
+
+use std::mem::size_of;
+use std::ops::Range;
+
+use object::macho;
+use object::pe;
+use object::read::macho::{MachHeader, MachOFile32, MachOFile64};
+use object::read::pe::{ImageNtHeaders, PeFile32, PeFile64};
+use object::{Endianness, FileKind, Object as _, ObjectSection as _};
+
+/// ELF sections holding an id derived from, or chosen at random for, each build.
+const ELF_BUILD_ID_SECTIONS: [&str; 2] = [".note.gnu.build-id", ".note.go.buildid"];
+
+/// Zero the build ids and timestamps in `contents`, returning `false` if it isn't an ELF, PE or
+/// Mach-O file.
+pub(crate) fn mask(contents: &mut [u8]) -> bool {
+    let Ok(Some(ranges)) = build_dependent_ranges(contents) else {
+        return false;
+    };
+    for range in ranges {
+        if let Some(bytes) = contents.get_mut(range) {
+            bytes.fill(0);
+        }
+    }
+    true
+}
+
+fn build_dependent_ranges(data: &[u8]) -> object::Result<Option<Vec<Range<usize>>>> {
+    let ranges = match FileKind::parse(data)? {
+        FileKind::Elf32 | FileKind::Elf64 => elf_ranges(&object::File::parse(data)?),
+        FileKind::Pe32 => pe_ranges(&PeFile32::parse(data)?, data),
+        FileKind::Pe64 => pe_ranges(&PeFile64::parse(data)?, data),
+        FileKind::MachO32 => macho_ranges(&MachOFile32::<Endianness>::parse(data)?)?,
+        FileKind::MachO64 => macho_ranges(&MachOFile64::<Endianness>::parse(data)?)?,
+        _ => return Ok(None),
+    };
+    Ok(Some(ranges))
+}
+
+fn elf_ranges(file: &object::File<'_>) -> Vec<Range<usize>> {
+    file.sections()
+        .filter(|section| {
+            section
+                .name()
+                .map(|name| ELF_BUILD_ID_SECTIONS.contains(&name))
+                .unwrap_or(false)
+        })
+        .filter_map(|section| section.file_range())
+        .map(|(offset, len)| to_range(offset, len))
+        .collect()
+}
+
+/// The link timestamp and the checksum covering it, and the debug directory's timestamps and
+/// the PDB signature, which MSVC generates for each build.
+fn pe_ranges<Pe: ImageNtHeaders>(
+    file: &object::read::pe::PeFile<'_, Pe>,
+    data: &[u8],
+) -> Vec<Range<usize>> {
+    let nt_headers = file.dos_header().nt_headers_offset() as usize;
+    // After the `PE\0\0` signature, `TimeDateStamp` is the third field of the file header, and
+    // `CheckSum` is at the same offset in the 32 and 64-bit optional headers.
+    let file_header = nt_headers + 4;
+    let optional_header = file_header + size_of::<pe::ImageFileHeader>();
+    let mut ranges = vec![
+        file_header + 4..file_header + 8,
+        optional_header + 64..optional_header + 68,
+    ];
+
+    let Some(debug) = file.data_directory(pe::IMAGE_DIRECTORY_ENTRY_DEBUG) else {
+        return ranges;
+    };
+    let Ok((offset, len)) = debug.file_range(&file.section_table()) else {
+        return ranges;
+    };
+    let directory = to_range(offset.into(), len.into());
+    let entry_len = size_of::<pe::ImageDebugDirectory>();
+    for entry in (directory.start..directory.end).step_by(entry_len) {
+        let Some(fields) = data.get(entry..entry + entry_len) else {
+            break;
+        };
+        let field = |at: usize| u32::from_le_bytes(fields[at..at + 4].try_into().unwrap());
+        ranges.push(entry + 4..entry + 8);
+        let raw = to_range(field(24).into(), field(16).into());
+        match field(12) {
+            // `RSDS`, then the GUID and age identifying the matching PDB.
+            pe::IMAGE_DEBUG_TYPE_CODEVIEW
+                if data
+                    .get(raw.clone())
+                    .map(|raw| raw.starts_with(b"RSDS"))
+                    .unwrap_or(false) =>
+            {
+                ranges.push(raw.start + 4..raw.start + 24);
+            }
+            // A hash of the output, which the timestamps are derived from with `/Brepro`.
+            pe::IMAGE_DEBUG_TYPE_REPRO => ranges.push(raw),
+            _ => {}
+        }
+    }
+    ranges
+}
+
+/// The UUID, and the timestamps recorded for dynamic libraries.
+fn macho_ranges<Mach: MachHeader<Endian = Endianness>>(
+    file: &object::read::macho::MachOFile<'_, Mach>,
+) -> object::Result<Vec<Range<usize>>> {
+    let mut ranges = Vec::new();
+    let mut offset = size_of::<Mach>();
+    let mut commands = file.macho_load_commands()?;
+    while let Some(command) = commands.next()? {
+        match command.cmd() {
+            macho::LC_UUID => ranges.push(offset + 8..offset + 24),
+            macho::LC_ID_DYLIB
+            | macho::LC_LOAD_DYLIB
+            | macho::LC_LOAD_WEAK_DYLIB
+            | macho::LC_REEXPORT_DYLIB => ranges.push(offset + 12..offset + 16),
+            _ => {}
+        }
+        offset += command.cmdsize() as usize;
+    }
+    Ok(ranges)
+}
+
+fn to_range(offset: u64, len: u64) -> Range<usize> {
+    let start = usize::try_from(offset).unwrap_or(usize::MAX);
+    start..start.saturating_add(usize::try_from(len).unwrap_or(usize::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write `bytes` at `at`, growing `buffer` as needed.
+    fn put(buffer: &mut Vec<u8>, at: usize, bytes: &[u8]) {
+        if buffer.len() < at + bytes.len() {
+            buffer.resize(at + bytes.len(), 0);
+        }
+        buffer[at..at + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn elf(build_id: &[u8; 8], code: u8) -> Vec<u8> {
+        let mut elf = Vec::new();
+        put(&mut elf, 0, b"\x7fELF\x02\x01\x01");
+        put(&mut elf, 16, &[2, 0, 62, 0, 1, 0, 0, 0]);
+        put(&mut elf, 40, &128u64.to_le_bytes());
+        put(&mut elf, 52, &[64, 0, 56, 0, 0, 0, 64, 0, 3, 0, 2, 0]);
+        // The build id note, then the section names.
+        put(&mut elf, 64, &[4, 0, 0, 0, 8, 0, 0, 0, 3, 0, 0, 0]);
+        put(&mut elf, 76, b"GNU\0");
+        put(&mut elf, 80, build_id);
+        put(&mut elf, 88, b"\0.note.gnu.build-id\0.shstrtab\0");
+        put(&mut elf, 118, &[code]);
+        for (index, name, kind, offset, size) in [(1, 1, 7, 64, 24), (2, 20, 3, 88, 30)] {
+            let header = 128 + index * 64;
+            put(&mut elf, header, &[name, 0, 0, 0, kind, 0, 0, 0]);
+            put(&mut elf, header + 24, &u64::to_le_bytes(offset));
+            put(&mut elf, header + 32, &u64::to_le_bytes(size));
+        }
+        elf.resize(128 + 3 * 64, 0);
+        elf
+    }
+
+    fn pe(timestamp: u32, guid: &[u8; 16], code: u8) -> Vec<u8> {
+        let mut pe = Vec::new();
+        put(&mut pe, 0, b"MZ");
+        put(&mut pe, 0x3c, &64u32.to_le_bytes());
+        put(&mut pe, 64, b"PE\0\0\x64\x86\x01\0");
+        put(&mut pe, 72, &timestamp.to_le_bytes());
+        put(&mut pe, 84, &[240, 0, 0x22, 0]);
+        // The optional header, with its checksum, size of headers, and debug directory.
+        put(&mut pe, 88, &[0x0b, 0x02]);
+        put(&mut pe, 88 + 32, &[0, 0x10, 0, 0, 0, 2, 0, 0]);
+        put(&mut pe, 88 + 60, &512u32.to_le_bytes());
+        put(&mut pe, 88 + 64, &timestamp.wrapping_mul(3).to_le_bytes());
+        put(&mut pe, 88 + 108, &16u32.to_le_bytes());
+        put(&mut pe, 88 + 112 + 6 * 8, &[0, 0x10, 0, 0, 28, 0, 0, 0]);
+        // One section, mapping the debug directory and the PDB signature it points to.
+        put(&mut pe, 328, b".rdata\0\0");
+        put(
+            &mut pe,
+            336,
+            &[0, 2, 0, 0, 0, 0x10, 0, 0, 0, 2, 0, 0, 0, 2, 0, 0],
+        );
+        put(&mut pe, 512 + 4, &timestamp.to_le_bytes());
+        put(
+            &mut pe,
+            512 + 12,
+            &pe::IMAGE_DEBUG_TYPE_CODEVIEW.to_le_bytes(),
+        );
+        put(
+            &mut pe,
+            512 + 16,
+            &[30, 0, 0, 0, 28, 0x10, 0, 0, 28, 2, 0, 0],
+        );
+        put(&mut pe, 540, b"RSDS");
+        put(&mut pe, 544, guid);
+        put(&mut pe, 564, b"\x01\0\0\0a.pdb\0");
+        put(&mut pe, 1023, &[code]);
+        pe
+    }
+
+    fn macho(uuid: &[u8; 16], timestamp: u32, code: u8) -> Vec<u8> {
+        let mut macho = Vec::new();
+        put(
+            &mut macho,
+            0,
+            b"\xcf\xfa\xed\xfe\x07\0\0\x01\x03\0\0\0\x06\0\0\0",
+        );
+        put(&mut macho, 16, &[2, 0, 0, 0, 56, 0, 0, 0]);
+        put(&mut macho, 32, &[0x1b, 0, 0, 0, 24, 0, 0, 0]);
+        put(&mut macho, 40, uuid);
+        put(&mut macho, 56, &[0xd, 0, 0, 0, 32, 0, 0, 0, 24, 0, 0, 0]);
+        put(&mut macho, 68, &timestamp.to_le_bytes());
+        put(&mut macho, 80, b"x\0\0\0\0\0\0\0");
+        put(&mut macho, 95, &[code]);
+        macho
+    }
+
+    fn same_build(mut left: Vec<u8>, mut right: Vec<u8>) -> bool {
+        assert!(mask(&mut left));
+        assert!(mask(&mut right));
+        left == right
+    }
+
+    #[test]
+    fn test_elf() {
+        assert!(same_build(elf(&[1; 8], 0), elf(&[2; 8], 0)));
+        assert!(!same_build(elf(&[1; 8], 0), elf(&[2; 8], 1)));
+    }
+
+    #[test]
+    fn test_pe() {
+        assert!(same_build(pe(1, &[1; 16], 0), pe(2, &[2; 16], 0)));
+        assert!(!same_build(pe(1, &[1; 16], 0), pe(2, &[2; 16], 1)));
+    }
+
+    #[test]
+    fn test_macho() {
+        assert!(same_build(macho(&[1; 16], 1, 0), macho(&[2; 16], 2, 0)));
+        assert!(!same_build(macho(&[1; 16], 1, 0), macho(&[2; 16], 2, 1)));
+    }
+
+    #[test]
+    fn test_not_binary() {
+        assert!(!mask(&mut b"\x7fELF, but not really".to_vec()));
+        assert!(!mask(&mut b"text".to_vec()));
+    }
+}
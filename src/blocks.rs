@@ -0,0 +1,224 @@
+//! Summarizing how much of a file survived in another, the way rsync finds blocks to reuse.
+//!
+//! The left file is cut into fixed-size blocks, each hashed twice: with a weak checksum that can
+//! be rolled along a byte at a time, and with a strong hash to confirm matches.  A window the
+//! size of a block is then rolled over the right file, so blocks are found wherever they moved
+//! to, such as after bytes were inserted ahead of them.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufReader, Read};
+
+use crate::compare::read_chunk;
+use crate::report::{BlockSummary, Side};
+
+/// Find how many blocks of `left` also appear in `right`.
+pub(crate) fn summarize(
+    left: impl Read,
+    right: impl Read,
+    block_size: usize,
+) -> Result<BlockSummary, (Side, io::Error)> {
+    let signature = Signature::new(left, block_size).map_err(|err| (Side::Left, err))?;
+    let matching = signature.scan(right).map_err(|err| (Side::Right, err))?;
+    Ok(BlockSummary::new(
+        block_size as u64,
+        signature.blocks.len() as u64,
+        matching,
+    ))
+}
+
+/// The hashes of each block of a file.
+struct Signature {
+    block_size: usize,
+    /// The strong hash of each block, in order.
+    blocks: Vec<blake3::Hash>,
+    /// The blocks with each weak checksum.  Only whole blocks are listed, as a window over the
+    /// other file is always a whole block.
+    by_checksum: HashMap<u32, Vec<usize>>,
+    /// The block at the end of the file, if it is shorter than the others.
+    short_block: Option<Vec<u8>>,
+}
+
+impl Signature {
+    fn new(mut file: impl Read, block_size: usize) -> io::Result<Self> {
+        let mut signature = Self {
+            block_size,
+            blocks: Vec::new(),
+            by_checksum: HashMap::new(),
+            short_block: None,
+        };
+        let mut buffer = vec![0; block_size];
+        loop {
+            let len = read_chunk(&mut file, &mut buffer)?;
+            if len == 0 {
+                break;
+            }
+            let block = &buffer[..len];
+            if len == block_size {
+                signature
+                    .by_checksum
+                    .entry(Checksum::of(block).value())
+                    .or_default()
+                    .push(signature.blocks.len());
+            } else {
+                signature.short_block = Some(block.to_vec());
+            }
+            signature.blocks.push(blake3::hash(block));
+            if len < block_size {
+                break;
+            }
+        }
+        Ok(signature)
+    }
+
+    /// Count the distinct blocks found in `file`.
+    fn scan(&self, file: impl Read) -> io::Result<u64> {
+        let mut matched = vec![false; self.blocks.len()];
+        let mut window = VecDeque::with_capacity(self.block_size);
+        let mut checksum = Checksum::default();
+        // The end of the file, to look for a short last block in.
+        let tail_len = self.short_block.as_ref().map(Vec::len).unwrap_or(0);
+        let mut tail = VecDeque::with_capacity(tail_len);
+
+        for byte in BufReader::new(file).bytes() {
+            let byte = byte?;
+            if tail_len > 0 {
+                if tail.len() == tail_len {
+                    tail.pop_front();
+                }
+                tail.push_back(byte);
+            }
+
+            if window.len() == self.block_size {
+                let out = window.pop_front().expect("the window is full");
+                checksum.roll(out, byte, self.block_size);
+            } else {
+                checksum.push(byte);
+            }
+            window.push_back(byte);
+            if window.len() < self.block_size {
+                continue;
+            }
+
+            let Some(candidates) = self.by_checksum.get(&checksum.value()) else {
+                continue;
+            };
+            let hash = blake3::hash(window.make_contiguous());
+            let mut found = false;
+            // Prefer a block not matched yet, when several have the same contents.
+            for &index in candidates {
+                if self.blocks[index] == hash {
+                    found = true;
+                    if !matched[index] {
+                        matched[index] = true;
+                        break;
+                    }
+                }
+            }
+            if found {
+                // Carry on after the matched block, as rsync does.
+                window.clear();
+                checksum = Checksum::default();
+            }
+        }
+
+        if let Some(short_block) = &self.short_block {
+            if tail.iter().eq(short_block.iter()) {
+                *matched.last_mut().expect("the short block is a block") = true;
+            }
+        }
+        Ok(matched.into_iter().filter(|&matched| matched).count() as u64)
+    }
+}
+
+/// rsync's rolling checksum: a sum of the bytes and a sum of the bytes weighted by their distance
+/// from the end of the window, both modulo 2^16.
+#[derive(Copy, Clone, Default)]
+struct Checksum {
+    a: u16,
+    b: u16,
+}
+
+impl Checksum {
+    fn of(block: &[u8]) -> Self {
+        let mut checksum = Self::default();
+        for &byte in block {
+            checksum.push(byte);
+        }
+        checksum
+    }
+
+    /// Add `byte` at the end of a window that isn't full yet.
+    ///
+    /// Each byte already pushed gains another multiple of itself in `b`, so once the window is
+    /// full every byte is weighted by its distance from the end, as `roll` expects.
+    fn push(&mut self, byte: u8) {
+        self.a = self.a.wrapping_add(u16::from(byte));
+        self.b = self.b.wrapping_add(self.a);
+    }
+
+    /// Slide a full window of `len` bytes along by one, from `out` to `byte`.
+    fn roll(&mut self, out: u8, byte: u8, len: usize) {
+        self.a = self
+            .a
+            .wrapping_sub(u16::from(out))
+            .wrapping_add(u16::from(byte));
+        self.b = self
+            .b
+            .wrapping_sub((len as u16).wrapping_mul(u16::from(out)))
+            .wrapping_add(self.a);
+    }
+
+    fn value(self) -> u32 {
+        u32::from(self.a) | (u32::from(self.b) << 16)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rolling_checksum() {
+        let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        let mut checksum = Checksum::of(&data[..64]);
+        for start in 1..=data.len() - 64 {
+            checksum.roll(data[start - 1], data[start + 63], 64);
+            assert_eq!(
+                checksum.value(),
+                Checksum::of(&data[start..start + 64]).value()
+            );
+        }
+    }
+
+    #[test]
+    fn test_summarize() {
+        let left: Vec<u8> = (0..100).collect();
+        let summarize = |right: &[u8]| summarize(&left[..], right, 10).ok().unwrap();
+
+        let same = summarize(&left);
+        assert_eq!((same.blocks(), same.matching_blocks()), (10, 10));
+
+        // Patching a byte only loses the block it is in.
+        let mut patched = left.clone();
+        patched[55] = 0;
+        assert_eq!(summarize(&patched).differing_blocks(), 1);
+
+        // Inserting bytes shifts the rest, but they are still found.
+        let mut inserted = left.clone();
+        inserted.splice(5..5, [1, 2, 3]);
+        assert_eq!(summarize(&inserted).differing_blocks(), 1);
+
+        assert_eq!(summarize(&[]).differing_blocks(), 10);
+        let short = summarize(&left[..95]);
+        assert_eq!((short.blocks(), short.matching_blocks()), (10, 9));
+    }
+
+    #[test]
+    fn test_short_block() {
+        let left: Vec<u8> = (0..25).collect();
+        let summary = summarize(&left[..], &[&[9, 9][..], &left[..]].concat()[..], 10)
+            .ok()
+            .unwrap();
+        assert_eq!((summary.blocks(), summary.matching_blocks()), (3, 3));
+    }
+}
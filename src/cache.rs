@@ -0,0 +1,193 @@
+//! Content hashes that outlive a single comparison.
+
+use std::collections::HashMap;
+use std::io::{BufRead as _, Write as _};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use crate::error::{Phase, ResultExt as _};
+use crate::hash::{from_hex, hash_reader, to_hex, HashAlgorithm};
+use crate::hooks::{Hooks, Metrics};
+use crate::source::Metadata;
+use crate::Error;
+
+/// The name of the file a cache is persisted to, inside the cache directory.
+///
+/// The version is bumped whenever the format changes, which simply starts a fresh cache.
+/// Hashes by algorithms other than BLAKE3 go to a file named after the algorithm.
+const CACHE_FILE: &str = "dir-diff-hashes-v1";
+
+/// Content hashes of local files, keyed by path.
+///
+/// An entry is only reused while the file's length and modification time are unchanged.
+pub(crate) struct HashCache {
+    algorithm: HashAlgorithm,
+    hashes: Mutex<HashMap<PathBuf, CachedHash>>,
+    dirty: AtomicBool,
+    metrics: Option<Arc<dyn Metrics>>,
+}
+
+struct CachedHash {
+    len: u64,
+    modified: Option<SystemTime>,
+    hash: [u8; 32],
+}
+
+impl HashCache {
+    /// An empty cache of hashes by `algorithm`.
+    pub(crate) fn new(algorithm: HashAlgorithm) -> Self {
+        Self {
+            algorithm,
+            hashes: Mutex::default(),
+            dirty: AtomicBool::new(false),
+            metrics: None,
+        }
+    }
+
+    /// Load the cache of hashes by `algorithm` persisted in `dir`, if any.
+    ///
+    /// Entries that can't be parsed are dropped, as they can always be recomputed.
+    pub(crate) fn load(dir: &Path, algorithm: HashAlgorithm) -> Result<Self, Error> {
+        let path = dir.join(file_name(algorithm));
+        let file = match std::fs::File::open(&path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self::new(algorithm))
+            }
+            Err(err) => return Err(err).context(Phase::LoadingCache, || path),
+        };
+
+        let mut hashes = HashMap::new();
+        for line in std::io::BufReader::new(file).lines() {
+            let line = line.context(Phase::LoadingCache, || path.clone())?;
+            if let Some((path, cached)) = parse_line(&line) {
+                hashes.insert(path, cached);
+            }
+        }
+        Ok(Self {
+            algorithm,
+            hashes: Mutex::new(hashes),
+            dirty: AtomicBool::new(false),
+            metrics: None,
+        })
+    }
+
+    /// Count hits and misses with the metrics registered in `hooks`, if any.
+    pub(crate) fn with_metrics(mut self, hooks: &Hooks) -> Self {
+        self.metrics = hooks.metrics.clone();
+        self
+    }
+
+    /// Persist the cache to `dir`, if it has changed since it was loaded.
+    pub(crate) fn save(&self, dir: &Path) -> Result<(), Error> {
+        if !self.dirty.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        self.write(dir)
+            .context(Phase::SavingCache, || dir.join(file_name(self.algorithm)))
+    }
+
+    fn write(&self, dir: &Path) -> Result<(), Error> {
+        std::fs::create_dir_all(dir)?;
+        let file_name = file_name(self.algorithm);
+        let temp = dir.join(format!("{file_name}.{}.tmp", std::process::id()));
+        let mut out = std::io::BufWriter::new(std::fs::File::create(&temp)?);
+        for (path, cached) in self.lock().iter() {
+            // Entries that can't be written unambiguously, or couldn't be validated when loaded,
+            // are left out and rehashed next time.
+            let Some(path) = path.to_str().filter(|path| !path.contains('\n')) else {
+                continue;
+            };
+            let Some(modified) = cached
+                .modified
+                .and_then(|modified| modified.duration_since(SystemTime::UNIX_EPOCH).ok())
+            else {
+                continue;
+            };
+            writeln!(
+                out,
+                "{} {} {}.{:09} {path}",
+                to_hex(&cached.hash),
+                cached.len,
+                modified.as_secs(),
+                modified.subsec_nanos()
+            )?;
+        }
+        out.into_inner()
+            .map_err(|err| err.into_error())?
+            .sync_all()?;
+        std::fs::rename(&temp, dir.join(file_name))?;
+        Ok(())
+    }
+
+    pub(crate) fn hash(&self, path: &Path, metadata: &Metadata) -> Result<[u8; 32], Error> {
+        self.lookup(path, metadata).map(|(hash, _)| hash)
+    }
+
+    /// The hash of the file at `path`, and whether it was found in the cache.
+    pub(crate) fn lookup(
+        &self,
+        path: &Path,
+        metadata: &Metadata,
+    ) -> Result<([u8; 32], bool), Error> {
+        let len = metadata.len();
+        let modified = metadata.modified();
+        if let Some(cached) = self.lock().get(path) {
+            if cached.len == len && cached.modified == modified {
+                if let Some(metrics) = &self.metrics {
+                    metrics.cache_hit();
+                }
+                return Ok((cached.hash, true));
+            }
+        }
+        if let Some(metrics) = &self.metrics {
+            metrics.cache_miss();
+        }
+
+        let hash = hash_reader(self.algorithm, std::fs::File::open(path)?)?;
+        self.lock().insert(
+            path.to_owned(),
+            CachedHash {
+                len,
+                modified,
+                hash,
+            },
+        );
+        self.dirty.store(true, Ordering::Relaxed);
+        Ok((hash, false))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<PathBuf, CachedHash>> {
+        self.hashes
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+fn file_name(algorithm: HashAlgorithm) -> String {
+    if algorithm == HashAlgorithm::Blake3 {
+        CACHE_FILE.to_owned()
+    } else {
+        format!("{CACHE_FILE}-{}", algorithm.name())
+    }
+}
+
+/// Parse a `<hash> <len> <modified> <path>` line.
+fn parse_line(line: &str) -> Option<(PathBuf, CachedHash)> {
+    let mut fields = line.splitn(4, ' ');
+    let hash = from_hex(fields.next()?)?;
+    let len = fields.next()?.parse().ok()?;
+    let (secs, nanos) = fields.next()?.split_once('.')?;
+    let modified = SystemTime::UNIX_EPOCH + Duration::new(secs.parse().ok()?, nanos.parse().ok()?);
+    let path = PathBuf::from(fields.next()?);
+    Some((
+        path,
+        CachedHash {
+            len,
+            modified: Some(modified),
+            hash,
+        },
+    ))
+}
@@ -0,0 +1,46 @@
+//! Stopping a comparison from another thread.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A flag for stopping comparisons early, shared between its clones.
+///
+/// Pass it to [`DirDiffOptions::cancel_token`][crate::DirDiffOptions::cancel_token], then call
+/// [`CancelToken::cancel`] from anywhere to make running comparisons fail with
+/// [`Error::Cancelled`][crate::Error::Cancelled].
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let token = dir_diff::CancelToken::new();
+/// let options = dir_diff::DirDiffOptions::new().cancel_token(token.clone());
+/// let comparison = std::thread::spawn(move || options.diff("huge/a", "huge/b"));
+/// token.cancel();
+/// assert!(matches!(
+///     comparison.join().unwrap(),
+///     Err(dir_diff::Error::Cancelled) | Ok(_)
+/// ));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancelToken {
+    /// A token that hasn't been cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stop every comparison using this token at the next entry.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Has [`CancelToken::cancel`] been called?
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,404 @@
+//! A C API, for build systems that aren't written in Rust.
+//!
+//! The declarations are in `include/dir_diff.h`, generated with `cbindgen` from this module.
+//! Build the crate as a `cdylib` or `staticlib` with the `capi` feature to link against it, for
+//! example with `cargo rustc --release --features capi --crate-type cdylib`.
+//!
+//! Paths, given and returned, are NUL-terminated strings: bytes on Unix, and UTF-8 elsewhere.
+//! Functions that fail record why, which [`dir_diff_last_error`] returns.  A panic never unwinds
+//! into C: it is recorded the same way, and the function returns what it does on failure, or
+//! `false`, 0 or `NULL` where it can't fail.
+//!
+//! # Examples
+//!
+//! ```c
+//! DirDiffReport *report = dir_diff_report_new("target/out", "tests/golden");
+//! if (report == NULL) {
+//!     fprintf(stderr, "%s\n", dir_diff_last_error());
+//!     return 2;
+//! }
+//! for (size_t i = 0; i < dir_diff_report_len(report); i++) {
+//!     printf("%d %s\n", dir_diff_report_kind(report, i), dir_diff_report_path(report, i));
+//! }
+//! dir_diff_report_free(report);
+//! ```
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+
+use crate::render::ColorChoice;
+use crate::report::{DiffReport, DifferenceKind};
+use crate::Error;
+
+/// [`DifferenceKind::OnlyInLeft`].
+pub const DIR_DIFF_KIND_ONLY_IN_LEFT: c_int = 0;
+/// [`DifferenceKind::OnlyInRight`].
+pub const DIR_DIFF_KIND_ONLY_IN_RIGHT: c_int = 1;
+/// [`DifferenceKind::FileType`].
+pub const DIR_DIFF_KIND_FILE_TYPE: c_int = 2;
+/// [`DifferenceKind::Content`].
+pub const DIR_DIFF_KIND_CONTENT: c_int = 3;
+/// [`DifferenceKind::SymlinkEscape`].
+pub const DIR_DIFF_KIND_SYMLINK_ESCAPE: c_int = 4;
+/// [`DifferenceKind::Volatile`].
+pub const DIR_DIFF_KIND_VOLATILE: c_int = 5;
+/// [`DifferenceKind::RootName`].
+pub const DIR_DIFF_KIND_ROOT_NAME: c_int = 6;
+/// [`DifferenceKind::Timeout`].
+pub const DIR_DIFF_KIND_TIMEOUT: c_int = 7;
+/// [`DifferenceKind::Permissions`].
+pub const DIR_DIFF_KIND_PERMISSIONS: c_int = 8;
+/// [`DifferenceKind::Modified`].
+pub const DIR_DIFF_KIND_MODIFIED: c_int = 9;
+/// [`DifferenceKind::Acl`].
+pub const DIR_DIFF_KIND_ACL: c_int = 10;
+/// [`DifferenceKind::Encoding`].
+pub const DIR_DIFF_KIND_ENCODING: c_int = 11;
+/// [`DifferenceKind::BrokenSymlink`], on either side.
+pub const DIR_DIFF_KIND_BROKEN_SYMLINK: c_int = 12;
+
+/// A [`DiffReport`], with its paths ready to be handed out as C strings.
+#[derive(Debug)]
+pub struct DirDiffReport {
+    report: DiffReport,
+    paths: Vec<CString>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Are the contents of two directories different?
+///
+/// Returns 1 if they are, 0 if they aren't, and -1 if they couldn't be compared.
+///
+/// # Safety
+///
+/// `a_base` and `b_base` must be NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn dir_diff_is_different(
+    a_base: *const c_char,
+    b_base: *const c_char,
+) -> c_int {
+    catch(-1, || {
+        // SAFETY: upheld by the caller.
+        let paths = unsafe { (path(a_base), path(b_base)) };
+        let result = match paths {
+            (Some(a_base), Some(b_base)) => crate::is_different(a_base, b_base),
+            _ => Err(invalid_path()),
+        };
+        match result {
+            Ok(true) => 1,
+            Ok(false) => 0,
+            Err(err) => {
+                set_last_error(&err);
+                -1
+            }
+        }
+    })
+}
+
+/// Find every difference between the contents of two directories.
+///
+/// Returns `NULL` if they couldn't be compared.  Free the report with [`dir_diff_report_free`].
+///
+/// # Safety
+///
+/// `a_base` and `b_base` must be NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn dir_diff_report_new(
+    a_base: *const c_char,
+    b_base: *const c_char,
+) -> *mut DirDiffReport {
+    catch(std::ptr::null_mut(), || {
+        // SAFETY: upheld by the caller.
+        let paths = unsafe { (path(a_base), path(b_base)) };
+        let result = match paths {
+            (Some(a_base), Some(b_base)) => crate::diff(a_base, b_base),
+            _ => Err(invalid_path()),
+        };
+        match result {
+            Ok(report) => {
+                let paths = report
+                    .differences()
+                    .iter()
+                    .map(|difference| c_path(difference.path()))
+                    .collect();
+                Box::into_raw(Box::new(DirDiffReport { report, paths }))
+            }
+            Err(err) => {
+                set_last_error(&err);
+                std::ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Free a report from [`dir_diff_report_new`].  Does nothing given `NULL`.
+///
+/// # Safety
+///
+/// `report` must be `NULL`, or a report from [`dir_diff_report_new`] that isn't used after.
+#[no_mangle]
+pub unsafe extern "C" fn dir_diff_report_free(report: *mut DirDiffReport) {
+    catch((), || {
+        if !report.is_null() {
+            // SAFETY: the report was boxed by `dir_diff_report_new`, and is given back once.
+            drop(unsafe { Box::from_raw(report) });
+        }
+    });
+}
+
+/// Whether the report holds any differences.
+///
+/// # Safety
+///
+/// `report` must be a report from [`dir_diff_report_new`].
+#[no_mangle]
+pub unsafe extern "C" fn dir_diff_report_is_different(report: *const DirDiffReport) -> bool {
+    // SAFETY: upheld by the caller.
+    catch(false, || unsafe { &*report }.report.is_different())
+}
+
+/// The number of differences in the report.
+///
+/// # Safety
+///
+/// `report` must be a report from [`dir_diff_report_new`].
+#[no_mangle]
+pub unsafe extern "C" fn dir_diff_report_len(report: *const DirDiffReport) -> usize {
+    // SAFETY: upheld by the caller.
+    catch(0, || unsafe { &*report }.paths.len())
+}
+
+/// The relative path of the difference at `index`, or `NULL` past the end.
+///
+/// The string belongs to the report, and lives as long as it.
+///
+/// # Safety
+///
+/// `report` must be a report from [`dir_diff_report_new`].
+#[no_mangle]
+pub unsafe extern "C" fn dir_diff_report_path(
+    report: *const DirDiffReport,
+    index: usize,
+) -> *const c_char {
+    catch(std::ptr::null(), || {
+        // SAFETY: upheld by the caller.
+        match unsafe { &*report }.paths.get(index) {
+            Some(path) => path.as_ptr(),
+            None => std::ptr::null(),
+        }
+    })
+}
+
+/// The kind of the difference at `index`, as one of the `DIR_DIFF_KIND_*` constants, or -1
+/// past the end.
+///
+/// # Safety
+///
+/// `report` must be a report from [`dir_diff_report_new`].
+#[no_mangle]
+pub unsafe extern "C" fn dir_diff_report_kind(report: *const DirDiffReport, index: usize) -> c_int {
+    catch(-1, || {
+        // SAFETY: upheld by the caller.
+        let report = &unsafe { &*report }.report;
+        let Some(difference) = report.differences().get(index) else {
+            return -1;
+        };
+        match difference.kind() {
+            DifferenceKind::OnlyInLeft => DIR_DIFF_KIND_ONLY_IN_LEFT,
+            DifferenceKind::OnlyInRight => DIR_DIFF_KIND_ONLY_IN_RIGHT,
+            DifferenceKind::FileType => DIR_DIFF_KIND_FILE_TYPE,
+            DifferenceKind::Content => DIR_DIFF_KIND_CONTENT,
+            DifferenceKind::SymlinkEscape => DIR_DIFF_KIND_SYMLINK_ESCAPE,
+            DifferenceKind::Volatile => DIR_DIFF_KIND_VOLATILE,
+            DifferenceKind::RootName => DIR_DIFF_KIND_ROOT_NAME,
+            DifferenceKind::Timeout => DIR_DIFF_KIND_TIMEOUT,
+            DifferenceKind::Permissions => DIR_DIFF_KIND_PERMISSIONS,
+            DifferenceKind::Modified => DIR_DIFF_KIND_MODIFIED,
+            DifferenceKind::Acl => DIR_DIFF_KIND_ACL,
+            DifferenceKind::Encoding => DIR_DIFF_KIND_ENCODING,
+            DifferenceKind::BrokenSymlink { .. } => DIR_DIFF_KIND_BROKEN_SYMLINK,
+        }
+    })
+}
+
+/// The report rendered as a tree, as by [`DiffReport::render`], without colors.
+///
+/// Free the string with [`dir_diff_string_free`].
+///
+/// # Safety
+///
+/// `report` must be a report from [`dir_diff_report_new`].
+#[no_mangle]
+pub unsafe extern "C" fn dir_diff_report_render(report: *const DirDiffReport) -> *mut c_char {
+    catch(std::ptr::null_mut(), || {
+        // SAFETY: upheld by the caller.
+        let report = &unsafe { &*report }.report;
+        c_string(report.render(ColorChoice::Never)).into_raw()
+    })
+}
+
+/// Free a string from [`dir_diff_report_render`].  Does nothing given `NULL`.
+///
+/// # Safety
+///
+/// `string` must be `NULL`, or a string from [`dir_diff_report_render`] that isn't used after.
+#[no_mangle]
+pub unsafe extern "C" fn dir_diff_string_free(string: *mut c_char) {
+    catch((), || {
+        if !string.is_null() {
+            // SAFETY: the string was made by `CString::into_raw`, and is given back once.
+            drop(unsafe { CString::from_raw(string) });
+        }
+    });
+}
+
+/// Why the last function that failed on this thread did, or `NULL` if none has.
+///
+/// The string lives until another function fails on this thread.
+#[no_mangle]
+pub extern "C" fn dir_diff_last_error() -> *const c_char {
+    catch(std::ptr::null(), || {
+        LAST_ERROR.with(|last| match &*last.borrow() {
+            Some(message) => message.as_ptr(),
+            None => std::ptr::null(),
+        })
+    })
+}
+
+/// The path in the NUL-terminated string at `path`, if it is one.
+///
+/// # Safety
+///
+/// `path` must be `NULL` or a NUL-terminated string that outlives the path.
+unsafe fn path<'a>(path: *const c_char) -> Option<&'a Path> {
+    if path.is_null() {
+        return None;
+    }
+    // SAFETY: upheld by the caller.
+    let bytes = unsafe { CStr::from_ptr(path) }.to_bytes();
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt as _;
+        Some(Path::new(std::ffi::OsStr::from_bytes(bytes)))
+    }
+    #[cfg(not(unix))]
+    {
+        std::str::from_utf8(bytes).ok().map(Path::new)
+    }
+}
+
+fn invalid_path() -> Error {
+    Error::Io {
+        source: std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "paths must be non-null, and UTF-8 outside of Unix",
+        ),
+        phase: None,
+        path: None::<PathBuf>,
+    }
+}
+
+/// `string` as a C string, with any NUL bytes replaced.
+fn c_string(string: String) -> CString {
+    CString::new(string.replace('\0', "\u{fffd}")).expect("NUL bytes are replaced")
+}
+
+/// `path` as a C string: its bytes on Unix, and UTF-8 elsewhere, with any NUL bytes replaced.
+fn c_path(path: &Path) -> CString {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt as _;
+        if let Ok(path) = CString::new(path.as_os_str().as_bytes()) {
+            return path;
+        }
+    }
+    c_string(path.display().to_string())
+}
+
+fn set_last_error(err: &Error) {
+    set_last_message(err.to_string());
+}
+
+fn set_last_message(message: String) {
+    let message = c_string(message);
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Run `f`, returning `failed` if it panics, with the panic recorded as the last error, since
+/// unwinding into C is undefined behavior.
+fn catch<T>(failed: T, f: impl FnOnce() -> T) -> T {
+    match std::panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(value) => value,
+        Err(payload) => {
+            let message = if let Some(message) = payload.downcast_ref::<&str>() {
+                message
+            } else if let Some(message) = payload.downcast_ref::<String>() {
+                message.as_str()
+            } else {
+                "unknown cause"
+            };
+            set_last_message(format!("dir-diff panicked: {message}"));
+            failed
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let left = CString::new("tests/easy/bad/dir1").unwrap();
+        let right = CString::new("tests/easy/bad/dir2").unwrap();
+        let same = CString::new("tests/easy/good/dir1").unwrap();
+        let same_too = CString::new("tests/easy/good/dir2").unwrap();
+        let missing = CString::new("tests/missing").unwrap();
+        // SAFETY: the strings are NUL-terminated, and the report is freed once.
+        unsafe {
+            assert_eq!(dir_diff_is_different(same.as_ptr(), same_too.as_ptr()), 0);
+            assert_eq!(dir_diff_is_different(left.as_ptr(), right.as_ptr()), 1);
+            assert_eq!(dir_diff_is_different(left.as_ptr(), missing.as_ptr()), -1);
+            assert_eq!(dir_diff_is_different(left.as_ptr(), std::ptr::null()), -1);
+            assert!(!dir_diff_last_error().is_null());
+
+            let report = dir_diff_report_new(left.as_ptr(), right.as_ptr());
+            assert!(!report.is_null());
+            assert!(dir_diff_report_is_different(report));
+            assert_eq!(dir_diff_report_len(report), 1);
+            assert_eq!(
+                CStr::from_ptr(dir_diff_report_path(report, 0)).to_str(),
+                Ok("test.txt")
+            );
+            assert_eq!(dir_diff_report_kind(report, 0), DIR_DIFF_KIND_CONTENT);
+            assert!(dir_diff_report_path(report, 1).is_null());
+            assert_eq!(dir_diff_report_kind(report, 1), -1);
+            let rendered = dir_diff_report_render(report);
+            assert!(CStr::from_ptr(rendered)
+                .to_str()
+                .unwrap()
+                .contains("M test.txt"));
+            dir_diff_string_free(rendered);
+            dir_diff_report_free(report);
+
+            assert!(dir_diff_report_new(left.as_ptr(), missing.as_ptr()).is_null());
+        }
+    }
+
+    #[test]
+    fn test_panic() {
+        assert_eq!(catch(-1, || panic!("comparing files panicked")), -1);
+        // SAFETY: the error is a NUL-terminated string, read before anything else fails.
+        let message = unsafe { CStr::from_ptr(dir_diff_last_error()) };
+        assert_eq!(
+            message.to_str(),
+            Ok("dir-diff panicked: comparing files panicked")
+        );
+        assert!(catch(std::ptr::null::<c_char>(), || panic!("{}", 1)).is_null());
+    }
+}
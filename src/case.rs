@@ -0,0 +1,165 @@
+//! Matching names between trees with or without regard to case.
+
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::ffi::{OsStr, OsString};
+use std::path::Path;
+
+use crate::source::TreeSource;
+
+/// How [`DirDiffOptions::case_sensitivity`][crate::DirDiffOptions::case_sensitivity] matches
+/// names between the trees.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CaseSensitivity {
+    /// Names only match if they are the same, so `A.txt` in one tree is missing from another
+    /// holding `a.txt`.
+    #[default]
+    Sensitive,
+    /// Names that only differ by case match, as they would on Windows or macOS.
+    ///
+    /// An entry whose name only differs by case from a sibling collides with it, and is reported
+    /// as missing from the other tree.
+    Insensitive,
+    /// Names that only differ by case match if either root is on a case-insensitive file system,
+    /// as found by [`crate::is_case_sensitive`].
+    ///
+    /// Trees that aren't local are taken to be case-sensitive.
+    Detect,
+}
+
+/// Is the file system holding the directory `dir` case-sensitive?
+///
+/// An entry of `dir` whose name has letters is looked up with their case swapped.  If there
+/// isn't any, an empty file is briefly created in `dir` to probe it instead, so a directory
+/// without such entries has to be writable.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// if !dir_diff::is_case_sensitive("target").unwrap() {
+///     println!("`A.txt` and `a.txt` are the same file here");
+/// }
+/// ```
+pub fn is_case_sensitive<P: AsRef<Path>>(dir: P) -> std::io::Result<bool> {
+    let dir = dir.as_ref();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        // A dangling symlink can't be told apart from a missing entry.
+        if entry.file_type()?.is_symlink() {
+            continue;
+        }
+        let Some(swapped) = swap_case(&entry.file_name()) else {
+            continue;
+        };
+        let swapped = dir.join(swapped);
+        return match std::fs::symlink_metadata(&swapped) {
+            // Both names being there is as telling as the swapped one missing.
+            Ok(_) => Ok(!same_file::is_same_file(entry.path(), swapped)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(true),
+            Err(err) => Err(err),
+        };
+    }
+
+    let name = format!(".dir-diff-case-probe-{}", std::process::id());
+    let probe = dir.join(&name);
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&probe)?;
+    let sensitive = match std::fs::symlink_metadata(dir.join(name.to_uppercase())) {
+        Ok(_) => Ok(false),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(true),
+        Err(err) => Err(err),
+    };
+    let removed = std::fs::remove_file(&probe);
+    let sensitive = sensitive?;
+    removed?;
+    Ok(sensitive)
+}
+
+/// Whether names should be matched regardless of case when comparing `left` and `right`.
+pub(crate) fn fold_case<L: TreeSource, R: TreeSource>(
+    sensitivity: CaseSensitivity,
+    left: &L,
+    right: &R,
+) -> bool {
+    let is_insensitive = |local: Option<std::path::PathBuf>| {
+        local
+            .map(|local| !is_case_sensitive(local).unwrap_or_else(|_| default_is_sensitive()))
+            .unwrap_or(false)
+    };
+    match sensitivity {
+        CaseSensitivity::Sensitive => false,
+        CaseSensitivity::Insensitive => true,
+        CaseSensitivity::Detect => {
+            is_insensitive(left.local_path(Path::new("")))
+                || is_insensitive(right.local_path(Path::new("")))
+        }
+    }
+}
+
+/// Whether file systems are usually case-sensitive on this platform, for when probing fails.
+fn default_is_sensitive() -> bool {
+    !cfg!(any(windows, target_os = "macos"))
+}
+
+/// Order names regardless of case, and names that only differ by case by their bytes.
+pub(crate) fn cmp_names(a: &OsStr, b: &OsStr) -> Ordering {
+    fold(a).cmp(&fold(b)).then_with(|| a.cmp(b))
+}
+
+/// Order relative paths component by component, regardless of case.
+pub(crate) fn cmp_paths_folded(a: &Path, b: &Path) -> Ordering {
+    a.iter().map(fold).cmp(b.iter().map(fold))
+}
+
+/// `name` in lowercase, if it is valid Unicode.
+pub(crate) fn fold(name: &OsStr) -> Cow<'_, OsStr> {
+    match name.to_str() {
+        Some(name) if name.chars().any(char::is_uppercase) => {
+            Cow::Owned(OsString::from(name.to_lowercase()))
+        }
+        _ => Cow::Borrowed(name),
+    }
+}
+
+/// `name` with the case of its letters swapped, if that changes it.
+fn swap_case(name: &OsStr) -> Option<OsString> {
+    let name = name.to_str()?;
+    let swapped: String = name
+        .chars()
+        .flat_map(|c| {
+            if c.is_lowercase() {
+                c.to_uppercase().collect::<Vec<_>>()
+            } else {
+                c.to_lowercase().collect()
+            }
+        })
+        .collect();
+    (swapped != name).then(|| swapped.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ordering() {
+        let mut names = ["b", "A.txt", "a.txt", "B", "a"].map(OsStr::new);
+        names.sort_by(|a, b| cmp_names(a, b));
+        assert_eq!(names, ["a", "A.txt", "a.txt", "B", "b"].map(OsStr::new));
+        assert_eq!(
+            cmp_paths_folded(Path::new("Docs/README"), Path::new("docs/readme")),
+            Ordering::Equal
+        );
+        assert_eq!(
+            cmp_paths_folded(Path::new("a/z"), Path::new("A.txt")),
+            Ordering::Less
+        );
+        assert_eq!(swap_case(OsStr::new("Hello")), Some("hELLO".into()));
+        assert_eq!(swap_case(OsStr::new("123")), None);
+    }
+}
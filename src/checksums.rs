@@ -0,0 +1,270 @@
+//! Writing and checking `SHA256SUMS` files, as written by `sha256sum`.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead as _, Read as _, Write};
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest as _, Sha256};
+
+use crate::error::{Phase, ResultExt as _};
+use crate::hash::{from_hex, to_hex};
+use crate::options::{DirDiffOptions, Settings};
+use crate::report::{DiffReport, Difference, DifferenceKind, Side};
+use crate::source::{full_path, FileType, FsSource, TreeSource};
+use crate::Error;
+
+/// Write the SHA-256 hash of every file in `dir` to `out`, in the format of `sha256sum`.
+///
+/// Files are listed by their path relative to `dir`, with `/` separators, in path order.
+/// Symlinks aren't followed, and so aren't listed, like other entries that aren't files.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let mut sums = std::fs::File::create("dist/SHA256SUMS").unwrap();
+/// dir_diff::write_checksums("dist/release", &mut sums).unwrap();
+/// ```
+pub fn write_checksums<P: AsRef<Path>>(dir: P, out: impl Write) -> Result<(), Error> {
+    DirDiffOptions::new().write_checksums(dir, out)
+}
+
+/// Check the files in `dir` against the checksum file at `sums`, as written by `sha256sum` or
+/// [`write_checksums`].
+///
+/// The checksum file is the left side of the report: files it lists that are missing from
+/// `dir` are [`DifferenceKind::OnlyInLeft`], files whose hash doesn't match are
+/// [`DifferenceKind::Content`], and files it doesn't list are [`DifferenceKind::OnlyInRight`].
+/// Exclude the checksum file itself with [`DirDiffOptions::exclude`] if it is inside `dir`.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let report = dir_diff::verify_checksums("dist/SHA256SUMS", "dist/release").unwrap();
+/// assert!(!report.is_different());
+/// ```
+pub fn verify_checksums<S: AsRef<Path>, P: AsRef<Path>>(
+    sums: S,
+    dir: P,
+) -> Result<DiffReport, Error> {
+    DirDiffOptions::new().verify_checksums(sums, dir)
+}
+
+impl DirDiffOptions {
+    /// Write the SHA-256 hash of every file in `dir` that isn't excluded to `out`.
+    ///
+    /// See [`crate::write_checksums`].
+    pub fn write_checksums<P: AsRef<Path>>(
+        &self,
+        dir: P,
+        mut out: impl Write,
+    ) -> Result<(), Error> {
+        write(&FsSource::new(dir.as_ref()), &self.settings()?, &mut out)
+    }
+
+    /// Check the files in `dir` that aren't excluded against the checksum file at `sums`.
+    ///
+    /// See [`crate::verify_checksums`].  Files the checksum file lists are excluded by the same
+    /// globs.
+    pub fn verify_checksums<S: AsRef<Path>, P: AsRef<Path>>(
+        &self,
+        sums: S,
+        dir: P,
+    ) -> Result<DiffReport, Error> {
+        verify(
+            sums.as_ref(),
+            &FsSource::new(dir.as_ref()),
+            &self.settings()?,
+        )
+    }
+}
+
+/// Write a line for each file in `source` to `out`, ordered by path.
+fn write<S: TreeSource>(source: &S, settings: &Settings, out: &mut dyn Write) -> Result<(), Error> {
+    for (path, hash) in hash_files(source, settings, Side::Left)? {
+        let line = format_line(&path, &hash);
+        out.write_all(line.as_bytes())
+            .context(Phase::Reading(Side::Left), || full_path(source, &path))?;
+    }
+    Ok(())
+}
+
+/// Compare the files listed in the checksum file at `sums`, on the left, with `source`.
+fn verify<S: TreeSource>(
+    sums: &Path,
+    source: &S,
+    settings: &Settings,
+) -> Result<DiffReport, Error> {
+    let file = std::fs::File::open(sums).context(Phase::Reading(Side::Left), || sums.to_owned())?;
+    let mut expected = BTreeMap::new();
+    for (number, line) in std::io::BufReader::new(file).lines().enumerate() {
+        let line = line.context(Phase::Reading(Side::Left), || sums.to_owned())?;
+        if line.is_empty() {
+            continue;
+        }
+        let (path, hash) = parse_line(&line).ok_or_else(|| {
+            Error::from(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("line {} isn't a checksum and a path", number + 1),
+            ))
+            .context(Phase::Reading(Side::Left), || sums.to_owned())
+        })?;
+        // As when walking, entries inside excluded directories are excluded too.
+        let excluded = path
+            .ancestors()
+            .take_while(|path| !path.as_os_str().is_empty())
+            .any(|path| settings.is_excluded(path));
+        if !excluded {
+            expected.insert(path, hash);
+        }
+    }
+
+    let mut actual = hash_files(source, settings, Side::Right)?;
+    let mut differences = Vec::new();
+    for (path, hash) in expected {
+        let kind = match actual.remove(&path) {
+            None => DifferenceKind::OnlyInLeft,
+            Some(actual) if actual != hash => DifferenceKind::Content,
+            Some(_) => continue,
+        };
+        differences.push(Difference::new(path, kind));
+    }
+    differences.extend(
+        actual
+            .into_keys()
+            .map(|path| Difference::new(path, DifferenceKind::OnlyInRight)),
+    );
+    differences.sort_by(|a, b| a.path().cmp(b.path()));
+    Ok(
+        DiffReport::new(sums.to_owned(), source.root().to_owned(), differences)
+            .with_separator(settings.path_separator()),
+    )
+}
+
+/// The SHA-256 hash of each file in `source` that isn't excluded, by relative path.
+///
+/// Symlinks aren't followed, and so aren't listed, like other entries that aren't files.
+fn hash_files<S: TreeSource>(
+    source: &S,
+    settings: &Settings,
+    side: Side,
+) -> Result<BTreeMap<PathBuf, [u8; 32]>, Error> {
+    let mut hashes = BTreeMap::new();
+    let mut dirs = vec![PathBuf::new()];
+    while let Some(dir) = dirs.pop() {
+        let entries = source
+            .read_dir(&dir)
+            .context(Phase::Walking(side), || full_path(source, &dir))?;
+        for entry in entries {
+            let path = dir.join(entry.name());
+            if settings.is_excluded(&path) {
+                continue;
+            }
+            match entry.file_type() {
+                FileType::Dir => dirs.push(path),
+                FileType::File => {
+                    let hash = settings
+                        .retry(|| sha256(source, &path))
+                        .context(Phase::Reading(side), || full_path(source, &path))?;
+                    hashes.insert(path, hash);
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(hashes)
+}
+
+/// The SHA-256 hash of the file at `path`.
+pub(crate) fn sha256<S: TreeSource>(source: &S, path: &Path) -> Result<[u8; 32], Error> {
+    let mut reader = source.open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(hasher.finalize().into())
+}
+
+/// A line as `sha256sum` writes it, escaping backslashes and newlines in the path.
+fn format_line(path: &Path, hash: &[u8; 32]) -> String {
+    let name = path
+        .iter()
+        .map(|component| component.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    let escaped = name.replace('\\', "\\\\").replace('\n', "\\n");
+    let prefix = if escaped == name { "" } else { "\\" };
+    format!("{prefix}{}  {escaped}\n", to_hex(hash))
+}
+
+/// The path and hash of a line, in text or binary mode.
+fn parse_line(line: &str) -> Option<(PathBuf, [u8; 32])> {
+    let (escaped, line) = match line.strip_prefix('\\') {
+        Some(line) => (true, line),
+        None => (false, line),
+    };
+    let (hex, name) = line.split_once(' ')?;
+    let name = name.strip_prefix(|c| c == ' ' || c == '*')?;
+    let hash = from_hex(hex)?;
+    let name = if escaped {
+        unescape(name)?
+    } else {
+        name.to_owned()
+    };
+    let path: PathBuf = name.trim_start_matches("./").split('/').collect();
+    Some((path, hash))
+}
+
+fn unescape(name: &str) -> Option<String> {
+    let mut unescaped = String::with_capacity(name.len());
+    let mut chars = name.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next()? {
+                '\\' => unescaped.push('\\'),
+                'n' => unescaped.push('\n'),
+                _ => return None,
+            }
+        } else {
+            unescaped.push(c);
+        }
+    }
+    Some(unescaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines() {
+        let hash = [0xab; 32];
+        for name in ["a.txt", "dir/b c.txt", "new\nline", "back\\slash"] {
+            let path: PathBuf = name.split('/').collect();
+            let line = format_line(&path, &hash);
+            assert_eq!(
+                parse_line(line.trim_end_matches('\n')),
+                Some((path, hash)),
+                "{line:?}"
+            );
+        }
+        assert_eq!(
+            format_line(Path::new("a"), &[0; 32]),
+            format!("{}  a\n", "0".repeat(64))
+        );
+        let binary = format!("{} *./bin/tool", "ab".repeat(32));
+        assert_eq!(
+            parse_line(&binary),
+            Some((PathBuf::from("bin").join("tool"), hash))
+        );
+        assert_eq!(parse_line("abc  a.txt"), None);
+    }
+}
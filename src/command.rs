@@ -0,0 +1,148 @@
+//! Comparing files with an external command, for formats only other tools can compare.
+
+use std::ffi::{OsStr, OsString};
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::source::TreeSource;
+use crate::Error;
+
+/// Run the command `template`, as passed to
+/// [`DirDiffOptions::compare_with_command`][crate::DirDiffOptions::compare_with_command], on two
+/// local files, and return whether it found them the same.
+///
+/// `at` is where errors say the comparison failed.
+pub(crate) fn same_files(
+    template: &str,
+    left: &Path,
+    right: &Path,
+    at: impl FnOnce() -> PathBuf,
+) -> Result<bool, Error> {
+    let mut words = template
+        .split_whitespace()
+        .map(|word| expand(word, left, right));
+    let Some(program) = words.next() else {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "the comparison command is empty",
+        )
+        .into());
+    };
+    let output = Command::new(&program)
+        .args(words)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|err| Error::Io {
+            source: err,
+            phase: None,
+            path: Some(PathBuf::from(program)),
+        })?;
+    match output.status.code() {
+        Some(0) => Ok(true),
+        Some(1) => Ok(false),
+        _ => Err(Error::Command {
+            command: template.to_owned(),
+            path: at(),
+            status: output.status,
+            stderr: String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        }),
+    }
+}
+
+/// Replace `%L` and `%R` in a word of the template with the files' paths, and `%%` with `%`.
+fn expand(word: &str, left: &Path, right: &Path) -> OsString {
+    let mut expanded = OsString::new();
+    let mut chars = word.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c.encode_utf8(&mut [0; 4]));
+            continue;
+        }
+        match chars.next() {
+            Some('L') => expanded.push(left),
+            Some('R') => expanded.push(right),
+            Some('%') => expanded.push("%"),
+            Some(other) => {
+                expanded.push("%");
+                expanded.push(other.encode_utf8(&mut [0; 4]));
+            }
+            None => expanded.push("%"),
+        }
+    }
+    expanded
+}
+
+/// A file a command can be pointed at: the file itself when the source is local, or a copy of it
+/// in the system's temporary directory, removed when dropped.
+#[derive(Debug)]
+pub(crate) enum LocalFile {
+    Local(PathBuf),
+    Copy(PathBuf),
+}
+
+impl LocalFile {
+    pub(crate) fn new(source: &impl TreeSource, path: &Path) -> Result<Self, Error> {
+        if let Some(local) = source.local_path(path) {
+            return Ok(LocalFile::Local(local));
+        }
+        let mut contents = Vec::new();
+        std::io::copy(&mut source.open(path)?, &mut contents)?;
+        // Keep the file's name, as some tools tell formats apart by extension.
+        let name = path.file_name().unwrap_or_else(|| OsStr::new("file"));
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        loop {
+            let mut file_name = OsString::from(format!(
+                "dir-diff-{}-{}-",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            ));
+            file_name.push(name);
+            let copy = std::env::temp_dir().join(file_name);
+            match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&copy)
+            {
+                Ok(mut file) => {
+                    let copy = LocalFile::Copy(copy);
+                    file.write_all(&contents)?;
+                    return Ok(copy);
+                }
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        match self {
+            LocalFile::Local(path) | LocalFile::Copy(path) => path,
+        }
+    }
+}
+
+impl Drop for LocalFile {
+    fn drop(&mut self) {
+        if let LocalFile::Copy(path) = self {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let (left, right) = (Path::new("a/x.bin"), Path::new("b/x.bin"));
+        assert_eq!(expand("%L", left, right), "a/x.bin");
+        assert_eq!(expand("--right=%R", left, right), "--right=b/x.bin");
+        assert_eq!(expand("100%%", left, right), "100%");
+        assert_eq!(expand("%x%", left, right), "%x%");
+    }
+}
@@ -0,0 +1,99 @@
+//! Serializing reports and Merkle trees to binary formats, which are smaller and faster to parse
+//! than JSON for large trees.
+
+use std::io::{Read, Write};
+
+use crate::digest::MerkleTree;
+use crate::report::DiffReport;
+
+/// A binary format for [`DiffReport::write_compact`] and [`MerkleTree::write_compact`].
+///
+/// Both formats hold the same data as the JSON the `serde` feature serializes to, field names
+/// included, so a report from a newer version can still be read by an older one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CompactFormat {
+    /// CBOR, as in RFC 8949.  Needs the `cbor` feature.
+    #[cfg(feature = "cbor")]
+    Cbor,
+    /// `MessagePack`.  Needs the `msgpack` feature.
+    #[cfg(feature = "msgpack")]
+    MessagePack,
+}
+
+impl DiffReport {
+    /// Write the report to `out` in a compact binary `format`, to be read back with
+    /// [`DiffReport::read_compact`].
+    pub fn write_compact(&self, format: CompactFormat, out: impl Write) -> std::io::Result<()> {
+        write(format, self, out)
+    }
+
+    /// Read a report written by [`DiffReport::write_compact`] in the same `format`.
+    pub fn read_compact(format: CompactFormat, input: impl Read) -> std::io::Result<Self> {
+        read(format, input)
+    }
+}
+
+impl MerkleTree {
+    /// Write the table of digests to `out` in a compact binary `format`, to be read back with
+    /// [`MerkleTree::read_compact`].
+    ///
+    /// Unlike [`MerkleTree::write_to`], a tree with a path that isn't valid UTF-8 can't be
+    /// written, and is an error.
+    pub fn write_compact(&self, format: CompactFormat, out: impl Write) -> std::io::Result<()> {
+        write(format, self, out)
+    }
+
+    /// Read a table of digests written by [`MerkleTree::write_compact`] in the same `format`.
+    pub fn read_compact(format: CompactFormat, input: impl Read) -> std::io::Result<Self> {
+        read(format, input)
+    }
+}
+
+fn write<T: serde::Serialize>(
+    format: CompactFormat,
+    value: &T,
+    out: impl Write,
+) -> std::io::Result<()> {
+    let mut out = std::io::BufWriter::new(out);
+    match format {
+        #[cfg(feature = "cbor")]
+        CompactFormat::Cbor => {
+            ciborium::into_writer(value, &mut out).map_err(|err| match err {
+                ciborium::ser::Error::Io(err) => err,
+                ciborium::ser::Error::Value(message) => invalid_data(message),
+            })?;
+        }
+        #[cfg(feature = "msgpack")]
+        CompactFormat::MessagePack => {
+            // Named fields, as fields left out when empty would misalign positional ones.
+            rmp_serde::encode::write_named(&mut out, value)
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+        }
+    }
+    out.flush()
+}
+
+fn read<T: serde::de::DeserializeOwned>(
+    format: CompactFormat,
+    input: impl Read,
+) -> std::io::Result<T> {
+    let input = std::io::BufReader::new(input);
+    match format {
+        #[cfg(feature = "cbor")]
+        CompactFormat::Cbor => ciborium::from_reader(input).map_err(|err| match err {
+            ciborium::de::Error::Io(err) => err,
+            err => invalid_data(err.to_string()),
+        }),
+        #[cfg(feature = "msgpack")]
+        CompactFormat::MessagePack => rmp_serde::from_read(input).map_err(|err| match err {
+            rmp_serde::decode::Error::InvalidMarkerRead(err)
+            | rmp_serde::decode::Error::InvalidDataRead(err) => err,
+            err => invalid_data(err.to_string()),
+        }),
+    }
+}
+
+fn invalid_data(message: String) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,200 @@
+//! Comparing the contents of files in formats only their users understand.
+
+use std::path::Path;
+use std::sync::Arc;
+
+/// Compares the contents of two files semantically, registered by extension with
+/// [`DirDiffOptions::comparator`][crate::DirDiffOptions::comparator].
+///
+/// Implemented for closures taking both files' contents, so a comparator can be a function.
+/// It may be called from several threads at once, such as by [`crate::compare_many`].
+///
+/// # Examples
+///
+/// ```
+/// extern crate dir_diff;
+///
+/// // Text files that only differ in case are the same.
+/// let options = dir_diff::DirDiffOptions::new().comparator("txt", |left: &[u8], right: &[u8]| {
+///     left.eq_ignore_ascii_case(right)
+/// });
+/// assert!(!options
+///     .is_different("tests/easy/good/dir1", "tests/easy/good/dir2")
+///     .unwrap());
+/// ```
+pub trait Comparator: Send + Sync {
+    /// Whether `left` and `right`, the contents of two files that differ byte for byte, hold the
+    /// same data.
+    fn same(&self, left: &[u8], right: &[u8]) -> bool;
+}
+
+impl<F: Fn(&[u8], &[u8]) -> bool + Send + Sync> Comparator for F {
+    fn same(&self, left: &[u8], right: &[u8]) -> bool {
+        self(left, right)
+    }
+}
+
+/// The comparators registered on [`DirDiffOptions`][crate::DirDiffOptions], by extension.
+#[derive(Clone, Default)]
+pub(crate) struct Comparators(Vec<(String, Arc<dyn Comparator>)>);
+
+impl Comparators {
+    pub(crate) fn push(&mut self, extension: String, comparator: Arc<dyn Comparator>) {
+        self.0.push((extension, comparator));
+    }
+
+    /// The extensions with a comparator registered, without the dot.
+    pub(crate) fn extensions(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(|(extension, _)| extension.as_str())
+    }
+
+    /// The comparator for the file at `path`, the one registered last for its extension.
+    pub(crate) fn get(&self, path: &Path) -> Option<Arc<dyn Comparator>> {
+        let extension = path.extension()?;
+        self.0
+            .iter()
+            .rev()
+            .find(|(registered, _)| extension == registered.as_str())
+            .map(|(_, comparator)| comparator.clone())
+    }
+}
+
+impl std::fmt::Debug for Comparators {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|(extension, _)| extension))
+            .finish()
+    }
+}
+
+/// A [`Comparator`] for streams of length-delimited protobuf messages, as written by
+/// `writeDelimitedTo` in Java or `SerializeDelimitedToOstream` in C++.
+///
+/// Without the schema, each message is compared by its top-level fields, whatever order they
+/// were written in: fields with the same number stay in order, so repeated fields still have to
+/// match element for element.  Varints are compared by value, so different encodings of the
+/// same number match, but nested messages are compared byte for byte.  Streams that aren't
+/// valid protobuf are the same only byte for byte.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let report = dir_diff::DirDiffOptions::new()
+///     .comparator("pb", dir_diff::DelimitedProtobuf)
+///     .diff("target/events", "tests/golden/events")
+///     .unwrap();
+/// assert!(!report.is_different());
+/// ```
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[allow(clippy::exhaustive_structs)] // a unit struct has nothing to add
+pub struct DelimitedProtobuf;
+
+impl Comparator for DelimitedProtobuf {
+    fn same(&self, left: &[u8], right: &[u8]) -> bool {
+        match (protobuf::messages(left), protobuf::messages(right)) {
+            (Some(left), Some(right)) => left == right,
+            _ => left == right,
+        }
+    }
+}
+
+/// Just enough of the protobuf wire format to compare messages without their schema.
+mod protobuf {
+    /// The value of a field, as far as it can be told without the schema.
+    #[derive(Debug, PartialEq, Eq)]
+    pub(super) enum Value<'a> {
+        Varint(u64),
+        Bytes(&'a [u8]),
+    }
+
+    /// Every message of a length-delimited stream, each as its fields sorted by number.
+    pub(super) fn messages(mut stream: &[u8]) -> Option<Vec<Vec<(u64, Value<'_>)>>> {
+        let mut messages = Vec::new();
+        while !stream.is_empty() {
+            let len = usize::try_from(varint(&mut stream)?).ok()?;
+            let message = take(&mut stream, len)?;
+            messages.push(fields(message)?);
+        }
+        Some(messages)
+    }
+
+    fn fields(mut message: &[u8]) -> Option<Vec<(u64, Value<'_>)>> {
+        let mut fields = Vec::new();
+        while !message.is_empty() {
+            let key = varint(&mut message)?;
+            let value = match key & 7 {
+                0 => Value::Varint(varint(&mut message)?),
+                1 => Value::Bytes(take(&mut message, 8)?),
+                2 => {
+                    let len = usize::try_from(varint(&mut message)?).ok()?;
+                    Value::Bytes(take(&mut message, len)?)
+                }
+                5 => Value::Bytes(take(&mut message, 4)?),
+                // Groups are deprecated, and can't be skipped without parsing them.
+                _ => return None,
+            };
+            fields.push((key >> 3, value));
+        }
+        // Stable, so repeated fields keep their order.
+        fields.sort_by_key(|(number, _)| *number);
+        Some(fields)
+    }
+
+    fn varint(input: &mut &[u8]) -> Option<u64> {
+        let mut value = 0_u64;
+        for shift in (0..64).step_by(7) {
+            let (&byte, rest) = input.split_first()?;
+            *input = rest;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn take<'a>(input: &mut &'a [u8], len: usize) -> Option<&'a [u8]> {
+        if input.len() < len {
+            return None;
+        }
+        let (taken, rest) = input.split_at(len);
+        *input = rest;
+        Some(taken)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delimited_protobuf() {
+        // Field 1 = 150, field 2 = "hi".
+        let message = [0x08, 0x96, 0x01, 0x12, 0x02, b'h', b'i'];
+        let reordered = [0x12, 0x02, b'h', b'i', 0x08, 0x96, 0x01];
+        let padded = [0x12, 0x02, b'h', b'i', 0x08, 0x96, 0x81, 0x00];
+        let stream = |messages: &[&[u8]]| {
+            let mut stream = Vec::new();
+            for message in messages {
+                stream.push(u8::try_from(message.len()).unwrap());
+                stream.extend_from_slice(message);
+            }
+            stream
+        };
+        let comparator = DelimitedProtobuf;
+        assert!(comparator.same(&stream(&[&message]), &stream(&[&reordered])));
+        assert!(comparator.same(&stream(&[&message]), &stream(&[&padded])));
+        assert!(!comparator.same(&stream(&[&message]), &stream(&[&message, &message])));
+        assert!(!comparator.same(&stream(&[&message]), &stream(&[&message[..3]])));
+
+        // Repeated fields keep their order.
+        let repeated = [0x08, 0x01, 0x08, 0x02];
+        let swapped = [0x08, 0x02, 0x08, 0x01];
+        assert!(!comparator.same(&stream(&[&repeated]), &stream(&[&swapped])));
+
+        // Truncated streams are compared byte for byte.
+        assert!(!comparator.same(&[0x05, 0x08], &[0x05, 0x09]));
+    }
+}
@@ -0,0 +1,1598 @@
+//! The comparison engine shared by every public entry point.
+//!
+//! Both trees are walked depth-first with siblings sorted by file name, which yields entries in
+//! the same order as comparing their relative paths component-wise.  That lets the two walks be
+//! merged like sorted lists, one entry at a time, without buffering either tree.
+
+use std::cmp::Ordering;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+
+use crate::blocks;
+use crate::cache::HashCache;
+use crate::case;
+use crate::command::{self, LocalFile};
+use crate::comparator::Comparator;
+use crate::error::{Limit, Phase, ResultExt as _};
+use crate::hooks::{SkipReason, Visit};
+use crate::incremental::{Incremental, StatCache};
+use crate::options::Settings;
+use crate::prefetch::Prefetcher;
+use crate::reflink;
+use crate::report::{BlockSummary, Check, Difference, DifferenceKind, Side, Similarity};
+use crate::source::{full_path, FileType, Metadata, SourceEntry, TreeSource};
+use crate::text::{self, TextRules};
+use crate::textdiff;
+use crate::Error;
+
+/// Lazily yields the differences between two trees, in relative path order.
+///
+/// Differences that are allowed are set aside rather than yielded.
+pub(crate) struct Differences<'c, L, R> {
+    left: Walker<L>,
+    right: Walker<R>,
+    settings: Settings,
+    cache: Option<&'c HashCache>,
+    /// A cache owned by the walk itself, when it outlives the options it was started from.
+    own_cache: Option<HashCache>,
+    /// The results of an earlier comparison, for [`DirDiffOptions::diff_incremental`].
+    ///
+    /// [`DirDiffOptions::diff_incremental`]: crate::DirDiffOptions::diff_incremental
+    incremental: Option<Incremental<'c>>,
+    allowed: Vec<Difference>,
+    /// A difference found before the walk started, to be yielded first.
+    pending: Option<Difference>,
+    /// The total size of the files whose contents were compared so far, on both sides.
+    bytes: u64,
+    /// Whether names are matched regardless of case.
+    fold_case: bool,
+    done: bool,
+}
+
+impl<'c, L: TreeSource, R: TreeSource> Differences<'c, L, R> {
+    pub(crate) fn new(
+        left: L,
+        right: R,
+        settings: Settings,
+        cache: Option<&'c HashCache>,
+    ) -> Result<Self, Error> {
+        check_overlap(&left, &right)?;
+        let pending = (settings.compare_root_names() && root_name(&left) != root_name(&right))
+            .then(|| Difference::new(PathBuf::new(), DifferenceKind::RootName));
+        let mut differences = Self::new_at(left, right, settings, cache, Path::new(""))?;
+        differences.pending = pending;
+        Ok(differences)
+    }
+
+    /// Only compare the entries under `start`, a directory on both sides.
+    ///
+    /// Differences are still reported relative to the roots.
+    pub(crate) fn new_at(
+        left: L,
+        right: R,
+        settings: Settings,
+        cache: Option<&'c HashCache>,
+        start: &Path,
+    ) -> Result<Self, Error> {
+        // Only local directories are prefetched, so virtual trees don't need the threads.
+        let prefetch = (settings.walk_threads() > 1
+            && (left.lists_local_dirs() || right.lists_local_dirs()))
+        .then(|| Arc::new(Prefetcher::new(settings.walk_threads())));
+        let fold_case = case::fold_case(settings.case_sensitivity(), &left, &right);
+        Ok(Self {
+            left: Walker::new(left, Side::Left, start, prefetch.clone(), fold_case)?,
+            right: Walker::new(right, Side::Right, start, prefetch, fold_case)?,
+            settings,
+            cache,
+            own_cache: None,
+            incremental: None,
+            allowed: Vec::new(),
+            pending: None,
+            bytes: 0,
+            fold_case,
+            done: false,
+        })
+    }
+
+    /// Use `cache`, owned by the walk, for the hashes of local files.
+    pub(crate) fn with_own_cache(mut self, cache: HashCache) -> Self {
+        self.own_cache = Some(cache);
+        self
+    }
+
+    pub(crate) fn own_cache(&self) -> Option<&HashCache> {
+        self.own_cache.as_ref()
+    }
+
+    /// Reuse the results of an earlier comparison for files that haven't changed since.
+    pub(crate) fn with_incremental(mut self, incremental: Incremental<'c>) -> Self {
+        self.incremental = Some(incremental);
+        self
+    }
+
+    /// The files compared so far, if reusing the results of an earlier comparison.
+    pub(crate) fn take_stats(&mut self) -> Option<StatCache> {
+        self.incremental.take().map(Incremental::into_stats)
+    }
+
+    /// The differences skipped so far because they are allowed.
+    pub(crate) fn into_allowed(self) -> Vec<Difference> {
+        self.allowed
+    }
+
+    fn step(&mut self) -> Result<Option<Difference>, Error> {
+        if let Some(difference) = self.pending.take() {
+            return Ok(Some(difference));
+        }
+        loop {
+            if self.settings.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+            if self.settings.is_past_deadline() {
+                return Err(Error::DeadlineExceeded);
+            }
+            self.left.fill(&self.settings)?;
+            self.right.fill(&self.settings)?;
+
+            let ordering = match (&self.left.peeked, &self.right.peeked) {
+                (None, None) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(
+                        left_entries = self.left.entries,
+                        right_entries = self.right.entries,
+                        bytes = self.bytes,
+                        "finished walking"
+                    );
+                    return Ok(None);
+                }
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (Some(left), Some(right)) if self.fold_case => {
+                    case::cmp_paths_folded(&left.relative, &right.relative)
+                }
+                (Some(left), Some(right)) => left.relative.cmp(&right.relative),
+            };
+
+            match ordering {
+                Ordering::Less => {
+                    let left = self.left.take_and_skip();
+                    if self.settings.is_extra_ignored(Side::Left, &left.relative) {
+                        self.settings
+                            .hooks()
+                            .skip(&left.relative, SkipReason::Extra(Side::Left));
+                        continue;
+                    }
+                    let metadata = self.left.metadata(&left)?;
+                    if self.settings.empty_files_as_missing()
+                        && metadata.file_type() == FileType::File
+                        && metadata.is_empty()
+                    {
+                        self.settings
+                            .hooks()
+                            .skip(&left.relative, SkipReason::Empty(Side::Left));
+                        continue;
+                    }
+                    self.settings
+                        .hooks()
+                        .explain(&left.relative, Check::OnlyIn(Side::Left), false);
+                    return Ok(Some(
+                        Difference::new(left.relative, DifferenceKind::OnlyInLeft)
+                            .with_metadata(Some(metadata), None),
+                    ));
+                }
+                Ordering::Greater => {
+                    let right = self.right.take_and_skip();
+                    if self.settings.is_extra_ignored(Side::Right, &right.relative) {
+                        self.settings
+                            .hooks()
+                            .skip(&right.relative, SkipReason::Extra(Side::Right));
+                        continue;
+                    }
+                    let metadata = self.right.metadata(&right)?;
+                    if self.settings.empty_files_as_missing()
+                        && metadata.file_type() == FileType::File
+                        && metadata.is_empty()
+                    {
+                        self.settings
+                            .hooks()
+                            .skip(&right.relative, SkipReason::Empty(Side::Right));
+                        continue;
+                    }
+                    self.settings.hooks().explain(
+                        &right.relative,
+                        Check::OnlyIn(Side::Right),
+                        false,
+                    );
+                    return Ok(Some(
+                        Difference::new(right.relative, DifferenceKind::OnlyInRight)
+                            .with_metadata(None, Some(metadata)),
+                    ));
+                }
+                Ordering::Equal => {
+                    let left = self.left.take();
+                    let right = self.right.take();
+                    let hooks = self.settings.hooks();
+                    hooks.explain(&left.relative, Check::Name, true);
+
+                    if left.escapes || right.escapes {
+                        hooks.explain(&left.relative, Check::SymlinkEscape, false);
+                        return self.difference(left, right, DifferenceKind::SymlinkEscape);
+                    }
+
+                    // Both dangling, they are compared as the links they are.
+                    if left.dangling != right.dangling {
+                        hooks.explain(&left.relative, Check::BrokenSymlink, false);
+                        let side = if left.dangling {
+                            Side::Left
+                        } else {
+                            Side::Right
+                        };
+                        self.left.skip_current_dir();
+                        self.right.skip_current_dir();
+                        return self.difference(
+                            left,
+                            right,
+                            DifferenceKind::BrokenSymlink { side },
+                        );
+                    }
+
+                    if left.file_type != right.file_type {
+                        hooks.explain(&left.relative, Check::FileType, false);
+                        self.left.skip_current_dir();
+                        self.right.skip_current_dir();
+                        return self.difference(left, right, DifferenceKind::FileType);
+                    }
+
+                    match left.file_type {
+                        FileType::Dir => {
+                            if self.same_file(&left.target, &right.target) {
+                                self.left.skip_current_dir();
+                                self.right.skip_current_dir();
+                                hooks.skip(&left.relative, SkipReason::SameFile);
+                            } else if self.settings.same_file_system()
+                                && (self.left.crosses_file_system(&left.target)
+                                    || self.right.crosses_file_system(&right.target))
+                            {
+                                self.left.skip_current_dir();
+                                self.right.skip_current_dir();
+                                hooks.skip(&left.relative, SkipReason::OtherFileSystem);
+                            } else if hooks.enter_dir(&left.relative) == Visit::Skip {
+                                self.left.skip_current_dir();
+                                self.right.skip_current_dir();
+                                hooks.skip(&left.relative, SkipReason::Hook);
+                            }
+                            if self.settings.compare_permissions() {
+                                let left_metadata = self.left.metadata(&left)?;
+                                let right_metadata = self.right.metadata(&right)?;
+                                if differs(
+                                    left_metadata.permissions(),
+                                    right_metadata.permissions(),
+                                ) {
+                                    return Ok(Some(
+                                        Difference::new(left.relative, DifferenceKind::Permissions)
+                                            .with_metadata(
+                                                Some(left_metadata),
+                                                Some(right_metadata),
+                                            ),
+                                    ));
+                                }
+                            }
+                            if self.acls_differ(&left.target, &right.target)? {
+                                return self.difference(left, right, DifferenceKind::Acl);
+                            }
+                        }
+                        FileType::File => {
+                            let started = self.settings.hooks().timer();
+                            let left_metadata = self.left.metadata(&left)?;
+                            let right_metadata = self.right.metadata(&right)?;
+                            if self.settings.ignore_contents() {
+                                let kind =
+                                    self.metadata_difference(&left_metadata, &right_metadata);
+                                self.settings.hooks().explain(
+                                    &left.relative,
+                                    Check::Metadata,
+                                    kind.is_none(),
+                                );
+                                if let Some(kind) = kind {
+                                    return Ok(Some(
+                                        Difference::new(left.relative, kind).with_metadata(
+                                            Some(left_metadata),
+                                            Some(right_metadata),
+                                        ),
+                                    ));
+                                }
+                                if self.acls_differ(&left.target, &right.target)? {
+                                    return self.difference(left, right, DifferenceKind::Acl);
+                                }
+                                continue;
+                            }
+                            let reused = self.incremental.as_mut().and_then(|incremental| {
+                                incremental.unchanged(
+                                    &left.relative,
+                                    &left_metadata,
+                                    &right_metadata,
+                                )
+                            });
+                            #[cfg(feature = "tracing")]
+                            if let Some(reused) = &reused {
+                                tracing::trace!(
+                                    path = %left.relative.display(),
+                                    same = reused.is_none(),
+                                    "reused earlier result"
+                                );
+                            }
+                            if reused.is_some() {
+                                self.settings.hooks().explain(
+                                    &left.relative,
+                                    Check::Reused,
+                                    matches!(reused, Some(None)),
+                                );
+                            }
+                            if let Some(Some(difference)) = reused {
+                                self.settings.hooks().compare_file(
+                                    &left.relative,
+                                    false,
+                                    started.elapsed(),
+                                );
+                                return Ok(Some(difference));
+                            }
+                            if let Some(tolerance) = self.settings.size_tolerance(&left.relative) {
+                                // Only the sizes decide, so no other comparison reads the files.
+                                let same =
+                                    left_metadata.len().abs_diff(right_metadata.len()) <= tolerance;
+                                self.settings.hooks().explain(
+                                    &left.relative,
+                                    Check::SizeTolerance,
+                                    same,
+                                );
+                                if reused.is_none() {
+                                    if let Some(incremental) = &mut self.incremental {
+                                        incremental.record(
+                                            &left.relative,
+                                            &left_metadata,
+                                            &right_metadata,
+                                        );
+                                    }
+                                }
+                                self.settings.hooks().compare_file(
+                                    &left.relative,
+                                    same,
+                                    started.elapsed(),
+                                );
+                                let kind = if same {
+                                    let kind =
+                                        self.metadata_difference(&left_metadata, &right_metadata);
+                                    if self.settings.compare_permissions()
+                                        || self.settings.compare_modified()
+                                    {
+                                        self.settings.hooks().explain(
+                                            &left.relative,
+                                            Check::Metadata,
+                                            kind.is_none(),
+                                        );
+                                    }
+                                    kind
+                                } else {
+                                    Some(DifferenceKind::Content)
+                                };
+                                if let Some(kind) = kind {
+                                    return Ok(Some(
+                                        Difference::new(left.relative, kind).with_metadata(
+                                            Some(left_metadata),
+                                            Some(right_metadata),
+                                        ),
+                                    ));
+                                }
+                                if self.acls_differ(&left.target, &right.target)? {
+                                    return self.difference(left, right, DifferenceKind::Acl);
+                                }
+                                continue;
+                            }
+                            let delimiter = self.settings.record_delimiter(&left.relative);
+                            let command = self
+                                .settings
+                                .compare_command(&left.relative)
+                                .map(str::to_owned);
+                            let rules = self.settings.text_rules(&left.relative);
+                            // Files of different sizes are only read to be normalized or have
+                            // their records counted, or by a command.
+                            if reused.is_none()
+                                && (delimiter.is_some()
+                                    || command.is_some()
+                                    || !rules.is_empty()
+                                    || left_metadata.len() == right_metadata.len())
+                            {
+                                self.count_bytes(left_metadata.len() + right_metadata.len())?;
+                            }
+                            let same = if reused.is_some() {
+                                true
+                            } else if let Some(delimiter) = delimiter {
+                                let same = self.settings.retry(|| {
+                                    self.same_record_count(&left.target, &right.target, delimiter)
+                                })?;
+                                self.settings.hooks().explain(
+                                    &left.relative,
+                                    Check::RecordCount,
+                                    same,
+                                );
+                                same
+                            } else if let Some(command) = &command {
+                                let same = self.settings.retry(|| {
+                                    self.same_by_command(command, &left.target, &right.target)
+                                })?;
+                                self.settings
+                                    .hooks()
+                                    .explain(&left.relative, Check::Command, same);
+                                same
+                            } else if rules.is_empty() {
+                                let compared = self.settings.retry(|| {
+                                    self.same_contents_in_time(
+                                        (&left.target, &left_metadata),
+                                        (&right.target, &right_metadata),
+                                    )
+                                })?;
+                                let Some((same, check)) = compared else {
+                                    self.settings.hooks().explain(
+                                        &left.relative,
+                                        Check::Timeout,
+                                        false,
+                                    );
+                                    return Ok(Some(
+                                        Difference::new(left.relative, DifferenceKind::Timeout)
+                                            .with_metadata(
+                                                Some(left_metadata),
+                                                Some(right_metadata),
+                                            ),
+                                    ));
+                                };
+                                self.settings.hooks().explain(&left.relative, check, same);
+                                same
+                            } else {
+                                let same = self.settings.retry(|| {
+                                    self.same_text(&rules, &left.target, &right.target)
+                                })?;
+                                self.settings
+                                    .hooks()
+                                    .explain(&left.relative, Check::Text, same);
+                                same
+                            };
+                            let explained = |check, same| {
+                                self.settings.hooks().explain(&left.relative, check, same);
+                                same
+                            };
+                            #[cfg(feature = "binary")]
+                            let same = same
+                                || (self.settings.ignore_build_ids()
+                                    && explained(
+                                        Check::Format,
+                                        self.same_build(
+                                            (&left.target, &left_metadata),
+                                            (&right.target, &right_metadata),
+                                        )?,
+                                    ));
+                            #[cfg(feature = "zip")]
+                            let same = same
+                                || (self.settings.is_archive(&left.relative)
+                                    && explained(
+                                        Check::Format,
+                                        self.same_archive(&left.target, &right.target)?,
+                                    ));
+                            #[cfg(feature = "csv")]
+                            let same = same
+                                || match self.settings.csv_epsilon(&left.relative) {
+                                    Some(epsilon) => explained(
+                                        Check::Format,
+                                        self.same_csv(
+                                            &left.relative,
+                                            (&left.target, &right.target),
+                                            epsilon,
+                                        )?,
+                                    ),
+                                    None => false,
+                                };
+                            let same = same
+                                || match self.settings.comparator(&left.relative) {
+                                    Some(comparator) => explained(
+                                        Check::Comparator,
+                                        self.same_with(
+                                            comparator.as_ref(),
+                                            &left.target,
+                                            &right.target,
+                                        )?,
+                                    ),
+                                    None => false,
+                                };
+                            let same = same || {
+                                let placeholders = self.settings.placeholders(&left.relative);
+                                !placeholders.is_empty()
+                                    && explained(
+                                        Check::Placeholders,
+                                        self.same_placeholders(
+                                            &placeholders,
+                                            &left.target,
+                                            &right.target,
+                                        )?,
+                                    )
+                            };
+                            if self.settings.detect_volatile() {
+                                let left_after = self.left.restat(&left)?;
+                                let right_after = self.right.restat(&right)?;
+                                if left_after != left_metadata || right_after != right_metadata {
+                                    return Ok(Some(
+                                        Difference::new(left.relative, DifferenceKind::Volatile)
+                                            .with_metadata(Some(left_after), Some(right_after)),
+                                    ));
+                                }
+                            }
+                            if reused.is_none() {
+                                if let Some(incremental) = &mut self.incremental {
+                                    incremental.record(
+                                        &left.relative,
+                                        &left_metadata,
+                                        &right_metadata,
+                                    );
+                                }
+                            }
+                            self.settings.hooks().compare_file(
+                                &left.relative,
+                                same,
+                                started.elapsed(),
+                            );
+                            if !same {
+                                if self.settings.detect_encoding_changes()
+                                    && self.same_text_encoded_differently(
+                                        (&left.target, &left_metadata),
+                                        (&right.target, &right_metadata),
+                                    )?
+                                {
+                                    return Ok(Some(
+                                        Difference::new(left.relative, DifferenceKind::Encoding)
+                                            .with_metadata(
+                                                Some(left_metadata),
+                                                Some(right_metadata),
+                                            ),
+                                    ));
+                                }
+                                let similarity = if self.settings.similarity() {
+                                    self.similarity(
+                                        (&left.target, &left_metadata),
+                                        (&right.target, &right_metadata),
+                                    )?
+                                } else {
+                                    None
+                                };
+                                let blocks = match self.settings.summarize_blocks() {
+                                    Some(block_size) => Some(self.summarize_blocks(
+                                        &left.target,
+                                        &right.target,
+                                        block_size,
+                                    )?),
+                                    None => None,
+                                };
+                                return Ok(Some(
+                                    Difference::new(left.relative, DifferenceKind::Content)
+                                        .with_metadata(Some(left_metadata), Some(right_metadata))
+                                        .with_similarity(similarity)
+                                        .with_blocks(blocks),
+                                ));
+                            }
+                            let kind = self.metadata_difference(&left_metadata, &right_metadata);
+                            if self.settings.compare_permissions()
+                                || self.settings.compare_modified()
+                            {
+                                self.settings.hooks().explain(
+                                    &left.relative,
+                                    Check::Metadata,
+                                    kind.is_none(),
+                                );
+                            }
+                            if let Some(kind) = kind {
+                                return Ok(Some(
+                                    Difference::new(left.relative, kind)
+                                        .with_metadata(Some(left_metadata), Some(right_metadata)),
+                                ));
+                            }
+                            if self.acls_differ(&left.target, &right.target)? {
+                                return Ok(Some(
+                                    Difference::new(left.relative, DifferenceKind::Acl)
+                                        .with_metadata(Some(left_metadata), Some(right_metadata)),
+                                ));
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    /// How the metadata of two files with the same contents differs, among what is compared.
+    fn metadata_difference(&self, left: &Metadata, right: &Metadata) -> Option<DifferenceKind> {
+        if self.settings.compare_permissions() && differs(left.permissions(), right.permissions()) {
+            Some(DifferenceKind::Permissions)
+        } else if self.settings.compare_modified() && differs(left.modified(), right.modified()) {
+            Some(DifferenceKind::Modified)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the local entries at `left` and `right` have different ACLs, if they are compared.
+    #[cfg(feature = "acl")]
+    fn acls_differ(&self, left: &Path, right: &Path) -> Result<bool, Error> {
+        if !self.settings.compare_acls() {
+            return Ok(false);
+        }
+        let (Some(left), Some(right)) = (
+            self.left.source.local_path(left),
+            self.right.source.local_path(right),
+        ) else {
+            return Ok(false);
+        };
+        let left_acl =
+            crate::acl::read(&left).context(Phase::Reading(Side::Left), || left.clone())?;
+        let right_acl =
+            crate::acl::read(&right).context(Phase::Reading(Side::Right), || right.clone())?;
+        Ok(left_acl != right_acl)
+    }
+
+    #[cfg(not(feature = "acl"))]
+    fn acls_differ(&self, _left: &Path, _right: &Path) -> Result<bool, Error> {
+        Ok(false)
+    }
+
+    /// Count `bytes` more to be read, unless that takes the total past the limit.
+    fn count_bytes(&mut self, bytes: u64) -> Result<(), Error> {
+        self.bytes = self.bytes.saturating_add(bytes);
+        #[cfg(feature = "tracing")]
+        tracing::trace!(bytes, total = self.bytes, "reading files");
+        self.settings.hooks().bytes_read(bytes);
+        match self.settings.max_total_bytes() {
+            Some(max) if max < self.bytes => Err(Error::LimitExceeded {
+                limit: Limit::TotalBytes(max),
+                side: None,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// A difference between two entries at the same path, recording both sides' metadata.
+    fn difference(
+        &self,
+        left: Entry,
+        right: Entry,
+        kind: DifferenceKind,
+    ) -> Result<Option<Difference>, Error> {
+        let left_metadata = self.left.metadata(&left)?;
+        let right_metadata = self.right.metadata(&right)?;
+        Ok(Some(
+            Difference::new(left.relative, kind)
+                .with_metadata(Some(left_metadata), Some(right_metadata)),
+        ))
+    }
+
+    /// Are `left` and `right` the same local file or directory, such as through a symlink or a
+    /// bind mount?
+    fn same_file(&self, left: &Path, right: &Path) -> bool {
+        let (Some(left), Some(right)) = (
+            self.left.source.local_path(left),
+            self.right.source.local_path(right),
+        ) else {
+            return false;
+        };
+        let _open_files = self.settings.open_files(2);
+        same_file::is_same_file(left, right).unwrap_or(false)
+    }
+
+    /// Compare two files like [`Self::same_contents`], or `None` if that takes longer than the
+    /// settings allow.
+    fn same_contents_in_time(
+        &self,
+        (left, left_metadata): (&Path, &Metadata),
+        (right, right_metadata): (&Path, &Metadata),
+    ) -> Result<Option<(bool, Check)>, Error> {
+        let local_paths = (
+            self.left.source.local_path(left),
+            self.right.source.local_path(right),
+        );
+        let (Some(timeout), (Some(left_path), Some(right_path))) =
+            (self.settings.file_timeout(), local_paths)
+        else {
+            return self
+                .same_contents((left, left_metadata), (right, right_metadata))
+                .map(Some);
+        };
+        if left_metadata.len() != right_metadata.len() {
+            return Ok(Some((false, Check::Size)));
+        }
+
+        let open_files = self.settings.open_files(2);
+        let (sender, receiver) = mpsc::channel();
+        let buffer_size = self.settings.buffer_size();
+        // Left running if it hangs, as a blocked read can't be interrupted.
+        let worker = std::thread::Builder::new().spawn(move || {
+            let open = |path: &Path, side| std::fs::File::open(path).map_err(|err| (side, err));
+            let same = open(&left_path, Side::Left).and_then(|left| {
+                let right = open(&right_path, Side::Right)?;
+                same_stream(left, right, buffer_size)
+            });
+            let _ = sender.send(same);
+        });
+        let Ok(worker) = worker else {
+            // Without threads, as on WASI, reads can't be timed out.
+            drop(open_files);
+            return self
+                .same_contents((left, left_metadata), (right, right_metadata))
+                .map(Some);
+        };
+        match receiver.recv_timeout(timeout) {
+            Ok(Ok(same)) => Ok(Some((same, Check::Read))),
+            Ok(Err((Side::Left, err))) => Err(Error::from(err)
+                .context(Phase::Reading(Side::Left), || {
+                    full_path(&self.left.source, left)
+                })),
+            Ok(Err((Side::Right, err))) => Err(Error::from(err)
+                .context(Phase::Reading(Side::Right), || {
+                    full_path(&self.right.source, right)
+                })),
+            Err(RecvTimeoutError::Timeout) if self.settings.is_past_deadline() => {
+                Err(Error::DeadlineExceeded)
+            }
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => {
+                // The worker only hangs up without answering when it panics, so pass its panic on
+                // as if the files had been compared on this thread.
+                if let Err(panic) = worker.join() {
+                    std::panic::resume_unwind(panic);
+                }
+                Err(Error::from(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "comparing the files stopped without an answer",
+                )))
+            }
+        }
+    }
+
+    /// Whether two files have the same contents, and the check that decided it.
+    fn same_contents(
+        &self,
+        (left, left_metadata): (&Path, &Metadata),
+        (right, right_metadata): (&Path, &Metadata),
+    ) -> Result<(bool, Check), Error> {
+        let left_at = || full_path(&self.left.source, left);
+        let right_at = || full_path(&self.right.source, right);
+        if left_metadata.len() != right_metadata.len() {
+            return Ok((false, Check::Size));
+        }
+        let read = |same: Result<bool, Error>| same.map(|same| (same, Check::Read));
+
+        let side_error = |(side, err): (Side, std::io::Error)| match side {
+            Side::Left => Error::from(err).context(Phase::Reading(side), left_at),
+            Side::Right => Error::from(err).context(Phase::Reading(side), right_at),
+        };
+
+        let _open_files = self.settings.open_files(2);
+        if let (Some(left_path), Some(right_path)) = (
+            self.left.source.local_path(left),
+            self.right.source.local_path(right),
+        ) {
+            let same_file = same_file::is_same_file(&left_path, &right_path).unwrap_or(false);
+            if same_file || reflink::same_extents(&left_path, &right_path) {
+                #[cfg(feature = "tracing")]
+                tracing::trace!(path = %left.display(), "same file or extents, not read");
+                return Ok((true, Check::SameFile));
+            }
+            if let Some(cache) = self.cache.or(self.own_cache.as_ref()) {
+                #[cfg(feature = "tracing")]
+                tracing::trace!(path = %left.display(), "comparing cached hashes");
+                let (left_hash, left_cached) = cache
+                    .lookup(&left_path, left_metadata)
+                    .context(Phase::Reading(Side::Left), left_at)?;
+                let (right_hash, right_cached) = cache
+                    .lookup(&right_path, right_metadata)
+                    .context(Phase::Reading(Side::Right), right_at)?;
+                let cached = left_cached && right_cached;
+                return Ok((left_hash == right_hash, Check::Hash { cached }));
+            }
+            #[cfg(target_os = "linux")]
+            if matches!(self.settings.mmap_threshold(), Some(threshold) if left_metadata.len() >= threshold)
+            {
+                if let Some(same) =
+                    crate::mmap::same_files(&left_path, &right_path, left_metadata.len())
+                {
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!(path = %left.display(), "compared memory maps");
+                    return read(same.map_err(side_error));
+                }
+            }
+            #[cfg(target_os = "linux")]
+            if self.settings.sequential_reads() {
+                return read(
+                    crate::fadvise::same_files(
+                        &left_path,
+                        &right_path,
+                        self.settings.buffer_size(),
+                    )
+                    .map_err(side_error),
+                );
+            }
+            #[cfg(all(feature = "io_uring", target_os = "linux"))]
+            if let Some(same) =
+                crate::uring::same_files(&left_path, &right_path, self.settings.buffer_size())
+            {
+                #[cfg(feature = "tracing")]
+                tracing::trace!(path = %left.display(), "compared with io_uring");
+                return read(same.map_err(side_error));
+            }
+        }
+
+        let left_reader = self
+            .left
+            .source
+            .open(left)
+            .context(Phase::Reading(Side::Left), left_at)?;
+        let right_reader = self
+            .right
+            .source
+            .open(right)
+            .context(Phase::Reading(Side::Right), right_at)?;
+        read(
+            same_stream(left_reader, right_reader, self.settings.buffer_size()).map_err(side_error),
+        )
+    }
+
+    /// Whether two files have as many records as each other, ended by `delimiter`.
+    fn same_record_count(&self, left: &Path, right: &Path, delimiter: u8) -> Result<bool, Error> {
+        let _open_files = self.settings.open_files(2);
+        let buffer_size = self.settings.buffer_size();
+        let left_count = count_records(&self.left.source, left, delimiter, buffer_size)
+            .context(Phase::Reading(Side::Left), || {
+                full_path(&self.left.source, left)
+            })?;
+        let right_count = count_records(&self.right.source, right, delimiter, buffer_size)
+            .context(Phase::Reading(Side::Right), || {
+                full_path(&self.right.source, right)
+            })?;
+        Ok(left_count == right_count)
+    }
+
+    /// Whether an external command finds two files the same.
+    fn same_by_command(&self, command: &str, left: &Path, right: &Path) -> Result<bool, Error> {
+        let _open_files = self.settings.open_files(2);
+        let left_file = LocalFile::new(&self.left.source, left)
+            .context(Phase::Reading(Side::Left), || {
+                full_path(&self.left.source, left)
+            })?;
+        let right_file = LocalFile::new(&self.right.source, right)
+            .context(Phase::Reading(Side::Right), || {
+                full_path(&self.right.source, right)
+            })?;
+        command::same_files(command, left_file.path(), right_file.path(), || {
+            full_path(&self.left.source, left)
+        })
+    }
+
+    /// Compare two files after normalizing them, which needs them read in full.
+    fn same_text(&self, rules: &TextRules, left: &Path, right: &Path) -> Result<bool, Error> {
+        let _open_files = self.settings.open_files(2);
+        let left_contents = read_all(&self.left.source, left)
+            .context(Phase::Reading(Side::Left), || {
+                full_path(&self.left.source, left)
+            })?;
+        let right_contents = read_all(&self.right.source, right)
+            .context(Phase::Reading(Side::Right), || {
+                full_path(&self.right.source, right)
+            })?;
+        Ok(rules.normalize(&left_contents) == rules.normalize(&right_contents))
+    }
+
+    /// Whether two differing files are builds of the same sources, differing only in build ids
+    /// and timestamps.
+    #[cfg(feature = "binary")]
+    fn same_build(
+        &self,
+        (left, left_metadata): (&Path, &Metadata),
+        (right, right_metadata): (&Path, &Metadata),
+    ) -> Result<bool, Error> {
+        if left_metadata.len() != right_metadata.len() {
+            return Ok(false);
+        }
+        let _open_files = self.settings.open_files(2);
+        let mut left_contents = read_all(&self.left.source, left)
+            .context(Phase::Reading(Side::Left), || {
+                full_path(&self.left.source, left)
+            })?;
+        let mut right_contents = read_all(&self.right.source, right)
+            .context(Phase::Reading(Side::Right), || {
+                full_path(&self.right.source, right)
+            })?;
+        Ok(crate::binary::mask(&mut left_contents)
+            && crate::binary::mask(&mut right_contents)
+            && left_contents == right_contents)
+    }
+
+    /// Whether two differing files are zip archives with the same members.
+    #[cfg(feature = "zip")]
+    fn same_archive(&self, left: &Path, right: &Path) -> Result<bool, Error> {
+        use crate::source::ZipSource;
+
+        let left_at = full_path(&self.left.source, left);
+        let right_at = full_path(&self.right.source, right);
+        let (left_contents, right_contents) = {
+            // Released before the members are compared, which may open files of their own.
+            let _open_files = self.settings.open_files(2);
+            let left_contents = read_all(&self.left.source, left)
+                .context(Phase::Reading(Side::Left), || left_at.clone())?;
+            let right_contents = read_all(&self.right.source, right)
+                .context(Phase::Reading(Side::Right), || right_at.clone())?;
+            (left_contents, right_contents)
+        };
+        let (Ok(left), Ok(right)) = (
+            ZipSource::from_bytes(left_at, left_contents),
+            ZipSource::from_bytes(right_at, right_contents),
+        ) else {
+            return Ok(false);
+        };
+        let mut members = Differences::new(left, right, self.settings.for_archive_members(), None)?;
+        Ok(members.next().transpose()?.is_none())
+    }
+
+    /// Whether two differing CSV files hold the same records, allowing numbers to differ by
+    /// `epsilon`.
+    #[cfg(feature = "csv")]
+    fn same_csv(
+        &self,
+        relative: &Path,
+        (left, right): (&Path, &Path),
+        epsilon: f64,
+    ) -> Result<bool, Error> {
+        let (left_contents, right_contents) = self.read_both(left, right)?;
+        Ok(crate::numeric::same_records(
+            relative,
+            &left_contents,
+            &right_contents,
+            epsilon,
+        ))
+    }
+
+    /// Whether a comparator registered for two differing files finds them the same.
+    fn same_with(
+        &self,
+        comparator: &dyn Comparator,
+        left: &Path,
+        right: &Path,
+    ) -> Result<bool, Error> {
+        let (left_contents, right_contents) = self.read_both(left, right)?;
+        Ok(comparator.same(&left_contents, &right_contents))
+    }
+
+    /// Whether a differing left file matches the right one once its placeholders are filled in.
+    fn same_placeholders(
+        &self,
+        placeholders: &[(&str, &str)],
+        left: &Path,
+        right: &Path,
+    ) -> Result<bool, Error> {
+        let (left_contents, right_contents) = self.read_both(left, right)?;
+        Ok(crate::placeholder::matches(
+            &left_contents,
+            &right_contents,
+            placeholders,
+        ))
+    }
+
+    /// The contents of two files, read in full.
+    fn read_both(&self, left: &Path, right: &Path) -> Result<(Vec<u8>, Vec<u8>), Error> {
+        let _open_files = self.settings.open_files(2);
+        let left_contents = read_all(&self.left.source, left)
+            .context(Phase::Reading(Side::Left), || {
+                full_path(&self.left.source, left)
+            })?;
+        let right_contents = read_all(&self.right.source, right)
+            .context(Phase::Reading(Side::Right), || {
+                full_path(&self.right.source, right)
+            })?;
+        Ok((left_contents, right_contents))
+    }
+
+    /// Whether two differing files hold the same text in different encodings, unless they are
+    /// too large to decode.
+    fn same_text_encoded_differently(
+        &self,
+        (left, left_metadata): (&Path, &Metadata),
+        (right, right_metadata): (&Path, &Metadata),
+    ) -> Result<bool, Error> {
+        if MAX_ENCODING_LEN < left_metadata.len().max(right_metadata.len()) {
+            return Ok(false);
+        }
+        let (left_contents, right_contents) = self.read_both(left, right)?;
+        Ok(text::same_text(&left_contents, &right_contents))
+    }
+
+    /// How many lines two differing files have in common, unless they are too large to diff or
+    /// aren't text.
+    fn similarity(
+        &self,
+        (left, left_metadata): (&Path, &Metadata),
+        (right, right_metadata): (&Path, &Metadata),
+    ) -> Result<Option<Similarity>, Error> {
+        if MAX_SIMILARITY_LEN < left_metadata.len().max(right_metadata.len()) {
+            return Ok(None);
+        }
+        let _open_files = self.settings.open_files(2);
+        let left_text = read_text(&self.left.source, left)
+            .context(Phase::Reading(Side::Left), || {
+                full_path(&self.left.source, left)
+            })?;
+        let right_text = read_text(&self.right.source, right)
+            .context(Phase::Reading(Side::Right), || {
+                full_path(&self.right.source, right)
+            })?;
+        let (Some(left_text), Some(right_text)) = (left_text, right_text) else {
+            return Ok(None);
+        };
+        let left_lines: Vec<_> = left_text.lines().collect();
+        let right_lines: Vec<_> = right_text.lines().collect();
+        let total = left_lines.len() + right_lines.len();
+        if MAX_SIMILARITY_LINES < total {
+            return Ok(None);
+        }
+        let common = total - textdiff::edit_distance(&left_lines, &right_lines);
+        Ok(Some(Similarity::new(common as u64, total as u64)))
+    }
+
+    /// How many blocks of the left file are found in the right one.
+    fn summarize_blocks(
+        &self,
+        left: &Path,
+        right: &Path,
+        block_size: usize,
+    ) -> Result<BlockSummary, Error> {
+        let left_at = || full_path(&self.left.source, left);
+        let right_at = || full_path(&self.right.source, right);
+        let _open_files = self.settings.open_files(2);
+        let left_reader = self
+            .left
+            .source
+            .open(left)
+            .context(Phase::Reading(Side::Left), left_at)?;
+        let right_reader = self
+            .right
+            .source
+            .open(right)
+            .context(Phase::Reading(Side::Right), right_at)?;
+        blocks::summarize(left_reader, right_reader, block_size).map_err(|(side, err)| match side {
+            Side::Left => Error::from(err).context(Phase::Reading(side), left_at),
+            Side::Right => Error::from(err).context(Phase::Reading(side), right_at),
+        })
+    }
+}
+
+/// The largest file decoded for [`Settings::detect_encoding_changes`].
+const MAX_ENCODING_LEN: u64 = 16 * 1024 * 1024;
+/// The largest file whose similarity is measured.
+const MAX_SIMILARITY_LEN: u64 = 1024 * 1024;
+/// The most lines two files may have between them for their similarity to be measured, which
+/// bounds the time spent diffing two files with nothing in common.
+const MAX_SIMILARITY_LINES: usize = 20_000;
+
+/// The name of the root of `source`, resolving roots like `.` on the local file system.
+fn root_name(source: &impl TreeSource) -> Option<std::ffi::OsString> {
+    let root = source.root();
+    match root.file_name() {
+        Some(name) => Some(name.to_owned()),
+        None => std::fs::canonicalize(root)
+            .ok()?
+            .file_name()
+            .map(ToOwned::to_owned),
+    }
+}
+
+fn read_all(source: &impl TreeSource, path: &Path) -> Result<Vec<u8>, Error> {
+    let mut contents = Vec::new();
+    source.open(path)?.read_to_end(&mut contents)?;
+    Ok(contents)
+}
+
+/// How many records ended by `delimiter` a file holds, counting a last one left unterminated.
+fn count_records(
+    source: &impl TreeSource,
+    path: &Path,
+    delimiter: u8,
+    buffer_size: usize,
+) -> Result<u64, Error> {
+    let mut reader = source.open(path)?;
+    let mut buffer = vec![0; buffer_size];
+    let mut count = 0;
+    let mut last = None;
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        };
+        let chunk = &buffer[..read];
+        count += chunk.iter().filter(|byte| **byte == delimiter).count() as u64;
+        last = chunk.last().copied();
+    }
+    if matches!(last, Some(byte) if byte != delimiter) {
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// The contents of a file, or `None` if it isn't valid UTF-8.
+fn read_text(source: &impl TreeSource, path: &Path) -> Result<Option<String>, Error> {
+    Ok(String::from_utf8(read_all(source, path)?).ok())
+}
+
+impl<L: TreeSource, R: TreeSource> Iterator for Differences<'_, L, R> {
+    type Item = Result<Difference, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        loop {
+            let separator = self.settings.path_separator();
+            let next = self
+                .step()
+                .map(|difference| difference.map(|difference| difference.with_separator(separator)))
+                .transpose();
+            match next {
+                Some(Ok(difference)) if self.settings.is_allowed(difference.path()) => {
+                    self.allowed.push(difference);
+                }
+                Some(Ok(_)) => return next,
+                _ => {
+                    self.done = true;
+                    return next;
+                }
+            }
+        }
+    }
+}
+
+/// Refuse to compare a local directory with itself, its parent or its child.
+///
+/// The result would either be trivially equal or report one tree as containing the other, which
+/// is never what was intended.
+pub(crate) fn check_overlap<L: TreeSource, R: TreeSource>(
+    left: &L,
+    right: &R,
+) -> Result<(), Error> {
+    let root = Path::new("");
+    let (Some(left_root), Some(right_root)) = (left.local_path(root), right.local_path(root))
+    else {
+        return Ok(());
+    };
+    // A root that can't be resolved doesn't exist, which is reported once the walk starts.
+    let (Ok(left_canonical), Ok(right_canonical)) = (
+        std::fs::canonicalize(&left_root),
+        std::fs::canonicalize(&right_root),
+    ) else {
+        return Ok(());
+    };
+    if left_canonical.starts_with(&right_canonical) || right_canonical.starts_with(&left_canonical)
+    {
+        return Err(Error::OverlappingRoots {
+            left: left_root,
+            right: right_root,
+        });
+    }
+    Ok(())
+}
+
+/// Compare two readers chunk by chunk, stopping at the first chunk that differs.
+///
+/// Errors are returned with the side they happened on.
+pub(crate) fn same_stream(
+    mut left: impl Read,
+    mut right: impl Read,
+    buffer_size: usize,
+) -> Result<bool, (Side, std::io::Error)> {
+    let mut left_buffer = vec![0; buffer_size];
+    let mut right_buffer = vec![0; buffer_size];
+    loop {
+        let left_len = read_chunk(&mut left, &mut left_buffer).map_err(|err| (Side::Left, err))?;
+        let right_len =
+            read_chunk(&mut right, &mut right_buffer).map_err(|err| (Side::Right, err))?;
+        if left_buffer[..left_len] != right_buffer[..right_len] {
+            return Ok(false);
+        }
+        if left_len == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// Fill `buffer` unless the end of `reader` is reached first, returning the number of bytes read.
+///
+/// A single `read` may return less than is available, which would misalign the two sides.
+pub(crate) fn read_chunk(reader: &mut impl Read, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(filled)
+}
+
+/// How many symlinks may be followed to resolve a single entry, like Linux's `MAXSYMLINKS`.
+const MAX_LINK_HOPS: usize = 40;
+
+/// A depth-first walk of a [`TreeSource`] with siblings sorted by file name.
+///
+/// Directories are only read once the walk moves past them, so a directory can be skipped after
+/// it has been taken.
+struct Walker<S> {
+    source: S,
+    side: Side,
+    /// The directories being read, by the path they are read from, with their remaining entries.
+    stack: Vec<(PathBuf, std::vec::IntoIter<Entry>)>,
+    /// The most recently taken directory, to be descended into unless skipped.
+    pending: Option<(PathBuf, PathBuf)>,
+    peeked: Option<Entry>,
+    prefetch: Option<Arc<Prefetcher>>,
+    /// The entries taken from the walk so far.
+    entries: u64,
+    /// The device of the root, once looked up, if the tree is local.
+    root_device: Option<Option<u64>>,
+    /// Whether siblings are sorted regardless of case.
+    fold_case: bool,
+}
+
+struct Entry {
+    relative: PathBuf,
+    /// Where the entry is read from, which differs from `relative` when symlinks are followed.
+    target: PathBuf,
+    file_type: FileType,
+    /// A symlink that points outside of the tree, so was not followed.
+    escapes: bool,
+    /// A symlink that points to nothing, so was not followed.
+    dangling: bool,
+    /// The metadata of a file, if it was looked up while listing its directory.
+    metadata: Option<Metadata>,
+}
+
+impl<S: TreeSource> Walker<S> {
+    fn new(
+        source: S,
+        side: Side,
+        start: &Path,
+        prefetch: Option<Arc<Prefetcher>>,
+        fold_case: bool,
+    ) -> Result<Self, Error> {
+        let start_metadata = match source.metadata(start) {
+            Ok(metadata) => metadata,
+            Err(Error::Io { source: err, .. })
+                if start.as_os_str().is_empty() && err.kind() == std::io::ErrorKind::NotFound =>
+            {
+                return Err(Error::RootMissing {
+                    side,
+                    path: source.root().to_owned(),
+                });
+            }
+            Err(err) => return Err(err.context(Phase::Walking(side), || full_path(&source, start))),
+        };
+        let pending = (start_metadata.file_type() == FileType::Dir)
+            .then(|| (start.to_owned(), start.to_owned()));
+        let prefetch = prefetch.filter(|_| source.lists_local_dirs());
+        Ok(Self {
+            source,
+            side,
+            stack: Vec::new(),
+            pending,
+            peeked: None,
+            prefetch,
+            entries: 0,
+            root_device: None,
+            fold_case,
+        })
+    }
+
+    /// Whether the entry at `target` is a local file with Windows' hidden attribute set.
+    #[cfg(windows)]
+    fn has_hidden_attribute(&self, target: &Path) -> bool {
+        use std::os::windows::fs::MetadataExt as _;
+
+        const FILE_ATTRIBUTE_HIDDEN: u32 = 0x2;
+        self.source
+            .local_path(target)
+            .and_then(|local| std::fs::symlink_metadata(local).ok())
+            .map(|metadata| metadata.file_attributes() & FILE_ATTRIBUTE_HIDDEN != 0)
+            .unwrap_or(false)
+    }
+
+    #[cfg(not(windows))]
+    fn has_hidden_attribute(&self, _target: &Path) -> bool {
+        false
+    }
+
+    /// Whether the local directory at `target` is on another file system than the root.
+    #[cfg(unix)]
+    fn crosses_file_system(&mut self, target: &Path) -> bool {
+        use std::os::unix::fs::MetadataExt as _;
+
+        let source = &self.source;
+        let device = |path: &Path| {
+            source
+                .local_path(path)
+                .and_then(|local| std::fs::metadata(local).ok())
+                .map(|metadata| metadata.dev())
+        };
+        let root = *self
+            .root_device
+            .get_or_insert_with(|| device(Path::new("")));
+        match (root, device(target)) {
+            (Some(root), Some(device)) => root != device,
+            _ => false,
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn crosses_file_system(&mut self, _target: &Path) -> bool {
+        false
+    }
+
+    /// Ensure the next entry that isn't excluded, if any, has been read.
+    fn fill(&mut self, settings: &Settings) -> Result<(), Error> {
+        while self.peeked.is_none() {
+            if let Some((relative, target)) = self.pending.take() {
+                let mut children: Vec<_> = settings
+                    .retry(|| self.read_dir(&target))
+                    .context(Phase::Walking(self.side), || {
+                        full_path(&self.source, &target)
+                    })?
+                    .into_iter()
+                    .map(|(child, metadata)| Entry {
+                        relative: relative.join(child.name()),
+                        target: target.join(child.name()),
+                        file_type: child.file_type(),
+                        escapes: false,
+                        dangling: false,
+                        metadata,
+                    })
+                    .collect();
+                if self.fold_case {
+                    children.sort_by(|a, b| {
+                        case::cmp_names(
+                            a.relative.file_name().unwrap_or_default(),
+                            b.relative.file_name().unwrap_or_default(),
+                        )
+                    });
+                } else {
+                    children.sort_by(|a, b| a.relative.file_name().cmp(&b.relative.file_name()));
+                }
+                if let Some(prefetch) = &self.prefetch {
+                    for child in &children {
+                        if child.file_type != FileType::Dir || settings.is_excluded(&child.relative)
+                        {
+                            continue;
+                        }
+                        if let Some(local) = self.source.local_path(&child.target) {
+                            prefetch.request(local);
+                        }
+                    }
+                }
+                self.stack.push((target, children.into_iter()));
+            }
+
+            let Some((_, siblings)) = self.stack.last_mut() else {
+                break;
+            };
+            let Some(mut entry) = siblings.next() else {
+                self.stack.pop();
+                continue;
+            };
+            if settings.is_excluded(&entry.relative)
+                || (settings.ignore_hidden() && self.has_hidden_attribute(&entry.target))
+            {
+                settings
+                    .hooks()
+                    .skip(&entry.relative, SkipReason::Excluded(self.side));
+                continue;
+            }
+            self.entries += 1;
+            #[cfg(feature = "tracing")]
+            tracing::trace!(side = ?self.side, path = %entry.relative.display(), "visited entry");
+            if let Some(max) = settings.max_entries() {
+                if max < self.entries {
+                    return Err(Error::LimitExceeded {
+                        limit: Limit::Entries(max),
+                        side: Some(self.side),
+                    });
+                }
+            }
+            if entry.file_type == FileType::Symlink && settings.follow_symlinks() {
+                self.follow(&mut entry)?;
+            }
+            if settings.strict() {
+                self.check_supported(&entry, settings.follow_symlinks())?;
+            }
+            if entry.file_type == FileType::Dir {
+                self.pending = Some((entry.relative.clone(), entry.target.clone()));
+            }
+            self.peeked = Some(entry);
+        }
+        Ok(())
+    }
+
+    /// List the directory at `target`, from the prefetched listings if it is there.
+    fn read_dir(&self, target: &Path) -> Result<Vec<(SourceEntry, Option<Metadata>)>, Error> {
+        if let (Some(prefetch), Some(local)) = (&self.prefetch, self.source.local_path(target)) {
+            if let Some(listing) = prefetch.take(&local) {
+                return Ok(listing?);
+            }
+        }
+        Ok(self
+            .source
+            .read_dir(target)?
+            .into_iter()
+            .map(|entry| (entry, None))
+            .collect())
+    }
+
+    /// Replace a symlink with what it points to, unless that is outside of the tree or missing.
+    fn follow(&self, entry: &mut Entry) -> Result<(), Error> {
+        let link = resolve_link(&self.source, &entry.target)
+            .context(Phase::Walking(self.side), || {
+                full_path(&self.source, &entry.target)
+            })?;
+        let symlink_loop = || Error::SymlinkLoop {
+            side: self.side,
+            path: full_path(&self.source, &entry.relative),
+        };
+        match link {
+            Link::Target(target, file_type) => {
+                // A directory containing the link can only be walked again through the link.
+                let is_ancestor = self.stack.iter().any(|(dir, _)| dir.starts_with(&target));
+                if file_type == FileType::Dir && is_ancestor {
+                    return Err(symlink_loop());
+                }
+                entry.target = target;
+                entry.file_type = file_type;
+                entry.metadata = None;
+            }
+            Link::Escapes => entry.escapes = true,
+            Link::Dangling => entry.dangling = true,
+            Link::TooManyHops => return Err(symlink_loop()),
+        }
+        Ok(())
+    }
+
+    /// Fail on an entry that no option says how to compare, for
+    /// [`DirDiffOptions::strict`][crate::DirDiffOptions::strict].
+    fn check_supported(&self, entry: &Entry, followed: bool) -> Result<(), Error> {
+        let supported = match entry.file_type {
+            FileType::Other => false,
+            // Still a symlink once followed, so nothing is at its target.
+            FileType::Symlink if followed => !entry.dangling,
+            FileType::Symlink => {
+                let link = resolve_link(&self.source, &entry.target)
+                    .context(Phase::Walking(self.side), || {
+                        full_path(&self.source, &entry.target)
+                    })?;
+                !matches!(link, Link::Dangling)
+            }
+            _ => true,
+        };
+        if supported {
+            return Ok(());
+        }
+        Err(Error::UnsupportedEntry {
+            side: self.side,
+            path: full_path(&self.source, &entry.relative),
+            file_type: entry.file_type,
+        })
+    }
+
+    fn take(&mut self) -> Entry {
+        self.peeked.take().expect("`fill` is called first")
+    }
+
+    /// Take the next entry, without descending into it if it is a directory.
+    fn take_and_skip(&mut self) -> Entry {
+        let entry = self.take();
+        self.skip_current_dir();
+        entry
+    }
+
+    /// Look up the metadata of an entry taken from this walk, unless it already was.
+    fn metadata(&self, entry: &Entry) -> Result<Metadata, Error> {
+        match &entry.metadata {
+            Some(metadata) => Ok(metadata.clone()),
+            None => self.restat(entry),
+        }
+    }
+
+    /// Look up the current metadata of an entry taken from this walk.
+    fn restat(&self, entry: &Entry) -> Result<Metadata, Error> {
+        self.source
+            .metadata(&entry.target)
+            .context(Phase::Walking(self.side), || {
+                full_path(&self.source, &entry.target)
+            })
+    }
+
+    /// Don't descend into the most recently taken entry, if it is a directory.
+    fn skip_current_dir(&mut self) {
+        let Some((_, target)) = self.pending.take() else {
+            return;
+        };
+        if let (Some(prefetch), Some(local)) = (&self.prefetch, self.source.local_path(&target)) {
+            prefetch.discard(&local);
+        }
+    }
+}
+
+/// Whether both values are known, and differ.
+fn differs<T: PartialEq>(left: Option<T>, right: Option<T>) -> bool {
+    left.is_some() && right.is_some() && left != right
+}
+
+enum Link {
+    Target(PathBuf, FileType),
+    Escapes,
+    Dangling,
+    TooManyHops,
+}
+
+/// Follow the symlink at `link`, and any symlinks it points to, to an entry in the tree.
+fn resolve_link<S: TreeSource>(source: &S, link: &Path) -> Result<Link, Error> {
+    let mut link = link.to_owned();
+    for _ in 0..MAX_LINK_HOPS {
+        let target = source.read_link(&link)?;
+        let Some(resolved) = resolve_target(source, &link, &target) else {
+            return Ok(Link::Escapes);
+        };
+        let metadata = match source.metadata(&resolved) {
+            Ok(metadata) => metadata,
+            Err(Error::Io { source, .. }) if source.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Link::Dangling)
+            }
+            Err(err) => return Err(err),
+        };
+        if metadata.file_type() != FileType::Symlink {
+            return Ok(Link::Target(resolved, metadata.file_type()));
+        }
+        link = resolved;
+    }
+    Ok(Link::TooManyHops)
+}
+
+/// The path in the tree that the symlink at `link` pointing to `target` refers to, or `None` if
+/// it is outside of the tree.
+///
+/// Relative targets are resolved lexically.  Absolute targets can only be inside a tree on the
+/// local filesystem.
+fn resolve_target<S: TreeSource>(source: &S, link: &Path, target: &Path) -> Option<PathBuf> {
+    let (mut resolved, target) = if target.is_absolute() {
+        let root = source.local_path(Path::new(""))?;
+        let root = std::fs::canonicalize(&root).unwrap_or(root);
+        (PathBuf::new(), target.strip_prefix(root).ok()?)
+    } else {
+        (link.parent().unwrap_or(Path::new("")).to_owned(), target)
+    };
+    for component in target.components() {
+        match component {
+            Component::Normal(name) => resolved.push(name),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !resolved.pop() {
+                    return None;
+                }
+            }
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(resolved)
+}
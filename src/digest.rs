@@ -0,0 +1,729 @@
+//! Hashing a whole tree into one digest, for comparing trees on different machines.
+
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead as _, Write as _};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::cache::HashCache;
+use crate::error::{Phase, ResultExt as _};
+use crate::hash::{from_hex, hash_reader, to_hex, HashAlgorithm, Hasher};
+use crate::options::{DirDiffOptions, Settings};
+use crate::report::Side;
+use crate::source::{full_path, FileType, FsSource, Metadata, TreeSource};
+use crate::Error;
+
+/// A digest of the structure and contents of `dir`, the same for any two directories with the
+/// same contents, on any platform.
+///
+/// The digest is a Merkle-style hash, by BLAKE3 unless
+/// [`DirDiffOptions::hash_algorithm`] says otherwise: each file is hashed with its contents, each
+/// symlink with its target, and each directory with the names, types and digests of its
+/// entries.  Metadata like permissions and modification times isn't part of it, and neither is
+/// the root's name.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let digest = dir_diff::tree_digest("target/out").unwrap();
+/// let expected = std::fs::read("golden.digest").unwrap();
+/// assert_eq!(digest[..], expected[..]);
+/// ```
+pub fn tree_digest<P: AsRef<Path>>(dir: P) -> Result<[u8; 32], Error> {
+    DirDiffOptions::new().tree_digest(dir)
+}
+
+/// The digest of every entry of `dir`, as [`tree_digest`] computes them.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let mut tree = dir_diff::merkle_tree("target/out").unwrap();
+/// // After regenerating `target/out/docs`, only its files are hashed again.
+/// dir_diff::DirDiffOptions::new()
+///     .update_merkle_tree(&mut tree, "target/out", ["docs"])
+///     .unwrap();
+/// println!("{:?}", tree.digest());
+/// ```
+pub fn merkle_tree<P: AsRef<Path>>(dir: P) -> Result<MerkleTree, Error> {
+    DirDiffOptions::new().merkle_tree(dir)
+}
+
+/// How alike two directories are, from 0 when they have no files in common to 1 when they have
+/// the same files.
+///
+/// Each file counts for its size, and each symlink for one byte, so a large file that differs
+/// outweighs many small ones that match.  A file is in common when the other tree has a file
+/// at the same path with the same [`tree_digest`], and the score is the share of bytes of both
+/// trees in common.  Two trees without any files are alike.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let variants = ["golden/linux", "golden/macos", "golden/windows"];
+/// if dir_diff::matches_any("target/out", variants).unwrap().is_none() {
+///     let mut scores: Vec<_> = variants
+///         .iter()
+///         .map(|variant| (dir_diff::similarity("target/out", variant).unwrap(), variant))
+///         .collect();
+///     scores.sort_by(|a, b| b.0.total_cmp(&a.0));
+///     panic!("output doesn't match, but is closest to {}", scores[0].1);
+/// }
+/// ```
+pub fn similarity<A: AsRef<Path>, B: AsRef<Path>>(a_base: A, b_base: B) -> Result<f64, Error> {
+    DirDiffOptions::new().tree_similarity(a_base, b_base)
+}
+
+/// Partition `dirs` into groups of directories with the same contents, by their
+/// [`tree_digest`].
+///
+/// Each group lists the indices of its directories in `dirs`, in order, and the groups are
+/// ordered by their first directory, so a directory unlike any other is a group of its own.
+/// Each directory is only walked once, whatever the number of directories, and several are
+/// walked at once.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let dirs: Vec<std::path::PathBuf> = std::fs::read_dir("target/artifacts")
+///     .unwrap()
+///     .map(|entry| entry.unwrap().path())
+///     .collect();
+/// for group in dir_diff::group_identical(&dirs).unwrap() {
+///     // Keep the first of each group.
+///     for duplicate in &group[1..] {
+///         std::fs::remove_dir_all(&dirs[*duplicate]).unwrap();
+///     }
+/// }
+/// ```
+pub fn group_identical(dirs: &[PathBuf]) -> Result<Vec<Vec<usize>>, Error> {
+    DirDiffOptions::new().group_identical(dirs)
+}
+
+impl DirDiffOptions {
+    /// A digest of the structure and contents of `dir`, leaving out excluded entries.
+    ///
+    /// See [`crate::tree_digest`].  Entries are left out by [`DirDiffOptions::exclude`] and the
+    /// options like it, and empty files by [`DirDiffOptions::empty_files_as_missing`], so two
+    /// directories with the same digest don't differ by [`DirDiffOptions::is_different`] unless
+    /// other options change how entries are compared.  The hash cache is used for files.
+    pub fn tree_digest<P: AsRef<Path>>(&self, dir: P) -> Result<[u8; 32], Error> {
+        self.tree_digest_source(FsSource::new(dir.as_ref()))
+    }
+
+    /// A digest of the structure and contents of a [`TreeSource`], leaving out excluded
+    /// entries.
+    ///
+    /// See [`DirDiffOptions::tree_digest`].
+    pub fn tree_digest_source<S: TreeSource>(&self, source: S) -> Result<[u8; 32], Error> {
+        self.run(|settings, cache| {
+            check_root(&source)?;
+            let mut walk = Walk {
+                source: &source,
+                settings: &settings,
+                cache,
+                previous: None,
+                nodes: None,
+            };
+            Ok(walk.dir(PathBuf::new())?.digest)
+        })
+    }
+
+    /// How alike two directories are, leaving out excluded entries.
+    ///
+    /// See [`crate::similarity`], and [`DirDiffOptions::tree_digest`] for what the options
+    /// change.  Not to be confused with [`DirDiffOptions::similarity`], which scores each file
+    /// that differs.
+    pub fn tree_similarity<A: AsRef<Path>, B: AsRef<Path>>(
+        &self,
+        a_base: A,
+        b_base: B,
+    ) -> Result<f64, Error> {
+        let a = self.merkle_tree(a_base)?;
+        let b = self.merkle_tree(b_base)?;
+        Ok(a.similarity(&b))
+    }
+
+    /// Partition `dirs` into groups of directories with the same contents, leaving out
+    /// excluded entries.
+    ///
+    /// See [`crate::group_identical`], and [`DirDiffOptions::tree_digest`] for what the
+    /// options change.
+    pub fn group_identical(&self, dirs: &[PathBuf]) -> Result<Vec<Vec<usize>>, Error> {
+        self.run(|settings, cache| {
+            let mut groups: Vec<Vec<usize>> = Vec::new();
+            let mut by_digest: HashMap<[u8; 32], usize> = HashMap::new();
+            for (index, digest) in digest_all(dirs, &settings, cache)?.into_iter().enumerate() {
+                match by_digest.entry(digest) {
+                    Entry::Occupied(group) => groups[*group.get()].push(index),
+                    Entry::Vacant(group) => {
+                        group.insert(groups.len());
+                        groups.push(vec![index]);
+                    }
+                }
+            }
+            Ok(groups)
+        })
+    }
+
+    /// The digest of every entry of `dir` that isn't excluded, as
+    /// [`DirDiffOptions::tree_digest`] computes them.
+    ///
+    /// See [`crate::merkle_tree`].
+    pub fn merkle_tree<P: AsRef<Path>>(&self, dir: P) -> Result<MerkleTree, Error> {
+        let mut tree = MerkleTree::new(self.settings()?.hash_algorithm());
+        self.update_merkle_tree(&mut tree, dir, [""])?;
+        Ok(tree)
+    }
+
+    /// Bring `tree` up to date with `dir` after the entries at `changed`, relative to `dir`,
+    /// were added, modified or removed.
+    ///
+    /// Only the subtrees at `changed` are walked again, and only the files in them whose size or
+    /// modification time changed are hashed again.  Then the digests of the directories
+    /// containing them are recomputed from the digests already in `tree`.  Pass `[""]` to walk
+    /// the whole tree again, still only hashing the files that changed.
+    ///
+    /// A tree computed with another [`DirDiffOptions::hash_algorithm`] is recomputed in full.
+    pub fn update_merkle_tree<P, I>(
+        &self,
+        tree: &mut MerkleTree,
+        dir: P,
+        changed: I,
+    ) -> Result<(), Error>
+    where
+        P: AsRef<Path>,
+        I: IntoIterator,
+        I::Item: AsRef<Path>,
+    {
+        let source = FsSource::new(dir.as_ref());
+        self.run(|settings, cache| {
+            check_root(&source)?;
+            if tree.algorithm != settings.hash_algorithm() {
+                *tree = MerkleTree::new(settings.hash_algorithm());
+                return tree.update(&source, &settings, cache, Path::new(""));
+            }
+            for path in changed {
+                tree.update(&source, &settings, cache, path.as_ref())?;
+            }
+            Ok(())
+        })
+    }
+}
+
+/// The digest of every entry of a tree, from [`DirDiffOptions::merkle_tree`].
+///
+/// The digest of the whole tree is only recomputed where it changed by
+/// [`DirDiffOptions::update_merkle_tree`], and the table of digests can be written out with
+/// [`MerkleTree::write_to`] to be read back by a later run.  With the `serde` feature, the table
+/// can also be serialized.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MerkleTree {
+    #[cfg_attr(feature = "serde", serde(default))]
+    algorithm: HashAlgorithm,
+    /// Every entry, by relative path, the root included.
+    nodes: BTreeMap<PathBuf, Node>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Node {
+    file_type: FileType,
+    digest: [u8; 32],
+    /// The size and modification time of a file when it was hashed, to tell whether it changed.
+    len: u64,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    modified: Option<SystemTime>,
+}
+
+impl MerkleTree {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        Self {
+            algorithm,
+            nodes: BTreeMap::new(),
+        }
+    }
+
+    /// The algorithm the digests were computed with.
+    pub fn algorithm(&self) -> HashAlgorithm {
+        self.algorithm
+    }
+
+    /// The digest of the whole tree, as [`crate::tree_digest`] computes it.
+    pub fn digest(&self) -> [u8; 32] {
+        self.get("").unwrap_or_default()
+    }
+
+    /// The digest of the entry at `path`, relative to the root, if it is in the tree.
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<[u8; 32]> {
+        self.nodes.get(path.as_ref()).map(|node| node.digest)
+    }
+
+    /// Every directory in the tree, by relative path, with its digest.
+    ///
+    /// Comparing the directories of two trees from the root down narrows down where they differ
+    /// without looking at the rest.
+    pub fn dirs(&self) -> impl Iterator<Item = (&Path, [u8; 32])> + '_ {
+        self.nodes
+            .iter()
+            .filter(|(_, node)| node.file_type == FileType::Dir)
+            .map(|(path, node)| (path.as_path(), node.digest))
+    }
+
+    /// How alike this tree is to `other`, as [`crate::similarity`] scores it.
+    ///
+    /// Trees hashed with different algorithms have no files in common.
+    pub fn similarity(&self, other: &MerkleTree) -> f64 {
+        let weight = |node: &Node| match node.file_type {
+            FileType::File => node.len.max(1),
+            FileType::Symlink => 1,
+            FileType::Dir | FileType::Other => 0,
+        };
+        let total = |tree: &MerkleTree| tree.nodes.values().map(weight).sum::<u64>();
+        let common: u64 = self
+            .nodes
+            .iter()
+            .filter(|(path, node)| {
+                self.algorithm == other.algorithm
+                    && other.nodes.get(*path).map(|other| {
+                        other.file_type == node.file_type && other.digest == node.digest
+                    }) == Some(true)
+            })
+            .map(|(_, node)| weight(node))
+            .sum();
+        let total = total(self) + total(other);
+        if total == 0 {
+            return 1.0;
+        }
+        2.0 * common as f64 / total as f64
+    }
+
+    /// Write the table of digests to `out`, to be read back with [`MerkleTree::read_from`].
+    ///
+    /// The table starts with a `# <algorithm>` line naming the
+    /// [`HashAlgorithm`].  Each entry is then a line of its digest, its type, and for files the size and modification
+    /// time it was hashed at, followed by its path with `/` separators.  Entries whose path
+    /// isn't valid UTF-8 or holds a newline can't be written, and are left out along with the
+    /// directories containing them, which are then fully walked again by the next update.
+    pub fn write_to(&self, mut out: impl std::io::Write) -> std::io::Result<()> {
+        let mut out = std::io::BufWriter::new(&mut out);
+        writeln!(out, "# {}", self.algorithm.name())?;
+        let mut unwritable: Option<&Path> = None;
+        for (path, node) in self.nodes.iter().rev() {
+            // Parents come after their children when iterating in reverse.
+            if unwritable
+                .map(|child| child.starts_with(path))
+                .unwrap_or(false)
+            {
+                unwritable = Some(path);
+                continue;
+            }
+            let Some(name) = to_slashes(path) else {
+                unwritable = Some(path);
+                continue;
+            };
+            let file_type = match node.file_type {
+                FileType::File => 'f',
+                FileType::Dir => 'd',
+                FileType::Symlink => 'l',
+                FileType::Other => 'o',
+            };
+            let modified = node
+                .modified
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|modified| format!("{}.{:09}", modified.as_secs(), modified.subsec_nanos()))
+                .unwrap_or_else(|| "-".to_owned());
+            writeln!(
+                out,
+                "{} {file_type} {} {modified} {name}",
+                to_hex(&node.digest),
+                node.len
+            )?;
+        }
+        out.flush()
+    }
+
+    /// Read a table of digests written by [`MerkleTree::write_to`].
+    ///
+    /// A table without an algorithm line is taken to be of BLAKE3 digests, and one of an
+    /// algorithm this build doesn't support is an error.
+    pub fn read_from(input: impl std::io::Read) -> std::io::Result<Self> {
+        let mut tree = Self::new(HashAlgorithm::default());
+        for (index, line) in std::io::BufReader::new(input).lines().enumerate() {
+            let line = line?;
+            if let Some(name) = line.strip_prefix("# ").filter(|_| index == 0) {
+                tree.algorithm = HashAlgorithm::from_name(name).ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("unsupported hash algorithm {name:?}"),
+                    )
+                })?;
+                continue;
+            }
+            let (path, node) = parse_line(&line).ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("line {} isn't a Merkle tree node", index + 1),
+                )
+            })?;
+            tree.nodes.insert(path, node);
+        }
+        Ok(tree)
+    }
+
+    fn update<S: TreeSource>(
+        &mut self,
+        source: &S,
+        settings: &Settings,
+        cache: Option<&HashCache>,
+        changed: &Path,
+    ) -> Result<(), Error> {
+        // Walk from the first directory missing from the table, as a new entry may be in a new
+        // directory.
+        let start = changed
+            .ancestors()
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+            .find(|path| !self.nodes.contains_key(*path))
+            .unwrap_or(changed)
+            .to_owned();
+
+        let previous = self.split_off(&start);
+        let excluded = start
+            .ancestors()
+            .take_while(|path| !path.as_os_str().is_empty())
+            .any(|path| settings.is_excluded(path));
+        let metadata = match source.metadata(&start) {
+            _ if excluded => None,
+            Ok(metadata) => Some(metadata),
+            Err(Error::Io { source: err, .. }) if err.kind() == std::io::ErrorKind::NotFound => {
+                None
+            }
+            Err(err) => {
+                return Err(err.context(Phase::Walking(Side::Left), || full_path(source, &start)))
+            }
+        };
+        if let Some(metadata) = metadata {
+            let mut walk = Walk {
+                source,
+                settings,
+                cache,
+                previous: Some(&previous),
+                nodes: Some(BTreeMap::new()),
+            };
+            let node = match metadata.file_type() {
+                FileType::Dir => Some(walk.dir(start.clone())?),
+                file_type => walk.entry(&start, file_type)?,
+            };
+            if let Some(node) = node {
+                self.nodes.insert(start.clone(), node);
+            }
+            self.nodes.extend(walk.nodes.unwrap_or_default());
+        }
+
+        for dir in start.ancestors().skip(1) {
+            let digest = self.dir_digest(dir);
+            self.nodes.insert(dir.to_owned(), dir_node(digest));
+        }
+        Ok(())
+    }
+
+    /// Take the entry at `path` and everything under it out of the table.
+    fn split_off(&mut self, path: &Path) -> BTreeMap<PathBuf, Node> {
+        let mut rest = self.nodes.split_off(path);
+        let mut after = BTreeMap::new();
+        if let Some(end) = rest.keys().find(|key| !key.starts_with(path)).cloned() {
+            after = rest.split_off(&end);
+        }
+        self.nodes.append(&mut after);
+        rest
+    }
+
+    /// The digest of the directory at `dir`, from the digests of its entries in the table.
+    fn dir_digest(&self, dir: &Path) -> [u8; 32] {
+        let mut hasher = DirHasher::new(self.algorithm);
+        let children = self
+            .nodes
+            .range::<Path, _>((std::ops::Bound::Excluded(dir), std::ops::Bound::Unbounded))
+            .take_while(|(path, _)| path.starts_with(dir))
+            .filter(|(path, _)| path.parent() == Some(dir));
+        for (path, node) in children {
+            hasher.add(path, node.digest);
+        }
+        hasher.finish()
+    }
+}
+
+/// A walk of a tree computing digests, recording the nodes of a [`MerkleTree`] if asked to.
+struct Walk<'a, S> {
+    source: &'a S,
+    settings: &'a Settings,
+    cache: Option<&'a HashCache>,
+    /// Nodes from before, whose digests are reused for files that look unchanged.
+    previous: Option<&'a BTreeMap<PathBuf, Node>>,
+    nodes: Option<BTreeMap<PathBuf, Node>>,
+}
+
+impl<S: TreeSource> Walk<'_, S> {
+    /// The node of the directory at `relative`, after those of its entries.
+    fn dir(&mut self, relative: PathBuf) -> Result<Node, Error> {
+        let source = self.source;
+        let mut entries = self
+            .settings
+            .retry(|| source.read_dir(&relative))
+            .context(Phase::Walking(Side::Left), || full_path(source, &relative))?;
+        entries.sort_by(|a, b| a.name().cmp(b.name()));
+
+        let mut hasher = DirHasher::new(self.settings.hash_algorithm());
+        for entry in entries {
+            let path = relative.join(entry.name());
+            if self.settings.is_excluded(&path) {
+                continue;
+            }
+            let node = match entry.file_type() {
+                FileType::Dir => self.dir(path.clone())?,
+                file_type => match self.entry(&path, file_type)? {
+                    Some(node) => node,
+                    None => continue,
+                },
+            };
+            hasher.add(&path, node.digest);
+            if let Some(nodes) = &mut self.nodes {
+                nodes.insert(path, node);
+            }
+        }
+        Ok(dir_node(hasher.finish()))
+    }
+
+    /// The node of the entry at `path`, which isn't a directory, unless it is left out.
+    fn entry(&self, path: &Path, file_type: FileType) -> Result<Option<Node>, Error> {
+        let source = self.source;
+        let mut node = Node {
+            file_type,
+            digest: [0; 32],
+            len: 0,
+            modified: None,
+        };
+        let mut hasher = Hasher::new(self.settings.hash_algorithm());
+        match file_type {
+            FileType::File => {
+                let metadata = source
+                    .metadata(path)
+                    .context(Phase::Walking(Side::Left), || full_path(source, path))?;
+                if self.settings.empty_files_as_missing() && metadata.is_empty() {
+                    return Ok(None);
+                }
+                node.len = metadata.len();
+                node.modified = metadata.modified();
+                let unchanged = self
+                    .previous
+                    .and_then(|previous| previous.get(path))
+                    .filter(|previous| {
+                        previous.file_type == FileType::File
+                            && previous.len == node.len
+                            && previous.modified.is_some()
+                            && previous.modified == node.modified
+                    });
+                if let Some(previous) = unchanged {
+                    return Ok(Some(*previous));
+                }
+                let contents = self
+                    .settings
+                    .retry(|| self.hash_contents(path, &metadata))
+                    .context(Phase::Reading(Side::Left), || full_path(source, path))?;
+                hasher.update(b"file");
+                hasher.update(&contents);
+            }
+            FileType::Symlink => {
+                let target = source
+                    .read_link(path)
+                    .context(Phase::Reading(Side::Left), || full_path(source, path))?;
+                // With `/` separators, to be the same on every platform.
+                let target = target
+                    .iter()
+                    .map(|component| component.to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                hasher.update(b"link");
+                hasher.update(target.as_bytes());
+            }
+            FileType::Dir | FileType::Other => {
+                hasher.update(b"other");
+            }
+        }
+        node.digest = hasher.finalize();
+        Ok(Some(node))
+    }
+
+    fn hash_contents(&self, path: &Path, metadata: &Metadata) -> Result<[u8; 32], Error> {
+        if let (Some(cache), Some(local)) = (self.cache, self.source.local_path(path)) {
+            return cache.hash(&local, metadata);
+        }
+        Ok(hash_reader(
+            self.settings.hash_algorithm(),
+            self.source.open(path)?,
+        )?)
+    }
+}
+
+/// Hashes the entries of a directory, in order of their names.
+struct DirHasher(Hasher);
+
+impl DirHasher {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        let mut hasher = Hasher::new(algorithm);
+        hasher.update(b"dir");
+        Self(hasher)
+    }
+
+    fn add(&mut self, path: &Path, digest: [u8; 32]) {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        self.0.update(&(name.len() as u64).to_le_bytes());
+        self.0.update(name.as_bytes());
+        self.0.update(&digest);
+    }
+
+    fn finish(self) -> [u8; 32] {
+        self.0.finalize()
+    }
+}
+
+fn dir_node(digest: [u8; 32]) -> Node {
+    Node {
+        file_type: FileType::Dir,
+        digest,
+        len: 0,
+        modified: None,
+    }
+}
+
+/// The digest of each of `dirs`, walking them on a pool of threads.
+fn digest_all(
+    dirs: &[PathBuf],
+    settings: &Settings,
+    cache: Option<&HashCache>,
+) -> Result<Vec<[u8; 32]>, Error> {
+    crate::pool::map(dirs, |dir| {
+        let source = FsSource::new(dir);
+        check_root(&source)?;
+        let mut walk = Walk {
+            source: &source,
+            settings,
+            cache,
+            previous: None,
+            nodes: None,
+        };
+        Ok(walk.dir(PathBuf::new())?.digest)
+    })
+    .into_iter()
+    .collect()
+}
+
+fn check_root<S: TreeSource>(source: &S) -> Result<(), Error> {
+    match source.metadata(Path::new("")) {
+        Ok(metadata) if metadata.file_type() == FileType::Dir => Ok(()),
+        Ok(_) => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "not a directory",
+        ))
+        .context(Phase::Walking(Side::Left), || source.root().to_owned()),
+        Err(Error::Io { source: err, .. }) if err.kind() == std::io::ErrorKind::NotFound => {
+            Err(Error::RootMissing {
+                side: Side::Left,
+                path: source.root().to_owned(),
+            })
+        }
+        Err(err) => Err(err.context(Phase::Walking(Side::Left), || source.root().to_owned())),
+    }
+}
+
+/// `path` with `/` between its components, or `.` for the root, if it is valid UTF-8 without
+/// newlines.
+pub(crate) fn to_slashes(path: &Path) -> Option<String> {
+    if path.as_os_str().is_empty() {
+        return Some(".".to_owned());
+    }
+    let components: Option<Vec<_>> = path
+        .iter()
+        .map(|component| component.to_str().filter(|name| !name.contains('\n')))
+        .collect();
+    Some(components?.join("/"))
+}
+
+/// Parse a `<digest> <type> <len> <modified> <path>` line.
+fn parse_line(line: &str) -> Option<(PathBuf, Node)> {
+    let mut fields = line.splitn(5, ' ');
+    let digest = from_hex(fields.next()?)?;
+    let file_type = match fields.next()? {
+        "f" => FileType::File,
+        "d" => FileType::Dir,
+        "l" => FileType::Symlink,
+        "o" => FileType::Other,
+        _ => return None,
+    };
+    let len = fields.next()?.parse().ok()?;
+    let modified = match fields.next()? {
+        "-" => None,
+        modified => {
+            let (secs, nanos) = modified.split_once('.')?;
+            Some(UNIX_EPOCH + Duration::new(secs.parse().ok()?, nanos.parse().ok()?))
+        }
+    };
+    let path = match fields.next()? {
+        "." => PathBuf::new(),
+        path => path.split('/').collect(),
+    };
+    let node = Node {
+        file_type,
+        digest,
+        len,
+        modified,
+    };
+    Some((path, node))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines() {
+        let tree = MerkleTree {
+            algorithm: HashAlgorithm::Blake3,
+            nodes: [
+                (PathBuf::new(), dir_node([1; 32])),
+                (
+                    ["a b", "c.txt"].iter().collect(),
+                    Node {
+                        file_type: FileType::File,
+                        digest: [2; 32],
+                        len: 5,
+                        modified: Some(UNIX_EPOCH + Duration::new(1_700_000_000, 42)),
+                    },
+                ),
+                (PathBuf::from("a b"), dir_node([3; 32])),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        let mut table = Vec::new();
+        tree.write_to(&mut table).unwrap();
+        assert_eq!(MerkleTree::read_from(&table[..]).unwrap(), tree);
+        assert!(MerkleTree::read_from(&b"abc d 0 - x\n"[..]).is_err());
+        assert!(MerkleTree::read_from(&b"# md5\n"[..]).is_err());
+    }
+}
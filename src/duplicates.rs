@@ -0,0 +1,135 @@
+//! Finding files that were copied rather than added.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::cache::HashCache;
+use crate::error::{Phase, ResultExt as _};
+use crate::hash::hash_reader;
+use crate::options::Settings;
+use crate::report::{Difference, DifferenceKind, DuplicateGroup, RelPath, Side};
+use crate::source::{full_path, FileType, TreeSource};
+use crate::Error;
+
+/// Group the files in both trees by content, keeping the groups that include a file without a
+/// counterpart on the other side.
+pub(crate) fn find<L: TreeSource, R: TreeSource>(
+    left: &L,
+    right: &R,
+    settings: &Settings,
+    cache: Option<&HashCache>,
+    differences: &[Difference],
+) -> Result<Vec<DuplicateGroup>, Error> {
+    let mut files = Vec::new();
+    collect_files(left, settings, Side::Left, &mut files)?;
+    collect_files(right, settings, Side::Right, &mut files)?;
+
+    // Only files of the same length can share contents, so most files are never read.
+    let mut by_len: HashMap<u64, Vec<(Side, PathBuf)>> = HashMap::new();
+    for (side, path, len) in files {
+        by_len.entry(len).or_default().push((side, path));
+    }
+
+    let mut by_hash: HashMap<[u8; 32], DuplicateGroup> = HashMap::new();
+    for candidates in by_len.into_values() {
+        if candidates.len() < 2
+            || !candidates
+                .iter()
+                .any(|(side, path)| is_unmatched(*side, path, differences))
+        {
+            continue;
+        }
+        for (side, path) in candidates {
+            let _open_files = settings.open_files(1);
+            let hash = match side {
+                Side::Left => hash(left, &path, settings, cache)
+                    .context(Phase::Reading(side), || full_path(left, &path))?,
+                Side::Right => hash(right, &path, settings, cache)
+                    .context(Phase::Reading(side), || full_path(right, &path))?,
+            };
+            let group = by_hash.entry(hash).or_default();
+            match side {
+                Side::Left => group.left.push(RelPath::new(path)),
+                Side::Right => group.right.push(RelPath::new(path)),
+            }
+        }
+    }
+
+    let mut groups: Vec<_> = by_hash
+        .into_values()
+        .filter(|group| {
+            group.left.len() + group.right.len() > 1
+                && (group
+                    .left
+                    .iter()
+                    .any(|path| is_unmatched(Side::Left, path, differences))
+                    || group
+                        .right
+                        .iter()
+                        .any(|path| is_unmatched(Side::Right, path, differences)))
+        })
+        .collect();
+    for group in &mut groups {
+        group.left.sort();
+        group.right.sort();
+    }
+    groups.sort_by(|a, b| a.first().cmp(&b.first()));
+    Ok(groups)
+}
+
+/// Is the file at `path` missing, as a file, from the other side?
+fn is_unmatched(side: Side, path: &Path, differences: &[Difference]) -> bool {
+    differences.iter().any(|difference| {
+        let one_sided = matches!(
+            (side, difference.kind()),
+            (_, DifferenceKind::FileType)
+                | (Side::Left, DifferenceKind::OnlyInLeft)
+                | (Side::Right, DifferenceKind::OnlyInRight)
+        );
+        one_sided && path.starts_with(difference.path())
+    })
+}
+
+fn collect_files<S: TreeSource>(
+    source: &S,
+    settings: &Settings,
+    side: Side,
+    files: &mut Vec<(Side, PathBuf, u64)>,
+) -> Result<(), Error> {
+    let mut dirs = vec![PathBuf::new()];
+    while let Some(dir) = dirs.pop() {
+        let entries = source
+            .read_dir(&dir)
+            .context(Phase::Walking(side), || full_path(source, &dir))?;
+        for entry in entries {
+            let path = dir.join(entry.name());
+            if settings.is_excluded(&path) {
+                continue;
+            }
+            match entry.file_type() {
+                FileType::Dir => dirs.push(path),
+                FileType::File => {
+                    let len = source
+                        .metadata(&path)
+                        .context(Phase::Walking(side), || full_path(source, &path))?
+                        .len();
+                    files.push((side, path, len));
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+fn hash<S: TreeSource>(
+    source: &S,
+    path: &Path,
+    settings: &Settings,
+    cache: Option<&HashCache>,
+) -> Result<[u8; 32], Error> {
+    if let (Some(cache), Some(local_path)) = (cache, source.local_path(path)) {
+        return cache.hash(&local_path, &source.metadata(path)?);
+    }
+    Ok(hash_reader(settings.hash_algorithm(), source.open(path)?)?)
+}
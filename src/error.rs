@@ -0,0 +1,396 @@
+//! What went wrong, and where.
+
+use std::path::{Path, PathBuf};
+
+use crate::report::Side;
+use crate::source::FileType;
+
+/// The various errors that can happen when diffing two directories
+///
+/// Errors carry the path they concern, where known, and the [`Phase`] of the comparison they
+/// happened in.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// An I/O operation failed.
+    Io {
+        /// The error returned by the operation.
+        source: std::io::Error,
+        /// What was being done, if the error happened during a comparison.
+        phase: Option<Phase>,
+        /// The path being accessed, if known.
+        path: Option<PathBuf>,
+    },
+    /// A glob in the options is invalid.
+    Glob {
+        /// Why the glob is invalid.
+        source: globset::Error,
+        /// The glob, as passed to the options.
+        glob: String,
+    },
+    /// A regular expression in the options is invalid.
+    Regex {
+        /// Why the regular expression is invalid.
+        source: regex::Error,
+        /// The regular expression, as passed to the options.
+        pattern: String,
+    },
+    /// A template passed to [`DirDiffOptions::map_path`][crate::DirDiffOptions::map_path] has
+    /// more wildcards than its glob.
+    PathMap {
+        /// The glob, as passed to the options.
+        glob: String,
+        /// The template, as passed to the options.
+        template: String,
+    },
+    /// Two options both say how to compare the files matching a glob, found by
+    /// [`DirDiffOptions::validate`][crate::DirDiffOptions::validate].
+    ConflictingOptions {
+        /// The option given the glob, by the name of its method, as in `size_tolerance`.
+        option: &'static str,
+        /// The option it conflicts with, given a glob that may match the same files or applying
+        /// to every file.
+        other: &'static str,
+        /// The glob, as passed to the options.
+        glob: String,
+    },
+    /// A root passed to the comparison doesn't exist.
+    RootMissing {
+        /// Which root is missing.
+        side: Side,
+        /// The root, as passed to the comparison.
+        path: PathBuf,
+    },
+    /// The comparison was stopped through a [`CancelToken`][crate::CancelToken].
+    Cancelled,
+    /// The comparison ran past its
+    /// [`DirDiffOptions::deadline`][crate::DirDiffOptions::deadline].
+    DeadlineExceeded,
+    /// The trees are larger than the options allow.
+    LimitExceeded {
+        /// The limit that was exceeded.
+        limit: Limit,
+        /// The tree that exceeded it, for a limit on each tree.
+        side: Option<Side>,
+    },
+    /// Following a symlink leads back to a directory containing it.
+    SymlinkLoop {
+        /// The tree containing the symlink.
+        side: Side,
+        /// The symlink.
+        path: PathBuf,
+    },
+    /// An entry is of a type that no option says how to compare, with
+    /// [`DirDiffOptions::strict`][crate::DirDiffOptions::strict].
+    UnsupportedEntry {
+        /// The tree containing the entry.
+        side: Side,
+        /// The entry.
+        path: PathBuf,
+        /// The type of the entry: [`FileType::Other`] for sockets, device nodes and the like, or
+        /// [`FileType::Symlink`] for a dangling symlink.
+        file_type: FileType,
+    },
+    /// The roots are the same directory, or one is inside the other.
+    OverlappingRoots {
+        /// The left root.
+        left: PathBuf,
+        /// The right root.
+        right: PathBuf,
+    },
+    /// A command from
+    /// [`DirDiffOptions::compare_with_command`][crate::DirDiffOptions::compare_with_command]
+    /// failed, rather than telling whether two files are the same.
+    Command {
+        /// The command's template, as passed to the options.
+        command: String,
+        /// The left file being compared.
+        path: PathBuf,
+        /// How the command exited.
+        status: std::process::ExitStatus,
+        /// What the command wrote to its standard error, trimmed.
+        stderr: String,
+    },
+    /// A [`Patch`][crate::Patch] doesn't apply to the directory it was applied to.
+    PatchConflict {
+        /// Where in the directory the patch doesn't apply.
+        path: PathBuf,
+        /// Why it doesn't apply, as in `it already exists`.
+        reason: String,
+    },
+}
+
+impl Error {
+    /// The path the error concerns, if any.
+    ///
+    /// For [`Error::OverlappingRoots`] this is the left root.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::Io { path, .. } => path.as_deref(),
+            Error::Glob { .. }
+            | Error::Regex { .. }
+            | Error::PathMap { .. }
+            | Error::ConflictingOptions { .. }
+            | Error::Cancelled
+            | Error::DeadlineExceeded
+            | Error::LimitExceeded { .. } => None,
+            Error::RootMissing { path, .. }
+            | Error::SymlinkLoop { path, .. }
+            | Error::UnsupportedEntry { path, .. }
+            | Error::Command { path, .. }
+            | Error::PatchConflict { path, .. } => Some(path),
+            Error::OverlappingRoots { left, .. } => Some(left),
+        }
+    }
+
+    /// What the comparison was doing when the error happened, if known.
+    pub fn phase(&self) -> Option<Phase> {
+        match self {
+            Error::Io { phase, .. } => *phase,
+            Error::Glob { .. }
+            | Error::Regex { .. }
+            | Error::PathMap { .. }
+            | Error::ConflictingOptions { .. }
+            | Error::OverlappingRoots { .. } => Some(Phase::Configuring),
+            Error::RootMissing { side, .. }
+            | Error::SymlinkLoop { side, .. }
+            | Error::UnsupportedEntry { side, .. } => Some(Phase::Walking(*side)),
+            Error::LimitExceeded {
+                side: Some(side), ..
+            } => Some(Phase::Walking(*side)),
+            Error::Cancelled
+            | Error::DeadlineExceeded
+            | Error::LimitExceeded { side: None, .. }
+            | Error::Command { .. }
+            | Error::PatchConflict { .. } => None,
+        }
+    }
+
+    /// The tree the error happened in, if it is specific to one.
+    pub fn side(&self) -> Option<Side> {
+        match self.phase()? {
+            Phase::Walking(side) | Phase::Reading(side) => Some(side),
+            _ => None,
+        }
+    }
+
+    /// The underlying I/O error, for [`Error::Io`].
+    pub fn io_error(&self) -> Option<&std::io::Error> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+
+    /// Fill in where an I/O error happened, unless it is already known.
+    pub(crate) fn context(self, phase: Phase, path: impl FnOnce() -> PathBuf) -> Self {
+        match self {
+            Error::Io {
+                source,
+                phase: existing_phase,
+                path: existing_path,
+            } => Error::Io {
+                source,
+                phase: existing_phase.or(Some(phase)),
+                path: existing_path.or_else(|| Some(path())),
+            },
+            err => err,
+        }
+    }
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io {
+                source,
+                phase,
+                path,
+            } => {
+                write!(f, "I/O error")?;
+                if let Some(phase) = phase {
+                    write!(f, " while {phase}")?;
+                }
+                if let Some(path) = path {
+                    write!(f, " at {}", path.display())?;
+                }
+                write!(f, ": {source}")
+            }
+            Error::Glob { source, glob } => write!(f, "Glob error in `{glob}`: {source}"),
+            Error::Regex { source, pattern } => {
+                write!(f, "Regex error in `{pattern}`: {source}")
+            }
+            Error::PathMap { glob, template } => write!(
+                f,
+                "Template `{template}` has more wildcards than `{glob}` to fill them"
+            ),
+            Error::ConflictingOptions {
+                option,
+                other,
+                glob,
+            } => write!(
+                f,
+                "Options `{option}` and `{other}` can't both apply to `{glob}`"
+            ),
+            Error::RootMissing { side, path } => {
+                write!(f, "The {side} root {} doesn't exist", path.display())
+            }
+            Error::Cancelled => write!(f, "Comparison cancelled"),
+            Error::DeadlineExceeded => write!(f, "Comparison ran past its deadline"),
+            Error::LimitExceeded { limit, side } => {
+                write!(f, "Comparison stopped at {limit}")?;
+                if let Some(side) = side {
+                    write!(f, " in the {side} tree")?;
+                }
+                Ok(())
+            }
+            Error::SymlinkLoop { side, path } => {
+                write!(f, "Symlink loop in the {side} tree at {}", path.display())
+            }
+            Error::UnsupportedEntry {
+                side,
+                path,
+                file_type,
+            } => {
+                let what = match file_type {
+                    FileType::Symlink => "dangling symlink",
+                    _ => "not a file, directory or symlink",
+                };
+                write!(
+                    f,
+                    "Unsupported entry in the {side} tree at {}: {what}",
+                    path.display()
+                )
+            }
+            Error::OverlappingRoots { left, right } => write!(
+                f,
+                "Roots overlap: {} and {}",
+                left.display(),
+                right.display()
+            ),
+            Error::Command {
+                command,
+                path,
+                status,
+                stderr,
+            } => {
+                write!(
+                    f,
+                    "Command `{command}` failed comparing {}: {status}",
+                    path.display()
+                )?;
+                if !stderr.is_empty() {
+                    write!(f, ": {stderr}")?;
+                }
+                Ok(())
+            }
+            Error::PatchConflict { path, reason } => {
+                write!(f, "Patch doesn't apply at {}: {reason}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Glob { source, .. } => Some(source),
+            Error::Regex { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Error {
+        Error::Io {
+            source: e,
+            phase: None,
+            path: None,
+        }
+    }
+}
+
+impl From<walkdir::Error> for Error {
+    fn from(e: walkdir::Error) -> Error {
+        let path = e.path().map(Path::to_owned);
+        Error::Io {
+            source: e.into(),
+            phase: None,
+            path,
+        }
+    }
+}
+
+impl From<globset::Error> for Error {
+    fn from(e: globset::Error) -> Error {
+        Error::Glob {
+            glob: e.glob().unwrap_or_default().to_owned(),
+            source: e,
+        }
+    }
+}
+
+/// Attach a [`Phase`] and path to the error of a fallible operation.
+pub(crate) trait ResultExt<T> {
+    fn context(self, phase: Phase, path: impl FnOnce() -> PathBuf) -> Result<T, Error>;
+}
+
+impl<T, E: Into<Error>> ResultExt<T> for Result<T, E> {
+    fn context(self, phase: Phase, path: impl FnOnce() -> PathBuf) -> Result<T, Error> {
+        self.map_err(|err| err.into().context(phase, path))
+    }
+}
+
+/// A limit on the size of a comparison, from [`Error::LimitExceeded`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Limit {
+    /// [`DirDiffOptions::max_entries`][crate::DirDiffOptions::max_entries], on each tree.
+    Entries(u64),
+    /// [`DirDiffOptions::max_total_bytes`][crate::DirDiffOptions::max_total_bytes], on both trees
+    /// together.
+    TotalBytes(u64),
+}
+
+/// Describes the limit, as in `more than 1000 entries`.
+impl std::fmt::Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Limit::Entries(max) => write!(f, "more than {max} entries"),
+            Limit::TotalBytes(max) => write!(f, "more than {max} bytes of files to compare"),
+        }
+    }
+}
+
+/// A stage of a comparison, for locating errors.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Phase {
+    /// Preparing the options and checking the roots.
+    Configuring,
+    /// Listing directories, or looking up metadata or symlinks, in one tree.
+    Walking(Side),
+    /// Reading file contents in one tree.
+    Reading(Side),
+    /// Loading the hash cache.
+    LoadingCache,
+    /// Saving the hash cache.
+    SavingCache,
+    /// Watching the trees for changes.
+    Watching,
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Phase::Configuring => write!(f, "configuring"),
+            Phase::Walking(side) => write!(f, "walking the {side} tree"),
+            Phase::Reading(side) => write!(f, "reading the {side} tree"),
+            Phase::LoadingCache => write!(f, "loading the hash cache"),
+            Phase::SavingCache => write!(f, "saving the hash cache"),
+            Phase::Watching => write!(f, "watching for changes"),
+        }
+    }
+}
@@ -0,0 +1,126 @@
+//! Sizing up a comparison from metadata alone, before running it.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Phase, ResultExt as _};
+use crate::options::Settings;
+use crate::report::Side;
+use crate::source::{full_path, FileType, TreeSource};
+use crate::Error;
+
+/// How much work comparing two trees takes, from [`DirDiffOptions::estimate`].
+///
+/// Only metadata is read to make it, and only entries that aren't excluded are counted.
+///
+/// [`DirDiffOptions::estimate`]: crate::DirDiffOptions::estimate
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Estimate {
+    left_entries: u64,
+    right_entries: u64,
+    left_bytes: u64,
+    right_bytes: u64,
+    bytes_to_compare: u64,
+}
+
+impl Estimate {
+    /// The entries in the `side` tree, not counting the root.
+    pub fn entries(&self, side: Side) -> u64 {
+        match side {
+            Side::Left => self.left_entries,
+            Side::Right => self.right_entries,
+        }
+    }
+
+    /// The total size of the files in the `side` tree.
+    pub fn bytes(&self, side: Side) -> u64 {
+        match side {
+            Side::Left => self.left_bytes,
+            Side::Right => self.right_bytes,
+        }
+    }
+
+    /// The bytes read, counting both sides, if every file at the same path in both trees is
+    /// compared in full.
+    ///
+    /// Files of different sizes differ without being read, so they aren't counted.  The actual
+    /// comparison reads less when files differ early, or when the hash cache has them.
+    pub fn bytes_to_compare(&self) -> u64 {
+        self.bytes_to_compare
+    }
+}
+
+pub(crate) fn estimate<L: TreeSource, R: TreeSource>(
+    left: &L,
+    right: &R,
+    settings: &Settings,
+) -> Result<Estimate, Error> {
+    let left_files = list_files(left, settings, Side::Left)?;
+    let right_files = list_files(right, settings, Side::Right)?;
+    let bytes_to_compare = left_files
+        .files
+        .iter()
+        .filter(|(path, len)| right_files.files.get(*path) == Some(len))
+        .map(|(_, len)| len * 2)
+        .sum();
+    Ok(Estimate {
+        left_entries: left_files.entries,
+        right_entries: right_files.entries,
+        left_bytes: left_files.files.values().sum(),
+        right_bytes: right_files.files.values().sum(),
+        bytes_to_compare,
+    })
+}
+
+struct Listing {
+    entries: u64,
+    /// The size of each file, by relative path.
+    files: HashMap<PathBuf, u64>,
+}
+
+fn list_files<S: TreeSource>(
+    source: &S,
+    settings: &Settings,
+    side: Side,
+) -> Result<Listing, Error> {
+    let root = Path::new("");
+    match source.metadata(root) {
+        Ok(_) => {}
+        Err(Error::Io { source: err, .. }) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Err(Error::RootMissing {
+                side,
+                path: source.root().to_owned(),
+            });
+        }
+        Err(err) => return Err(err.context(Phase::Walking(side), || source.root().to_owned())),
+    }
+
+    let mut listing = Listing {
+        entries: 0,
+        files: HashMap::new(),
+    };
+    let mut dirs = vec![PathBuf::new()];
+    while let Some(dir) = dirs.pop() {
+        let entries = source
+            .read_dir(&dir)
+            .context(Phase::Walking(side), || full_path(source, &dir))?;
+        for entry in entries {
+            let path = dir.join(entry.name());
+            if settings.is_excluded(&path) {
+                continue;
+            }
+            listing.entries += 1;
+            match entry.file_type() {
+                FileType::Dir => dirs.push(path),
+                FileType::File => {
+                    let metadata = source
+                        .metadata(&path)
+                        .context(Phase::Walking(side), || full_path(source, &path))?;
+                    listing.files.insert(path, metadata.len());
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(listing)
+}
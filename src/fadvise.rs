@@ -0,0 +1,49 @@
+//! Reading local files once, front to back, with hints to the kernel on Linux.
+//!
+//! Telling the kernel the files are read sequentially doubles its readahead window, and asking
+//! for the start of each file up front gets the disk streaming before the first read, which
+//! matters most on spinning disks with a cold cache.  Once compared, the files' pages are
+//! dropped from the cache, so verifying a whole tree doesn't evict everything else.
+
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd as _;
+use std::path::Path;
+
+use crate::compare::same_stream;
+use crate::report::Side;
+
+/// How much of each file to ask the kernel to read ahead before comparing it.
+const READAHEAD: libc::off_t = 8 * 1024 * 1024;
+
+/// Compare two local files, hinting that they are read sequentially and only once.
+///
+/// Errors are returned with the side they happened on.
+pub(crate) fn same_files(
+    left: &Path,
+    right: &Path,
+    buffer_size: usize,
+) -> Result<bool, (Side, io::Error)> {
+    let left = File::open(left).map_err(|err| (Side::Left, err))?;
+    let right = File::open(right).map_err(|err| (Side::Right, err))?;
+    for file in [&left, &right] {
+        advise(file, 0, 0, libc::POSIX_FADV_SEQUENTIAL);
+        advise(file, 0, 0, libc::POSIX_FADV_NOREUSE);
+        advise(file, 0, READAHEAD, libc::POSIX_FADV_WILLNEED);
+    }
+    let same = same_stream(&left, &right, buffer_size);
+    for file in [&left, &right] {
+        advise(file, 0, 0, libc::POSIX_FADV_DONTNEED);
+    }
+    same
+}
+
+/// Pass `advice` for `len` bytes from `offset` of `file`, or to its end if `len` is 0.
+///
+/// The advice is only a hint, so failing to give it is ignored.
+fn advise(file: &File, offset: libc::off_t, len: libc::off_t, advice: libc::c_int) {
+    // SAFETY: the descriptor is open for as long as `file` is borrowed.
+    unsafe {
+        libc::posix_fadvise(file.as_raw_fd(), offset, len, advice);
+    }
+}
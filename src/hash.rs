@@ -0,0 +1,189 @@
+//! The hash functions files and trees are identified by.
+
+use std::io::Read;
+
+/// Which hash function [`DirDiffOptions::hash_algorithm`][crate::DirDiffOptions::hash_algorithm]
+/// identifies contents with.
+///
+/// Every algorithm produces a 32-byte digest, so digests of different algorithms can't be told
+/// apart by their length, only by the algorithm they were computed with.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+#[non_exhaustive]
+pub enum HashAlgorithm {
+    /// BLAKE3, which is both fast and collision resistant.
+    #[default]
+    Blake3,
+    /// SHA-256, for digests that have to be checked by tools that only know it.
+    ///
+    /// Slower than BLAKE3 without hardware support.  Needs the `sha256` feature.
+    #[cfg(feature = "sha256")]
+    Sha256,
+    /// The 128-bit XXH3, padded with zeros to 32 bytes.
+    ///
+    /// The fastest, but not collision resistant: files crafted to collide hash the same, so only
+    /// use it for trees from trusted sources.  Needs the `xxhash` feature.
+    #[cfg(feature = "xxhash")]
+    #[cfg_attr(feature = "serde", serde(rename = "xxh3-128"))]
+    Xxh3,
+}
+
+impl HashAlgorithm {
+    /// The name the algorithm is recorded by, as in the table of
+    /// [`MerkleTree::write_to`][crate::MerkleTree::write_to].
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Blake3 => "blake3",
+            #[cfg(feature = "sha256")]
+            Self::Sha256 => "sha256",
+            #[cfg(feature = "xxhash")]
+            Self::Xxh3 => "xxh3-128",
+        }
+    }
+
+    /// The algorithm recorded as `name`, if it is one this build supports.
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            Self::Blake3,
+            #[cfg(feature = "sha256")]
+            Self::Sha256,
+            #[cfg(feature = "xxhash")]
+            Self::Xxh3,
+        ]
+        .into_iter()
+        .find(|algorithm| algorithm.name() == name)
+    }
+}
+
+/// An incremental hash by any [`HashAlgorithm`].
+pub(crate) enum Hasher {
+    Blake3(Box<blake3::Hasher>),
+    #[cfg(feature = "sha256")]
+    Sha256(sha2::Sha256),
+    #[cfg(feature = "xxhash")]
+    Xxh3(Box<xxhash_rust::xxh3::Xxh3>),
+}
+
+impl Hasher {
+    pub(crate) fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Blake3 => Self::Blake3(Box::default()),
+            #[cfg(feature = "sha256")]
+            HashAlgorithm::Sha256 => Self::Sha256(sha2::Digest::new()),
+            #[cfg(feature = "xxhash")]
+            HashAlgorithm::Xxh3 => Self::Xxh3(Box::default()),
+        }
+    }
+
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        match self {
+            Self::Blake3(hasher) => {
+                hasher.update(bytes);
+            }
+            #[cfg(feature = "sha256")]
+            Self::Sha256(hasher) => sha2::Digest::update(hasher, bytes),
+            #[cfg(feature = "xxhash")]
+            Self::Xxh3(hasher) => hasher.update(bytes),
+        }
+    }
+
+    /// Hash everything read from `reader`.
+    pub(crate) fn update_reader(&mut self, mut reader: impl Read) -> std::io::Result<()> {
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let read = match reader.read(&mut buffer) {
+                Ok(0) => return Ok(()),
+                Ok(read) => read,
+                Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            self.update(&buffer[..read]);
+        }
+    }
+
+    pub(crate) fn finalize(self) -> [u8; 32] {
+        match self {
+            Self::Blake3(hasher) => *hasher.finalize().as_bytes(),
+            #[cfg(feature = "sha256")]
+            Self::Sha256(hasher) => sha2::Digest::finalize(hasher).into(),
+            #[cfg(feature = "xxhash")]
+            Self::Xxh3(hasher) => {
+                let mut digest = [0; 32];
+                digest[..16].copy_from_slice(&hasher.digest128().to_be_bytes());
+                digest
+            }
+        }
+    }
+}
+
+/// The hash of everything read from `reader`.
+pub(crate) fn hash_reader(
+    algorithm: HashAlgorithm,
+    reader: impl Read,
+) -> std::io::Result<[u8; 32]> {
+    let mut hasher = Hasher::new(algorithm);
+    hasher.update_reader(reader)?;
+    Ok(hasher.finalize())
+}
+
+pub(crate) fn to_hex(hash: &[u8; 32]) -> String {
+    hash.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+pub(crate) fn from_hex(hex: &str) -> Option<[u8; 32]> {
+    let mut hash = [0; 32];
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    for (byte, pair) in hash.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_names() {
+        let algorithms = [
+            HashAlgorithm::Blake3,
+            #[cfg(feature = "sha256")]
+            HashAlgorithm::Sha256,
+            #[cfg(feature = "xxhash")]
+            HashAlgorithm::Xxh3,
+        ];
+        for algorithm in algorithms {
+            assert_eq!(HashAlgorithm::from_name(algorithm.name()), Some(algorithm));
+        }
+        assert_eq!(HashAlgorithm::from_name("md5"), None);
+    }
+
+    #[test]
+    fn test_known_digests() {
+        let blake3 = hash_reader(HashAlgorithm::Blake3, &b"abc"[..]).unwrap();
+        assert_eq!(
+            to_hex(&blake3),
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+        #[cfg(feature = "sha256")]
+        {
+            let sha256 = hash_reader(HashAlgorithm::Sha256, &b"abc"[..]).unwrap();
+            assert_eq!(
+                to_hex(&sha256),
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+            );
+        }
+        #[cfg(feature = "xxhash")]
+        {
+            let xxh3 = hash_reader(HashAlgorithm::Xxh3, &b"abc"[..]).unwrap();
+            assert_eq!(
+                xxh3[..16],
+                xxhash_rust::xxh3::xxh3_128(b"abc").to_be_bytes()
+            );
+            assert_eq!(xxh3[16..], [0; 16]);
+        }
+    }
+}
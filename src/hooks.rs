@@ -0,0 +1,261 @@
+//! Callbacks into the walk, for logging, metrics and custom skipping.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::report::{Check, Explanation, Side, Step};
+
+/// Whether to walk into a directory, as decided by
+/// [`DirDiffOptions::on_enter_dir`][crate::DirDiffOptions::on_enter_dir].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[allow(clippy::exhaustive_enums)] // a directory is either walked or not
+pub enum Visit {
+    /// Compare the contents of the directory.
+    Continue,
+    /// Don't look inside the directory on either side.
+    Skip,
+}
+
+/// Why an entry was passed to [`DirDiffOptions::on_skip`][crate::DirDiffOptions::on_skip].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SkipReason {
+    /// The entry matched [`DirDiffOptions::exclude`][crate::DirDiffOptions::exclude] in this tree.
+    Excluded(Side),
+    /// The entry is only in this tree, and matched
+    /// [`DirDiffOptions::ignore_extra`][crate::DirDiffOptions::ignore_extra] for it.
+    Extra(Side),
+    /// The entry is an empty file only in this tree, and
+    /// [`DirDiffOptions::empty_files_as_missing`][crate::DirDiffOptions::empty_files_as_missing]
+    /// is set.
+    Empty(Side),
+    /// [`DirDiffOptions::on_enter_dir`][crate::DirDiffOptions::on_enter_dir] returned
+    /// [`Visit::Skip`] for the directory.
+    Hook,
+    /// The directory is the same directory on both sides, such as through a symlink or a bind
+    /// mount, so its contents must be the same.
+    SameFile,
+    /// The directory is on another file system than its tree's root, in at least one tree, and
+    /// [`DirDiffOptions::same_file_system`][crate::DirDiffOptions::same_file_system] is set.
+    OtherFileSystem,
+}
+
+/// Counters and timings of comparisons, registered with
+/// [`DirDiffOptions::metrics`][crate::DirDiffOptions::metrics], to be exported to a monitoring
+/// system.
+///
+/// Every method does nothing by default, so only the ones of interest need implementing.  They
+/// may be called from several threads at once, such as by [`crate::compare_many`].
+///
+/// # Examples
+///
+/// ```
+/// extern crate dir_diff;
+///
+/// use std::sync::atomic::{AtomicU64, Ordering};
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// #[derive(Default)]
+/// struct Counters {
+///     files: AtomicU64,
+///     bytes: AtomicU64,
+/// }
+///
+/// impl dir_diff::Metrics for Counters {
+///     fn file_compared(&self, _same: bool, _elapsed: Duration) {
+///         self.files.fetch_add(1, Ordering::Relaxed);
+///     }
+///
+///     fn bytes_read(&self, bytes: u64) {
+///         self.bytes.fetch_add(bytes, Ordering::Relaxed);
+///     }
+/// }
+///
+/// let counters = Arc::new(Counters::default());
+/// dir_diff::DirDiffOptions::new()
+///     .metrics(counters.clone())
+///     .diff("tests/easy/good/dir1", "tests/easy/good/dir2")
+///     .unwrap();
+/// assert_eq!(counters.files.load(Ordering::Relaxed), 1);
+/// ```
+pub trait Metrics: Send + Sync {
+    /// The contents of a file were compared, taking `elapsed`, and were the same or not.
+    fn file_compared(&self, _same: bool, _elapsed: Duration) {}
+
+    /// The contents of files totalling `bytes` are about to be compared.
+    ///
+    /// Files are counted whether they end up read, or their hashes are found in the cache.
+    fn bytes_read(&self, _bytes: u64) {}
+
+    /// The hash of a file was found in the cache of
+    /// [`DirDiffOptions::cache_dir`][crate::DirDiffOptions::cache_dir].
+    fn cache_hit(&self) {}
+
+    /// The hash of a file wasn't in the cache, or was stale, so the file was read.
+    fn cache_miss(&self) {}
+
+    /// A comparison, or computing a digest, finished after `elapsed`, whether it succeeded or not.
+    fn finished(&self, _elapsed: Duration) {}
+}
+
+impl<M: Metrics + ?Sized> Metrics for Arc<M> {
+    fn file_compared(&self, same: bool, elapsed: Duration) {
+        (**self).file_compared(same, elapsed);
+    }
+
+    fn bytes_read(&self, bytes: u64) {
+        (**self).bytes_read(bytes);
+    }
+
+    fn cache_hit(&self) {
+        (**self).cache_hit();
+    }
+
+    fn cache_miss(&self) {
+        (**self).cache_miss();
+    }
+
+    fn finished(&self, elapsed: Duration) {
+        (**self).finished(elapsed);
+    }
+}
+
+type EnterDir = dyn Fn(&Path) -> Visit + Send + Sync;
+type Skip = dyn Fn(&Path, SkipReason) + Send + Sync;
+type CompareFile = dyn Fn(&Path, bool) + Send + Sync;
+type Steps = Mutex<BTreeMap<PathBuf, Vec<Step>>>;
+
+/// The callbacks registered on [`DirDiffOptions`][crate::DirDiffOptions].
+#[derive(Clone, Default)]
+pub(crate) struct Hooks {
+    pub(crate) enter_dir: Option<Arc<EnterDir>>,
+    pub(crate) skip: Option<Arc<Skip>>,
+    pub(crate) compare_file: Option<Arc<CompareFile>>,
+    pub(crate) metrics: Option<Arc<dyn Metrics>>,
+    /// The checks run on each entry, with
+    /// [`DirDiffOptions::explain`][crate::DirDiffOptions::explain], shared between clones.
+    pub(crate) explanation: Option<Arc<Steps>>,
+}
+
+impl Hooks {
+    pub(crate) fn enter_dir(&self, relative: &Path) -> Visit {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(path = %relative.display(), "entering directory");
+        match &self.enter_dir {
+            Some(hook) => hook(relative),
+            None => Visit::Continue,
+        }
+    }
+
+    pub(crate) fn skip(&self, relative: &Path, reason: SkipReason) {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(path = %relative.display(), ?reason, "skipped entry");
+        self.explain(relative, Check::Skipped(reason), true);
+        if let Some(hook) = &self.skip {
+            hook(relative, reason);
+        }
+    }
+
+    /// Record that `check` ran on the entry at `relative`, when explaining the comparison.
+    pub(crate) fn explain(&self, relative: &Path, check: Check, same: bool) {
+        if let Some(explanation) = &self.explanation {
+            lock(explanation)
+                .entry(relative.to_owned())
+                .or_default()
+                .push(Step::new(check, same));
+        }
+    }
+
+    /// The checks recorded so far, if the comparison is being explained.
+    pub(crate) fn take_explanation(&self) -> Option<Explanation> {
+        let explanation = self.explanation.as_ref()?;
+        Some(Explanation::new(std::mem::take(&mut *lock(explanation))))
+    }
+
+    pub(crate) fn compare_file(&self, relative: &Path, same: bool, elapsed: Duration) {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(path = %relative.display(), same, ?elapsed, "compared file");
+        if let Some(metrics) = &self.metrics {
+            metrics.file_compared(same, elapsed);
+        }
+        if let Some(hook) = &self.compare_file {
+            hook(relative, same);
+        }
+    }
+
+    pub(crate) fn bytes_read(&self, bytes: u64) {
+        if let Some(metrics) = &self.metrics {
+            metrics.bytes_read(bytes);
+        }
+    }
+
+    pub(crate) fn finished(&self, elapsed: Duration) {
+        if let Some(metrics) = &self.metrics {
+            metrics.finished(elapsed);
+        }
+    }
+
+    /// A timer for something whose duration is reported, started only if anything reports it.
+    pub(crate) fn timer(&self) -> Timer {
+        let timed = cfg!(feature = "tracing") || self.metrics.is_some();
+        Timer(timed.then(Instant::now))
+    }
+}
+
+/// Measures how long something takes, without reading the clock unless it was started.
+///
+/// The clock isn't available everywhere sources without local paths can be compared, like on
+/// `wasm32-unknown-unknown`.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct Timer(Option<Instant>);
+
+impl Timer {
+    /// The time since the timer started, or zero if it didn't.
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.0.map(|started| started.elapsed()).unwrap_or_default()
+    }
+}
+
+impl std::fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Hooks")
+            .field("enter_dir", &self.enter_dir.is_some())
+            .field("skip", &self.skip.is_some())
+            .field("compare_file", &self.compare_file.is_some())
+            .field("metrics", &self.metrics.is_some())
+            .field("explanation", &self.explanation.is_some())
+            .finish()
+    }
+}
+
+fn lock(steps: &Steps) -> std::sync::MutexGuard<'_, BTreeMap<PathBuf, Vec<Step>>> {
+    steps
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+// With tracing, every timer is started.
+#[cfg(all(test, not(feature = "tracing")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timer() {
+        struct NoMetrics;
+
+        impl Metrics for NoMetrics {}
+
+        assert_eq!(Hooks::default().timer().elapsed(), Duration::ZERO);
+
+        let hooks = Hooks {
+            metrics: Some(Arc::new(NoMetrics)),
+            ..Hooks::default()
+        };
+        let timer = hooks.timer();
+        std::thread::sleep(Duration::from_millis(1));
+        assert!(timer.elapsed() > Duration::ZERO);
+    }
+}
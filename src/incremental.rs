@@ -0,0 +1,255 @@
+//! Reusing the results of an earlier comparison for files that haven't changed since.
+
+use std::collections::BTreeMap;
+use std::io::{BufRead as _, Write as _};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::digest::to_slashes;
+use crate::options::DirDiffOptions;
+use crate::report::{DiffReport, Difference, DifferenceKind};
+use crate::source::Metadata;
+use crate::Error;
+
+/// Compare two directories like [`crate::diff`], reusing the results of an earlier comparison of
+/// the same directories for files that haven't changed since.
+///
+/// See [`DirDiffOptions::diff_incremental`].
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let mut stats = dir_diff::StatCache::default();
+/// let mut report = dir_diff::DiffReport::default();
+/// for _ in 0..2 {
+///     report = dir_diff::diff_incremental("dir/a", "dir/b", &report, &mut stats).unwrap();
+/// }
+/// stats.write_to(std::fs::File::create("stats").unwrap()).unwrap();
+/// ```
+pub fn diff_incremental<A: AsRef<Path>, B: AsRef<Path>>(
+    a_base: A,
+    b_base: B,
+    previous: &DiffReport,
+    stats: &mut StatCache,
+) -> Result<DiffReport, Error> {
+    DirDiffOptions::new().diff_incremental(a_base, b_base, previous, stats)
+}
+
+impl DirDiffOptions {
+    /// Compare two directories like [`DirDiffOptions::diff`], reusing the results in `previous`
+    /// for files that haven't changed since it was produced.
+    ///
+    /// `stats` holds the size and modification time of both sides of every file `previous`
+    /// compared, and is updated with those of this comparison.  A file whose size and
+    /// modification time match on both sides isn't read again: it differs if `previous` reported
+    /// its contents differing, and is the same otherwise.  Its metadata is still compared.  Start
+    /// with an empty [`DiffReport::default`] and [`StatCache::default`], and keep using the same
+    /// directories and options, as results for other ones can't be told apart.  Like the hash
+    /// cache, a change that keeps the size and lands within the filesystem's timestamp
+    /// granularity goes unnoticed.
+    pub fn diff_incremental<A: AsRef<Path>, B: AsRef<Path>>(
+        &self,
+        a_base: A,
+        b_base: B,
+        previous: &DiffReport,
+        stats: &mut StatCache,
+    ) -> Result<DiffReport, Error> {
+        self.run(|settings, cache| {
+            let (left, right) =
+                crate::snapshot::local_trees(a_base.as_ref(), b_base.as_ref(), &settings)?;
+            crate::diff_with_previous(left, right, settings, cache, Some((previous, stats)))
+        })
+    }
+}
+
+/// The size and modification time of both sides of every file compared by
+/// [`DirDiffOptions::diff_incremental`], to tell which files changed since.
+///
+/// It can be written out with [`StatCache::write_to`] to be read back by a later run, or
+/// serialized with the `serde` feature.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StatCache {
+    files: BTreeMap<PathBuf, [Stamp; 2]>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Stamp {
+    len: u64,
+    modified: SystemTime,
+}
+
+impl Stamp {
+    fn new(metadata: &Metadata) -> Option<Self> {
+        Some(Self {
+            len: metadata.len(),
+            modified: metadata.modified()?,
+        })
+    }
+}
+
+impl StatCache {
+    /// The number of files recorded.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Are no files recorded?
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Write the recorded files to `out`, to be read back with [`StatCache::read_from`].
+    ///
+    /// Each file is a line of the size and modification time of its left then right side,
+    /// followed by its path with `/` separators.  Files whose path isn't valid UTF-8 or holds a
+    /// newline are left out, and so compared again by the next run.
+    pub fn write_to(&self, mut out: impl std::io::Write) -> std::io::Result<()> {
+        let mut out = std::io::BufWriter::new(&mut out);
+        for (path, stamps) in &self.files {
+            let Some(name) = to_slashes(path) else {
+                continue;
+            };
+            let [Some(left), Some(right)] = stamps.map(format_stamp) else {
+                continue;
+            };
+            writeln!(out, "{left} {right} {name}")?;
+        }
+        out.flush()
+    }
+
+    /// Read the files written by [`StatCache::write_to`].
+    pub fn read_from(input: impl std::io::Read) -> std::io::Result<Self> {
+        let mut files = BTreeMap::new();
+        for (index, line) in std::io::BufReader::new(input).lines().enumerate() {
+            let line = line?;
+            let (path, stamps) = parse_line(&line).ok_or_else(|| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!(
+                        "line {} isn't a file's sizes and modification times",
+                        index + 1
+                    ),
+                )
+            })?;
+            files.insert(path, stamps);
+        }
+        Ok(Self { files })
+    }
+}
+
+/// The results of an earlier comparison, and the files recorded by this one.
+pub(crate) struct Incremental<'a> {
+    /// The differences in contents found before, by path.
+    changed: BTreeMap<&'a Path, &'a Difference>,
+    previous: &'a StatCache,
+    stats: StatCache,
+}
+
+impl<'a> Incremental<'a> {
+    pub(crate) fn new(report: &'a DiffReport, previous: &'a StatCache) -> Self {
+        let changed = report
+            .differences()
+            .iter()
+            .chain(report.allowed())
+            .filter(|difference| {
+                matches!(
+                    difference.kind(),
+                    DifferenceKind::Content | DifferenceKind::Encoding
+                )
+            })
+            .map(|difference| (difference.path().as_path(), difference))
+            .collect();
+        Self {
+            changed,
+            previous,
+            stats: StatCache::default(),
+        }
+    }
+
+    /// The earlier result for the files at `relative`, if neither side changed since: the
+    /// difference in their contents, or `None` if they were the same.
+    pub(crate) fn unchanged(
+        &mut self,
+        relative: &Path,
+        left: &Metadata,
+        right: &Metadata,
+    ) -> Option<Option<Difference>> {
+        let stamps = [Stamp::new(left)?, Stamp::new(right)?];
+        if self.previous.files.get(relative) != Some(&stamps) {
+            return None;
+        }
+        self.stats.files.insert(relative.to_owned(), stamps);
+        Some(self.changed.get(relative).map(|difference| {
+            (*difference)
+                .clone()
+                .with_metadata(Some(left.clone()), Some(right.clone()))
+        }))
+    }
+
+    /// Record the files at `relative` as compared, with the given metadata.
+    pub(crate) fn record(&mut self, relative: &Path, left: &Metadata, right: &Metadata) {
+        if let (Some(left), Some(right)) = (Stamp::new(left), Stamp::new(right)) {
+            self.stats.files.insert(relative.to_owned(), [left, right]);
+        }
+    }
+
+    pub(crate) fn into_stats(self) -> StatCache {
+        self.stats
+    }
+}
+
+fn format_stamp(stamp: Stamp) -> Option<String> {
+    let modified = stamp.modified.duration_since(UNIX_EPOCH).ok()?;
+    Some(format!(
+        "{} {}.{:09}",
+        stamp.len,
+        modified.as_secs(),
+        modified.subsec_nanos()
+    ))
+}
+
+/// Parse a `<len> <modified> <len> <modified> <path>` line.
+fn parse_line(line: &str) -> Option<(PathBuf, [Stamp; 2])> {
+    let mut fields = line.splitn(5, ' ');
+    let mut stamp = || -> Option<Stamp> {
+        let len = fields.next()?.parse().ok()?;
+        let (secs, nanos) = fields.next()?.split_once('.')?;
+        let modified = UNIX_EPOCH + Duration::new(secs.parse().ok()?, nanos.parse().ok()?);
+        Some(Stamp { len, modified })
+    };
+    let stamps = [stamp()?, stamp()?];
+    let path = fields.next()?.split('/').collect();
+    Some((path, stamps))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines() {
+        let stamp = |len, secs| Stamp {
+            len,
+            modified: UNIX_EPOCH + Duration::new(secs, 42),
+        };
+        let stats = StatCache {
+            files: [
+                (
+                    PathBuf::from("a.txt"),
+                    [stamp(1, 1_700_000_000), stamp(2, 0)],
+                ),
+                (["dir", "b c"].iter().collect(), [stamp(0, 5), stamp(0, 5)]),
+            ]
+            .into_iter()
+            .collect(),
+        };
+        let mut table = Vec::new();
+        stats.write_to(&mut table).unwrap();
+        assert_eq!(StatCache::read_from(&table[..]).unwrap(), stats);
+        assert!(StatCache::read_from(&b"1 2.3 x\n"[..]).is_err());
+    }
+}
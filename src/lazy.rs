@@ -0,0 +1,143 @@
+//! Yielding differences as the walk finds them, rather than collecting them into a report.
+
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use crate::cache::HashCache;
+use crate::compare;
+use crate::hooks::Hooks;
+use crate::mapping::Mapped;
+use crate::options::DirDiffOptions;
+use crate::report::Difference;
+use crate::snapshot::{local_trees, LocalTree};
+use crate::Error;
+
+type Walk = compare::Differences<'static, Mapped<LocalTree>, Mapped<LocalTree>>;
+
+/// Lazily yield the differences between two directories, in relative path order.
+///
+/// See [`DirDiffOptions::differences`].
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// for difference in dir_diff::differences("dir/a", "dir/b").take(10) {
+///     println!("{}", difference.unwrap().path().display());
+/// }
+/// ```
+pub fn differences<A: AsRef<Path>, B: AsRef<Path>>(a_base: A, b_base: B) -> Differences {
+    DirDiffOptions::new().differences(a_base, b_base)
+}
+
+impl DirDiffOptions {
+    /// Lazily yield the differences between two directories, in relative path order, as the
+    /// walk finds them.
+    ///
+    /// Nothing is compared past the last difference taken, so dropping the iterator stops the
+    /// walk, and a consumer can show differences as they are found.  Differences allowed by
+    /// [`DirDiffOptions::allow_different`] are left out, and duplicates and name issues aren't
+    /// looked for, as they need the whole walk.  An error ends the iteration, including invalid
+    /// options or a missing root, which are yielded first.
+    pub fn differences<A: AsRef<Path>, B: AsRef<Path>>(&self, a_base: A, b_base: B) -> Differences {
+        let started = Instant::now();
+        let walk = self.settings().and_then(|settings| {
+            let cache = match self.cache_dir_path() {
+                Some(dir) => Some(
+                    HashCache::load(dir, settings.hash_algorithm())?.with_metrics(settings.hooks()),
+                ),
+                None => None,
+            };
+            let hooks = settings.hooks().clone();
+            let (left, right) = local_trees(a_base.as_ref(), b_base.as_ref(), &settings)?;
+            let left = settings.map_left(left)?;
+            let right = settings.map_right(right)?;
+            let walk = Walk::new(left, right, settings, None)?;
+            let walk = match cache {
+                Some(cache) => walk.with_own_cache(cache),
+                None => walk,
+            };
+            Ok((walk, hooks))
+        });
+        match walk {
+            Ok((walk, hooks)) => Differences {
+                walk: Some(walk),
+                error: None,
+                cache_dir: self.cache_dir_path().map(Path::to_owned),
+                hooks,
+                started,
+            },
+            Err(err) => Differences {
+                walk: None,
+                error: Some(err),
+                cache_dir: None,
+                hooks: Hooks::default(),
+                started,
+            },
+        }
+    }
+}
+
+/// An iterator over the differences between two directories, found as it is advanced.
+///
+/// Created by [`DirDiffOptions::differences`].  Hashes added to the cache of
+/// [`DirDiffOptions::cache_dir`] are saved once the iterator is exhausted or dropped.
+pub struct Differences {
+    walk: Option<Walk>,
+    /// An error starting the walk, to be yielded first.
+    error: Option<Error>,
+    cache_dir: Option<PathBuf>,
+    hooks: Hooks,
+    started: Instant,
+}
+
+impl Differences {
+    /// Stop walking, and save the cache.
+    fn finish(&mut self) -> Result<(), Error> {
+        let Some(walk) = self.walk.take() else {
+            return Ok(());
+        };
+        self.hooks.finished(self.started.elapsed());
+        match (walk.own_cache(), &self.cache_dir) {
+            (Some(cache), Some(dir)) => cache.save(dir),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Iterator for Differences {
+    type Item = Result<Difference, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(err) = self.error.take() {
+            return Some(Err(err));
+        }
+        match self.walk.as_mut()?.next() {
+            Some(Ok(difference)) => Some(Ok(difference)),
+            Some(Err(err)) => {
+                // The walk's error is the one worth reporting.
+                let _ = self.finish();
+                Some(Err(err))
+            }
+            None => self.finish().err().map(Err),
+        }
+    }
+}
+
+impl Drop for Differences {
+    fn drop(&mut self) {
+        // Hashes computed before stopping early are still worth keeping.
+        let _ = self.finish();
+    }
+}
+
+impl std::fmt::Debug for Differences {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Differences")
+            .field("error", &self.error)
+            .field("cache_dir", &self.cache_dir)
+            .field("done", &self.walk.is_none())
+            .finish()
+    }
+}
@@ -1,7 +1,7 @@
 //! Determine if two directories have different contents.
 //!
-//! For now, only one function exists: are they different, or not? In the future,
-//! more functionality to actually determine the difference may be added.
+//! [`is_different`] answers whether two directories differ at all, stopping at the first
+//! difference.  [`diff`] walks both trees in full and reports every difference it finds.
 //!
 //! # Examples
 //!
@@ -15,35 +15,121 @@
 #![warn(clippy::print_stderr)]
 #![warn(clippy::print_stdout)]
 
-use std::cmp::Ordering;
-use std::path::Path;
-
-use walkdir::{DirEntry, WalkDir};
+#[cfg(feature = "acl")]
+mod acl;
+mod assertion;
+mod batch;
+#[cfg(feature = "binary")]
+mod binary;
+mod blocks;
+mod cache;
+mod cancel;
+#[cfg(feature = "capi")]
+pub mod capi;
+mod case;
+#[cfg(feature = "checksums")]
+mod checksums;
+mod command;
+#[cfg(any(feature = "cbor", feature = "msgpack"))]
+mod compact;
+mod comparator;
+mod compare;
+mod digest;
+mod duplicates;
+mod error;
+mod estimate;
+#[cfg(target_os = "linux")]
+mod fadvise;
+mod hash;
+mod hooks;
+mod incremental;
+mod lazy;
+mod mapping;
+mod merge;
+#[cfg(target_os = "linux")]
+mod mmap;
+mod mtree;
+mod names;
+#[cfg(feature = "ndjson")]
+mod ndjson;
+#[cfg(feature = "csv")]
+mod numeric;
+mod options;
+mod patch;
+mod placeholder;
+mod pool;
+mod portable;
+#[cfg(feature = "predicates")]
+mod predicate;
+mod prefetch;
+mod preset;
+mod reflink;
+mod render;
+pub mod report;
+mod retry;
+mod snapshot;
+pub mod source;
+#[cfg(feature = "proptest")]
+pub mod strategy;
+#[cfg(any(feature = "tempfile", feature = "assert_fs"))]
+mod temp;
+mod text;
+mod textdiff;
+#[cfg(all(feature = "io_uring", target_os = "linux"))]
+mod uring;
+#[cfg(feature = "watch")]
+mod watch;
 
-/// The various errors that can happen when diffing two directories
-#[allow(clippy::exhaustive_enums)] // breaking change
-#[allow(missing_docs)]
-#[derive(Debug)]
-pub enum Error {
-    Io(std::io::Error),
-    StripPrefix(std::path::StripPrefixError),
-    WalkDir(walkdir::Error),
-}
+#[doc(hidden)]
+pub use assertion::__assert_dir_eq;
+pub use batch::compare_many;
+pub use cancel::CancelToken;
+pub use case::{is_case_sensitive, CaseSensitivity};
+#[cfg(feature = "checksums")]
+pub use checksums::{verify_checksums, write_checksums};
+#[cfg(any(feature = "cbor", feature = "msgpack"))]
+pub use compact::CompactFormat;
+pub use comparator::{Comparator, DelimitedProtobuf};
+pub use digest::{group_identical, merkle_tree, similarity, tree_digest, MerkleTree};
+pub use error::{Error, Limit, Phase};
+pub use estimate::Estimate;
+pub use hash::HashAlgorithm;
+pub use hooks::{Metrics, SkipReason, Visit};
+pub use incremental::{diff_incremental, StatCache};
+pub use lazy::{differences, Differences};
+pub use merge::MergePolicy;
+pub use mtree::{diff_mtree, write_mtree};
+pub use names::{names_only_diff, NamesDiff};
+pub use options::DirDiffOptions;
+pub use patch::{apply_patch, Patch};
+#[cfg(feature = "predicates")]
+pub use predicate::DirMatchesPredicate;
+pub use preset::Preset;
+pub use render::ColorChoice;
+pub use report::{
+    BlockSummary, Check, DiffCounts, DiffReport, DiffStats, Difference, DifferenceKind,
+    DuplicateGroup, ExplainedEntry, Explanation, NameIssue, NameProblem, PathSeparator, Ranking,
+    RelPath, ReportDelta, Rollup, Side, Similarity, Step,
+};
+pub use retry::RetryPolicy;
+pub use snapshot::Snapshot;
+#[cfg(any(feature = "tempfile", feature = "assert_fs"))]
+pub use temp::{diff_temp, TempRoot};
+pub use text::{EncodingNormalization, Whitespace};
+#[cfg(feature = "watch")]
+pub use watch::{watch, WatchHandle};
 
-impl std::fmt::Display for Error {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Error::Io(inner) => write!(f, "I/O error: {inner}"),
-            Error::StripPrefix(inner) => write!(f, "Strip prefix error: {inner}"),
-            Error::WalkDir(inner) => write!(f, "Walk dir error: {inner}"),
-        }
-    }
-}
+use std::path::Path;
 
-impl std::error::Error for Error {}
+use cache::HashCache;
+use options::Settings;
+use source::TreeSource;
 
 /// Are the contents of two directories different?
 ///
+/// Stops at the first difference found, so it reads as little as it can when the directories
+/// differ.  Use [`diff`] to find every difference.
+///
 /// # Examples
 ///
 /// ```no_run
@@ -52,54 +138,169 @@ impl std::error::Error for Error {}
 /// assert!(dir_diff::is_different("dir/a", "dir/b").unwrap());
 /// ```
 pub fn is_different<A: AsRef<Path>, B: AsRef<Path>>(a_base: A, b_base: B) -> Result<bool, Error> {
-    let mut a_walker = walk_dir(a_base)?;
-    let mut b_walker = walk_dir(b_base)?;
-
-    for (a, b) in (&mut a_walker).zip(&mut b_walker) {
-        let a = a?;
-        let b = b?;
-
-        if a.depth() != b.depth()
-            || a.file_type() != b.file_type()
-            || a.file_name() != b.file_name()
-            || (a.file_type().is_file() && std::fs::read(a.path())? != std::fs::read(b.path())?)
-        {
-            return Ok(true);
-        }
-    }
+    DirDiffOptions::new().is_different(a_base, b_base)
+}
 
-    Ok(a_walker.next().is_some() || b_walker.next().is_some())
+/// Does `outer` contain every entry in `inner`, with the same contents?
+///
+/// Unlike [`is_different`], extra entries in `outer` are allowed.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// assert!(dir_diff::contains("install/prefix", "tests/expected-files").unwrap());
+/// ```
+pub fn contains<A: AsRef<Path>, B: AsRef<Path>>(outer: A, inner: B) -> Result<bool, Error> {
+    DirDiffOptions::new().contains(outer, inner)
 }
 
-fn walk_dir<P: AsRef<Path>>(path: P) -> Result<walkdir::IntoIter, std::io::Error> {
-    let mut walkdir = WalkDir::new(path).sort_by(compare_by_file_name).into_iter();
-    if let Some(Err(e)) = walkdir.next() {
-        Err(e.into())
-    } else {
-        Ok(walkdir)
-    }
+/// Check that `dst` holds a copy of everything in `src`, with the same contents, permissions
+/// and modification times.
+///
+/// Entries only in `dst` are ignored.  See [`DirDiffOptions::verify_copy`] to choose which
+/// metadata is checked.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let report = dir_diff::verify_copy("data", "/mnt/backup/data").unwrap();
+/// for difference in report.differences() {
+///     println!("{}: {:?}", difference.path().display(), difference.kind());
+/// }
+/// ```
+pub fn verify_copy<A: AsRef<Path>, B: AsRef<Path>>(src: A, dst: B) -> Result<DiffReport, Error> {
+    DirDiffOptions::new()
+        .compare_permissions(true)
+        .compare_modified(true)
+        .verify_copy(src, dst)
 }
 
-fn compare_by_file_name(a: &DirEntry, b: &DirEntry) -> Ordering {
-    a.file_name().cmp(b.file_name())
+/// Find which of the `expected` directories has the same contents as `actual`, returning its
+/// index.
+///
+/// For tests whose correct output legitimately differs between platforms.  The expected
+/// directories are tried in order, stopping at the first match, and each must exist.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let matched = dir_diff::matches_any("out", ["golden/unix", "golden/windows"]).unwrap();
+/// assert!(matched.is_some());
+/// ```
+pub fn matches_any<A, I>(actual: A, expected: I) -> Result<Option<usize>, Error>
+where
+    A: AsRef<Path>,
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    DirDiffOptions::new().matches_any(actual, expected)
 }
 
-impl From<std::io::Error> for Error {
-    fn from(e: std::io::Error) -> Error {
-        Error::Io(e)
-    }
+/// Find every difference between the contents of two directories.
+///
+/// Always walks both trees in full, however many differences there are.  Use [`is_different`]
+/// to stop at the first one.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let report = dir_diff::diff("dir/a", "dir/b").unwrap();
+/// for difference in report.differences() {
+///     println!("{}: {:?}", difference.path().display(), difference.kind());
+/// }
+/// ```
+pub fn diff<A: AsRef<Path>, B: AsRef<Path>>(a_base: A, b_base: B) -> Result<DiffReport, Error> {
+    DirDiffOptions::new().diff(a_base, b_base)
 }
 
-impl From<std::path::StripPrefixError> for Error {
-    fn from(e: std::path::StripPrefixError) -> Error {
-        Error::StripPrefix(e)
-    }
+/// Count the entries and bytes comparing two directories involves, from their metadata alone.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let estimate = dir_diff::estimate("dir/a", "dir/b").unwrap();
+/// println!("up to {} bytes to read", estimate.bytes_to_compare());
+/// ```
+pub fn estimate<A: AsRef<Path>, B: AsRef<Path>>(a_base: A, b_base: B) -> Result<Estimate, Error> {
+    DirDiffOptions::new().estimate(a_base, b_base)
+}
+
+fn diff_with<L: TreeSource, R: TreeSource>(
+    left: L,
+    right: R,
+    settings: Settings,
+    cache: Option<&HashCache>,
+) -> Result<DiffReport, Error> {
+    diff_with_previous(left, right, settings, cache, None)
 }
 
-impl From<walkdir::Error> for Error {
-    fn from(e: walkdir::Error) -> Error {
-        Error::WalkDir(e)
+/// Compare two trees in full, reusing the results of `previous` for files its stats show
+/// unchanged, then replacing those stats with this comparison's.
+fn diff_with_previous<L: TreeSource, R: TreeSource>(
+    left: L,
+    right: R,
+    settings: Settings,
+    cache: Option<&HashCache>,
+    previous: Option<(&DiffReport, &mut StatCache)>,
+) -> Result<DiffReport, Error> {
+    let left = settings.map_left(left)?;
+    let right = settings.map_right(right)?;
+    let left_root = left.root().to_owned();
+    let right_root = right.root().to_owned();
+    #[cfg(feature = "tracing")]
+    let _span = tracing::debug_span!(
+        "diff",
+        left = %left_root.display(),
+        right = %right_root.display()
+    )
+    .entered();
+    let (report, stats) = match previous {
+        Some((report, stats)) => (Some(report), Some(stats)),
+        None => (None, None),
+    };
+    let mut walk = compare::Differences::new(&left, &right, settings.clone(), cache)?;
+    if let (Some(report), Some(stats)) = (report, &stats) {
+        walk = walk.with_incremental(incremental::Incremental::new(report, stats));
     }
+    let differences: Vec<_> = walk.by_ref().collect::<Result<_, _>>()?;
+    let new_stats = walk.take_stats();
+    let allowed = walk.into_allowed();
+    if let (Some(stats), Some(new_stats)) = (stats, new_stats) {
+        *stats = new_stats;
+    }
+    let duplicates = if settings.find_duplicates() {
+        duplicates::find(&left, &right, &settings, cache, &differences)?
+    } else {
+        Vec::new()
+    };
+    let name_issues = if settings.check_portable_names() {
+        portable::check(&left, &right, &settings)?
+    } else {
+        Vec::new()
+    };
+    #[cfg(feature = "tracing")]
+    tracing::debug!(
+        differences = differences.len(),
+        allowed = allowed.len(),
+        "compared trees"
+    );
+    Ok(DiffReport::new(left_root, right_root, differences)
+        .with_allowed(allowed)
+        .with_duplicates(duplicates)
+        .with_name_issues(name_issues)
+        .with_stats(DiffStats::new(settings.retries()))
+        .with_explanation(settings.hooks().take_explanation())
+        .with_separator(settings.path_separator()))
 }
 
 #[cfg(test)]
@@ -113,7 +314,7 @@ mod tests {
         assert_eq!(
             format!(
                 "{}",
-                Error::Io(std::io::Error::new(ErrorKind::Other, "oh no!"))
+                Error::from(std::io::Error::new(ErrorKind::Other, "oh no!"))
             ),
             "I/O error: oh no!"
         );
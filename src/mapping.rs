@@ -0,0 +1,427 @@
+//! Rewriting the paths of a tree, for trees with known structural renames or golden trees
+//! with variables in their paths.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::error::{Phase, ResultExt as _};
+use crate::report::Side;
+use crate::source::{full_path, FileType, Metadata, SourceEntry, TreeSource};
+use crate::Error;
+
+/// How [`Mapped`] rewrites the paths of a tree.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Rewrite<'a> {
+    /// By the first rule that matches.
+    Rules(&'a [PathRule]),
+    /// By replacing `{{name}}` in each component with the value of the variable `name`.
+    Variables(&'a BTreeMap<String, String>),
+}
+
+impl Rewrite<'_> {
+    fn is_empty(&self) -> bool {
+        match self {
+            Rewrite::Rules(rules) => rules.is_empty(),
+            Rewrite::Variables(variables) => variables.is_empty(),
+        }
+    }
+
+    /// Where `path` is moved to, if anywhere.
+    fn apply(&self, path: &Path) -> Option<PathBuf> {
+        match self {
+            Rewrite::Rules(rules) => rules.iter().find_map(|rule| rule.apply(path)),
+            Rewrite::Variables(variables) => expand(path, variables),
+        }
+    }
+}
+
+/// `path` with the variables in its components replaced, if it has any that are defined.
+///
+/// Placeholders naming undefined variables, and components that aren't valid UTF-8, are left as
+/// they are.
+fn expand(path: &Path, variables: &BTreeMap<String, String>) -> Option<PathBuf> {
+    let mut expanded = PathBuf::new();
+    let mut changed = false;
+    for component in path.components() {
+        let Some(mut rest) = component.as_os_str().to_str() else {
+            expanded.push(component);
+            continue;
+        };
+        let mut name = String::new();
+        while let Some(start) = rest.find("{{") {
+            let value = rest[start + 2..].find("}}").and_then(|len| {
+                let value = variables.get(&rest[start + 2..start + 2 + len])?;
+                Some((value, start + 2 + len + 2))
+            });
+            match value {
+                Some((value, end)) => {
+                    name.push_str(&rest[..start]);
+                    name.push_str(value);
+                    rest = &rest[end..];
+                    changed = true;
+                }
+                None => {
+                    name.push_str(&rest[..start + 2]);
+                    rest = &rest[start + 2..];
+                }
+            }
+        }
+        name.push_str(rest);
+        expanded.push(name);
+    }
+    changed.then_some(expanded)
+}
+
+/// A part of a glob or template.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Literal(char),
+    /// `*` or `**`, matching any run of characters, including path separators.
+    Any,
+    /// `?`, matching any one character.
+    One,
+}
+
+/// A rule from [`DirDiffOptions::map_path`][crate::DirDiffOptions::map_path].
+#[derive(Clone, Debug)]
+pub(crate) struct PathRule {
+    glob: Vec<Token>,
+    template: Vec<Token>,
+}
+
+impl PathRule {
+    pub(crate) fn new(glob: &str, template: &str) -> Result<Self, Error> {
+        let rule = Self {
+            glob: parse(glob),
+            template: parse(template),
+        };
+        let count = |tokens: &[Token]| {
+            tokens
+                .iter()
+                .filter(|token| !matches!(token, Token::Literal(_)))
+                .count()
+        };
+        if count(&rule.glob) < count(&rule.template) {
+            return Err(Error::PathMap {
+                glob: glob.to_owned(),
+                template: template.to_owned(),
+            });
+        }
+        Ok(rule)
+    }
+
+    /// Where the rule moves `path` to, if it matches.
+    fn apply(&self, path: &Path) -> Option<PathBuf> {
+        let path = to_slashes(path)?;
+        let mut captures = Vec::new();
+        if !matches(&self.glob, &path, &mut captures) {
+            return None;
+        }
+        let mut captures = captures.into_iter();
+        let mut mapped = String::new();
+        for token in &self.template {
+            match token {
+                Token::Literal(c) => mapped.push(*c),
+                Token::Any | Token::One => mapped.push_str(captures.next().unwrap_or_default()),
+            }
+        }
+        Some(mapped.split('/').filter(|part| !part.is_empty()).collect())
+    }
+}
+
+fn parse(glob: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for c in glob.chars() {
+        match c {
+            '*' if tokens.last() == Some(&Token::Any) => {}
+            '*' => tokens.push(Token::Any),
+            '?' => tokens.push(Token::One),
+            c => tokens.push(Token::Literal(c)),
+        }
+    }
+    tokens
+}
+
+/// `path` with `/` between its components, if it is valid UTF-8.
+fn to_slashes(path: &Path) -> Option<String> {
+    let components: Option<Vec<_>> = path
+        .components()
+        .map(|component| component.as_os_str().to_str())
+        .collect();
+    Some(components?.join("/"))
+}
+
+/// Whether `text` matches `tokens`, pushing what each wildcard matched onto `captures`.
+fn matches<'t>(tokens: &[Token], text: &'t str, captures: &mut Vec<&'t str>) -> bool {
+    let Some((token, rest)) = tokens.split_first() else {
+        return text.is_empty();
+    };
+    match token {
+        Token::Literal(c) => text
+            .strip_prefix(*c)
+            .map(|text| matches(rest, text, captures))
+            .unwrap_or(false),
+        Token::One => {
+            let Some(c) = text.chars().next() else {
+                return false;
+            };
+            let (one, text) = text.split_at(c.len_utf8());
+            captures.push(one);
+            matches(rest, text, captures) || {
+                captures.pop();
+                false
+            }
+        }
+        Token::Any => {
+            let ends = text
+                .char_indices()
+                .map(|(end, _)| end)
+                .chain(std::iter::once(text.len()));
+            for end in ends {
+                captures.push(&text[..end]);
+                if matches(rest, &text[end..], captures) {
+                    return true;
+                }
+                captures.pop();
+            }
+            false
+        }
+    }
+}
+
+/// A directory in the rewritten tree.
+#[derive(Debug, Default)]
+struct Dir {
+    /// Where its metadata is read from.
+    real: PathBuf,
+    entries: BTreeMap<OsString, FileType>,
+}
+
+/// `source` with its paths rewritten, or as it is when there is nothing to rewrite them by.
+///
+/// Otherwise, the tree is listed in full up front.  Directories are implied by the rewritten
+/// paths, so a directory whose entries all move elsewhere is left out.
+pub(crate) struct Mapped<S> {
+    source: S,
+    side: Side,
+    dirs: HashMap<PathBuf, Dir>,
+    /// Where each entry that isn't a directory is read from.
+    others: HashMap<PathBuf, PathBuf>,
+}
+
+impl<S: TreeSource> Mapped<S> {
+    pub(crate) fn new(source: S, side: Side, rewrite: Rewrite<'_>) -> Result<Self, Error> {
+        let mut mapped = Self {
+            source,
+            side,
+            dirs: HashMap::new(),
+            others: HashMap::new(),
+        };
+        if rewrite.is_empty() {
+            return Ok(mapped);
+        }
+
+        let root = Path::new("");
+        match mapped.source.metadata(root) {
+            Ok(metadata) if metadata.file_type() == FileType::Dir => {}
+            // Left for the comparison to report.
+            Ok(_) => return Ok(mapped),
+            Err(Error::Io { source: err, .. }) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Err(Error::RootMissing {
+                    side,
+                    path: mapped.source.root().to_owned(),
+                });
+            }
+            Err(err) => {
+                return Err(err.context(Phase::Walking(side), || mapped.source.root().to_owned()))
+            }
+        }
+
+        mapped.dirs.insert(PathBuf::new(), Dir::default());
+        let mut pending = vec![PathBuf::new()];
+        while let Some(dir) = pending.pop() {
+            let entries = mapped
+                .source
+                .read_dir(&dir)
+                .context(Phase::Walking(side), || full_path(&mapped.source, &dir))?;
+            if entries.is_empty() && !dir.as_os_str().is_empty() {
+                mapped.insert(rewrite, dir.clone(), FileType::Dir)?;
+            }
+            for entry in entries {
+                let path = dir.join(entry.name());
+                match entry.file_type() {
+                    FileType::Dir => pending.push(path),
+                    file_type => mapped.insert(rewrite, path, file_type)?,
+                }
+            }
+        }
+        Ok(mapped)
+    }
+
+    /// Add the entry at `real`, and the directories containing it, to the rewritten tree.
+    fn insert(
+        &mut self,
+        rewrite: Rewrite<'_>,
+        real: PathBuf,
+        file_type: FileType,
+    ) -> Result<(), Error> {
+        let path = rewrite.apply(&real).unwrap_or_else(|| real.clone());
+        let collision = |path: &Path| {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::AlreadyExists,
+                format!("more than one entry is mapped to `{}`", path.display()),
+            ))
+            .context(Phase::Walking(self.side), || full_path(&self.source, &real))
+        };
+        if self.others.contains_key(&path)
+            || (file_type != FileType::Dir && self.dirs.contains_key(&path))
+        {
+            return collision(&path);
+        }
+        if let Some(file) = path.ancestors().find(|dir| self.others.contains_key(*dir)) {
+            return collision(file);
+        }
+
+        let mut real_parent = real.parent();
+        for dir in path.ancestors().skip(1) {
+            self.dirs.entry(dir.to_owned()).or_insert_with(|| Dir {
+                real: real_parent.unwrap_or(Path::new("")).to_owned(),
+                entries: BTreeMap::new(),
+            });
+            real_parent = real_parent.and_then(Path::parent);
+        }
+        let mut child = path.as_path();
+        let mut child_type = file_type;
+        while let Some(dir) = child.parent() {
+            let (Some(name), Some(dir_entry)) = (child.file_name(), self.dirs.get_mut(dir)) else {
+                break;
+            };
+            dir_entry.entries.insert(name.to_owned(), child_type);
+            child = dir;
+            child_type = FileType::Dir;
+        }
+
+        if file_type == FileType::Dir {
+            self.dirs.entry(path).or_insert(Dir {
+                real,
+                entries: BTreeMap::new(),
+            });
+        } else {
+            self.others.insert(path, real);
+        }
+        Ok(())
+    }
+
+    /// Where `path` in the rewritten tree is read from.
+    ///
+    /// Paths outside of the rewritten tree, as found by following symlinks, are read as they are.
+    fn real<'p>(&'p self, path: &'p Path) -> &'p Path {
+        if let Some(real) = self.others.get(path) {
+            real
+        } else if let Some(dir) = self.dirs.get(path) {
+            &dir.real
+        } else {
+            path
+        }
+    }
+}
+
+impl<S: TreeSource> TreeSource for Mapped<S> {
+    fn root(&self) -> &Path {
+        self.source.root()
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<SourceEntry>, Error> {
+        match self.dirs.get(path) {
+            Some(dir) => Ok(dir
+                .entries
+                .iter()
+                .map(|(name, file_type)| SourceEntry::new(name.clone(), *file_type))
+                .collect()),
+            None => self.source.read_dir(path),
+        }
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, Error> {
+        self.source.metadata(self.real(path))
+    }
+
+    fn open(&self, path: &Path) -> Result<Box<dyn Read + '_>, Error> {
+        self.source.open(self.real(path))
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf, Error> {
+        self.source.read_link(self.real(path))
+    }
+
+    fn local_path(&self, path: &Path) -> Option<PathBuf> {
+        self.source.local_path(self.real(path))
+    }
+
+    fn lists_local_dirs(&self) -> bool {
+        // Rewritten directories are listed from `dirs`, not from where they are read.
+        self.dirs.is_empty() && self.source.lists_local_dirs()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(glob: &str, template: &str, path: &str) -> Option<PathBuf> {
+        PathRule::new(glob, template)
+            .unwrap()
+            .apply(Path::new(path))
+    }
+
+    #[test]
+    fn test_apply() {
+        assert_eq!(
+            apply("lib64/**", "lib/**", "lib64/x/libc.so"),
+            Some(PathBuf::from("lib/x/libc.so"))
+        );
+        assert_eq!(apply("lib64/**", "lib/**", "lib/libc.so"), None);
+        assert_eq!(apply("lib64/**", "lib/**", "lib64"), None);
+        assert_eq!(
+            apply("*.htm", "*.html", "docs/index.htm"),
+            Some(PathBuf::from("docs/index.html"))
+        );
+        assert_eq!(
+            apply("v?/*", "versions/?/*", "v1/a"),
+            Some(PathBuf::from("versions/1/a"))
+        );
+        assert_eq!(apply("a/*", "b", "a/c"), Some(PathBuf::from("b")));
+    }
+
+    #[test]
+    fn test_expand() {
+        let variables = [("target", "x86_64-unknown-linux-gnu"), ("ext", "so")]
+            .into_iter()
+            .map(|(name, value)| (name.to_owned(), value.to_owned()))
+            .collect();
+        let expand = |path: &str| expand(Path::new(path), &variables);
+        assert_eq!(
+            expand("bin/{{target}}/app"),
+            Some(PathBuf::from("bin/x86_64-unknown-linux-gnu/app"))
+        );
+        assert_eq!(
+            expand("lib/libc.{{ext}}.{{ext}}"),
+            Some(PathBuf::from("lib/libc.so.so"))
+        );
+        assert_eq!(
+            expand("{{other}}/lib.{{ext}}"),
+            Some(PathBuf::from("{{other}}/lib.so"))
+        );
+        assert_eq!(expand("bin/{{other}}/{{target"), None);
+    }
+
+    #[test]
+    fn test_too_many_wildcards() {
+        assert!(matches!(
+            PathRule::new("a/*", "b/*/*"),
+            Err(Error::PathMap { .. })
+        ));
+    }
+}
@@ -0,0 +1,165 @@
+//! Merging two compared trees into a third.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::compare::check_overlap;
+use crate::error::{Phase, ResultExt as _};
+use crate::report::{DiffReport, Difference, DifferenceKind, Side};
+use crate::snapshot::{copy_file, copy_link, copy_tree};
+use crate::source::FsSource;
+use crate::{Error, Snapshot};
+
+/// Which side [`DiffReport::merge`] takes an entry from when both trees have it, but differently.
+///
+/// # Examples
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use dir_diff::{Difference, MergePolicy, Side};
+///
+/// // Keep the left side's files, but take lock files from the right.
+/// let policy = MergePolicy::Callback(Arc::new(|difference: &Difference| {
+///     match difference.path().extension() {
+///         Some(extension) if extension == "lock" => Side::Right,
+///         _ => Side::Left,
+///     }
+/// }));
+/// ```
+#[derive(Clone)]
+#[non_exhaustive]
+pub enum MergePolicy {
+    /// Always take the left entry.
+    PreferLeft,
+    /// Always take the right entry.
+    PreferRight,
+    /// Take the entry modified last, or the left one if that can't be told.
+    PreferNewer,
+    /// Ask, for each difference, which side to take.
+    Callback(Arc<dyn Fn(&Difference) -> Side + Send + Sync>),
+}
+
+impl std::fmt::Debug for MergePolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MergePolicy::PreferLeft => write!(f, "PreferLeft"),
+            MergePolicy::PreferRight => write!(f, "PreferRight"),
+            MergePolicy::PreferNewer => write!(f, "PreferNewer"),
+            MergePolicy::Callback(_) => write!(f, "Callback(..)"),
+        }
+    }
+}
+
+impl DiffReport {
+    /// Merge the two trees into a new directory at `out`, leaving them as they are.
+    ///
+    /// Entries the trees have in common are copied from the left one, and entries only one
+    /// tree has are copied from it.  An entry both trees have, but which differs between them,
+    /// is copied whole from the side `policy` picks, even when it is a directory.  Like
+    /// [`DiffReport::write_patch`], the trees are read from [`DiffReport::left`] and
+    /// [`DiffReport::right`], and whatever the comparison excluded is copied from the left
+    /// tree as it is.
+    ///
+    /// `out` must not exist, nor be inside either tree.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// extern crate dir_diff;
+    ///
+    /// let report = dir_diff::diff("tests/fixtures", "target/fixtures").unwrap();
+    /// report
+    ///     .merge(&dir_diff::MergePolicy::PreferNewer, "target/merged")
+    ///     .unwrap();
+    /// ```
+    pub fn merge<P: AsRef<Path>>(&self, policy: &MergePolicy, out: P) -> Result<(), Error> {
+        let out = out.as_ref();
+        std::fs::create_dir_all(out.parent().unwrap_or(Path::new("")))
+            .and_then(|()| std::fs::DirBuilder::new().create(out))
+            .map_err(|err| writing(err, out))?;
+        let merged = self.merge_into(policy, out);
+        if merged.is_err() {
+            let _ = std::fs::remove_dir_all(out);
+        }
+        merged
+    }
+
+    fn merge_into(&self, policy: &MergePolicy, out: &Path) -> Result<(), Error> {
+        check_overlap(&FsSource::new(self.left()), &FsSource::new(out))?;
+        check_overlap(&FsSource::new(self.right()), &FsSource::new(out))?;
+        copy_tree(self.left(), out, Snapshot::Copy, Side::Left)?;
+        for difference in self.differences() {
+            let side = match difference.kind() {
+                DifferenceKind::OnlyInLeft | DifferenceKind::RootName => continue,
+                DifferenceKind::OnlyInRight => Side::Right,
+                _ => self.pick(policy, difference)?,
+            };
+            if side == Side::Left {
+                continue;
+            }
+            let target = difference.path().resolve(out);
+            match std::fs::symlink_metadata(&target) {
+                Ok(metadata) if metadata.is_dir() => std::fs::remove_dir_all(&target),
+                Ok(_) => std::fs::remove_file(&target),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                Err(err) => Err(err),
+            }
+            .map_err(|err| writing(err, &target))?;
+            copy_entry(&self.resolve(difference.path(), Side::Right), &target)?;
+        }
+        Ok(())
+    }
+
+    /// The side `policy` takes the entry of `difference` from.
+    fn pick(&self, policy: &MergePolicy, difference: &Difference) -> Result<Side, Error> {
+        let side = match policy {
+            MergePolicy::PreferLeft => Side::Left,
+            MergePolicy::PreferRight => Side::Right,
+            MergePolicy::PreferNewer => {
+                let modified = |side| {
+                    let path = self.resolve(difference.path(), side);
+                    std::fs::symlink_metadata(&path)
+                        .context(Phase::Walking(side), || path)
+                        .map(|metadata| metadata.modified().ok())
+                };
+                match (modified(Side::Left)?, modified(Side::Right)?) {
+                    (Some(left), Some(right)) if left < right => Side::Right,
+                    _ => Side::Left,
+                }
+            }
+            MergePolicy::Callback(callback) => callback(difference),
+        };
+        Ok(side)
+    }
+}
+
+/// Copy the entry at `from` to `to`, with everything under it.
+fn copy_entry(from: &Path, to: &Path) -> Result<(), Error> {
+    let metadata =
+        std::fs::symlink_metadata(from).context(Phase::Walking(Side::Right), || from.to_owned())?;
+    let file_type = metadata.file_type();
+    let copied = if file_type.is_dir() {
+        std::fs::create_dir_all(to).map_err(|err| writing(err, to))?;
+        return copy_tree(from, to, Snapshot::Copy, Side::Right);
+    } else if file_type.is_symlink() {
+        copy_link(from, to)
+    } else if file_type.is_file() {
+        copy_file(from, to, Snapshot::Copy)
+    } else {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "only files, directories and symlinks can be merged",
+        ))
+    };
+    copied.context(Phase::Walking(Side::Right), || from.to_owned())
+}
+
+/// An error writing the merged tree at `path`.
+fn writing(err: std::io::Error, path: &Path) -> Error {
+    Error::Io {
+        source: err,
+        phase: None,
+        path: Some(path.to_owned()),
+    }
+}
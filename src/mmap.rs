@@ -0,0 +1,265 @@
+//! Comparing large local files by mapping them into memory on Linux.
+//!
+//! Pages are only read as the comparison reaches them, and dropped once compared, so memory use
+//! stays flat no matter how large the files are.
+//!
+//! Reading a mapped page past the end of a file that was truncated after it was mapped raises
+//! `SIGBUS`.  While a file is mapped here, a handler replaces the missing page with zeros and
+//! flags the mapping, so the truncation is reported as an error instead of killing the process.
+
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd as _;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+use std::sync::Once;
+
+use crate::report::Side;
+
+/// How much of the files to compare before checking for truncation and dropping the pages.
+const WINDOW: usize = 1024 * 1024;
+
+/// Compare two local files of `len` bytes through memory maps.
+///
+/// Returns `None` if the files can't be mapped, in which case they should be read the usual
+/// way.  Errors are returned with the side they happened on.
+pub(crate) fn same_files(
+    left: &Path,
+    right: &Path,
+    len: u64,
+) -> Option<Result<bool, (Side, io::Error)>> {
+    let len = usize::try_from(len).ok()?;
+    if len == 0 {
+        return Some(Ok(true));
+    }
+    let left_file = match File::open(left) {
+        Ok(file) => file,
+        Err(err) => return Some(Err((Side::Left, err))),
+    };
+    let right_file = match File::open(right) {
+        Ok(file) => file,
+        Err(err) => return Some(Err((Side::Right, err))),
+    };
+    install_handler();
+    let left_map = Mapping::new(&left_file, len)?;
+    let right_map = Mapping::new(&right_file, len)?;
+
+    let mut start = 0;
+    while start < len {
+        let end = len.min(start + WINDOW);
+        let same = left_map.bytes()[start..end] == right_map.bytes()[start..end];
+        for (side, map) in [(Side::Left, &left_map), (Side::Right, &right_map)] {
+            if map.slot.truncated.load(Ordering::SeqCst) {
+                return Some(Err((side, truncated())));
+            }
+        }
+        if !same {
+            return Some(Ok(false));
+        }
+        left_map.release(start, end);
+        right_map.release(start, end);
+        start = end;
+    }
+    Some(Ok(true))
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "file was truncated while it was being compared",
+    )
+}
+
+/// A file mapped read-only into memory, registered with the `SIGBUS` handler.
+struct Mapping {
+    ptr: *mut libc::c_void,
+    len: usize,
+    slot: &'static Slot,
+}
+
+impl Mapping {
+    fn new(file: &File, len: usize) -> Option<Self> {
+        let slot = Slot::claim()?;
+        // SAFETY: mapping a file we have open, at an address of the kernel's choosing.
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            slot.release();
+            return None;
+        }
+        // SAFETY: advising on the mapping just made.  Failure only loses the hint.
+        unsafe {
+            libc::madvise(ptr, len, libc::MADV_SEQUENTIAL);
+        }
+        slot.register(ptr as usize, len);
+        Some(Self { ptr, len, slot })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        // SAFETY: the mapping is readable for `len` bytes until dropped.  Pages past the end of
+        // a truncated file read as zeros, thanks to the handler.
+        unsafe { std::slice::from_raw_parts(self.ptr.cast::<u8>(), self.len) }
+    }
+
+    /// Drop the pages between `start` and `end` from memory, now that they are compared.
+    fn release(&self, start: usize, end: usize) {
+        let page = page_size();
+        let start = start / page * page;
+        // SAFETY: the range is within the mapping, and is read again from the file if needed.
+        unsafe {
+            libc::madvise(
+                self.ptr.cast::<u8>().add(start).cast(),
+                end - start,
+                libc::MADV_DONTNEED,
+            );
+        }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        // SAFETY: unmapping the mapping made in `new`, which nothing borrows any more.
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+        self.slot.release();
+    }
+}
+
+/// A mapping the `SIGBUS` handler may patch up.
+///
+/// The handler can't take locks or allocate, so mappings are registered in a fixed table of
+/// atomics.
+struct Slot {
+    in_use: AtomicBool,
+    start: AtomicUsize,
+    len: AtomicUsize,
+    truncated: AtomicBool,
+}
+
+/// How many files can be mapped at once, across all threads.  More fall back to reading.
+const SLOTS: usize = 64;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY_SLOT: Slot = Slot {
+    in_use: AtomicBool::new(false),
+    start: AtomicUsize::new(0),
+    len: AtomicUsize::new(0),
+    truncated: AtomicBool::new(false),
+};
+
+static TABLE: [Slot; SLOTS] = [EMPTY_SLOT; SLOTS];
+
+impl Slot {
+    fn claim() -> Option<&'static Slot> {
+        TABLE.iter().find(|slot| {
+            slot.in_use
+                .compare_exchange(false, true, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+        })
+    }
+
+    fn register(&self, start: usize, len: usize) {
+        self.truncated.store(false, Ordering::SeqCst);
+        self.len.store(len, Ordering::SeqCst);
+        self.start.store(start, Ordering::SeqCst);
+    }
+
+    fn release(&self) {
+        self.start.store(0, Ordering::SeqCst);
+        self.len.store(0, Ordering::SeqCst);
+        self.in_use.store(false, Ordering::SeqCst);
+    }
+
+    fn contains(&self, addr: usize) -> bool {
+        let start = self.start.load(Ordering::SeqCst);
+        start != 0 && addr >= start && addr - start < self.len.load(Ordering::SeqCst)
+    }
+}
+
+static PAGE_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+fn page_size() -> usize {
+    PAGE_SIZE.load(Ordering::Relaxed)
+}
+
+/// The handler that was installed before ours, to pass on faults that aren't ours.
+static PREVIOUS: AtomicPtr<libc::sigaction> = AtomicPtr::new(std::ptr::null_mut());
+
+fn install_handler() {
+    static INSTALL: Once = Once::new();
+    INSTALL.call_once(|| {
+        // SAFETY: `sysconf` has no preconditions.
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        PAGE_SIZE.store(usize::try_from(page).unwrap_or(4096), Ordering::Relaxed);
+
+        // SAFETY: a zeroed `sigaction` is valid, and is filled in before use.
+        let mut action: libc::sigaction = unsafe { std::mem::zeroed() };
+        action.sa_sigaction = on_sigbus as *const () as usize;
+        action.sa_flags = libc::SA_SIGINFO | libc::SA_NODEFER;
+        let previous = Box::leak(Box::new(unsafe { std::mem::zeroed::<libc::sigaction>() }));
+        // SAFETY: both pointers are valid for the duration of the call.
+        if unsafe { libc::sigaction(libc::SIGBUS, &action, previous) } == 0 {
+            PREVIOUS.store(previous, Ordering::SeqCst);
+        }
+    });
+}
+
+extern "C" fn on_sigbus(
+    signal: libc::c_int,
+    info: *mut libc::siginfo_t,
+    context: *mut libc::c_void,
+) {
+    // SAFETY: the kernel passes a valid `siginfo_t` to `SA_SIGINFO` handlers.
+    let addr = unsafe { (*info).si_addr() } as usize;
+    if let Some(slot) = TABLE.iter().find(|slot| slot.contains(addr)) {
+        let page = addr / page_size() * page_size();
+        // SAFETY: replacing a page of our own mapping with zeros, so the faulting read can
+        // finish.  `mmap` is async-signal-safe.
+        let patched = unsafe {
+            libc::mmap(
+                page as *mut libc::c_void,
+                page_size(),
+                libc::PROT_READ,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_FIXED,
+                -1,
+                0,
+            )
+        };
+        if patched != libc::MAP_FAILED {
+            slot.truncated.store(true, Ordering::SeqCst);
+            return;
+        }
+    }
+
+    // Not ours: behave as if we weren't here.
+    let previous = PREVIOUS.load(Ordering::SeqCst);
+    // SAFETY: `previous` was filled in by `sigaction` and is never freed.
+    let previous = unsafe { &*previous };
+    if previous.sa_flags & libc::SA_SIGINFO != 0 {
+        // SAFETY: a handler installed with `SA_SIGINFO` has this signature.
+        let handler: extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void) =
+            unsafe { std::mem::transmute(previous.sa_sigaction) };
+        handler(signal, info, context);
+    } else if previous.sa_sigaction == libc::SIG_DFL || previous.sa_sigaction == libc::SIG_IGN {
+        // Restore the default, so the faulting read raises the signal again and ends the
+        // process as it would have.
+        // SAFETY: `sigaction` is async-signal-safe, and `previous` is valid.
+        unsafe {
+            libc::sigaction(signal, previous, std::ptr::null_mut());
+        }
+    } else {
+        // SAFETY: a handler installed without `SA_SIGINFO` has this signature.
+        let handler: extern "C" fn(libc::c_int) =
+            unsafe { std::mem::transmute(previous.sa_sigaction) };
+        handler(signal);
+    }
+}
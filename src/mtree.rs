@@ -0,0 +1,518 @@
+//! Describing trees in the mtree format of BSD `mtree` and libarchive, and checking trees
+//! against such descriptions.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::{Phase, ResultExt as _};
+use crate::options::{DirDiffOptions, Settings};
+use crate::report::{DiffReport, Difference, DifferenceKind, Side};
+use crate::source::{full_path, FileType, FsSource, Metadata, TreeSource};
+use crate::Error;
+
+/// Write a description of `dir` to `out` in the mtree format.
+///
+/// Each entry gets a line with its path, relative to `dir`, and its `type`, `mode`, `size`,
+/// `time` and, for symlinks, `link` keywords.  With the `checksums` feature, files also get a
+/// `sha256digest`.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let mut spec = std::fs::File::create("dist/release.mtree").unwrap();
+/// dir_diff::write_mtree("dist/release", &mut spec).unwrap();
+/// ```
+pub fn write_mtree<P: AsRef<Path>>(dir: P, out: impl Write) -> Result<(), Error> {
+    DirDiffOptions::new().write_mtree(dir, out)
+}
+
+/// Find the differences between `dir` and the mtree spec at `spec`, as written by `mtree -c`,
+/// `bsdtar --format=mtree` or [`write_mtree`].
+///
+/// The spec is the left side of the report.  Entries are compared by `type`, `size`, `link`
+/// and, with the `checksums` feature, `sha256digest`, when the spec has them.  Other keywords,
+/// like `uid`, are ignored.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let report = dir_diff::diff_mtree("dist/release.mtree", "dist/release").unwrap();
+/// assert!(!report.is_different());
+/// ```
+pub fn diff_mtree<S: AsRef<Path>, P: AsRef<Path>>(spec: S, dir: P) -> Result<DiffReport, Error> {
+    DirDiffOptions::new().diff_mtree(spec, dir)
+}
+
+impl DirDiffOptions {
+    /// Write a description of the entries of `dir` that aren't excluded to `out`.
+    ///
+    /// See [`crate::write_mtree`].
+    pub fn write_mtree<P: AsRef<Path>>(&self, dir: P, mut out: impl Write) -> Result<(), Error> {
+        write(&FsSource::new(dir.as_ref()), &self.settings()?, &mut out)
+    }
+
+    /// Find the differences between the entries of `dir` that aren't excluded and the mtree spec
+    /// at `spec`.
+    ///
+    /// See [`crate::diff_mtree`].  `mode` is only compared with
+    /// [`DirDiffOptions::compare_permissions`], and `time` with
+    /// [`DirDiffOptions::compare_modified`].
+    pub fn diff_mtree<S: AsRef<Path>, P: AsRef<Path>>(
+        &self,
+        spec: S,
+        dir: P,
+    ) -> Result<DiffReport, Error> {
+        diff(
+            spec.as_ref(),
+            &FsSource::new(dir.as_ref()),
+            &self.settings()?,
+        )
+    }
+}
+
+/// The keywords of an entry in a spec, by name.
+type Keywords = HashMap<String, String>;
+
+/// An entry of a tree, as mtree describes it.
+struct Entry {
+    metadata: Metadata,
+    link: Option<PathBuf>,
+}
+
+fn write<S: TreeSource>(source: &S, settings: &Settings, out: &mut dyn Write) -> Result<(), Error> {
+    let entries = list(source, settings, Side::Left)?;
+    let mut spec = String::from("#mtree\n");
+    for (path, entry) in &entries {
+        spec.push_str(&escape_path(path));
+        let metadata = &entry.metadata;
+        let file_type = match metadata.file_type() {
+            FileType::File => "file",
+            FileType::Dir => "dir",
+            FileType::Symlink => "link",
+            // Which kind of special file isn't known.
+            _ => continue,
+        };
+        spec.push_str(&format!(" type={file_type}"));
+        if let Some(mode) = metadata.permissions() {
+            spec.push_str(&format!(" mode={mode:04o}"));
+        }
+        if metadata.file_type() == FileType::File {
+            spec.push_str(&format!(" size={}", metadata.len()));
+        }
+        if let Some(time) = metadata
+            .modified()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        {
+            spec.push_str(&format!(
+                " time={}.{:09}",
+                time.as_secs(),
+                time.subsec_nanos()
+            ));
+        }
+        if let Some(link) = &entry.link {
+            spec.push_str(&format!(" link={}", escape(&link.to_string_lossy())));
+        }
+        #[cfg(feature = "checksums")]
+        if metadata.file_type() == FileType::File {
+            let hash = settings
+                .retry(|| crate::checksums::sha256(source, path))
+                .context(Phase::Reading(Side::Left), || full_path(source, path))?;
+            spec.push_str(&format!(" sha256digest={}", crate::hash::to_hex(&hash)));
+        }
+        spec.push('\n');
+    }
+    out.write_all(spec.as_bytes())
+        .context(Phase::Reading(Side::Left), || source.root().to_owned())
+}
+
+fn diff<S: TreeSource>(spec: &Path, source: &S, settings: &Settings) -> Result<DiffReport, Error> {
+    let text =
+        std::fs::read_to_string(spec).context(Phase::Reading(Side::Left), || spec.to_owned())?;
+    let expected = parse(&text)
+        .map_err(|(number, problem)| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("line {number}: {problem}"),
+            )
+        })
+        .context(Phase::Reading(Side::Left), || spec.to_owned())?;
+    let actual = list(source, settings, Side::Right)?;
+
+    // The roots are compared by the caller choosing them.
+    let paths: BTreeSet<_> = expected
+        .keys()
+        .filter(|path| !is_excluded(settings, path))
+        .chain(actual.keys())
+        .filter(|path| !path.as_os_str().is_empty())
+        .collect();
+    let mut differences = Vec::new();
+    // Like a walk, entries inside a directory missing from one side aren't reported separately.
+    let mut skipped: Option<&Path> = None;
+    for path in paths {
+        if skipped.map(|dir| path.starts_with(dir)).unwrap_or(false) {
+            continue;
+        }
+        let (keywords, entry) = match (expected.get(path), actual.get(path)) {
+            (Some(keywords), Some(entry)) => (keywords, entry),
+            (Some(_), None) => {
+                skipped = Some(path);
+                differences.push(Difference::new(path.clone(), DifferenceKind::OnlyInLeft));
+                continue;
+            }
+            (None, Some(entry)) => {
+                skipped = Some(path);
+                differences.push(
+                    Difference::new(path.clone(), DifferenceKind::OnlyInRight)
+                        .with_metadata(None, Some(entry.metadata.clone())),
+                );
+                continue;
+            }
+            (None, None) => unreachable!("`paths` are from either side"),
+        };
+        let kind = compare(source, settings, path, keywords, entry)?;
+        if kind == Some(DifferenceKind::FileType) {
+            skipped = Some(path);
+        }
+        if let Some(kind) = kind {
+            differences.push(
+                Difference::new(path.clone(), kind)
+                    .with_metadata(None, Some(entry.metadata.clone())),
+            );
+        }
+    }
+    Ok(
+        DiffReport::new(spec.to_owned(), source.root().to_owned(), differences)
+            .with_separator(settings.path_separator()),
+    )
+}
+
+/// How `entry` differs from its description in the spec.
+fn compare<S: TreeSource>(
+    source: &S,
+    settings: &Settings,
+    path: &Path,
+    keywords: &Keywords,
+    entry: &Entry,
+) -> Result<Option<DifferenceKind>, Error> {
+    let metadata = &entry.metadata;
+    let invalid = |keyword: &str| {
+        Err(Error::from(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("invalid `{keyword}` for `{}`", path.display()),
+        ))
+        .context(Phase::Reading(Side::Left), || PathBuf::from(path)))
+    };
+
+    if let Some(file_type) = keywords.get("type") {
+        let same = match file_type.as_str() {
+            "file" => metadata.file_type() == FileType::File,
+            "dir" => metadata.file_type() == FileType::Dir,
+            "link" => metadata.file_type() == FileType::Symlink,
+            "block" | "char" | "fifo" | "socket" => metadata.file_type() == FileType::Other,
+            _ => return invalid("type"),
+        };
+        if !same {
+            return Ok(Some(DifferenceKind::FileType));
+        }
+    }
+    if metadata.file_type() == FileType::File {
+        if let Some(size) = keywords.get("size") {
+            let Ok(size) = size.parse::<u64>() else {
+                return invalid("size");
+            };
+            if size != metadata.len() {
+                return Ok(Some(DifferenceKind::Content));
+            }
+        }
+    }
+    if let (Some(expected), Some(link)) = (keywords.get("link"), &entry.link) {
+        if Path::new(&unescape(expected)) != link {
+            return Ok(Some(DifferenceKind::Content));
+        }
+    }
+    #[cfg(feature = "checksums")]
+    if let (Some(digest), FileType::File) = (
+        keywords.get("sha256digest").or(keywords.get("sha256")),
+        metadata.file_type(),
+    ) {
+        let Some(digest) = crate::hash::from_hex(digest) else {
+            return invalid("sha256digest");
+        };
+        let hash = settings
+            .retry(|| crate::checksums::sha256(source, path))
+            .context(Phase::Reading(Side::Right), || full_path(source, path))?;
+        if hash != digest {
+            return Ok(Some(DifferenceKind::Content));
+        }
+    }
+    #[cfg(not(feature = "checksums"))]
+    let _ = source;
+
+    if let (true, Some(mode), Some(actual)) = (
+        settings.compare_permissions(),
+        keywords.get("mode"),
+        metadata.permissions(),
+    ) {
+        let Ok(mode) = u32::from_str_radix(mode, 8) else {
+            return invalid("mode");
+        };
+        if mode & 0o7777 != actual {
+            return Ok(Some(DifferenceKind::Permissions));
+        }
+    }
+    if let (true, FileType::File, Some(time), Some(actual)) = (
+        settings.compare_modified(),
+        metadata.file_type(),
+        keywords.get("time"),
+        metadata.modified(),
+    ) {
+        let Some(time) = parse_time(time) else {
+            return invalid("time");
+        };
+        if time != actual {
+            return Ok(Some(DifferenceKind::Modified));
+        }
+    }
+    Ok(None)
+}
+
+/// Every entry of `source` that isn't excluded, by relative path, the root included.
+fn list<S: TreeSource>(
+    source: &S,
+    settings: &Settings,
+    side: Side,
+) -> Result<BTreeMap<PathBuf, Entry>, Error> {
+    let mut entries = BTreeMap::new();
+    let mut dirs = vec![PathBuf::new()];
+    while let Some(dir) = dirs.pop() {
+        let metadata = source
+            .metadata(&dir)
+            .context(Phase::Walking(side), || full_path(source, &dir))?;
+        entries.insert(
+            dir.clone(),
+            Entry {
+                metadata,
+                link: None,
+            },
+        );
+        let listing = source
+            .read_dir(&dir)
+            .context(Phase::Walking(side), || full_path(source, &dir))?;
+        for child in listing {
+            let path = dir.join(child.name());
+            if settings.is_excluded(&path) {
+                continue;
+            }
+            match child.file_type() {
+                FileType::Dir => dirs.push(path),
+                file_type => {
+                    let metadata = source
+                        .metadata(&path)
+                        .context(Phase::Walking(side), || full_path(source, &path))?;
+                    let link = match file_type {
+                        FileType::Symlink => Some(
+                            source
+                                .read_link(&path)
+                                .context(Phase::Walking(side), || full_path(source, &path))?,
+                        ),
+                        _ => None,
+                    };
+                    entries.insert(path, Entry { metadata, link });
+                }
+            }
+        }
+    }
+    Ok(entries)
+}
+
+fn is_excluded(settings: &Settings, path: &Path) -> bool {
+    path.ancestors()
+        .take_while(|path| !path.as_os_str().is_empty())
+        .any(|path| settings.is_excluded(path))
+}
+
+/// The entries described by an mtree spec, by relative path, with their keywords.
+///
+/// Both the relative form, where a directory's entries follow it until `..`, and the full path
+/// form are understood, along with `/set` and `/unset`.
+fn parse(text: &str) -> Result<BTreeMap<PathBuf, Keywords>, (usize, &'static str)> {
+    let mut entries: BTreeMap<PathBuf, Keywords> = BTreeMap::new();
+    let mut defaults = HashMap::new();
+    let mut cwd = PathBuf::new();
+    let mut continued = String::new();
+    for (index, line) in text.lines().enumerate() {
+        let number = index + 1;
+        if let Some(line) = line.strip_suffix('\\') {
+            continued.push_str(line);
+            continued.push(' ');
+            continue;
+        }
+        continued.push_str(line);
+        let line = std::mem::take(&mut continued);
+        let mut words = line.split_whitespace();
+        let Some(name) = words.next() else {
+            continue;
+        };
+        if name.starts_with('#') {
+            continue;
+        }
+        let keywords = words.map(|word| match word.split_once('=') {
+            Some((key, value)) => (key.to_owned(), value.to_owned()),
+            None => (word.to_owned(), String::new()),
+        });
+        match name {
+            "/set" => defaults.extend(keywords),
+            "/unset" => {
+                for (key, _) in keywords {
+                    if key == "all" {
+                        defaults.clear();
+                    } else {
+                        defaults.remove(&key);
+                    }
+                }
+            }
+            ".." => {
+                if !cwd.pop() {
+                    return Err((number, "`..` above the root"));
+                }
+            }
+            _ if name.starts_with('/') => return Err((number, "unknown special command")),
+            _ => {
+                let name = unescape(name);
+                let full_form = name.contains('/');
+                let path: PathBuf = if full_form {
+                    name.split('/').filter(|part| *part != ".").collect()
+                } else if name == "." {
+                    cwd.clone()
+                } else {
+                    cwd.join(&name)
+                };
+                let entry = entries.entry(path.clone()).or_default();
+                entry.extend(defaults.clone());
+                entry.extend(keywords);
+                if !full_form && name != "." && entry.get("type").map(String::as_str) == Some("dir")
+                {
+                    cwd = path;
+                }
+            }
+        }
+    }
+    Ok(entries)
+}
+
+/// `path` with `/` separators, as a full path from the root.
+fn escape_path(path: &Path) -> String {
+    let mut escaped = String::from(".");
+    for component in path.iter() {
+        escaped.push('/');
+        escaped.push_str(&escape(&component.to_string_lossy()));
+    }
+    escaped
+}
+
+/// Encode the bytes of `name` mtree can't hold as-is as octal escapes, as `\040` for a space.
+fn escape(name: &str) -> String {
+    let mut escaped = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        if byte <= b' ' || byte >= 0x7f || matches!(byte, b'\\' | b'#' | b'=') {
+            escaped.push_str(&format!("\\{byte:03o}"));
+        } else {
+            escaped.push(char::from(byte));
+        }
+    }
+    escaped
+}
+
+fn unescape(name: &str) -> String {
+    let bytes = name.as_bytes();
+    let mut unescaped = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        let octal = bytes
+            .get(index + 1..index + 4)
+            .filter(|digits| {
+                bytes[index] == b'\\' && digits.iter().all(|d| (b'0'..=b'7').contains(d))
+            })
+            .and_then(|digits| u8::from_str_radix(std::str::from_utf8(digits).ok()?, 8).ok());
+        match octal {
+            Some(byte) => {
+                unescaped.push(byte);
+                index += 4;
+            }
+            None => {
+                unescaped.push(bytes[index]);
+                index += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&unescaped).into_owned()
+}
+
+/// A `time` keyword, as seconds and nanoseconds since the Unix epoch.
+fn parse_time(time: &str) -> Option<SystemTime> {
+    let (secs, nanos) = time.split_once('.').unwrap_or((time, "0"));
+    let time = Duration::new(secs.parse().ok()?, nanos.parse().ok()?);
+    UNIX_EPOCH.checked_add(time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a b#c=d\\"), "a\\040b\\043c\\075d\\134");
+        assert_eq!(escape("é"), "\\303\\251");
+        assert_eq!(unescape(&escape("a b#c=d\\é")), "a b#c=d\\é");
+        assert_eq!(unescape("\\9"), "\\9");
+    }
+
+    #[test]
+    fn test_parse() {
+        let spec = "\
+#mtree
+/set type=file mode=0644
+. type=dir
+bin type=dir mode=0755
+    tool mode=0755 \\
+        size=12
+..
+README size=5
+./share/doc\\040files/NEWS size=3
+";
+        let entries = parse(spec).unwrap();
+        let paths: Vec<_> = entries.keys().cloned().collect();
+        let expected: Vec<PathBuf> = vec![
+            PathBuf::new(),
+            "README".into(),
+            "bin".into(),
+            ["bin", "tool"].iter().collect(),
+            ["share", "doc files", "NEWS"].iter().collect(),
+        ];
+        assert_eq!(paths, expected);
+        let tool = &entries[&PathBuf::from("bin").join("tool")];
+        assert_eq!(tool["type"], "file");
+        assert_eq!(tool["mode"], "0755");
+        assert_eq!(tool["size"], "12");
+        assert_eq!(parse("..\n").unwrap_err(), (1, "`..` above the root"));
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(
+            parse_time("1700000000.000000500"),
+            UNIX_EPOCH.checked_add(Duration::new(1_700_000_000, 500))
+        );
+        assert_eq!(
+            parse_time("12"),
+            UNIX_EPOCH.checked_add(Duration::from_secs(12))
+        );
+        assert_eq!(parse_time("soon"), None);
+    }
+}
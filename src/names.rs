@@ -0,0 +1,169 @@
+//! Comparing which entries two trees have, from their directory listings alone.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+
+use crate::error::{Phase, ResultExt as _};
+use crate::options::{DirDiffOptions, Settings};
+use crate::report::Side;
+use crate::source::{full_path, FileType, FsSource, TreeSource};
+use crate::Error;
+
+/// The relative paths two trees don't have in common, from [`names_only_diff`].
+///
+/// Only directory listings are read to make it: no contents, and no metadata beyond the types
+/// listings give.  Entries under a path whose type changed are left out, as they are by
+/// [`DirDiffOptions::diff`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct NamesDiff {
+    added: BTreeSet<PathBuf>,
+    removed: BTreeSet<PathBuf>,
+    type_changed: BTreeSet<PathBuf>,
+}
+
+impl NamesDiff {
+    /// The paths only in the right tree.
+    pub fn added(&self) -> &BTreeSet<PathBuf> {
+        &self.added
+    }
+
+    /// The paths only in the left tree.
+    pub fn removed(&self) -> &BTreeSet<PathBuf> {
+        &self.removed
+    }
+
+    /// The paths in both trees, but of a different type in each, like a file replaced by a
+    /// directory.
+    pub fn type_changed(&self) -> &BTreeSet<PathBuf> {
+        &self.type_changed
+    }
+
+    /// Whether the trees have different entries.
+    pub fn is_different(&self) -> bool {
+        !(self.added.is_empty() && self.removed.is_empty() && self.type_changed.is_empty())
+    }
+}
+
+/// Compare the names and types of the entries in two directories, without reading any files.
+///
+/// See [`DirDiffOptions::names_only_diff`].
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let names = dir_diff::names_only_diff("dir/a", "dir/b").unwrap();
+/// for path in names.added() {
+///     println!("+ {}", path.display());
+/// }
+/// ```
+pub fn names_only_diff<A: AsRef<Path>, B: AsRef<Path>>(
+    a_base: A,
+    b_base: B,
+) -> Result<NamesDiff, Error> {
+    DirDiffOptions::new().names_only_diff(a_base, b_base)
+}
+
+impl DirDiffOptions {
+    /// Compare the names and types of the entries in two directories, without reading any files.
+    ///
+    /// Each directory is listed once, and nothing else is looked up, so this is cheap enough
+    /// for hot paths like a shell prompt.  Entries that are excluded or whose extras are
+    /// ignored are left out, and paths are mapped as by [`DirDiffOptions::map_path`].  Symlinks
+    /// aren't followed, and options about contents or metadata don't apply.
+    pub fn names_only_diff<A: AsRef<Path>, B: AsRef<Path>>(
+        &self,
+        a_base: A,
+        b_base: B,
+    ) -> Result<NamesDiff, Error> {
+        self.names_only_diff_sources(
+            FsSource::new(a_base.as_ref()),
+            FsSource::new(b_base.as_ref()),
+        )
+    }
+
+    /// Compare the names and types of the entries in two [`TreeSource`]s, without reading any
+    /// files.
+    pub fn names_only_diff_sources<L: TreeSource, R: TreeSource>(
+        &self,
+        left: L,
+        right: R,
+    ) -> Result<NamesDiff, Error> {
+        let settings = self.settings()?;
+        let left = settings.map_left(left)?;
+        let right = settings.map_right(right)?;
+        let left = list(&left, &settings, Side::Left)?;
+        let mut right = list(&right, &settings, Side::Right)?;
+
+        let mut names = NamesDiff::default();
+        for (path, file_type) in left {
+            match right.remove(&path) {
+                Some(right_type) if right_type == file_type => {}
+                Some(_) => {
+                    names.type_changed.insert(path);
+                }
+                None => {
+                    names.removed.insert(path);
+                }
+            }
+        }
+        names.added = right.into_keys().collect();
+
+        // What is under a changed type isn't compared, nor what is under an ignored extra.
+        let type_changed = &names.type_changed;
+        let kept = |only: &BTreeSet<PathBuf>, side| {
+            only.iter()
+                .filter(|path| {
+                    !path.ancestors().any(|ancestor| {
+                        type_changed.contains(ancestor)
+                            || (only.contains(ancestor)
+                                && settings.is_extra_ignored(side, ancestor))
+                    })
+                })
+                .cloned()
+                .collect()
+        };
+        names.added = kept(&names.added, Side::Right);
+        names.removed = kept(&names.removed, Side::Left);
+        Ok(names)
+    }
+}
+
+/// Every entry of a tree that isn't left out, by relative path.
+fn list<S: TreeSource>(
+    source: &S,
+    settings: &Settings,
+    side: Side,
+) -> Result<BTreeMap<PathBuf, FileType>, Error> {
+    let root = Path::new("");
+    match source.metadata(root) {
+        Ok(_) => {}
+        Err(Error::Io { source: err, .. }) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Err(Error::RootMissing {
+                side,
+                path: source.root().to_owned(),
+            });
+        }
+        Err(err) => return Err(err.context(Phase::Walking(side), || source.root().to_owned())),
+    }
+
+    let mut listing = BTreeMap::new();
+    let mut dirs = vec![PathBuf::new()];
+    while let Some(dir) = dirs.pop() {
+        let entries = source
+            .read_dir(&dir)
+            .context(Phase::Walking(side), || full_path(source, &dir))?;
+        for entry in entries {
+            let path = dir.join(entry.name());
+            if settings.is_excluded(&path) {
+                continue;
+            }
+            if entry.file_type() == FileType::Dir {
+                dirs.push(path.clone());
+            }
+            listing.insert(path, entry.file_type());
+        }
+    }
+    Ok(listing)
+}
@@ -0,0 +1,75 @@
+//! Writing reports as newline-delimited JSON, one record per line, so huge reports can be
+//! streamed and processed a line at a time.
+
+use std::io::Write;
+
+use crate::lazy::Differences;
+use crate::report::{DiffReport, Difference, DuplicateGroup, NameIssue};
+use crate::Error;
+
+/// A line of newline-delimited JSON, tagged with what it records.
+#[derive(serde::Serialize)]
+#[serde(tag = "record", rename_all = "kebab-case")]
+enum Record<'r> {
+    Difference(&'r Difference),
+    Allowed(&'r Difference),
+    Duplicate(&'r DuplicateGroup),
+    NameIssue(&'r NameIssue),
+}
+
+fn write_record(out: &mut impl Write, record: &Record<'_>) -> std::io::Result<()> {
+    serde_json::to_writer(&mut *out, record)?;
+    out.write_all(b"\n")
+}
+
+impl DiffReport {
+    /// Write the report to `out` as newline-delimited JSON.
+    ///
+    /// Each line is a JSON object with a `record` field of `difference`, `allowed`, `duplicate`
+    /// or `name-issue`, and the fields the whole report would serialize that entry with.  To
+    /// avoid holding a huge report in memory at all, write the differences as they are found
+    /// with [`Differences::write_ndjson`] instead.
+    pub fn write_ndjson(&self, out: impl Write) -> std::io::Result<()> {
+        let mut out = std::io::BufWriter::new(out);
+        for difference in self.differences() {
+            write_record(&mut out, &Record::Difference(difference))?;
+        }
+        for difference in self.allowed() {
+            write_record(&mut out, &Record::Allowed(difference))?;
+        }
+        for group in self.duplicates() {
+            write_record(&mut out, &Record::Duplicate(group))?;
+        }
+        for issue in self.name_issues() {
+            write_record(&mut out, &Record::NameIssue(issue))?;
+        }
+        out.flush()
+    }
+}
+
+impl Differences {
+    /// Write each difference to `out` as a line of JSON as soon as it is found, like
+    /// [`DiffReport::write_ndjson`], and return how many there were.
+    ///
+    /// Only `difference` records are written, as the walk doesn't keep anything else.  `out` is
+    /// flushed after each one, so a consumer sees them as they are found.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// extern crate dir_diff;
+    ///
+    /// let out = std::fs::File::create("report.ndjson").unwrap();
+    /// let count = dir_diff::differences("dir/a", "dir/b").write_ndjson(out).unwrap();
+    /// println!("{count} differences");
+    /// ```
+    pub fn write_ndjson(self, mut out: impl Write) -> Result<u64, Error> {
+        let mut count = 0;
+        for difference in self {
+            write_record(&mut out, &Record::Difference(&difference?))?;
+            out.flush()?;
+            count += 1;
+        }
+        Ok(count)
+    }
+}
@@ -0,0 +1,90 @@
+//! Comparing delimited data files field by field, allowing numbers to differ slightly.
+
+use std::path::Path;
+
+/// Whether two CSV or TSV files hold the same records, counting numeric fields the same when
+/// they are within `epsilon` of each other.
+///
+/// Files that can't be parsed are only the same byte for byte.
+pub(crate) fn same_records(path: &Path, left: &[u8], right: &[u8], epsilon: f64) -> bool {
+    let delimiter = match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension)
+            if extension.eq_ignore_ascii_case("tsv") || extension.eq_ignore_ascii_case("tab") =>
+        {
+            b'\t'
+        }
+        _ => b',',
+    };
+    let reader = |contents| {
+        csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(contents)
+    };
+    let mut left_records = reader(left).into_byte_records();
+    let mut right_records = reader(right).into_byte_records();
+    loop {
+        match (left_records.next(), right_records.next()) {
+            (None, None) => return true,
+            (Some(Ok(left_record)), Some(Ok(right_record))) => {
+                if left_record.len() != right_record.len()
+                    || !left_record
+                        .iter()
+                        .zip(&right_record)
+                        .all(|(left, right)| same_field(left, right, epsilon))
+                {
+                    return false;
+                }
+            }
+            (Some(Err(_)), _) | (_, Some(Err(_))) => return left == right,
+            _ => return false,
+        }
+    }
+}
+
+/// Whether two fields are equal, or are numbers within `epsilon` of each other, relative to
+/// their magnitude once it is over 1.
+fn same_field(left: &[u8], right: &[u8], epsilon: f64) -> bool {
+    if left == right {
+        return true;
+    }
+    let (Some(left), Some(right)) = (number(left), number(right)) else {
+        return false;
+    };
+    let scale = left.abs().max(right.abs()).max(1.0);
+    (left - right).abs() <= epsilon * scale
+}
+
+fn number(field: &[u8]) -> Option<f64> {
+    let number: f64 = std::str::from_utf8(field).ok()?.trim().parse().ok()?;
+    number.is_finite().then_some(number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_records() {
+        let path = Path::new("out.csv");
+        assert!(same_records(
+            path,
+            b"x,y\n0.1,12345.678\n",
+            b"x,y\n0.1000000001,12345.679\n",
+            1e-6
+        ));
+        assert!(!same_records(path, b"x\n0.1\n", b"x\n0.2\n", 1e-6));
+        assert!(!same_records(path, b"x\n0.1\n", b"y\n0.1\n", 1e-6));
+        assert!(!same_records(path, b"x\n0.1\n", b"x\n0.1\n0.1\n", 1e-6));
+        assert!(!same_records(path, b"x,1\n", b"x,1,\n", 1e-6));
+        assert!(same_records(path, b"\"a,b\", 1.0\n", b"\"a,b\",1\n", 0.0));
+        assert!(!same_records(path, b"NaN\n", b"nan\n", 1.0));
+        assert!(same_records(
+            Path::new("out.TSV"),
+            b"1.5\t2\n",
+            b"1.50001\t2.0\n",
+            1e-4
+        ));
+    }
+}
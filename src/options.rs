@@ -0,0 +1,1770 @@
+//! Configuring how two directories are compared.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use globset::{Glob, GlobMatcher, GlobSet, GlobSetBuilder};
+
+use crate::cache::HashCache;
+use crate::cancel::CancelToken;
+use crate::case::CaseSensitivity;
+use crate::comparator::{Comparator, Comparators};
+use crate::compare::{check_overlap, Differences};
+use crate::error::{Phase, ResultExt as _};
+use crate::estimate::Estimate;
+use crate::hash::HashAlgorithm;
+use crate::hooks::{Hooks, Metrics, SkipReason, Visit};
+use crate::mapping::{Mapped, PathRule, Rewrite};
+use crate::preset::Preset;
+use crate::report::{DiffReport, Difference, DifferenceKind, PathSeparator, Side};
+use crate::retry::{self, RetryPolicy};
+use crate::snapshot::{local_trees, Snapshot};
+use crate::source::{FsSource, TreeSource};
+use crate::text::{EncodingNormalization, TextRules, Whitespace};
+use crate::Error;
+
+/// Options controlling a comparison.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let report = dir_diff::DirDiffOptions::new()
+///     .exclude("*.log")
+///     .diff("dir/a", "dir/b")
+///     .unwrap();
+/// assert!(!report.is_different());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct DirDiffOptions {
+    exclude: Vec<String>,
+    ignore_hidden: bool,
+    allow_different: Vec<String>,
+    ignore_extra_left: Vec<String>,
+    ignore_extra_right: Vec<String>,
+    map_paths: Vec<(String, String)>,
+    path_variables: Vec<(String, String)>,
+    cache_dir: Option<PathBuf>,
+    hash_algorithm: HashAlgorithm,
+    find_duplicates: bool,
+    check_portable_names: bool,
+    follow_symlinks: bool,
+    strict: bool,
+    same_file_system: bool,
+    empty_files_as_missing: bool,
+    compare_root_names: bool,
+    case_sensitivity: CaseSensitivity,
+    path_separator: PathSeparator,
+    detect_volatile: bool,
+    compare_permissions: bool,
+    compare_modified: bool,
+    ignore_contents: bool,
+    #[cfg(feature = "acl")]
+    compare_acls: bool,
+    similarity: bool,
+    detect_encoding_changes: bool,
+    summarize_blocks: Option<usize>,
+    #[cfg(feature = "binary")]
+    ignore_build_ids: bool,
+    #[cfg(feature = "zip")]
+    compare_archives: Vec<String>,
+    #[cfg(feature = "csv")]
+    compare_csv: Vec<(String, f64)>,
+    normalize_encoding: Option<EncodingNormalization>,
+    ignore_whitespace: Vec<(String, Whitespace)>,
+    ignore_line_order: Vec<String>,
+    compare_commands: Vec<(String, String)>,
+    size_tolerances: Vec<(String, u64)>,
+    record_counts: Vec<(String, u8)>,
+    comparators: Comparators,
+    placeholders: Vec<(String, String, String)>,
+    snapshot: Option<Snapshot>,
+    max_buffer_size: Option<usize>,
+    max_open_files: Option<Arc<OpenFiles>>,
+    walk_threads: Option<usize>,
+    mmap_threshold: Option<u64>,
+    sequential_reads: bool,
+    fd_traversal: bool,
+    max_entries: Option<u64>,
+    max_total_bytes: Option<u64>,
+    deadline: Option<Duration>,
+    read_timeout: Option<Duration>,
+    retry: Option<RetryPolicy>,
+    cancel_token: Option<CancelToken>,
+    hooks: Hooks,
+    explain: bool,
+}
+
+/// The chunk size files are compared in, unless [`DirDiffOptions::max_buffer_size`] is set.
+const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+impl DirDiffOptions {
+    /// The default options, equivalent to [`crate::diff`] and [`crate::is_different`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Skip entries whose path, relative to the roots, matches `glob`.
+    ///
+    /// Excluded entries are ignored on both sides and excluded directories are not descended
+    /// into.  `*` matches across path separators, so `*.log` excludes log files at any depth.
+    /// Invalid globs are reported when the comparison is run.
+    pub fn exclude(mut self, glob: impl Into<String>) -> Self {
+        self.exclude.push(glob.into());
+        self
+    }
+
+    /// Skip the entries listed by `preset`, as if each of its globs were passed to
+    /// [`DirDiffOptions::exclude`].
+    pub fn exclude_preset(mut self, preset: Preset) -> Self {
+        self.exclude
+            .extend(preset.globs().iter().map(|glob| (*glob).to_owned()));
+        self
+    }
+
+    /// Skip hidden entries, as if they were excluded.
+    ///
+    /// Entries whose names start with a dot are hidden, like `.DS_Store`, and so is everything in
+    /// a hidden directory.  On Windows, files in local trees with the hidden attribute set are too.
+    pub fn ignore_hidden(mut self, yes: bool) -> Self {
+        self.ignore_hidden = yes;
+        self
+    }
+
+    /// Don't report entries whose path matches `glob` when they are only in the `side` tree.
+    ///
+    /// Unlike [`DirDiffOptions::exclude`], entries in both trees are still compared, so the other
+    /// tree still has to be matched in full.  This suits generated trees holding incidental files
+    /// their golden tree leaves out.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use dir_diff::Side;
+    ///
+    /// // `target/out` may have bytecode that `golden` doesn't, but not the other way around.
+    /// let report = dir_diff::DirDiffOptions::new()
+    ///     .ignore_extra(Side::Left, "*.pyc")
+    ///     .diff("target/out", "golden")
+    ///     .unwrap();
+    /// assert!(!report.is_different());
+    /// ```
+    pub fn ignore_extra(mut self, side: Side, glob: impl Into<String>) -> Self {
+        match side {
+            Side::Left => self.ignore_extra_left.push(glob.into()),
+            Side::Right => self.ignore_extra_right.push(glob.into()),
+        }
+        self
+    }
+
+    /// Compare entries in the left tree whose path matches `glob` with the entries at
+    /// `template` in the right tree.
+    ///
+    /// `*` and `**` match any run of characters, including path separators, and `?` matches one
+    /// character.  Each of them in `template` is replaced with what the one in the same position
+    /// in `glob` matched.  The first rule matching an entry applies, and paths are rewritten
+    /// before being excluded or reported, so reports use the right tree's layout.
+    ///
+    /// The left tree is listed in full before the comparison starts when there are rules.  A
+    /// directory whose entries all move elsewhere is left out of the left tree, and two entries
+    /// moving to the same path fail the comparison.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let report = dir_diff::DirDiffOptions::new()
+    ///     .map_path("lib64/**", "lib/**")
+    ///     .diff("out/fedora", "out/debian")
+    ///     .unwrap();
+    /// assert!(!report.is_different());
+    /// ```
+    pub fn map_path(mut self, glob: impl Into<String>, template: impl Into<String>) -> Self {
+        self.map_paths.push((glob.into(), template.into()));
+        self
+    }
+
+    /// Replace `{{name}}` in the paths of the right tree with `value`.
+    ///
+    /// For golden trees whose layout depends on the build, like `bin/{{target}}/app` covering
+    /// every target triple.  Placeholders are replaced within each component of a path, before
+    /// it is excluded or compared, and those naming variables that aren't set are left as they
+    /// are.  Setting a variable again replaces its value.
+    ///
+    /// Like with [`DirDiffOptions::map_path`], the right tree is listed in full before the
+    /// comparison starts when there are variables, and two entries moving to the same path fail
+    /// the comparison.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let report = dir_diff::DirDiffOptions::new()
+    ///     .path_variable("target", "x86_64-unknown-linux-gnu")
+    ///     .diff("target/dist", "tests/golden/dist")
+    ///     .unwrap();
+    /// assert!(!report.is_different());
+    /// ```
+    pub fn path_variable(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.path_variables.push((name.into(), value.into()));
+        self
+    }
+
+    /// Allow entries whose path, relative to the roots, matches `glob` to differ.
+    ///
+    /// Unlike [`DirDiffOptions::exclude`], the entries are still compared, and differences found
+    /// are listed in [`DiffReport::allowed`] instead of making the trees different.  An entry
+    /// on only one side, or a directory on one side and not the other, is matched by its own path
+    /// and not by what it contains.
+    pub fn allow_different(mut self, glob: impl Into<String>) -> Self {
+        self.allow_different.push(glob.into());
+        self
+    }
+
+    /// Persist content hashes of local files in `dir` between comparisons.
+    ///
+    /// Before reading a file, its size and modification time are checked against the cache and
+    /// the cached hash is used if they match, so repeatedly comparing a mostly unchanged tree only
+    /// reads the files that changed.  Like any `mtime`-based cache, a change that keeps the size
+    /// and lands within the filesystem's timestamp granularity can go unnoticed.
+    ///
+    /// Files are recorded by their path as given to the comparison, so use the same root paths
+    /// between runs.
+    pub fn cache_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.cache_dir = Some(dir.into());
+        self
+    }
+
+    /// Identify contents by `algorithm`, rather than BLAKE3.
+    ///
+    /// This decides the digests of [`DirDiffOptions::tree_digest`] and
+    /// [`DirDiffOptions::merkle_tree`], and the hashes of [`DirDiffOptions::cache_dir`] and
+    /// [`DirDiffOptions::find_duplicates`].  Files compared side by side are still compared byte
+    /// by byte unless they are cached.  Each algorithm keeps its own cache file in the same
+    /// directory.
+    pub fn hash_algorithm(mut self, algorithm: HashAlgorithm) -> Self {
+        self.hash_algorithm = algorithm;
+        self
+    }
+
+    /// Group files with identical contents in [`DiffReport::duplicates`].
+    ///
+    /// This reads every file that is the same length as a file missing from the other side, so
+    /// it is off by default.  It has no effect on [`DirDiffOptions::is_different`].
+    pub fn find_duplicates(mut self, yes: bool) -> Self {
+        self.find_duplicates = yes;
+        self
+    }
+
+    /// List entries whose names couldn't be created on Windows in
+    /// [`DiffReport::name_issues`], for trees that will be extracted there.
+    ///
+    /// Names are checked for being reserved, ending with a dot or space, holding characters
+    /// Windows rejects, and making a path longer than 260 characters.  This walks both trees in
+    /// full, even where they don't differ, so it is off by default.  It has no effect on
+    /// [`DirDiffOptions::is_different`].
+    pub fn check_portable_names(mut self, yes: bool) -> Self {
+        self.check_portable_names = yes;
+        self
+    }
+
+    /// Compare what symlinks point to rather than the links themselves.
+    ///
+    /// A symlink to a directory is walked like a directory and a symlink to a file is compared by
+    /// contents.  A symlink that points outside of its tree is not followed and is reported as
+    /// [`DifferenceKind::SymlinkEscape`][crate::DifferenceKind::SymlinkEscape], and one that
+    /// leads back into a directory containing it fails the comparison with
+    /// [`Error::SymlinkLoop`].  A symlink dangling in one tree only is reported as
+    /// [`DifferenceKind::BrokenSymlink`][crate::DifferenceKind::BrokenSymlink], and symlinks
+    /// dangling in both are left as they are.
+    pub fn follow_symlinks(mut self, yes: bool) -> Self {
+        self.follow_symlinks = yes;
+        self
+    }
+
+    /// Fail with [`Error::UnsupportedEntry`] on entries that no option says how to compare,
+    /// rather than comparing them by type alone.
+    ///
+    /// Those are sockets, device nodes and other entries that aren't files, directories or
+    /// symlinks, and dangling symlinks, whether symlinks are followed or not.  Excluded entries
+    /// aren't looked at, so exclude any that are expected.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let report = dir_diff::DirDiffOptions::new()
+    ///     .strict(true)
+    ///     .exclude("**/*.sock")
+    ///     .diff("target/out", "tests/golden");
+    /// ```
+    pub fn strict(mut self, yes: bool) -> Self {
+        self.strict = yes;
+        self
+    }
+
+    /// Don't descend into directories on another file system than their tree's root, like mount
+    /// points, in either tree.
+    ///
+    /// Such a directory is still compared as a directory, but its contents aren't compared on
+    /// either side, and it is passed to [`DirDiffOptions::on_skip`] with
+    /// [`SkipReason::OtherFileSystem`][crate::SkipReason::OtherFileSystem].  File systems are
+    /// told apart by device ID on Unix, for trees that are local; elsewhere this has no effect.
+    pub fn same_file_system(mut self, yes: bool) -> Self {
+        self.same_file_system = yes;
+        self
+    }
+
+    /// Treat an empty file in one tree as the same as nothing at all in the other.
+    ///
+    /// For trees from generators that differ in whether they touch placeholder files.  Only a
+    /// file missing from the other tree altogether is ignored: an empty file and a non-empty one
+    /// at the same path still differ, as do an empty file and a directory.  Ignored files are
+    /// passed to [`DirDiffOptions::on_skip`] with
+    /// [`SkipReason::Empty`][crate::SkipReason::Empty].
+    pub fn empty_files_as_missing(mut self, yes: bool) -> Self {
+        self.empty_files_as_missing = yes;
+        self
+    }
+
+    /// Also compare the names of the roots, reporting
+    /// [`DifferenceKind::RootName`][crate::DifferenceKind::RootName] if they differ.
+    ///
+    /// By default only the entries under the roots are compared, so `out/foo-v1` and
+    /// `golden/foo-v2` are the same if their contents are.  Roots like `.` are named after the
+    /// directory they resolve to.
+    pub fn compare_root_names(mut self, yes: bool) -> Self {
+        self.compare_root_names = yes;
+        self
+    }
+
+    /// Match names between the trees as `sensitivity` says, rather than only when they are the
+    /// same.
+    ///
+    /// A tree copied to or from a case-insensitive file system can have the case of its names
+    /// changed, which [`CaseSensitivity::Insensitive`] ignores.  Differences are reported under
+    /// the name in the left tree.  Globs still match names as they are.
+    pub fn case_sensitivity(mut self, sensitivity: CaseSensitivity) -> Self {
+        self.case_sensitivity = sensitivity;
+        self
+    }
+
+    /// Separate the components of reported paths with `separator`.
+    ///
+    /// Paths are reported with `/` on every platform by default, so a report from Windows can be
+    /// compared with one from Linux, or with the same golden report.
+    pub fn path_separator(mut self, separator: PathSeparator) -> Self {
+        self.path_separator = separator;
+        self
+    }
+
+    /// Report files that change while they are being compared as
+    /// [`DifferenceKind::Volatile`][crate::DifferenceKind::Volatile].
+    ///
+    /// Each file is looked up again after its contents are compared, and if its size or
+    /// modification time changed in either tree, the comparison may have seen a mix of old and
+    /// new contents.  Without this, such a file is reported as equal or different depending on
+    /// what was read.
+    pub fn detect_volatile(mut self, yes: bool) -> Self {
+        self.detect_volatile = yes;
+        self
+    }
+
+    /// Report files and directories with the same contents but different permission bits as
+    /// [`DifferenceKind::Permissions`][crate::DifferenceKind::Permissions].
+    ///
+    /// Entries are only checked when both trees record permissions, as [`FsSource`] does on
+    /// Unix.  Symlinks aren't checked.
+    pub fn compare_permissions(mut self, yes: bool) -> Self {
+        self.compare_permissions = yes;
+        self
+    }
+
+    /// Report files and directories with the same contents but different access control lists
+    /// as [`DifferenceKind::Acl`][crate::DifferenceKind::Acl].
+    ///
+    /// POSIX ACLs, both the access ACL and a directory's default ACL, are compared on Linux, and
+    /// DACLs on Windows, including inherited entries.  Entries are only checked when both trees
+    /// are local, and permission bits are left to [`DirDiffOptions::compare_permissions`].
+    /// Symlinks aren't checked.
+    #[cfg(feature = "acl")]
+    pub fn compare_acls(mut self, yes: bool) -> Self {
+        self.compare_acls = yes;
+        self
+    }
+
+    /// Report files with the same contents but different modification times as
+    /// [`DifferenceKind::Modified`][crate::DifferenceKind::Modified].
+    ///
+    /// Times are compared exactly, so copies onto filesystems storing them less precisely
+    /// differ.  Directories aren't checked, since adding or removing entries changes their
+    /// modification times.
+    pub fn compare_modified(mut self, yes: bool) -> Self {
+        self.compare_modified = yes;
+        self
+    }
+
+    /// Don't compare the contents of files, only which entries there are, their types, and the
+    /// metadata that is compared.
+    ///
+    /// Files aren't read at all, so this is about as fast as listing both trees.
+    pub fn ignore_contents(mut self, yes: bool) -> Self {
+        self.ignore_contents = yes;
+        self
+    }
+
+    /// Measure how similar the two versions of each differing text file are, reported through
+    /// [`Difference::similarity`][crate::Difference::similarity].
+    ///
+    /// This distinguishes one line changing from a file being rewritten.  Both versions are read
+    /// in full and diffed line by line, so only files of valid UTF-8 of up to 1 MiB, with at most
+    /// 20,000 lines between them, are measured.
+    pub fn similarity(mut self, yes: bool) -> Self {
+        self.similarity = yes;
+        self
+    }
+
+    /// Report files holding the same text in different encodings, like UTF-8 and Latin-1, as
+    /// [`DifferenceKind::Encoding`][crate::DifferenceKind::Encoding] rather than as differences in
+    /// content.
+    ///
+    /// Each file is decoded as UTF-8 or UTF-16 if it has a byte order mark, or else as UTF-8 if
+    /// it is valid, or else as Latin-1.  This is done again after finding their bytes differ,
+    /// reading both versions in full, so only files of up to 16 MiB are decoded.  To treat such
+    /// files as the same instead, use [`DirDiffOptions::normalize_encoding`].
+    pub fn detect_encoding_changes(mut self, yes: bool) -> Self {
+        self.detect_encoding_changes = yes;
+        self
+    }
+
+    /// Count how many blocks of `block_size` bytes of each differing file are found in the other
+    /// version, reported through [`Difference::blocks`][crate::Difference::blocks].
+    ///
+    /// Meant for binary files, where [`DirDiffOptions::similarity`] doesn't apply: it tells a
+    /// file with one patched header from a corrupted one.  Blocks are found even if they moved,
+    /// using rsync's rolling checksum, at the cost of reading both versions again.  A
+    /// `block_size` of 0 is treated as 1.
+    pub fn summarize_blocks(mut self, block_size: usize) -> Self {
+        self.summarize_blocks = Some(block_size.max(1));
+        self
+    }
+
+    /// Compare ELF, PE and Mach-O files ignoring what changes from one build of the same sources
+    /// to the next, for checking that builds are reproducible.
+    ///
+    /// That is ELF build ids; PE link timestamps, checksums and debug directories, including the
+    /// GUID of the matching PDB; and Mach-O UUIDs and the timestamps recorded for dynamic
+    /// libraries.  Only files of the same size that differ are read in full to be parsed, and
+    /// files that don't parse as any of these formats are still compared byte for byte.
+    #[cfg(feature = "binary")]
+    pub fn ignore_build_ids(mut self, yes: bool) -> Self {
+        self.ignore_build_ids = yes;
+        self
+    }
+
+    /// Compare zip archives, like jars, whose path matches `glob` by their members' names and
+    /// contents, ignoring member timestamps and how the members were compressed.
+    ///
+    /// An archive that is the same byte for byte is never opened.  Otherwise both versions are
+    /// read into memory and their members compared with these same options, so archives nested
+    /// in matching archives are compared by their members too.  A file that isn't a zip archive
+    /// on both sides is compared byte for byte.  To compare two archives directly, use
+    /// [`ZipSource`][crate::source::ZipSource].
+    #[cfg(feature = "zip")]
+    pub fn compare_archives(mut self, glob: impl Into<String>) -> Self {
+        self.compare_archives.push(glob.into());
+        self
+    }
+
+    /// Compare CSV files whose path matches `glob` record by record, counting numbers within
+    /// `epsilon` of each other the same.
+    ///
+    /// Use this for numeric output that varies in the last digits across architectures.  Fields
+    /// are compared as text unless both sides parse as numbers, which match if they differ by at
+    /// most `epsilon`, scaled by the larger one's magnitude when that is over one.  Files ending
+    /// in `.tsv` or `.tab` are split at tabs rather than commas.  Files that are the same byte
+    /// for byte aren't parsed; others are read into memory, and only compare the same byte for
+    /// byte if either can't be parsed.  If several globs match a file, the epsilon added last
+    /// applies.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// extern crate dir_diff;
+    ///
+    /// let report = dir_diff::DirDiffOptions::new()
+    ///     .compare_csv("**/*.{csv,tsv}", 1e-9)
+    ///     .diff("target/results", "tests/golden")
+    ///     .unwrap();
+    /// assert!(!report.is_different());
+    /// ```
+    #[cfg(feature = "csv")]
+    pub fn compare_csv(mut self, glob: impl Into<String>, epsilon: f64) -> Self {
+        self.compare_csv.push((glob.into(), epsilon));
+        self
+    }
+
+    /// Compare files as text in the same encoding, ignoring byte order marks an editor may have
+    /// added or removed.
+    ///
+    /// Files are read in full to be normalized, rather than compared in chunks, and the hash
+    /// cache isn't used for them.
+    pub fn normalize_encoding(mut self, mode: EncodingNormalization) -> Self {
+        self.normalize_encoding = Some(mode);
+        self
+    }
+
+    /// Ignore `whitespace` when comparing files whose path, relative to the roots, matches `glob`.
+    ///
+    /// Use this for generated text whose formatting may change without its content changing.  If
+    /// several globs match a file, the most lenient of their policies applies.  Files are read in
+    /// full to be normalized, rather than compared in chunks, and the hash cache isn't used for
+    /// them.
+    pub fn ignore_whitespace(mut self, glob: impl Into<String>, whitespace: Whitespace) -> Self {
+        self.ignore_whitespace.push((glob.into(), whitespace));
+        self
+    }
+
+    /// Compare files whose path, relative to the roots, matches `glob` as lines in any order.
+    ///
+    /// Use this for generated files whose line order isn't deterministic, like those written
+    /// from hash maps.  The files must have the same lines, each as many times, and a missing
+    /// newline at the end doesn't matter.  Whitespace is ignored first, as
+    /// [`DirDiffOptions::ignore_whitespace`] says.  Files are read in full to be sorted, rather
+    /// than compared in chunks, and the hash cache isn't used for them.
+    pub fn ignore_line_order(mut self, glob: impl Into<String>) -> Self {
+        self.ignore_line_order.push(glob.into());
+        self
+    }
+
+    /// Compare files whose path, relative to the roots, matches `glob` by running `command`,
+    /// rather than byte for byte.
+    ///
+    /// Use this for formats only other tools can compare, like `cmp -s %L %R` or a domain-specific
+    /// validator.  `command` is split into words at whitespace, and `%L` and `%R` in each word
+    /// are replaced with the paths of the left and right files, and `%%` with `%`.  The command
+    /// is run without a shell, and exits with 0 if the files are the same or 1 if they differ;
+    /// any other exit is an [`Error::Command`] carrying what it wrote to its standard error.
+    /// Files that aren't on the local file system are first copied to the system's temporary
+    /// directory.  If several globs match a file, the command added last applies.
+    ///
+    /// The command runs even if the files' sizes differ, and the hash cache isn't used for them.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// extern crate dir_diff;
+    ///
+    /// let report = dir_diff::DirDiffOptions::new()
+    ///     .compare_with_command("**/*.pdf", "diff-pdf %L %R")
+    ///     .diff("out", "expected")
+    ///     .unwrap();
+    /// assert!(!report.is_different());
+    /// ```
+    pub fn compare_with_command(
+        mut self,
+        glob: impl Into<String>,
+        command: impl Into<String>,
+    ) -> Self {
+        self.compare_commands.push((glob.into(), command.into()));
+        self
+    }
+
+    /// Compare files whose path, relative to the roots, matches `glob` by size alone, counting
+    /// them the same if their sizes differ by at most `bytes`.
+    ///
+    /// Use this for files that grow slightly but predictably, like logs.  The files' contents
+    /// aren't read, so a size within the tolerance hides any change to them.  If several globs
+    /// match a file, the tolerance added last applies.  Options that compare the contents, like
+    /// [`DirDiffOptions::ignore_whitespace`], [`DirDiffOptions::normalize_encoding`] and
+    /// [`DirDiffOptions::compare_with_command`], have no effect on the files it applies to;
+    /// [`DirDiffOptions::validate`] reports them as conflicting.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// extern crate dir_diff;
+    ///
+    /// let report = dir_diff::DirDiffOptions::new()
+    ///     .size_tolerance("**/*.log", 1024)
+    ///     .diff("run/a", "run/b")
+    ///     .unwrap();
+    /// assert!(!report.is_different());
+    /// ```
+    pub fn size_tolerance(mut self, glob: impl Into<String>, bytes: u64) -> Self {
+        self.size_tolerances.push((glob.into(), bytes));
+        self
+    }
+
+    /// Compare files whose path, relative to the roots, matches `glob` by how many records they
+    /// hold, each ended by the byte `delimiter`, rather than by their contents.
+    ///
+    /// Use this for the output of data exporters whose order isn't deterministic, but whose
+    /// cardinality must match; pass `b'\n'` to count lines.  A last record without a delimiter
+    /// still counts.  If several globs match a file, the delimiter added last applies, and it
+    /// takes precedence over [`DirDiffOptions::compare_with_command`] and
+    /// [`DirDiffOptions::ignore_whitespace`], but not [`DirDiffOptions::size_tolerance`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// extern crate dir_diff;
+    ///
+    /// let report = dir_diff::DirDiffOptions::new()
+    ///     .compare_record_counts("export/*.jsonl", b'\n')
+    ///     .diff("target/export", "tests/golden/export")
+    ///     .unwrap();
+    /// assert!(!report.is_different());
+    /// ```
+    pub fn compare_record_counts(mut self, glob: impl Into<String>, delimiter: u8) -> Self {
+        self.record_counts.push((glob.into(), delimiter));
+        self
+    }
+
+    /// Compare files ending in `.extension` that differ byte for byte with `comparator`, which
+    /// decides whether they hold the same data.
+    ///
+    /// Use this for serialized data, like protobuf messages, whose bytes can change without
+    /// their meaning changing; [`DelimitedProtobuf`][crate::DelimitedProtobuf] compares streams
+    /// of protobuf messages.  Both files are read into memory for the comparator.  Extensions
+    /// are matched exactly, without the dot, and a comparator registered later for the same
+    /// extension replaces an earlier one.
+    pub fn comparator(
+        mut self,
+        extension: impl Into<String>,
+        comparator: impl Comparator + 'static,
+    ) -> Self {
+        let extension = extension.into();
+        let extension = extension.strip_prefix('.').unwrap_or(&extension).to_owned();
+        self.comparators.push(extension, Arc::new(comparator));
+        self
+    }
+
+    /// Let `placeholder` in right-hand files whose path matches `glob` stand for any text the
+    /// regular expression `pattern` matches in the left-hand file.
+    ///
+    /// Use this for golden files, on the right, holding values that change on every run, like
+    /// versions or UUIDs.  A file with placeholders is the same as one that matches it with
+    /// each placeholder replaced by some match of its pattern, and the rest of it taken
+    /// literally.  Both files are read into memory, and only when they differ byte for byte;
+    /// golden files that aren't UTF-8 aren't searched for placeholders.  Patterns use the
+    /// syntax of the [`regex`](https://docs.rs/regex) crate, where `.` doesn't match line
+    /// breaks.  Every placeholder whose glob matches a file applies to it.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// extern crate dir_diff;
+    ///
+    /// let report = dir_diff::DirDiffOptions::new()
+    ///     .placeholder("**/*.json", "{{VERSION}}", r"\d+\.\d+\.\d+")
+    ///     .placeholder("**", "[[UUID]]", "[0-9a-f]{8}(-[0-9a-f]{4}){3}-[0-9a-f]{12}")
+    ///     .diff("target/out", "tests/golden")
+    ///     .unwrap();
+    /// assert!(!report.is_different());
+    /// ```
+    pub fn placeholder(
+        mut self,
+        glob: impl Into<String>,
+        placeholder: impl Into<String>,
+        pattern: impl Into<String>,
+    ) -> Self {
+        self.placeholders
+            .push((glob.into(), placeholder.into(), pattern.into()));
+        self
+    }
+
+    /// Compare snapshots of directories, taken before the comparison starts, rather than the
+    /// directories themselves.
+    ///
+    /// Use this when other processes may be writing to the trees, so every entry is compared as
+    /// it was at roughly the same time.  Snapshots are made in the system's temporary directory,
+    /// and reported and named in errors as the directories they were taken from.  They are
+    /// removed once the comparison is done.
+    ///
+    /// Only applies to comparisons of directories given by path, not [`TreeSource`]s or
+    /// [`DirDiffOptions::watch`].
+    pub fn snapshot(mut self, mode: Snapshot) -> Self {
+        self.snapshot = Some(mode);
+        self
+    }
+
+    /// Read files in chunks of at most `bytes` when comparing their contents.
+    ///
+    /// Two buffers of this size are used per file comparison, no matter how large the files are.
+    /// The default is 64 KiB.  Sources that can't stream, like git trees, still hold each file in
+    /// memory while it is compared.
+    pub fn max_buffer_size(mut self, bytes: usize) -> Self {
+        self.max_buffer_size = Some(bytes.max(1));
+        self
+    }
+
+    /// Keep at most `count` files open at once, across every comparison run with these options
+    /// or their clones, even at the same time.
+    ///
+    /// Comparing two files needs both open, so `count` is raised to at least 2.  By default the
+    /// number of open files is only bounded by the number of comparisons running at once.
+    pub fn max_open_files(mut self, count: usize) -> Self {
+        self.max_open_files = Some(Arc::new(OpenFiles::new(count.max(2))));
+        self
+    }
+
+    /// List directories on `count` background threads, ahead of the comparison reaching them.
+    ///
+    /// Differences are still found in order, but directories and file metadata are looked up in
+    /// parallel, which speeds up walking trees of many small files on network file systems.  The
+    /// default of 1 lists every directory as it is reached.  Only directories on the local file
+    /// system are listed ahead.
+    pub fn walk_threads(mut self, count: usize) -> Self {
+        self.walk_threads = Some(count.max(1));
+        self
+    }
+
+    /// Compare local files of at least `bytes` by mapping them into memory rather than reading
+    /// them.
+    ///
+    /// Pages are only read as the comparison reaches them, and dropped once compared, so this
+    /// uses less memory than reading on large files.  A file truncated while it is mapped is
+    /// reported as an error.  Off by default, and only supported on Linux: elsewhere files are
+    /// always read.
+    pub fn mmap_threshold(mut self, bytes: u64) -> Self {
+        self.mmap_threshold = Some(bytes);
+        self
+    }
+
+    /// Tell the OS that local files are read once, front to back, when comparing them.
+    ///
+    /// The kernel then reads further ahead of the comparison, starting before the first read,
+    /// and drops the files from its page cache once they are compared.  This speeds up verifying
+    /// whole trees on spinning disks with a cold cache, and keeps a large verification from
+    /// evicting everything else, at the cost of reading the files from disk again next time.
+    /// Files at least as large as [`DirDiffOptions::mmap_threshold`] are still mapped.  Off by
+    /// default, and only supported on Linux.
+    pub fn sequential_reads(mut self, yes: bool) -> Self {
+        self.sequential_reads = yes;
+        self
+    }
+
+    /// Walk directories given by path through file descriptors, as
+    /// [`DirFdSource`][crate::source::DirFdSource] does, rather than by path.
+    ///
+    /// Use this when verifying trees that someone else may be changing to their advantage: a
+    /// directory renamed or swapped for a symlink mid-walk can't lead the comparison outside of
+    /// the tree.  The shortcuts that need paths, like the hash cache and memory maps, don't
+    /// apply, and a [`DirDiffOptions::snapshot`] is still read by path.  Only supported on
+    /// Linux: elsewhere directories are always walked by path.
+    pub fn fd_traversal(mut self, yes: bool) -> Self {
+        self.fd_traversal = yes;
+        self
+    }
+
+    /// Stop with [`Error::LimitExceeded`] once the walk of either tree reaches more than `max`
+    /// entries that aren't excluded.
+    ///
+    /// Protects against accidentally comparing a runaway directory of millions of files.
+    /// Entries inside a directory that is only in one tree aren't walked, so aren't counted.
+    pub fn max_entries(mut self, max: u64) -> Self {
+        self.max_entries = Some(max);
+        self
+    }
+
+    /// Stop with [`Error::LimitExceeded`] before reading more than `max` bytes of files to
+    /// compare them, counting both trees.
+    ///
+    /// Files are counted in full once their contents need comparing, even if they turn out to
+    /// differ early.
+    pub fn max_total_bytes(mut self, max: u64) -> Self {
+        self.max_total_bytes = Some(max);
+        self
+    }
+
+    /// Stop with [`Error::DeadlineExceeded`] once the comparison has run for `timeout`.
+    ///
+    /// The deadline is checked before each entry, and bounds the wait for each file as with
+    /// [`DirDiffOptions::read_timeout`], so a hung read can't hold the comparison past it.
+    /// Listing a directory or looking up metadata isn't interrupted though.
+    pub fn deadline(mut self, timeout: Duration) -> Self {
+        self.deadline = Some(timeout);
+        self
+    }
+
+    /// Give up on comparing a pair of local files after `timeout`, reporting it as
+    /// [`DifferenceKind::Timeout`] and moving on.
+    ///
+    /// For a hung network mount: the files are read on a thread of their own, which is left
+    /// behind if it doesn't finish in time.  Files read this way don't use the hash cache, memory
+    /// mapping or `io_uring`, and files from other [`TreeSource`]s aren't timed.  Neither are
+    /// files on targets that can't start threads, like WASI.
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Retry reads that fail with a transient error, as `policy` allows, counting the retries in
+    /// [`DiffReport::stats`].
+    ///
+    /// A file is compared again from the start, and a directory listed again, if reading it
+    /// fails.  Errors that persist once the retries run out are returned as usual.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = Some(policy);
+        self
+    }
+
+    /// Stop with [`Error::Cancelled`] once `token` is cancelled.
+    pub fn cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel_token = Some(token);
+        self
+    }
+
+    /// Call `hook` with the relative path of each directory present on both sides, before
+    /// walking into it.
+    ///
+    /// Returning [`Visit::Skip`] leaves the directory's contents uncompared, as if they were
+    /// excluded.
+    pub fn on_enter_dir(mut self, hook: impl Fn(&Path) -> Visit + Send + Sync + 'static) -> Self {
+        self.hooks.enter_dir = Some(Arc::new(hook));
+        self
+    }
+
+    /// Call `hook` with the relative path of each entry that is skipped, and why.
+    ///
+    /// An excluded entry is passed once for each tree it is in.
+    pub fn on_skip(mut self, hook: impl Fn(&Path, SkipReason) + Send + Sync + 'static) -> Self {
+        self.hooks.skip = Some(Arc::new(hook));
+        self
+    }
+
+    /// Call `hook` with the relative path of each file whose contents are compared, and whether
+    /// they are the same.
+    pub fn on_compare_file(mut self, hook: impl Fn(&Path, bool) + Send + Sync + 'static) -> Self {
+        self.hooks.compare_file = Some(Arc::new(hook));
+        self
+    }
+
+    /// Record every check run on every entry, and whether it found the entry the same, in
+    /// [`DiffReport::explanation`].
+    ///
+    /// Shows which shortcut decided each entry, like matching sizes or a hash found in the cache,
+    /// for when a comparison finds trees the same that were expected to differ.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// let report = dir_diff::DirDiffOptions::new()
+    ///     .explain(true)
+    ///     .diff("target/out", "tests/golden")
+    ///     .unwrap();
+    /// print!("{}", report.explanation().unwrap());
+    /// ```
+    pub fn explain(mut self, yes: bool) -> Self {
+        self.explain = yes;
+        self
+    }
+
+    /// Report counts and timings of the comparisons run with these options to `metrics`.
+    ///
+    /// Pass an [`Arc`] to keep reading the counters the comparisons update.
+    pub fn metrics(mut self, metrics: impl Metrics + 'static) -> Self {
+        self.hooks.metrics = Some(Arc::new(metrics));
+        self
+    }
+
+    /// Check the options and roots without comparing anything.
+    ///
+    /// Catches the mistakes that would otherwise only surface once a comparison starts, such as
+    /// invalid globs, missing roots, roots that overlap and a cache directory that is a file.
+    ///
+    /// Also fails with [`Error::ConflictingOptions`] for options that a comparison would
+    /// silently pick between, where one hides the other on the files both their globs may
+    /// match, as `*.log` and `**/*.log` or `a/*` and `a/b.txt` do:
+    /// [`DirDiffOptions::size_tolerance`] with any other option that compares the contents, or
+    /// with [`DirDiffOptions::normalize_encoding`] at all,
+    /// [`DirDiffOptions::compare_record_counts`] with
+    /// [`DirDiffOptions::compare_with_command`], [`DirDiffOptions::ignore_whitespace`] or
+    /// [`DirDiffOptions::ignore_line_order`], or with [`DirDiffOptions::normalize_encoding`] at
+    /// all, and [`DirDiffOptions::compare_with_command`] with
+    /// [`DirDiffOptions::ignore_whitespace`] or [`DirDiffOptions::ignore_line_order`].  Globs
+    /// count as overlapping when either matches the other as written, so some overlaps, like
+    /// `a*` and `*b`, go unnoticed.
+    pub fn validate<A: AsRef<Path>, B: AsRef<Path>>(
+        &self,
+        a_base: A,
+        b_base: B,
+    ) -> Result<(), Error> {
+        self.settings()?;
+        self.check_conflicts()?;
+
+        for (side, root) in [
+            (Side::Left, a_base.as_ref()),
+            (Side::Right, b_base.as_ref()),
+        ] {
+            let metadata = match std::fs::metadata(root) {
+                Ok(metadata) => metadata,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    return Err(Error::RootMissing {
+                        side,
+                        path: root.to_owned(),
+                    });
+                }
+                Err(err) => return Err(err).context(Phase::Configuring, || root.to_owned()),
+            };
+            if !metadata.is_dir() {
+                return Err(not_a_directory()).context(Phase::Configuring, || root.to_owned());
+            }
+        }
+        check_overlap(
+            &FsSource::new(a_base.as_ref()),
+            &FsSource::new(b_base.as_ref()),
+        )?;
+
+        if let Some(dir) = &self.cache_dir {
+            if dir.exists() && !dir.is_dir() {
+                return Err(not_a_directory()).context(Phase::Configuring, || dir.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Find options that both say how to compare the same files, for [`DirDiffOptions::validate`].
+    fn check_conflicts(&self) -> Result<(), Error> {
+        let conflict = |option, other, glob: &str| Error::ConflictingOptions {
+            option,
+            other,
+            glob: glob.to_owned(),
+        };
+        let record_counts = firsts(&self.record_counts);
+        let whitespace = firsts(&self.ignore_whitespace);
+        let commands = firsts(&self.compare_commands);
+        let line_order: Vec<&str> = self.ignore_line_order.iter().map(String::as_str).collect();
+        let placeholders = self.placeholders.iter().map(|(glob, _, _)| glob.as_str());
+        let comparators: Vec<String> = self
+            .comparators
+            .extensions()
+            .map(|extension| format!("*.{}", globset::escape(extension)))
+            .collect();
+        // A size tolerance hides every option that compares the contents, and a record count
+        // the ones that read them as text or with a command.
+        #[allow(unused_mut)]
+        let mut contents = vec![
+            ("compare_record_counts", record_counts.clone()),
+            ("ignore_whitespace", whitespace.clone()),
+            ("compare_with_command", commands.clone()),
+            ("ignore_line_order", line_order.clone()),
+            ("placeholder", placeholders.collect()),
+            (
+                "comparator",
+                comparators.iter().map(String::as_str).collect(),
+            ),
+        ];
+        #[cfg(feature = "zip")]
+        contents.push((
+            "compare_archives",
+            self.compare_archives.iter().map(String::as_str).collect(),
+        ));
+        #[cfg(feature = "csv")]
+        contents.push(("compare_csv", firsts(&self.compare_csv)));
+        let read = [
+            ("ignore_whitespace", &whitespace),
+            ("compare_with_command", &commands),
+            ("ignore_line_order", &line_order),
+        ];
+
+        for glob in firsts(&self.size_tolerances) {
+            if self.normalize_encoding.is_some() {
+                return Err(conflict("size_tolerance", "normalize_encoding", glob));
+            }
+            for (other, others) in &contents {
+                if overlaps_any(glob, others)? {
+                    return Err(conflict("size_tolerance", other, glob));
+                }
+            }
+        }
+        for glob in record_counts {
+            if self.normalize_encoding.is_some() {
+                return Err(conflict(
+                    "compare_record_counts",
+                    "normalize_encoding",
+                    glob,
+                ));
+            }
+            for (other, others) in &read {
+                if overlaps_any(glob, others)? {
+                    return Err(conflict("compare_record_counts", other, glob));
+                }
+            }
+        }
+        for (option, globs) in [
+            ("ignore_whitespace", &whitespace),
+            ("ignore_line_order", &line_order),
+        ] {
+            for glob in globs {
+                if overlaps_any(glob, &commands)? {
+                    return Err(conflict(option, "compare_with_command", glob));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Are the contents of two directories different?
+    ///
+    /// Stops at the first difference found, without measuring it, so
+    /// [`DirDiffOptions::similarity`] and [`DirDiffOptions::summarize_blocks`] don't slow it
+    /// down.  Use [`DirDiffOptions::diff`] to find every difference.
+    pub fn is_different<A: AsRef<Path>, B: AsRef<Path>>(
+        &self,
+        a_base: A,
+        b_base: B,
+    ) -> Result<bool, Error> {
+        self.run(|settings, cache| {
+            let settings = settings.yes_or_no();
+            let (left, right) = local_trees(a_base.as_ref(), b_base.as_ref(), &settings)?;
+            let left = settings.map_left(left)?;
+            let right = settings.map_right(right)?;
+            let mut differences = Differences::new(left, right, settings, cache)?;
+            Ok(differences.next().transpose()?.is_some())
+        })
+    }
+
+    /// Find the first difference between the contents of two directories, in relative path
+    /// order.
+    ///
+    /// Stops as soon as a difference is found.
+    pub fn first_difference<A: AsRef<Path>, B: AsRef<Path>>(
+        &self,
+        a_base: A,
+        b_base: B,
+    ) -> Result<Option<Difference>, Error> {
+        self.run(|settings, cache| {
+            let (left, right) = local_trees(a_base.as_ref(), b_base.as_ref(), &settings)?;
+            let left = settings.map_left(left)?;
+            let right = settings.map_right(right)?;
+            let mut differences = Differences::new(left, right, settings, cache)?;
+            differences.next().transpose()
+        })
+    }
+
+    /// Find every difference between the contents of two directories.
+    ///
+    /// Always walks both trees in full, however many differences there are.  Use
+    /// [`DirDiffOptions::is_different`] or [`DirDiffOptions::first_difference`] to stop early.
+    pub fn diff<A: AsRef<Path>, B: AsRef<Path>>(
+        &self,
+        a_base: A,
+        b_base: B,
+    ) -> Result<DiffReport, Error> {
+        self.run(|settings, cache| {
+            let (left, right) = local_trees(a_base.as_ref(), b_base.as_ref(), &settings)?;
+            crate::diff_with(left, right, settings, cache)
+        })
+    }
+
+    /// Does `outer` contain every entry in `inner`, with the same contents?
+    ///
+    /// Entries that only exist in `outer` are ignored.  Stops at the first entry of `inner` that
+    /// is missing from `outer` or differs.
+    pub fn contains<A: AsRef<Path>, B: AsRef<Path>>(
+        &self,
+        outer: A,
+        inner: B,
+    ) -> Result<bool, Error> {
+        self.run(|settings, cache| {
+            let settings = settings.yes_or_no();
+            let (outer, inner) = local_trees(outer.as_ref(), inner.as_ref(), &settings)?;
+            let outer = settings.map_left(outer)?;
+            let inner = settings.map_right(inner)?;
+            for difference in Differences::new(outer, inner, settings, cache)? {
+                if difference?.kind() != DifferenceKind::OnlyInLeft {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        })
+    }
+
+    /// Check that `dst` holds a copy of everything in `src`.
+    ///
+    /// Like [`DirDiffOptions::diff`], except entries only in `dst` are ignored, so the report
+    /// lists entries missing from the copy as
+    /// [`DifferenceKind::OnlyInLeft`][crate::DifferenceKind::OnlyInLeft] and corrupted ones
+    /// as [`DifferenceKind::Content`][crate::DifferenceKind::Content].  Which metadata has to
+    /// match is up to [`DirDiffOptions::compare_permissions`] and
+    /// [`DirDiffOptions::compare_modified`]; [`crate::verify_copy`] checks both.
+    pub fn verify_copy<A: AsRef<Path>, B: AsRef<Path>>(
+        &self,
+        src: A,
+        dst: B,
+    ) -> Result<DiffReport, Error> {
+        self.clone().ignore_extra(Side::Right, "**").diff(src, dst)
+    }
+
+    /// Find which of the `expected` directories has the same contents as `actual`.
+    ///
+    /// See [`crate::matches_any`].
+    pub fn matches_any<A, I>(&self, actual: A, expected: I) -> Result<Option<usize>, Error>
+    where
+        A: AsRef<Path>,
+        I: IntoIterator,
+        I::Item: AsRef<Path>,
+    {
+        self.run(|settings, cache| {
+            let settings = settings.yes_or_no();
+            for (index, expected) in expected.into_iter().enumerate() {
+                let (left, right) = local_trees(actual.as_ref(), expected.as_ref(), &settings)?;
+                let left = settings.map_left(left)?;
+                let right = settings.map_right(right)?;
+                let mut differences = Differences::new(left, right, settings.clone(), cache)?;
+                if differences.next().transpose()?.is_none() {
+                    return Ok(Some(index));
+                }
+            }
+            Ok(None)
+        })
+    }
+
+    /// Are the contents of two [`TreeSource`]s different?
+    ///
+    /// Stops at the first difference found, as [`DirDiffOptions::is_different`] does.
+    pub fn is_different_sources<L: TreeSource, R: TreeSource>(
+        &self,
+        left: L,
+        right: R,
+    ) -> Result<bool, Error> {
+        self.run(|settings, cache| {
+            let settings = settings.yes_or_no();
+            let left = settings.map_left(left)?;
+            let right = settings.map_right(right)?;
+            let mut differences = Differences::new(left, right, settings, cache)?;
+            Ok(differences.next().transpose()?.is_some())
+        })
+    }
+
+    /// Find every difference between the contents of two [`TreeSource`]s.
+    ///
+    /// Always walks both trees in full, as [`DirDiffOptions::diff`] does.
+    pub fn diff_sources<L: TreeSource, R: TreeSource>(
+        &self,
+        left: L,
+        right: R,
+    ) -> Result<DiffReport, Error> {
+        self.run(|settings, cache| crate::diff_with(left, right, settings, cache))
+    }
+
+    /// Count the entries and bytes comparing two directories involves, from their metadata
+    /// alone.
+    ///
+    /// For choosing between a full comparison and a cheaper one, or for sizing a progress bar.
+    /// Excluded entries aren't counted, and symlinks aren't followed.
+    pub fn estimate<A: AsRef<Path>, B: AsRef<Path>>(
+        &self,
+        a_base: A,
+        b_base: B,
+    ) -> Result<Estimate, Error> {
+        self.estimate_sources(
+            FsSource::new(a_base.as_ref()),
+            FsSource::new(b_base.as_ref()),
+        )
+    }
+
+    /// Count the entries and bytes comparing two [`TreeSource`]s involves, from their metadata
+    /// alone.
+    pub fn estimate_sources<L: TreeSource, R: TreeSource>(
+        &self,
+        left: L,
+        right: R,
+    ) -> Result<Estimate, Error> {
+        let settings = self.settings()?;
+        let left = settings.map_left(left)?;
+        let right = settings.map_right(right)?;
+        crate::estimate::estimate(&left, &right, &settings)
+    }
+
+    /// Run a comparison with the hash cache loaded, saving it afterwards.
+    pub(crate) fn run<T>(
+        &self,
+        compare: impl FnOnce(Settings, Option<&HashCache>) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let settings = self.settings()?;
+        let started = self.hooks.timer();
+        let Some(dir) = &self.cache_dir else {
+            let result = compare(settings, None);
+            self.hooks.finished(started.elapsed());
+            return result;
+        };
+
+        let cache = HashCache::load(dir, self.hash_algorithm)?.with_metrics(&self.hooks);
+        let result = compare(settings, Some(&cache));
+        self.hooks.finished(started.elapsed());
+        // Hashes computed before an error are still worth keeping.
+        let saved = cache.save(dir);
+        let value = result?;
+        saved?;
+        Ok(value)
+    }
+
+    pub(crate) fn cache_dir_path(&self) -> Option<&Path> {
+        self.cache_dir.as_deref()
+    }
+
+    fn whitespace_globs(&self, whitespace: Whitespace) -> Vec<String> {
+        self.ignore_whitespace
+            .iter()
+            .filter(|(_, policy)| *policy == whitespace)
+            .map(|(glob, _)| glob.clone())
+            .collect()
+    }
+
+    pub(crate) fn settings(&self) -> Result<Settings, Error> {
+        Ok(Settings {
+            exclude: build_glob_set(&self.exclude)?,
+            ignore_hidden: self.ignore_hidden,
+            allowed: build_glob_set(&self.allow_different)?,
+            extra_left: build_glob_set(&self.ignore_extra_left)?,
+            extra_right: build_glob_set(&self.ignore_extra_right)?,
+            path_rules: self
+                .map_paths
+                .iter()
+                .map(|(glob, template)| PathRule::new(glob, template))
+                .collect::<Result<_, _>>()?,
+            path_variables: self.path_variables.iter().cloned().collect(),
+            hash_algorithm: self.hash_algorithm,
+            find_duplicates: self.find_duplicates,
+            check_portable_names: self.check_portable_names,
+            follow_symlinks: self.follow_symlinks,
+            strict: self.strict,
+            same_file_system: self.same_file_system,
+            empty_files_as_missing: self.empty_files_as_missing,
+            compare_root_names: self.compare_root_names,
+            case_sensitivity: self.case_sensitivity,
+            path_separator: self.path_separator,
+            detect_volatile: self.detect_volatile,
+            compare_permissions: self.compare_permissions,
+            compare_modified: self.compare_modified,
+            ignore_contents: self.ignore_contents,
+            #[cfg(feature = "acl")]
+            compare_acls: self.compare_acls,
+            similarity: self.similarity,
+            detect_encoding_changes: self.detect_encoding_changes,
+            summarize_blocks: self.summarize_blocks,
+            #[cfg(feature = "binary")]
+            ignore_build_ids: self.ignore_build_ids,
+            #[cfg(feature = "zip")]
+            archives: build_glob_set(&self.compare_archives)?,
+            #[cfg(feature = "csv")]
+            csv: self
+                .compare_csv
+                .iter()
+                .map(|(glob, epsilon)| Ok((Glob::new(glob)?.compile_matcher(), *epsilon)))
+                .collect::<Result<_, Error>>()?,
+            encoding: self.normalize_encoding,
+            trailing_whitespace: build_glob_set(&self.whitespace_globs(Whitespace::Trailing))?,
+            whitespace_runs: build_glob_set(&self.whitespace_globs(Whitespace::Runs))?,
+            sorted_lines: build_glob_set(&self.ignore_line_order)?,
+            commands: self
+                .compare_commands
+                .iter()
+                .map(|(glob, command)| Ok((Glob::new(glob)?.compile_matcher(), command.clone())))
+                .collect::<Result<_, Error>>()?,
+            size_tolerances: self
+                .size_tolerances
+                .iter()
+                .map(|(glob, bytes)| Ok((Glob::new(glob)?.compile_matcher(), *bytes)))
+                .collect::<Result<_, Error>>()?,
+            record_counts: self
+                .record_counts
+                .iter()
+                .map(|(glob, delimiter)| Ok((Glob::new(glob)?.compile_matcher(), *delimiter)))
+                .collect::<Result<_, Error>>()?,
+            comparators: self.comparators.clone(),
+            placeholders: self
+                .placeholders
+                .iter()
+                .map(|(glob, placeholder, pattern)| {
+                    regex::Regex::new(pattern).map_err(|source| Error::Regex {
+                        source,
+                        pattern: pattern.clone(),
+                    })?;
+                    Ok((
+                        Glob::new(glob)?.compile_matcher(),
+                        placeholder.clone(),
+                        pattern.clone(),
+                    ))
+                })
+                .collect::<Result<_, Error>>()?,
+            snapshot: self.snapshot,
+            walk_threads: self.walk_threads.unwrap_or(1),
+            mmap_threshold: self.mmap_threshold,
+            sequential_reads: self.sequential_reads,
+            fd_traversal: self.fd_traversal,
+            buffer_size: self.max_buffer_size.unwrap_or(DEFAULT_BUFFER_SIZE),
+            open_files: self.max_open_files.clone(),
+            max_entries: self.max_entries,
+            max_total_bytes: self.max_total_bytes,
+            deadline: self.deadline.map(|timeout| Instant::now() + timeout),
+            read_timeout: self.read_timeout,
+            retry: self.retry,
+            retries: Arc::default(),
+            cancel_token: self.cancel_token.clone(),
+            hooks: Hooks {
+                explanation: self.explain.then(Arc::default),
+                ..self.hooks.clone()
+            },
+        })
+    }
+}
+
+/// [`DirDiffOptions`] prepared for use while walking.
+///
+/// Clones share the limit on open files.
+#[derive(Clone, Debug)]
+pub(crate) struct Settings {
+    exclude: GlobSet,
+    ignore_hidden: bool,
+    allowed: GlobSet,
+    extra_left: GlobSet,
+    extra_right: GlobSet,
+    path_rules: Vec<PathRule>,
+    path_variables: BTreeMap<String, String>,
+    hash_algorithm: HashAlgorithm,
+    find_duplicates: bool,
+    check_portable_names: bool,
+    follow_symlinks: bool,
+    strict: bool,
+    same_file_system: bool,
+    empty_files_as_missing: bool,
+    compare_root_names: bool,
+    case_sensitivity: CaseSensitivity,
+    path_separator: PathSeparator,
+    detect_volatile: bool,
+    compare_permissions: bool,
+    compare_modified: bool,
+    ignore_contents: bool,
+    #[cfg(feature = "acl")]
+    compare_acls: bool,
+    similarity: bool,
+    detect_encoding_changes: bool,
+    summarize_blocks: Option<usize>,
+    #[cfg(feature = "binary")]
+    ignore_build_ids: bool,
+    #[cfg(feature = "zip")]
+    archives: GlobSet,
+    #[cfg(feature = "csv")]
+    csv: Vec<(GlobMatcher, f64)>,
+    encoding: Option<EncodingNormalization>,
+    trailing_whitespace: GlobSet,
+    whitespace_runs: GlobSet,
+    sorted_lines: GlobSet,
+    commands: Vec<(GlobMatcher, String)>,
+    size_tolerances: Vec<(GlobMatcher, u64)>,
+    record_counts: Vec<(GlobMatcher, u8)>,
+    comparators: Comparators,
+    placeholders: Vec<(GlobMatcher, String, String)>,
+    snapshot: Option<Snapshot>,
+    walk_threads: usize,
+    mmap_threshold: Option<u64>,
+    sequential_reads: bool,
+    fd_traversal: bool,
+    buffer_size: usize,
+    open_files: Option<Arc<OpenFiles>>,
+    max_entries: Option<u64>,
+    max_total_bytes: Option<u64>,
+    deadline: Option<Instant>,
+    read_timeout: Option<Duration>,
+    retry: Option<RetryPolicy>,
+    /// Reads retried so far, shared between clones.
+    retries: Arc<AtomicU64>,
+    cancel_token: Option<CancelToken>,
+    hooks: Hooks,
+}
+
+impl Settings {
+    pub(crate) fn is_excluded(&self, relative: &Path) -> bool {
+        if self.ignore_hidden
+            && relative
+                .components()
+                .any(|component| component.as_os_str().to_string_lossy().starts_with('.'))
+        {
+            return true;
+        }
+        self.exclude.is_match(relative)
+    }
+
+    pub(crate) fn ignore_hidden(&self) -> bool {
+        self.ignore_hidden
+    }
+
+    pub(crate) fn is_extra_ignored(&self, side: Side, relative: &Path) -> bool {
+        match side {
+            Side::Left => self.extra_left.is_match(relative),
+            Side::Right => self.extra_right.is_match(relative),
+        }
+    }
+
+    /// These settings for answering whether trees differ, without the details of how, which
+    /// only [`DirDiffOptions::diff`] and [`DirDiffOptions::first_difference`] report.
+    pub(crate) fn yes_or_no(mut self) -> Self {
+        self.similarity = false;
+        self.summarize_blocks = None;
+        self
+    }
+
+    /// These settings for one of several comparisons run at once, which explains only its own
+    /// entries rather than sharing the explanation with the others.
+    pub(crate) fn for_pair(mut self) -> Self {
+        if self.hooks.explanation.is_some() {
+            self.hooks.explanation = Some(Arc::default());
+        }
+        self
+    }
+
+    /// `left` with its paths rewritten by [`DirDiffOptions::map_path`].
+    pub(crate) fn map_left<L: TreeSource>(&self, left: L) -> Result<Mapped<L>, Error> {
+        Mapped::new(left, Side::Left, Rewrite::Rules(&self.path_rules))
+    }
+
+    /// `right` with the variables in its paths replaced, as set by
+    /// [`DirDiffOptions::path_variable`].
+    pub(crate) fn map_right<R: TreeSource>(&self, right: R) -> Result<Mapped<R>, Error> {
+        Mapped::new(right, Side::Right, Rewrite::Variables(&self.path_variables))
+    }
+
+    pub(crate) fn is_allowed(&self, relative: &Path) -> bool {
+        self.allowed.is_match(relative)
+    }
+
+    pub(crate) fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash_algorithm
+    }
+
+    pub(crate) fn find_duplicates(&self) -> bool {
+        self.find_duplicates
+    }
+
+    pub(crate) fn check_portable_names(&self) -> bool {
+        self.check_portable_names
+    }
+
+    pub(crate) fn follow_symlinks(&self) -> bool {
+        self.follow_symlinks
+    }
+
+    pub(crate) fn strict(&self) -> bool {
+        self.strict
+    }
+
+    pub(crate) fn same_file_system(&self) -> bool {
+        self.same_file_system
+    }
+
+    pub(crate) fn empty_files_as_missing(&self) -> bool {
+        self.empty_files_as_missing
+    }
+
+    pub(crate) fn compare_root_names(&self) -> bool {
+        self.compare_root_names
+    }
+
+    pub(crate) fn case_sensitivity(&self) -> CaseSensitivity {
+        self.case_sensitivity
+    }
+
+    pub(crate) fn path_separator(&self) -> PathSeparator {
+        self.path_separator
+    }
+
+    pub(crate) fn detect_volatile(&self) -> bool {
+        self.detect_volatile
+    }
+
+    pub(crate) fn compare_permissions(&self) -> bool {
+        self.compare_permissions
+    }
+
+    pub(crate) fn compare_modified(&self) -> bool {
+        self.compare_modified
+    }
+
+    pub(crate) fn ignore_contents(&self) -> bool {
+        self.ignore_contents
+    }
+
+    #[cfg(feature = "acl")]
+    pub(crate) fn compare_acls(&self) -> bool {
+        self.compare_acls
+    }
+
+    pub(crate) fn similarity(&self) -> bool {
+        self.similarity
+    }
+
+    pub(crate) fn detect_encoding_changes(&self) -> bool {
+        self.detect_encoding_changes
+    }
+
+    pub(crate) fn summarize_blocks(&self) -> Option<usize> {
+        self.summarize_blocks
+    }
+
+    #[cfg(feature = "binary")]
+    pub(crate) fn ignore_build_ids(&self) -> bool {
+        self.ignore_build_ids
+    }
+
+    #[cfg(feature = "zip")]
+    pub(crate) fn is_archive(&self, relative: &Path) -> bool {
+        self.archives.is_match(relative)
+    }
+
+    /// The epsilon from [`DirDiffOptions::compare_csv`] to compare the file at `relative` with,
+    /// if any.
+    #[cfg(feature = "csv")]
+    pub(crate) fn csv_epsilon(&self, relative: &Path) -> Option<f64> {
+        self.csv
+            .iter()
+            .rev()
+            .find(|(glob, _)| glob.is_match(relative))
+            .map(|(_, epsilon)| *epsilon)
+    }
+
+    /// The settings for comparing the members of two archives, which are trees of their own.
+    #[cfg(feature = "zip")]
+    pub(crate) fn for_archive_members(&self) -> Self {
+        Self {
+            compare_root_names: false,
+            snapshot: None,
+            walk_threads: 1,
+            hooks: Hooks::default(),
+            ..self.clone()
+        }
+    }
+
+    /// The command from [`DirDiffOptions::compare_with_command`] to compare the file at
+    /// `relative` with, if any.
+    pub(crate) fn compare_command(&self, relative: &Path) -> Option<&str> {
+        self.commands
+            .iter()
+            .rev()
+            .find(|(glob, _)| glob.is_match(relative))
+            .map(|(_, command)| command.as_str())
+    }
+
+    /// The tolerance from [`DirDiffOptions::size_tolerance`] to compare the file at `relative`
+    /// with, if any.
+    pub(crate) fn size_tolerance(&self, relative: &Path) -> Option<u64> {
+        self.size_tolerances
+            .iter()
+            .rev()
+            .find(|(glob, _)| glob.is_match(relative))
+            .map(|(_, bytes)| *bytes)
+    }
+
+    /// The delimiter from [`DirDiffOptions::compare_record_counts`] to count the records of the
+    /// file at `relative` by, if any.
+    pub(crate) fn record_delimiter(&self, relative: &Path) -> Option<u8> {
+        self.record_counts
+            .iter()
+            .rev()
+            .find(|(glob, _)| glob.is_match(relative))
+            .map(|(_, delimiter)| *delimiter)
+    }
+
+    /// The comparator from [`DirDiffOptions::comparator`] for the file at `relative`, if any.
+    pub(crate) fn comparator(&self, relative: &Path) -> Option<Arc<dyn Comparator>> {
+        self.comparators.get(relative)
+    }
+
+    /// The placeholders from [`DirDiffOptions::placeholder`] in the file at `relative`, with
+    /// their patterns.
+    pub(crate) fn placeholders(&self, relative: &Path) -> Vec<(&str, &str)> {
+        self.placeholders
+            .iter()
+            .filter(|(glob, _, _)| glob.is_match(relative))
+            .map(|(_, placeholder, pattern)| (placeholder.as_str(), pattern.as_str()))
+            .collect()
+    }
+
+    /// How to normalize the file at `relative` before comparing it.
+    pub(crate) fn text_rules(&self, relative: &Path) -> TextRules {
+        let whitespace = if self.whitespace_runs.is_match(relative) {
+            Some(Whitespace::Runs)
+        } else if self.trailing_whitespace.is_match(relative) {
+            Some(Whitespace::Trailing)
+        } else {
+            None
+        };
+        TextRules {
+            encoding: self.encoding,
+            whitespace,
+            sort_lines: self.sorted_lines.is_match(relative),
+        }
+    }
+
+    pub(crate) fn snapshot(&self) -> Option<Snapshot> {
+        self.snapshot
+    }
+
+    pub(crate) fn walk_threads(&self) -> usize {
+        self.walk_threads
+    }
+
+    pub(crate) fn mmap_threshold(&self) -> Option<u64> {
+        self.mmap_threshold
+    }
+
+    pub(crate) fn sequential_reads(&self) -> bool {
+        self.sequential_reads
+    }
+
+    pub(crate) fn fd_traversal(&self) -> bool {
+        self.fd_traversal
+    }
+
+    pub(crate) fn hooks(&self) -> &Hooks {
+        &self.hooks
+    }
+
+    pub(crate) fn is_cancelled(&self) -> bool {
+        matches!(&self.cancel_token, Some(token) if token.is_cancelled())
+    }
+
+    /// Run `read`, retrying it as the policy allows while it fails with a transient error.
+    pub(crate) fn retry<T>(&self, mut read: impl FnMut() -> Result<T, Error>) -> Result<T, Error> {
+        let Some(policy) = self.retry else {
+            return read();
+        };
+        for retry in 0..policy.retries() {
+            match read() {
+                Err(err) if retry::is_transient(&err) => {
+                    self.retries.fetch_add(1, Ordering::Relaxed);
+                    std::thread::sleep(policy.delay(retry));
+                }
+                result => return result,
+            }
+        }
+        read()
+    }
+
+    pub(crate) fn retries(&self) -> u64 {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn max_entries(&self) -> Option<u64> {
+        self.max_entries
+    }
+
+    pub(crate) fn max_total_bytes(&self) -> Option<u64> {
+        self.max_total_bytes
+    }
+
+    pub(crate) fn is_past_deadline(&self) -> bool {
+        matches!(self.deadline, Some(deadline) if deadline <= Instant::now())
+    }
+
+    /// How long to wait for a pair of files to be compared, if they are timed.
+    pub(crate) fn file_timeout(&self) -> Option<Duration> {
+        let until_deadline = self
+            .deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        match (self.read_timeout, until_deadline) {
+            (Some(timeout), Some(until_deadline)) => Some(timeout.min(until_deadline)),
+            (timeout, until_deadline) => timeout.or(until_deadline),
+        }
+    }
+
+    pub(crate) fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+
+    /// Wait until `count` more files may be opened, keeping them reserved until the returned
+    /// guard is dropped.
+    pub(crate) fn open_files(&self, count: usize) -> Option<OpenFilesGuard<'_>> {
+        self.open_files
+            .as_ref()
+            .map(|open_files| open_files.acquire(count))
+    }
+}
+
+/// A counting semaphore for file handles.
+#[derive(Debug)]
+pub(crate) struct OpenFiles {
+    available: Mutex<usize>,
+    released: Condvar,
+    capacity: usize,
+}
+
+impl OpenFiles {
+    fn new(capacity: usize) -> Self {
+        Self {
+            available: Mutex::new(capacity),
+            released: Condvar::new(),
+            capacity,
+        }
+    }
+
+    fn acquire(&self, count: usize) -> OpenFilesGuard<'_> {
+        // Reserving every handle at once, rather than one at a time, means two comparisons can't
+        // each hold half of what they need.
+        let count = count.min(self.capacity);
+        let mut available = self
+            .released
+            .wait_while(self.lock(), |available| *available < count)
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        *available -= count;
+        OpenFilesGuard {
+            open_files: self,
+            count,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, usize> {
+        self.available
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+}
+
+/// Returns reserved file handles to [`OpenFiles`] when dropped.
+pub(crate) struct OpenFilesGuard<'o> {
+    open_files: &'o OpenFiles,
+    count: usize,
+}
+
+impl Drop for OpenFilesGuard<'_> {
+    fn drop(&mut self) {
+        *self.open_files.lock() += self.count;
+        self.open_files.released.notify_all();
+    }
+}
+
+fn not_a_directory() -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidInput, "not a directory")
+}
+
+/// The globs of options that pair each glob with a value.
+fn firsts<T>(options: &[(String, T)]) -> Vec<&str> {
+    options.iter().map(|(glob, _)| glob.as_str()).collect()
+}
+
+/// Whether `glob` and any of `others` may match the same path, judged by whether either matches
+/// the other as written, as `**/*.log` matches `*.log` and `a/*` matches `a/b.txt`.
+fn overlaps_any(glob: &str, others: &[&str]) -> Result<bool, Error> {
+    let matcher = Glob::new(glob)?.compile_matcher();
+    for other in others {
+        if glob == *other
+            || matcher.is_match(other)
+            || Glob::new(other)?.compile_matcher().is_match(glob)
+        {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+fn build_glob_set(globs: &[String]) -> Result<GlobSet, Error> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        builder.add(Glob::new(glob)?);
+    }
+    Ok(builder.build()?)
+}
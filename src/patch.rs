@@ -0,0 +1,808 @@
+//! Patches that turn one compared tree into the other.
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use crate::error::{Phase, ResultExt as _};
+use crate::report::{DiffReport, DifferenceKind, Side};
+use crate::source::{full_path, FileType, FsSource, TreeSource};
+use crate::textdiff;
+use crate::Error;
+
+/// The first line of every patch, naming its format and version.
+const HEADER: &str = "dir-diff patch 1";
+
+/// Unchanged lines around each hunk of a text diff.
+const CONTEXT_LINES: usize = 3;
+
+/// Files larger than this are replaced whole rather than diffed.
+const MAX_TEXT_DIFF_LEN: u64 = 1024 * 1024;
+
+/// Base64 characters on each line of a file's contents.
+const BASE64_LINE_LEN: usize = 76;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+impl DiffReport {
+    /// Write a patch that turns the right tree into the left one, as a bundle of text.
+    ///
+    /// Changed text files are written as unified diffs, as `diff -u` would, from `a/` the right
+    /// file to `b/` the left one, and new text files as diffs from `/dev/null`.  Everything else
+    /// is a command on a line of its own, which `patch` skips as it would an email's prose:
+    ///
+    /// ```text
+    /// dir-diff patch 1
+    /// delete path/only/in/right
+    /// mkdir path/only/in/left
+    /// binary 1234 path/to/binary/file
+    /// <the file's contents in base64, 76 characters a line>
+    /// symlink path/to/symlink
+    /// <the symlink's target>
+    /// mode 755 path/to/executable
+    /// ```
+    ///
+    /// The files are read from [`DiffReport::left`] and [`DiffReport::right`], so the trees
+    /// have to be local, and unchanged since they were compared.  Directories only in the left
+    /// tree are added whole, whatever the comparison excluded from them.  New files are made
+    /// executable if they are in the left tree, and other permissions only change where the
+    /// report has a [`DifferenceKind::Permissions`].  Differences that can't be patched, like
+    /// in modification times, and volatile files are left out.
+    ///
+    /// Paths are separated by `/`, and have to be UTF-8 without tabs or line breaks.  Apply the
+    /// patch with [`crate::apply_patch`], or read it back as a [`Patch`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// extern crate dir_diff;
+    ///
+    /// let report = dir_diff::diff("target/snapshots", "tests/snapshots").unwrap();
+    /// if report.is_different() {
+    ///     let patch = std::fs::File::create("target/snapshots.patch").unwrap();
+    ///     report.write_patch(patch).unwrap();
+    /// }
+    /// ```
+    pub fn write_patch(&self, mut out: impl Write) -> Result<(), Error> {
+        let left = FsSource::new(self.left());
+        let right = FsSource::new(self.right());
+        let mut patch = Writer {
+            left: &left,
+            right: &right,
+            out: &mut out,
+        };
+        writeln!(patch.out, "{HEADER}")?;
+        for difference in self.differences() {
+            let path = difference.path().as_path();
+            match difference.kind() {
+                DifferenceKind::OnlyInLeft => patch.add(path)?,
+                DifferenceKind::OnlyInRight => patch.delete(path)?,
+                DifferenceKind::FileType | DifferenceKind::BrokenSymlink { .. } => {
+                    patch.delete(path)?;
+                    patch.add(path)?;
+                }
+                DifferenceKind::Content | DifferenceKind::Encoding | DifferenceKind::Timeout => {
+                    patch.change(path)?;
+                }
+                DifferenceKind::Permissions => {
+                    if let Some(permissions) = difference
+                        .metadata(Side::Left)
+                        .and_then(|metadata| metadata.permissions())
+                    {
+                        patch.mode(path, permissions)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A patch being written from the trees of a report.
+struct Writer<'p> {
+    left: &'p FsSource,
+    right: &'p FsSource,
+    out: &'p mut dyn Write,
+}
+
+impl Writer<'_> {
+    fn delete(&mut self, path: &Path) -> Result<(), Error> {
+        writeln!(self.out, "delete {}", name(path)?)?;
+        Ok(())
+    }
+
+    /// Add the entry at `path` in the left tree, and everything under it.
+    fn add(&mut self, path: &Path) -> Result<(), Error> {
+        let metadata = self
+            .left
+            .metadata(path)
+            .context(Phase::Walking(Side::Left), || full_path(self.left, path))?;
+        match metadata.file_type() {
+            FileType::Dir => {
+                writeln!(self.out, "mkdir {}", name(path)?)?;
+                let mut entries = self
+                    .left
+                    .read_dir(path)
+                    .context(Phase::Walking(Side::Left), || full_path(self.left, path))?;
+                entries.sort_by(|a, b| a.name().cmp(b.name()));
+                for entry in entries {
+                    self.add(&path.join(entry.name()))?;
+                }
+            }
+            FileType::File => {
+                let contents = read(self.left, path, Side::Left)?;
+                match text(&contents, metadata.len()) {
+                    Some(text) if !text.is_empty() => self.diff(path, None, text)?,
+                    _ => self.binary(path, &contents)?,
+                }
+                if let Some(permissions) = metadata.permissions() {
+                    if permissions & 0o111 != 0 {
+                        self.mode(path, permissions)?;
+                    }
+                }
+            }
+            FileType::Symlink => self.symlink(path)?,
+            FileType::Other => {}
+        }
+        Ok(())
+    }
+
+    /// Replace the right file or symlink at `path` with the left one.
+    fn change(&mut self, path: &Path) -> Result<(), Error> {
+        let left = self
+            .left
+            .metadata(path)
+            .context(Phase::Walking(Side::Left), || full_path(self.left, path))?;
+        let right = self
+            .right
+            .metadata(path)
+            .context(Phase::Walking(Side::Right), || full_path(self.right, path))?;
+        match (left.file_type(), right.file_type()) {
+            (FileType::File, FileType::File) => {
+                let new = read(self.left, path, Side::Left)?;
+                let old = read(self.right, path, Side::Right)?;
+                match (text(&old, right.len()), text(&new, left.len())) {
+                    (Some(old), Some(new)) => self.diff(path, Some(old), new),
+                    _ if old == new => Ok(()),
+                    _ => self.binary(path, &new),
+                }
+            }
+            (FileType::Symlink, FileType::Symlink) => self.symlink(path),
+            _ => Ok(()),
+        }
+    }
+
+    /// Write a unified diff from `old` to `new`, or from nothing when `old` is `None`.
+    fn diff(&mut self, path: &Path, old: Option<&str>, new: &str) -> Result<(), Error> {
+        let hunks = textdiff::unified(old.unwrap_or_default(), new, CONTEXT_LINES);
+        if hunks.is_empty() {
+            return Ok(());
+        }
+
+        let name = name(path)?;
+        match old {
+            Some(_) => writeln!(self.out, "--- a/{name}")?,
+            None => writeln!(self.out, "--- /dev/null")?,
+        }
+        writeln!(self.out, "+++ b/{name}")?;
+        self.out.write_all(hunks.as_bytes())?;
+        Ok(())
+    }
+
+    fn binary(&mut self, path: &Path, contents: &[u8]) -> Result<(), Error> {
+        writeln!(self.out, "binary {} {}", contents.len(), name(path)?)?;
+        let encoded = base64(contents);
+        for line in encoded.as_bytes().chunks(BASE64_LINE_LEN) {
+            self.out.write_all(line)?;
+            self.out.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    fn symlink(&mut self, path: &Path) -> Result<(), Error> {
+        let target = self
+            .left
+            .read_link(path)
+            .context(Phase::Walking(Side::Left), || full_path(self.left, path))?;
+        let target = target
+            .to_str()
+            .filter(|target| !target.contains(['\n', '\r']))
+            .ok_or_else(|| unpatchable(path))?;
+        writeln!(self.out, "symlink {}", name(path)?)?;
+        writeln!(self.out, "{target}")?;
+        Ok(())
+    }
+
+    fn mode(&mut self, path: &Path, permissions: u32) -> Result<(), Error> {
+        writeln!(self.out, "mode {permissions:o} {}", name(path)?)?;
+        Ok(())
+    }
+}
+
+fn read(source: &FsSource, path: &Path, side: Side) -> Result<Vec<u8>, Error> {
+    let mut contents = Vec::new();
+    source
+        .open(path)
+        .and_then(|mut file| Ok(file.read_to_end(&mut contents)?))
+        .context(Phase::Reading(side), || full_path(source, path))?;
+    Ok(contents)
+}
+
+/// The contents of a file of `len` bytes as text, if they are small enough to diff.
+fn text(contents: &[u8], len: u64) -> Option<&str> {
+    if MAX_TEXT_DIFF_LEN < len || contents.contains(&0) {
+        return None;
+    }
+    std::str::from_utf8(contents).ok()
+}
+
+/// `path` as written in a patch, with `/` separators.
+fn name(path: &Path) -> Result<String, Error> {
+    let mut name = String::new();
+    for component in path.iter() {
+        let component = component
+            .to_str()
+            .filter(|component| !component.contains(['\t', '\n', '\r']))
+            .ok_or_else(|| unpatchable(path))?;
+        if !name.is_empty() {
+            name.push('/');
+        }
+        name.push_str(component);
+    }
+    Ok(name)
+}
+
+fn unpatchable(path: &Path) -> Error {
+    Error::Io {
+        source: std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "the path can't be written to a patch",
+        ),
+        phase: None,
+        path: Some(path.to_owned()),
+    }
+}
+
+/// A patch written by [`DiffReport::write_patch`], read back to be applied to a directory.
+///
+/// Applying a patch is all or nothing as far as the patch goes: every change is checked before
+/// anything is written, so a patch that doesn't apply leaves the directory as it was.  Hunks of
+/// text diffs have to match exactly where they say they apply, and files that are deleted,
+/// created or edited have to exist, or not, as they did in the right tree.  Binary files and
+/// symlinks are replaced whatever they held.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let patch = std::fs::File::open("snapshots.patch").unwrap();
+/// let patch = dir_diff::Patch::read(patch).unwrap();
+/// for path in patch.paths() {
+///     println!("{}", path.display());
+/// }
+/// patch.dry_run("tests/snapshots").unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Patch {
+    changes: Vec<Change>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Change {
+    path: PathBuf,
+    op: Op,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Op {
+    Delete,
+    Mkdir,
+    Binary(Vec<u8>),
+    Diff { new_file: bool, hunks: Vec<Hunk> },
+    Symlink(PathBuf),
+    Mode(u32),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Hunk {
+    /// The first line of the hunk in the old file, counted from 1, or the line before it when
+    /// the hunk only adds lines.
+    old_start: usize,
+    /// Each line, with its `' '`, `'-'` or `'+'` marker and its line ending, if any.
+    lines: Vec<(char, String)>,
+}
+
+impl Patch {
+    /// Read a patch written by [`DiffReport::write_patch`].
+    pub fn read(mut patch: impl Read) -> Result<Self, Error> {
+        let mut text = String::new();
+        patch.read_to_string(&mut text)?;
+        let mut lines = text.split_inclusive('\n').enumerate().peekable();
+        match lines.next() {
+            Some((_, line)) if line.trim_end_matches('\n') == HEADER => {}
+            _ => return Err(malformed(1, "isn't the header of a dir-diff patch")),
+        }
+
+        let mut changes = Vec::new();
+        while let Some((index, line)) = lines.next() {
+            let number = index + 1;
+            let line = line.trim_end_matches('\n');
+            let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+            let (path, op) = match command {
+                "delete" => (rest, Op::Delete),
+                "mkdir" => (rest, Op::Mkdir),
+                "binary" => {
+                    let (len, path) = rest
+                        .split_once(' ')
+                        .and_then(|(len, path)| Some((len.parse::<usize>().ok()?, path)))
+                        .ok_or_else(|| malformed(number, "isn't a length and a path"))?;
+                    let mut encoded = String::new();
+                    while encoded.len() < (len + 2) / 3 * 4 {
+                        let (_, line) = lines
+                            .next()
+                            .ok_or_else(|| malformed(number, "has its contents cut short"))?;
+                        encoded.push_str(line.trim_end_matches('\n'));
+                    }
+                    let contents = unbase64(&encoded)
+                        .filter(|contents| contents.len() == len)
+                        .ok_or_else(|| malformed(number, "has contents that aren't valid"))?;
+                    (path, Op::Binary(contents))
+                }
+                "symlink" => {
+                    let (_, target) = lines
+                        .next()
+                        .ok_or_else(|| malformed(number, "has no target"))?;
+                    (rest, Op::Symlink(target.trim_end_matches('\n').into()))
+                }
+                "mode" => {
+                    let (mode, path) = rest
+                        .split_once(' ')
+                        .and_then(|(mode, path)| Some((u32::from_str_radix(mode, 8).ok()?, path)))
+                        .ok_or_else(|| malformed(number, "isn't a mode and a path"))?;
+                    (path, Op::Mode(mode))
+                }
+                "---" => {
+                    let new_file = rest == "/dev/null";
+                    let path = lines
+                        .next()
+                        .and_then(|(_, line)| line.trim_end_matches('\n').strip_prefix("+++ b/"))
+                        .ok_or_else(|| malformed(number + 1, "isn't the new file of a diff"))?;
+                    let mut hunks = Vec::new();
+                    while let Some((index, header)) =
+                        lines.next_if(|(_, line)| line.starts_with("@@ "))
+                    {
+                        let (old_start, mut old_len, mut new_len) = hunk_header(header)
+                            .ok_or_else(|| malformed(index + 1, "isn't a hunk header"))?;
+                        let mut hunk = Vec::new();
+                        while 0 < old_len || 0 < new_len {
+                            let (index, line) = lines
+                                .next()
+                                .ok_or_else(|| malformed(index + 1, "has a hunk cut short"))?;
+                            let mut chars = line.chars();
+                            let marker = chars.next().unwrap_or_default();
+                            let counts = match marker {
+                                ' ' => (&mut old_len, Some(&mut new_len)),
+                                '-' => (&mut old_len, None),
+                                '+' => (&mut new_len, None),
+                                _ => return Err(malformed(index + 1, "isn't a line of a hunk")),
+                            };
+                            for count in std::iter::once(counts.0).chain(counts.1) {
+                                *count = count.checked_sub(1).ok_or_else(|| {
+                                    malformed(index + 1, "is past the end of its hunk")
+                                })?;
+                            }
+                            let mut text = chars.as_str().to_owned();
+                            if lines.next_if(|(_, line)| line.starts_with("\\ ")).is_some() {
+                                // The line has no line ending.
+                                text.pop();
+                            }
+                            hunk.push((marker, text));
+                        }
+                        hunks.push(Hunk {
+                            old_start,
+                            lines: hunk,
+                        });
+                    }
+                    (path, Op::Diff { new_file, hunks })
+                }
+                _ => return Err(malformed(number, "isn't part of a dir-diff patch")),
+            };
+            // Nothing outside the directory can be patched.
+            if path
+                .split('/')
+                .any(|name| name.is_empty() || name == "." || name == "..")
+            {
+                return Err(malformed(number, "has a path outside the directory"));
+            }
+            changes.push(Change {
+                path: path.split('/').collect(),
+                op,
+            });
+        }
+        Ok(Self { changes })
+    }
+
+    /// The relative paths the patch changes, in the order it changes them.
+    ///
+    /// A path is listed once for each change to it, like a file that is replaced by a
+    /// directory being deleted, created and filled in.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> + '_ {
+        self.changes.iter().map(|change| change.path.as_path())
+    }
+
+    /// Check that the patch applies to `dir`, without changing anything.
+    ///
+    /// Fails with [`Error::PatchConflict`] where [`Patch::apply`] would.
+    pub fn dry_run<P: AsRef<Path>>(&self, dir: P) -> Result<(), Error> {
+        self.plan(dir.as_ref()).map(|_| ())
+    }
+
+    /// Apply the patch to `dir`, turning it into the left tree of the report it was written
+    /// from.
+    ///
+    /// Nothing is written unless the whole patch applies.  Modes are only set on Unix.
+    pub fn apply<P: AsRef<Path>>(&self, dir: P) -> Result<(), Error> {
+        let dir = dir.as_ref();
+        for (path, step) in self.plan(dir)? {
+            let full = dir.join(path);
+            step.run(&full).map_err(|err| Error::Io {
+                source: err,
+                phase: None,
+                path: Some(full),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Work out what applying the patch to `dir` writes, checking that every change applies.
+    fn plan(&self, dir: &Path) -> Result<Vec<(&Path, Step)>, Error> {
+        let mut tree = Planned {
+            dir,
+            entries: BTreeMap::new(),
+        };
+        let mut steps = Vec::with_capacity(self.changes.len());
+        for Change { path, op } in &self.changes {
+            let conflict = |reason: &str| Error::PatchConflict {
+                path: dir.join(path),
+                reason: reason.to_owned(),
+            };
+            let current = tree.get(path)?;
+            let step = match op {
+                Op::Delete => {
+                    if current == Entry::Missing {
+                        return Err(conflict("it doesn't exist"));
+                    }
+                    tree.entries.retain(|planned, _| !planned.starts_with(path));
+                    tree.entries.insert(path.clone(), Entry::Missing);
+                    Step::Delete
+                }
+                Op::Mkdir => {
+                    tree.check_new(path, &current, &conflict)?;
+                    tree.entries.insert(path.clone(), Entry::Dir);
+                    Step::Mkdir
+                }
+                Op::Binary(contents) => {
+                    if current == Entry::Missing {
+                        tree.check_new(path, &current, &conflict)?;
+                    } else if !matches!(current, Entry::File(_)) {
+                        return Err(conflict("it isn't a file"));
+                    }
+                    tree.entries
+                        .insert(path.clone(), Entry::File(Some(contents.clone())));
+                    Step::Write(contents.clone())
+                }
+                Op::Diff { new_file, hunks } => {
+                    let old = if *new_file {
+                        tree.check_new(path, &current, &conflict)?;
+                        String::new()
+                    } else {
+                        let Entry::File(contents) = current else {
+                            return Err(conflict("it isn't a file"));
+                        };
+                        let contents = match contents {
+                            Some(contents) => contents,
+                            None => std::fs::read(dir.join(path))
+                                .context(Phase::Reading(Side::Right), || dir.join(path))?,
+                        };
+                        String::from_utf8(contents).map_err(|_| conflict("it isn't text"))?
+                    };
+                    let new = patch_text(&old, hunks)
+                        .map_err(|hunk| conflict(&format!("hunk {} doesn't match", hunk + 1)))?;
+                    tree.entries
+                        .insert(path.clone(), Entry::File(Some(new.clone().into_bytes())));
+                    Step::Write(new.into_bytes())
+                }
+                Op::Symlink(target) => {
+                    match current {
+                        Entry::Missing => tree.check_new(path, &current, &conflict)?,
+                        Entry::Symlink => {}
+                        _ => return Err(conflict("it isn't a symlink")),
+                    }
+                    tree.entries.insert(path.clone(), Entry::Symlink);
+                    Step::Symlink(target.clone())
+                }
+                Op::Mode(mode) => {
+                    if current == Entry::Missing {
+                        return Err(conflict("it doesn't exist"));
+                    }
+                    Step::Mode(*mode)
+                }
+            };
+            steps.push((path.as_path(), step));
+        }
+        Ok(steps)
+    }
+}
+
+/// Apply the patch read from `patch`, as written by [`DiffReport::write_patch`], to `dir`.
+///
+/// See [`Patch`], and [`Patch::dry_run`] to check it applies first.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let patch = std::fs::File::open("snapshots.patch").unwrap();
+/// dir_diff::apply_patch(patch, "tests/snapshots").unwrap();
+/// assert!(!dir_diff::is_different("target/snapshots", "tests/snapshots").unwrap());
+/// ```
+pub fn apply_patch<P: AsRef<Path>>(patch: impl Read, dir: P) -> Result<(), Error> {
+    Patch::read(patch)?.apply(dir)
+}
+
+/// An entry of the directory being patched, as the changes so far leave it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Entry {
+    Missing,
+    Dir,
+    /// A file, with its contents if the patch wrote them.
+    File(Option<Vec<u8>>),
+    Symlink,
+    Other,
+}
+
+/// The directory being patched, with the changes planned so far.
+struct Planned<'d> {
+    dir: &'d Path,
+    /// What the changes so far leave at each path they changed.  Entries under a path that is
+    /// missing or a new directory are missing unless listed.
+    entries: BTreeMap<PathBuf, Entry>,
+}
+
+impl Planned<'_> {
+    fn get(&self, path: &Path) -> Result<Entry, Error> {
+        for ancestor in path.ancestors() {
+            match self.entries.get(ancestor) {
+                Some(entry) if ancestor == path => return Ok(entry.clone()),
+                Some(_) => return Ok(Entry::Missing),
+                None => {}
+            }
+        }
+        let full = self.dir.join(path);
+        match std::fs::symlink_metadata(&full) {
+            Ok(metadata) if metadata.is_dir() => Ok(Entry::Dir),
+            Ok(metadata) if metadata.is_file() => Ok(Entry::File(None)),
+            Ok(metadata) if metadata.file_type().is_symlink() => Ok(Entry::Symlink),
+            Ok(_) => Ok(Entry::Other),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Entry::Missing),
+            Err(err) => Err(err).context(Phase::Walking(Side::Right), || full),
+        }
+    }
+
+    /// Check that an entry can be created at `path`, currently `current`.
+    fn check_new(
+        &self,
+        path: &Path,
+        current: &Entry,
+        conflict: &dyn Fn(&str) -> Error,
+    ) -> Result<(), Error> {
+        if *current != Entry::Missing {
+            return Err(conflict("it already exists"));
+        }
+        let parent = path.parent().unwrap_or(Path::new(""));
+        if self.get(parent)? != Entry::Dir {
+            return Err(conflict("its parent isn't a directory"));
+        }
+        Ok(())
+    }
+}
+
+/// A change to write to the directory being patched.
+enum Step {
+    Delete,
+    Mkdir,
+    Write(Vec<u8>),
+    Symlink(PathBuf),
+    Mode(u32),
+}
+
+impl Step {
+    fn run(&self, path: &Path) -> std::io::Result<()> {
+        match self {
+            Step::Delete => match std::fs::symlink_metadata(path)?.is_dir() {
+                true => std::fs::remove_dir_all(path),
+                false => std::fs::remove_file(path),
+            },
+            Step::Mkdir => std::fs::create_dir_all(path),
+            Step::Write(contents) => std::fs::write(path, contents),
+            Step::Symlink(target) => {
+                if std::fs::symlink_metadata(path).is_ok() {
+                    std::fs::remove_file(path)?;
+                }
+                symlink(target, path)
+            }
+            Step::Mode(mode) => set_mode(path, *mode),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn symlink(target: &Path, path: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, path)
+}
+
+#[cfg(windows)]
+fn symlink(target: &Path, path: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(target, path)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn symlink(_target: &Path, _path: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "symlinks can't be created on this platform",
+    ))
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt as _;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Apply `hunks` to `old`, or return the index of the first hunk that doesn't match.
+fn patch_text(old: &str, hunks: &[Hunk]) -> Result<String, usize> {
+    let old: Vec<_> = old.split_inclusive('\n').collect();
+    let mut new = String::new();
+    let mut next = 0;
+    for (index, hunk) in hunks.iter().enumerate() {
+        let removes = hunk.lines.iter().any(|(marker, _)| *marker != '+');
+        let start = if removes {
+            hunk.old_start.checked_sub(1).ok_or(index)?
+        } else {
+            hunk.old_start
+        };
+        if start < next || old.len() < start {
+            return Err(index);
+        }
+        new.extend(old[next..start].iter().copied());
+        next = start;
+        for (marker, text) in &hunk.lines {
+            if *marker != '+' {
+                if old.get(next) != Some(&text.as_str()) {
+                    return Err(index);
+                }
+                next += 1;
+            }
+            if *marker != '-' {
+                new.push_str(text);
+            }
+        }
+    }
+    new.extend(old[next..].iter().copied());
+    Ok(new)
+}
+
+/// The old start and the lengths of each side of a hunk header like `@@ -5,5 +5,6 @@`.
+fn hunk_header(header: &str) -> Option<(usize, usize, usize)> {
+    let ranges = header.strip_prefix("@@ -")?.split(" @@").next()?;
+    let (old, new) = ranges.split_once(" +")?;
+    let range = |range: &str| -> Option<(usize, usize)> {
+        match range.split_once(',') {
+            Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+            None => Some((range.parse().ok()?, 1)),
+        }
+    };
+    let (old_start, old_len) = range(old)?;
+    let (_, new_len) = range(new)?;
+    Some((old_start, old_len, new_len))
+}
+
+fn malformed(line: usize, problem: &str) -> Error {
+    std::io::Error::new(
+        std::io::ErrorKind::InvalidData,
+        format!("line {line} of the patch {problem}"),
+    )
+    .into()
+}
+
+fn base64(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() + 2) / 3 * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0_u32, |group, (i, byte)| {
+            group | u32::from(*byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(char::from(
+                    BASE64_ALPHABET[(group >> (18 - 6 * i)) as usize & 63],
+                ));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+fn unbase64(encoded: &str) -> Option<Vec<u8>> {
+    if encoded.len() % 4 != 0 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(encoded.len() / 4 * 3);
+    for chunk in encoded.as_bytes().chunks(4) {
+        let padding = chunk.iter().rev().take_while(|c| **c == b'=').count();
+        if 2 < padding {
+            return None;
+        }
+        let mut group = 0_u32;
+        for c in &chunk[..4 - padding] {
+            let value = BASE64_ALPHABET.iter().position(|a| a == c)?;
+            group = group << 6 | value as u32;
+        }
+        group <<= 6 * padding;
+        bytes.extend_from_slice(&group.to_be_bytes()[1..4 - padding]);
+    }
+    Some(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+        assert_eq!(base64(&[0xff, 0xfe, 0x00]), "//4A");
+        for bytes in [&b""[..], b"f", b"fo", b"foo", b"foob", &[0xff, 0xfe, 0x00]] {
+            assert_eq!(unbase64(&base64(bytes)).as_deref(), Some(bytes));
+        }
+        assert_eq!(unbase64("Zg="), None);
+        assert_eq!(unbase64("Z==="), None);
+    }
+
+    #[test]
+    fn test_patch_text() {
+        let hunk = |old_start, lines: &[(char, &str)]| Hunk {
+            old_start,
+            lines: lines
+                .iter()
+                .map(|(marker, text)| (*marker, (*text).to_owned()))
+                .collect(),
+        };
+        let old = "a\nb\nc\n";
+        assert_eq!(
+            patch_text(old, &[hunk(2, &[(' ', "b\n"), ('-', "c\n"), ('+', "d")])]).as_deref(),
+            Ok("a\nb\nd")
+        );
+        assert_eq!(
+            patch_text(old, &[hunk(0, &[('+', "0\n")]), hunk(3, &[('+', "e\n")])]).as_deref(),
+            Ok("0\na\nb\nc\ne\n")
+        );
+        assert_eq!(patch_text(old, &[hunk(2, &[('-', "c\n")])]), Err(0));
+        assert_eq!(patch_text(old, &[hunk(4, &[('-', "d\n")])]), Err(0));
+    }
+}
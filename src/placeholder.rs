@@ -0,0 +1,77 @@
+//! Matching files against golden files holding placeholders for values that change.
+
+/// Whether `actual` is the same as `golden` with each of its placeholders replaced by text
+/// their patterns match.
+///
+/// Placeholders are looked for in `golden` from the start, the earliest one first, and, of
+/// those starting at the same place, the longest.  Golden files that aren't UTF-8 have no
+/// placeholders.
+pub(crate) fn matches(actual: &[u8], golden: &[u8], placeholders: &[(&str, &str)]) -> bool {
+    let Ok(mut golden) = std::str::from_utf8(golden) else {
+        return false;
+    };
+    let mut pattern = String::from(r"\A");
+    loop {
+        let next = placeholders
+            .iter()
+            .filter(|(placeholder, _)| !placeholder.is_empty())
+            .filter_map(|(placeholder, regex)| {
+                golden
+                    .find(placeholder)
+                    .map(|start| (start, std::cmp::Reverse(placeholder.len()), *regex))
+            })
+            .min_by_key(|(start, len, _)| (*start, *len));
+        let Some((start, std::cmp::Reverse(len), regex)) = next else {
+            break;
+        };
+        pattern.push_str(&regex::escape(&golden[..start]));
+        pattern.push_str("(?:");
+        pattern.push_str(regex);
+        pattern.push(')');
+        golden = &golden[start + len..];
+    }
+    pattern.push_str(&regex::escape(golden));
+    pattern.push_str(r"\z");
+    match regex::bytes::Regex::new(&pattern) {
+        Ok(regex) => regex.is_match(actual),
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let placeholders = [("{{VERSION}}", r"\d+\.\d+\.\d+"), ("[[ID]]", "[0-9a-f]+")];
+        let golden = b"version = {{VERSION}}\nid = [[ID]] ([[ID]])\n";
+        assert!(matches(
+            b"version = 1.2.3\nid = 3f (a0)\n",
+            golden,
+            &placeholders
+        ));
+        assert!(!matches(
+            b"version = 1.2\nid = 3f (a0)\n",
+            golden,
+            &placeholders
+        ));
+        assert!(!matches(
+            b"version = 1.2.3\nid = 3f (a0)\nmore\n",
+            golden,
+            &placeholders
+        ));
+
+        // The rest of the golden file is taken literally.
+        assert!(matches(b"a.b 1", b"a.b {{N}}", &[("{{N}}", r"\d")]));
+        assert!(!matches(b"axb 1", b"a.b {{N}}", &[("{{N}}", r"\d")]));
+
+        // The longest placeholder wins where several start.
+        assert!(matches(
+            b"x 7",
+            b"x {{N}}}",
+            &[("{{N}}", "y"), ("{{N}}}", r"\d")]
+        ));
+        assert!(!matches(b"x", b"\xff{{N}}", &[("{{N}}", "")]));
+    }
+}
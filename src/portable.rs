@@ -0,0 +1,147 @@
+//! Finding entries that couldn't be created on Windows.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use crate::case;
+use crate::error::{Phase, ResultExt as _};
+use crate::options::Settings;
+use crate::report::{NameIssue, NameProblem, RelPath, Side};
+use crate::source::{full_path, FileType, TreeSource};
+use crate::Error;
+
+/// Device names Windows reserves in every directory, with or without an extension.
+const RESERVED: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Characters Windows doesn't allow in names, besides control characters.
+const INVALID: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// The longest relative path, in UTF-16 code units, that fits in Windows' `MAX_PATH` of 260
+/// including the terminating NUL.
+const MAX_PATH_LEN: usize = 259;
+
+/// Check the name of every entry in both trees.
+pub(crate) fn check<L: TreeSource, R: TreeSource>(
+    left: &L,
+    right: &R,
+    settings: &Settings,
+) -> Result<Vec<NameIssue>, Error> {
+    let mut issues = Vec::new();
+    check_tree(left, settings, Side::Left, &mut issues)?;
+    check_tree(right, settings, Side::Right, &mut issues)?;
+    // Stable, so the left side's issues come first for the same path.
+    issues.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(issues)
+}
+
+fn check_tree<S: TreeSource>(
+    source: &S,
+    settings: &Settings,
+    side: Side,
+    issues: &mut Vec<NameIssue>,
+) -> Result<(), Error> {
+    let mut dirs = vec![PathBuf::new()];
+    while let Some(dir) = dirs.pop() {
+        let mut entries = source
+            .read_dir(&dir)
+            .context(Phase::Walking(side), || full_path(source, &dir))?;
+        // Sorted, so the entry colliding with another is always the same one.
+        entries.sort_by(|a, b| a.name().cmp(b.name()));
+        let mut folded = HashSet::new();
+        for entry in entries {
+            let path = dir.join(entry.name());
+            if settings.is_excluded(&path) {
+                continue;
+            }
+            let mut problems = problems(&path);
+            if !folded.insert(case::fold(entry.name()).into_owned()) {
+                problems.push(NameProblem::CaseCollision);
+            }
+            for problem in problems {
+                issues.push(NameIssue {
+                    path: RelPath::new(path.clone()),
+                    side,
+                    problem,
+                });
+            }
+            if entry.file_type() == FileType::Dir {
+                dirs.push(path);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// What keeps the last component of `path` from being created on Windows.
+fn problems(path: &Path) -> Vec<NameProblem> {
+    let mut problems = Vec::new();
+    let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+        problems.push(NameProblem::NotUnicode);
+        return problems;
+    };
+    // `nul.txt` is as reserved as `nul`, and so is `nul .txt`.
+    let stem = name.split('.').next().unwrap_or(name).trim_end_matches(' ');
+    if RESERVED
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        problems.push(NameProblem::Reserved);
+    }
+    if name.ends_with('.') || name.ends_with(' ') {
+        problems.push(NameProblem::TrailingDotOrSpace);
+    }
+    if name
+        .chars()
+        .any(|c| c.is_ascii_control() || INVALID.contains(&c))
+    {
+        problems.push(NameProblem::InvalidCharacter);
+    }
+    // Everything under a path that is too long is too, so only report where it starts.
+    if is_too_long(path) && !path.parent().map(is_too_long).unwrap_or(false) {
+        problems.push(NameProblem::TooLong);
+    }
+    problems
+}
+
+fn is_too_long(path: &Path) -> bool {
+    path.as_os_str().to_string_lossy().encode_utf16().count() > MAX_PATH_LEN
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_problems() {
+        assert_eq!(problems(Path::new("dir/readme.md")), []);
+        assert_eq!(problems(Path::new("con")), [NameProblem::Reserved]);
+        assert_eq!(
+            problems(Path::new("dir/Nul.tar.gz")),
+            [NameProblem::Reserved]
+        );
+        assert_eq!(problems(Path::new("console")), []);
+        assert_eq!(
+            problems(Path::new("aux.")),
+            [NameProblem::Reserved, NameProblem::TrailingDotOrSpace]
+        );
+        assert_eq!(
+            problems(Path::new("name ")),
+            [NameProblem::TrailingDotOrSpace]
+        );
+        assert_eq!(problems(Path::new("a:b")), [NameProblem::InvalidCharacter]);
+        assert_eq!(
+            problems(Path::new("tab\t")),
+            [NameProblem::InvalidCharacter]
+        );
+        let long = "d/".repeat(130);
+        assert_eq!(problems(Path::new(&long[..259])), []);
+        assert_eq!(
+            problems(Path::new(&format!("{long}x"))),
+            [NameProblem::TooLong]
+        );
+        assert_eq!(problems(Path::new(&format!("{long}x/y"))), []);
+    }
+}
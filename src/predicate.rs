@@ -0,0 +1,83 @@
+//! Integration with the [`predicates`](https://docs.rs/predicates) ecosystem.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use predicates_core::reflection::{Case, PredicateReflection, Product};
+use predicates_core::Predicate;
+
+use crate::options::DirDiffOptions;
+
+/// A [`Predicate`] that a directory has the same contents as an expected directory.
+///
+/// When used with `assert_fs` or `assert_cmd`, a failed assertion shows the differences as a
+/// tree, with the actual directory on the left and the expected directory on the right.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+/// use predicates_core::Predicate;
+///
+/// let predicate = dir_diff::DirMatchesPredicate::new("tests/golden");
+/// assert!(predicate.eval(std::path::Path::new("target/out")));
+/// ```
+#[derive(Clone, Debug)]
+pub struct DirMatchesPredicate {
+    expected: PathBuf,
+    options: DirDiffOptions,
+}
+
+impl DirMatchesPredicate {
+    /// Match directories with the same contents as `expected`.
+    pub fn new(expected: impl Into<PathBuf>) -> Self {
+        Self {
+            expected: expected.into(),
+            options: DirDiffOptions::new(),
+        }
+    }
+
+    /// Compare directories using `options`.
+    pub fn options(mut self, options: DirDiffOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+impl Predicate<Path> for DirMatchesPredicate {
+    fn eval(&self, variable: &Path) -> bool {
+        matches!(
+            self.options.is_different(variable, &self.expected),
+            Ok(false)
+        )
+    }
+
+    fn find_case<'a>(&'a self, expected: bool, variable: &Path) -> Option<Case<'a>> {
+        let (actual, product) = match self.options.diff(variable, &self.expected) {
+            Ok(report) => (
+                !report.is_different(),
+                report
+                    .is_different()
+                    .then(|| Product::new("differences", report.to_string())),
+            ),
+            Err(err) => (false, Some(Product::new("error", err.to_string()))),
+        };
+        if actual != expected {
+            return None;
+        }
+
+        let case = Case::new(Some(self), actual);
+        Some(match product {
+            Some(product) => case.add_product(product),
+            None => case,
+        })
+    }
+}
+
+impl PredicateReflection for DirMatchesPredicate {}
+
+impl fmt::Display for DirMatchesPredicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "var matches dir {}", self.expected.display())
+    }
+}
@@ -0,0 +1,120 @@
+//! Listing local directories ahead of the walk, on background threads.
+//!
+//! The walk itself stays sequential so differences come out in order, but on slow file systems
+//! most of its time goes to waiting on directory listings and file metadata.  Listing the
+//! directories it will reach next in parallel hides most of that latency.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Condvar, Mutex};
+
+use crate::source::{FileType, Metadata, SourceEntry};
+
+/// The entries of a directory, with the metadata of the files among them.
+pub(crate) type Listing = std::io::Result<Vec<(SourceEntry, Option<Metadata>)>>;
+
+/// A pool of threads listing local directories on request.
+///
+/// The threads exit once the prefetcher is dropped.
+pub(crate) struct Prefetcher {
+    jobs: mpsc::Sender<PathBuf>,
+    listings: Arc<Listings>,
+    /// How many listings may be requested but not yet taken, to bound memory.
+    limit: usize,
+}
+
+/// Requested listings by directory, `None` until they are done.
+#[derive(Default)]
+struct Listings {
+    done: Mutex<HashMap<PathBuf, Option<Listing>>>,
+    ready: Condvar,
+}
+
+impl Prefetcher {
+    pub(crate) fn new(threads: usize) -> Self {
+        let (jobs, receiver) = mpsc::channel::<PathBuf>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let listings = Arc::new(Listings::default());
+        for _ in 0..threads {
+            let receiver = receiver.clone();
+            let listings = listings.clone();
+            std::thread::spawn(move || loop {
+                let dir = match receiver.lock().expect("not poisoned").recv() {
+                    Ok(dir) => dir,
+                    Err(_) => break,
+                };
+                if !listings.is_wanted(&dir) {
+                    continue;
+                }
+                let listing = list(&dir);
+                let mut done = listings.done.lock().expect("not poisoned");
+                // Discarded while it was being listed.
+                if let Some(slot) = done.get_mut(&dir) {
+                    *slot = Some(listing);
+                    listings.ready.notify_all();
+                }
+            });
+        }
+        Self {
+            jobs,
+            listings,
+            limit: threads * 64,
+        }
+    }
+
+    /// Start listing `dir`, unless too many listings are waiting to be taken.
+    pub(crate) fn request(&self, dir: PathBuf) {
+        let mut done = self.listings.done.lock().expect("not poisoned");
+        if done.len() >= self.limit || done.contains_key(&dir) {
+            return;
+        }
+        done.insert(dir.clone(), None);
+        let _ = self.jobs.send(dir);
+    }
+
+    /// The listing of `dir`, waiting for it if it is still in progress, or `None` if it wasn't
+    /// requested.
+    pub(crate) fn take(&self, dir: &Path) -> Option<Listing> {
+        let mut done = self.listings.done.lock().expect("not poisoned");
+        loop {
+            match done.get(dir) {
+                None => return None,
+                Some(Some(_)) => return done.remove(dir).flatten(),
+                Some(None) => done = self.listings.ready.wait(done).expect("not poisoned"),
+            }
+        }
+    }
+
+    /// Forget about `dir`, which won't be walked after all.
+    pub(crate) fn discard(&self, dir: &Path) {
+        self.listings.done.lock().expect("not poisoned").remove(dir);
+    }
+}
+
+impl Drop for Prefetcher {
+    fn drop(&mut self) {
+        // Queued listings are still handed to the threads, which skip them once unwanted.
+        self.listings.done.lock().expect("not poisoned").clear();
+    }
+}
+
+impl Listings {
+    fn is_wanted(&self, dir: &Path) -> bool {
+        self.done.lock().expect("not poisoned").contains_key(dir)
+    }
+}
+
+fn list(dir: &Path) -> Listing {
+    let mut entries = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = FileType::from(entry.file_type()?);
+        // Comparing a file starts with its length, so look that up while we're here.
+        let metadata = match file_type {
+            FileType::File => Some(Metadata::from(entry.metadata()?)),
+            _ => None,
+        };
+        entries.push((SourceEntry::new(entry.file_name(), file_type), metadata));
+    }
+    Ok(entries)
+}
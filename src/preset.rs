@@ -0,0 +1,117 @@
+//! Built-in lists of entries to exclude, and configurations for common comparisons.
+
+use crate::options::DirDiffOptions;
+use crate::text::Whitespace;
+
+/// A list of entries commonly left out of comparisons, for
+/// [`DirDiffOptions::exclude_preset`], or a configuration for a common kind of comparison, for
+/// [`DirDiffOptions::preset`].
+///
+/// Each list matches by name at any depth.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Preset {
+    /// Files the macOS Finder, Windows Explorer and KDE's Dolphin leave behind, like `.DS_Store`
+    /// and `Thumbs.db`.
+    OsJunk,
+    /// Version control metadata directories, like `.git`.
+    VcsDirs,
+    /// Editor backup, swap and lock files, like `*~` and `*.swp`.
+    EditorBackups,
+    /// Compare everything this build can: contents, permissions, modification times, ACLs with
+    /// the `acl` feature, and the names of the roots themselves.  Nothing is excluded.
+    Strict,
+    /// Compare generated output against checked-in expectations: the entries of
+    /// [`Preset::OsJunk`], [`Preset::VcsDirs`] and [`Preset::EditorBackups`] are excluded, and
+    /// whitespace at the end of lines, including the `\r` of Windows line endings, is ignored in
+    /// every file.
+    GoldenTest,
+    /// Check a backup against what it was made from: contents, permissions, modification times
+    /// and ACLs with the `acl` feature are compared, and files that change while being compared
+    /// are reported as volatile.
+    BackupVerify,
+    /// Only compare the layout of the trees: which entries there are, and their types.  Files'
+    /// contents aren't read.
+    Layout,
+}
+
+/// Every list, in order, so presets combining lists can share them.
+const EXCLUDED: [&str; 21] = [
+    // OsJunk
+    "**/.DS_Store",
+    "**/._*",
+    "**/.Spotlight-V100",
+    "**/.Trashes",
+    "**/.fseventsd",
+    "**/Thumbs.db",
+    "**/ehthumbs.db",
+    "**/desktop.ini",
+    "**/$RECYCLE.BIN",
+    "**/.directory",
+    // VcsDirs
+    "**/.git",
+    "**/.hg",
+    "**/.svn",
+    "**/.bzr",
+    "**/CVS",
+    "**/_darcs",
+    // EditorBackups
+    "*~",
+    "*.swp",
+    "*.swo",
+    "**/.#*",
+    "**/#*#",
+];
+
+impl Preset {
+    /// The globs the preset excludes.
+    pub fn globs(self) -> &'static [&'static str] {
+        match self {
+            Preset::OsJunk => &EXCLUDED[..10],
+            Preset::VcsDirs => &EXCLUDED[10..16],
+            Preset::EditorBackups => &EXCLUDED[16..],
+            Preset::GoldenTest => &EXCLUDED,
+            Preset::Strict | Preset::BackupVerify | Preset::Layout => &[],
+        }
+    }
+}
+
+impl DirDiffOptions {
+    /// Configure these options for the kind of comparison `preset` describes, on top of what is
+    /// already set.
+    ///
+    /// Options the preset doesn't mention are left as they are, and can still be changed after.
+    /// A list of entries, like [`Preset::OsJunk`], is excluded as by
+    /// [`DirDiffOptions::exclude_preset`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// extern crate dir_diff;
+    ///
+    /// let report = dir_diff::DirDiffOptions::new()
+    ///     .preset(dir_diff::Preset::GoldenTest)
+    ///     .exclude("*.log")
+    ///     .diff("target/out", "tests/golden")
+    ///     .unwrap();
+    /// assert!(!report.is_different());
+    /// ```
+    pub fn preset(self, preset: Preset) -> Self {
+        let options = self.exclude_preset(preset);
+        match preset {
+            Preset::OsJunk | Preset::VcsDirs | Preset::EditorBackups => options,
+            Preset::Strict => metadata(options).compare_root_names(true),
+            Preset::GoldenTest => options.ignore_whitespace("**", Whitespace::Trailing),
+            Preset::BackupVerify => metadata(options).detect_volatile(true),
+            Preset::Layout => options.ignore_contents(true),
+        }
+    }
+}
+
+/// Compare all the metadata this build can.
+fn metadata(options: DirDiffOptions) -> DirDiffOptions {
+    let options = options.compare_permissions(true).compare_modified(true);
+    #[cfg(feature = "acl")]
+    let options = options.compare_acls(true);
+    options
+}
@@ -0,0 +1,131 @@
+//! Recognizing files that share their storage, so their contents needn't be read.
+//!
+//! Copy-on-write file systems let a copy share the original's extents until either is written
+//! to.  Two files whose every extent is the same shared extent on the same device must hold the
+//! same bytes.
+
+use std::path::Path;
+
+/// Do `left` and `right` share all of their storage?
+///
+/// `false` means the contents have to be compared, either because the files differ in storage
+/// or because it couldn't be told.
+#[cfg(target_os = "linux")]
+pub(crate) fn same_extents(left: &Path, right: &Path) -> bool {
+    fiemap::same_extents(left, right).unwrap_or(false)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn same_extents(left: &Path, right: &Path) -> bool {
+    let _ = (left, right);
+    false
+}
+
+#[cfg(target_os = "linux")]
+mod fiemap {
+    use std::fs::File;
+    use std::os::unix::fs::MetadataExt as _;
+    use std::os::unix::io::AsRawFd as _;
+    use std::path::Path;
+
+    /// `_IOWR('f', 11, struct fiemap)`
+    const FS_IOC_FIEMAP: u32 = 0xC020_660B;
+    const FIEMAP_EXTENT_LAST: u32 = 0x0001;
+    /// The extent's location or contents can't be relied on: unknown, delayed allocation,
+    /// encrypted or stored inline in metadata.
+    const FIEMAP_EXTENT_UNRELIABLE: u32 = 0x0002 | 0x0004 | 0x0080 | 0x0200;
+    const FIEMAP_EXTENT_SHARED: u32 = 0x2000;
+
+    /// How many extents to ask for at a time.
+    const BATCH: usize = 64;
+    /// Give up on heavily fragmented files, which are cheaper to read than to map.
+    const MAX_EXTENTS: usize = 4096;
+
+    #[repr(C)]
+    #[derive(Copy, Clone, Default)]
+    struct Extent {
+        logical: u64,
+        physical: u64,
+        length: u64,
+        reserved64: [u64; 2],
+        flags: u32,
+        reserved: [u32; 3],
+    }
+
+    #[repr(C)]
+    struct Request {
+        start: u64,
+        length: u64,
+        flags: u32,
+        mapped_extents: u32,
+        extent_count: u32,
+        reserved: u32,
+        extents: [Extent; BATCH],
+    }
+
+    pub(super) fn same_extents(left: &Path, right: &Path) -> std::io::Result<bool> {
+        let left = File::open(left)?;
+        let right = File::open(right)?;
+        // Extent locations are only comparable on the same device.
+        if left.metadata()?.dev() != right.metadata()?.dev() {
+            return Ok(false);
+        }
+        let (Some(left), Some(right)) = (extents(&left)?, extents(&right)?) else {
+            return Ok(false);
+        };
+        let same = !left.is_empty()
+            && left.len() == right.len()
+            && left.iter().zip(&right).all(|(left, right)| {
+                left.flags & FIEMAP_EXTENT_SHARED != 0
+                    && (left.logical, left.physical, left.length)
+                        == (right.logical, right.physical, right.length)
+            });
+        Ok(same)
+    }
+
+    /// Every extent of `file`, or `None` if any of them can't be relied on.
+    fn extents(file: &File) -> std::io::Result<Option<Vec<Extent>>> {
+        let mut extents = Vec::new();
+        let mut start = 0;
+        loop {
+            let mut request = Request {
+                start,
+                length: u64::MAX - start,
+                flags: 0,
+                mapped_extents: 0,
+                extent_count: BATCH as u32,
+                reserved: 0,
+                extents: [Extent::default(); BATCH],
+            };
+            // SAFETY: `request` is a `struct fiemap` followed by room for `extent_count`
+            // extents, which is all the kernel writes to.
+            let result = unsafe {
+                libc::ioctl(
+                    file.as_raw_fd(),
+                    FS_IOC_FIEMAP as libc::Ioctl,
+                    &mut request as *mut Request,
+                )
+            };
+            if result != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+
+            let mapped = &request.extents[..request.mapped_extents as usize];
+            let Some(last) = mapped.last() else {
+                return Ok(Some(extents));
+            };
+            if mapped
+                .iter()
+                .any(|extent| extent.flags & FIEMAP_EXTENT_UNRELIABLE != 0)
+                || extents.len() + mapped.len() > MAX_EXTENTS
+            {
+                return Ok(None);
+            }
+            extents.extend_from_slice(mapped);
+            if last.flags & FIEMAP_EXTENT_LAST != 0 {
+                return Ok(Some(extents));
+            }
+            start = last.logical + last.length;
+        }
+    }
+}
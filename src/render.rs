@@ -0,0 +1,181 @@
+//! Rendering a [`DiffReport`] as a tree for the terminal.
+
+use std::fmt::Write as _;
+
+use anstyle::{AnsiColor, Style};
+
+use crate::report::tree::Node;
+use crate::report::{DiffReport, DifferenceKind, Rollup};
+
+/// Whether to style rendered output with ANSI colors.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ColorChoice {
+    /// Use colors if stdout supports them, respecting `NO_COLOR` and `CLICOLOR`.
+    #[default]
+    Auto,
+    /// Always use colors.
+    Always,
+    /// Never use colors.
+    Never,
+}
+
+impl ColorChoice {
+    fn use_color(self) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                anstream::AutoStream::choice(&std::io::stdout()) != anstream::ColorChoice::Never
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+const REMOVED: Style = AnsiColor::Red.on_default();
+const ADDED: Style = AnsiColor::Green.on_default();
+const CHANGED: Style = AnsiColor::Yellow.on_default();
+
+impl DiffReport {
+    /// Render the differences as a tree, as in:
+    ///
+    /// ```text
+    /// out (-) vs golden (+)
+    /// ├── - removed.txt
+    /// └── src
+    ///     ├── + added.txt
+    ///     ├── M changed.txt
+    ///     └── T was-a-directory
+    /// ```
+    ///
+    /// `-` entries only exist on the left and are colored red, `+` entries only exist on the right
+    /// and are colored green, and entries that exist on both sides but differ, like in content
+    /// (`M`) or file type (`T`), are colored yellow.  [`DifferenceKind::marker`] lists every
+    /// marker.
+    pub fn render(&self, color: ColorChoice) -> String {
+        let tree = Node::from_report(self);
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{} (-) vs {} (+)",
+            self.left().display(),
+            self.right().display()
+        );
+        match tree.kind {
+            // The roots themselves differ.
+            Some(kind) => write_entry(&mut out, "", kind, ".", color.use_color()),
+            None if tree.children.is_empty() => out.push_str("(no differences)\n"),
+            None => {}
+        }
+        render_children(&mut out, &tree, "", color.use_color());
+        out
+    }
+
+    /// Render the differences as a tree, as [`DiffReport::render`] does, but with each directory
+    /// followed by the differences under it and directories more than `max_depth` levels deep
+    /// collapsed, as in:
+    ///
+    /// ```text
+    /// out (-) vs golden (+)
+    /// ├── - removed.txt
+    /// └── src/ (3 changed, 1 added)
+    ///     ├── + added.txt
+    ///     └── nested/ (3 changed)
+    /// ```
+    ///
+    /// A `max_depth` of 1 only shows the entries directly under the roots.
+    pub fn render_rollup(&self, max_depth: usize, color: ColorChoice) -> String {
+        let rollup = self.rollup();
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{} (-) vs {} (+)",
+            self.left().display(),
+            self.right().display()
+        );
+        match rollup.kind() {
+            Some(kind) => write_entry(&mut out, "", kind, ".", color.use_color()),
+            None if rollup.children().is_empty() => out.push_str("(no differences)\n"),
+            None => {}
+        }
+        render_rollup(&mut out, &rollup, "", max_depth, color.use_color());
+        out
+    }
+}
+
+impl std::fmt::Display for DiffReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.render(ColorChoice::Never))
+    }
+}
+
+fn render_children(out: &mut String, node: &Node, prefix: &str, use_color: bool) {
+    let mut children = node.children.iter().peekable();
+    while let Some((name, child)) = children.next() {
+        let last = children.peek().is_none();
+        let (branch, indent) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        let name = name.to_string_lossy();
+        match child.kind {
+            Some(kind) => write_entry(out, &format!("{prefix}{branch}"), kind, &name, use_color),
+            None => {
+                let _ = writeln!(out, "{prefix}{branch}{name}");
+            }
+        }
+        render_children(out, child, &format!("{prefix}{indent}"), use_color);
+    }
+}
+
+fn render_rollup(out: &mut String, rollup: &Rollup, prefix: &str, depth: usize, use_color: bool) {
+    if depth == 0 {
+        return;
+    }
+    let mut children = rollup.children().iter().peekable();
+    while let Some(child) = children.next() {
+        let last = children.peek().is_none();
+        let (branch, indent) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        let name = child
+            .path()
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        match child.kind() {
+            Some(kind) => write_entry(out, &format!("{prefix}{branch}"), kind, &name, use_color),
+            None => {
+                let _ = writeln!(out, "{prefix}{branch}{name}/ ({})", child.counts());
+            }
+        }
+        render_rollup(
+            out,
+            child,
+            &format!("{prefix}{indent}"),
+            depth - 1,
+            use_color,
+        );
+    }
+}
+
+fn write_entry(out: &mut String, prefix: &str, kind: DifferenceKind, name: &str, use_color: bool) {
+    let marker = kind.marker();
+    let style = match kind {
+        DifferenceKind::OnlyInLeft => REMOVED,
+        DifferenceKind::OnlyInRight => ADDED,
+        _ => CHANGED,
+    };
+    let style = if use_color { style } else { Style::new() };
+    let _ = writeln!(
+        out,
+        "{prefix}{}{marker} {name}{}",
+        style.render(),
+        style.render_reset()
+    );
+}
@@ -0,0 +1,59 @@
+//! Comparing a report against an earlier one.
+
+use std::collections::HashSet;
+
+use crate::report::{DiffReport, Difference};
+
+/// How the differences in a report changed since a baseline report.
+///
+/// Created by [`DiffReport::compare_to_baseline`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReportDelta {
+    introduced: Vec<Difference>,
+    resolved: Vec<Difference>,
+}
+
+impl ReportDelta {
+    pub(crate) fn new(report: &DiffReport, baseline: &DiffReport) -> Self {
+        Self {
+            introduced: missing_from(report, baseline),
+            resolved: missing_from(baseline, report),
+        }
+    }
+
+    /// Differences in the report that weren't in the baseline, ordered by relative path.
+    ///
+    /// An entry that differs in a new way, like a changed file that has since been removed, is
+    /// both introduced and resolved.
+    pub fn introduced(&self) -> &[Difference] {
+        &self.introduced
+    }
+
+    /// Differences in the baseline that are no longer in the report, ordered by relative path.
+    pub fn resolved(&self) -> &[Difference] {
+        &self.resolved
+    }
+
+    /// Were any differences introduced since the baseline?
+    pub fn has_regressions(&self) -> bool {
+        !self.introduced.is_empty()
+    }
+}
+
+/// The differences in `report` that aren't in `other`, keeping their order.
+///
+/// Differences are matched by path and kind, ignoring metadata, which changes on every run.
+fn missing_from(report: &DiffReport, other: &DiffReport) -> Vec<Difference> {
+    let other: HashSet<_> = other
+        .differences()
+        .iter()
+        .map(|difference| (difference.path(), difference.kind()))
+        .collect();
+    report
+        .differences()
+        .iter()
+        .filter(|difference| !other.contains(&(difference.path(), difference.kind())))
+        .cloned()
+        .collect()
+}
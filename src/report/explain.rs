@@ -0,0 +1,222 @@
+//! How each entry was compared, recorded with
+//! [`DirDiffOptions::explain`][crate::DirDiffOptions::explain].
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::hooks::SkipReason;
+use crate::report::{PathSeparator, RelPath, Side};
+
+/// Every check run on every entry of a comparison, in [`DiffReport::explanation`].
+///
+/// Shows why an entry was considered the same when it was expected to differ, such as when
+/// its size matched and its hash was found in the cache.  Displays as text, one entry per line
+/// followed by its checks in the order they ran.
+///
+/// [`DiffReport::explanation`]: crate::DiffReport::explanation
+///
+/// # Examples
+///
+/// ```no_run
+/// let report = dir_diff::DirDiffOptions::new()
+///     .explain(true)
+///     .diff("target/out", "tests/golden")
+///     .unwrap();
+/// // Prints lines like `  size: same` and `  full read: different` under each path.
+/// print!("{}", report.explanation().unwrap());
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Explanation {
+    entries: Vec<ExplainedEntry>,
+}
+
+impl Explanation {
+    pub(crate) fn new(steps: BTreeMap<PathBuf, Vec<Step>>) -> Self {
+        Self {
+            entries: steps
+                .into_iter()
+                .map(|(path, steps)| ExplainedEntry {
+                    path: RelPath::new(path),
+                    steps,
+                })
+                .collect(),
+        }
+    }
+
+    pub(crate) fn with_separator(mut self, separator: PathSeparator) -> Self {
+        for entry in &mut self.entries {
+            entry.path = std::mem::take(&mut entry.path).with_separator(separator);
+        }
+        self
+    }
+
+    /// Every entry checked, ordered by relative path like the differences.
+    pub fn entries(&self) -> &[ExplainedEntry] {
+        &self.entries
+    }
+
+    /// The checks run on the entry at `path`, if it was reached.
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<&ExplainedEntry> {
+        let path = path.as_ref();
+        self.entries
+            .iter()
+            .find(|entry| entry.path.as_path() == path)
+    }
+}
+
+impl std::fmt::Display for Explanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{}", entry.path.display())?;
+            for step in &entry.steps {
+                let outcome = if step.same { "same" } else { "different" };
+                writeln!(f, "  {}: {outcome}", step.check)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The checks run on one entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExplainedEntry {
+    path: RelPath,
+    steps: Vec<Step>,
+}
+
+impl ExplainedEntry {
+    /// The relative path of the entry.
+    pub fn path(&self) -> &RelPath {
+        &self.path
+    }
+
+    /// The checks run, in order.
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+
+    /// Whether the last check found the entry the same.
+    ///
+    /// Later checks only run to give entries found different another chance, so the last one
+    /// decides.
+    pub fn is_same(&self) -> bool {
+        matches!(self.steps.last(), Some(step) if step.same)
+    }
+}
+
+/// A check run on an entry, and whether it found the entry the same on both sides.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Step {
+    check: Check,
+    same: bool,
+}
+
+impl Step {
+    pub(crate) fn new(check: Check, same: bool) -> Self {
+        Self { check, same }
+    }
+
+    /// What was checked.
+    pub fn check(&self) -> Check {
+        self.check
+    }
+
+    /// Whether the check found the entry the same on both sides.
+    pub fn is_same(&self) -> bool {
+        self.same
+    }
+}
+
+/// What was checked about an entry.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Check {
+    /// The entry has the same name in both trees.
+    Name,
+    /// The entry is only in this tree.
+    OnlyIn(Side),
+    /// The entry was skipped, and counts as the same.
+    Skipped(SkipReason),
+    /// The types of the entries, which differed.
+    FileType,
+    /// Where a symlink points, which is outside its tree.
+    SymlinkEscape,
+    /// Where a symlink points, which is nothing on one side.
+    BrokenSymlink,
+    /// The result of an earlier comparison, for a file unchanged since, with
+    /// [`crate::diff_incremental`].
+    Reused,
+    /// The sizes of the files, which differ when the contents do.
+    Size,
+    /// The files are the same file, or share their extents, so weren't read.
+    SameFile,
+    /// The hashes of the contents, from the cache of
+    /// [`DirDiffOptions::cache_dir`][crate::DirDiffOptions::cache_dir] when `cached`.
+    Hash {
+        /// Whether both hashes were found in the cache, rather than read.
+        cached: bool,
+    },
+    /// The contents, read in full.
+    Read,
+    /// The contents, which took too long to read.
+    Timeout,
+    /// The sizes, within [`DirDiffOptions::size_tolerance`][crate::DirDiffOptions::size_tolerance].
+    SizeTolerance,
+    /// The number of records, with
+    /// [`DirDiffOptions::compare_record_counts`][crate::DirDiffOptions::compare_record_counts].
+    RecordCount,
+    /// The contents, by a command from
+    /// [`DirDiffOptions::compare_with_command`][crate::DirDiffOptions::compare_with_command].
+    Command,
+    /// The contents, as text normalized by the whitespace and encoding options.
+    Text,
+    /// The contents, parsed in the format of the files, like archives, CSV or binaries.
+    Format,
+    /// The contents, by a comparator from
+    /// [`DirDiffOptions::comparator`][crate::DirDiffOptions::comparator].
+    Comparator,
+    /// The contents, with
+    /// [`DirDiffOptions::placeholder`][crate::DirDiffOptions::placeholder]s matched.
+    Placeholders,
+    /// The permissions and modification times compared.
+    Metadata,
+}
+
+impl std::fmt::Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Check::Name => write!(f, "name"),
+            Check::OnlyIn(side) => write!(f, "only in {side}"),
+            Check::Skipped(reason) => write!(f, "skipped ({})", skip_reason(*reason)),
+            Check::FileType => write!(f, "file type"),
+            Check::SymlinkEscape => write!(f, "symlink target"),
+            Check::BrokenSymlink => write!(f, "broken symlink"),
+            Check::Reused => write!(f, "earlier result"),
+            Check::Size => write!(f, "size"),
+            Check::SameFile => write!(f, "same file on disk"),
+            Check::Hash { cached: true } => write!(f, "hash cache hit"),
+            Check::Hash { cached: false } => write!(f, "hash"),
+            Check::Read => write!(f, "full read"),
+            Check::Timeout => write!(f, "read timed out"),
+            Check::SizeTolerance => write!(f, "size tolerance"),
+            Check::RecordCount => write!(f, "record count"),
+            Check::Command => write!(f, "command"),
+            Check::Text => write!(f, "normalized text"),
+            Check::Format => write!(f, "file format"),
+            Check::Comparator => write!(f, "comparator"),
+            Check::Placeholders => write!(f, "placeholders"),
+            Check::Metadata => write!(f, "metadata"),
+        }
+    }
+}
+
+fn skip_reason(reason: SkipReason) -> String {
+    match reason {
+        SkipReason::Excluded(side) => format!("excluded on the {side}"),
+        SkipReason::Extra(side) => format!("extra on the {side}"),
+        SkipReason::Empty(side) => format!("empty on the {side}"),
+        SkipReason::Hook => "by hook".to_owned(),
+        SkipReason::SameFile => "same directory".to_owned(),
+        SkipReason::OtherFileSystem => "other file system".to_owned(),
+    }
+}
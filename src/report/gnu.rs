@@ -0,0 +1,90 @@
+//! Rendering a [`DiffReport`] as `diff -rq` would.
+
+use std::path::{Path, PathBuf};
+
+use crate::report::{DiffReport, Difference, DifferenceKind, Side};
+use crate::source::FileType;
+
+impl DiffReport {
+    /// Render the differences as the lines GNU `diff -rq` prints, as in:
+    ///
+    /// ```text
+    /// Only in out: removed.txt
+    /// Only in golden/src: added.txt
+    /// Files out/src/changed.txt and golden/src/changed.txt differ
+    /// File out/src/was-a-directory is a directory while file golden/src/was-a-directory is a regular file
+    /// ```
+    ///
+    /// For scripts that already parse that output.  Paths are joined onto the roots as given.
+    /// Differences `diff` doesn't notice, as in permissions or modification times, are reported
+    /// as the files differing, and the roots having different names isn't reported.
+    pub fn render_diff_rq(&self) -> String {
+        let mut out = String::new();
+        for difference in &self.differences {
+            if let Some(line) = self.diff_rq_line(difference) {
+                out.push_str(&line);
+                out.push('\n');
+            }
+        }
+        out
+    }
+
+    fn diff_rq_line(&self, difference: &Difference) -> Option<String> {
+        let path = difference.path().as_path();
+        let left = join(self.left(), path);
+        let right = join(self.right(), path);
+        let line = match difference.kind() {
+            DifferenceKind::OnlyInLeft | DifferenceKind::OnlyInRight => {
+                let root = match difference.kind() {
+                    DifferenceKind::OnlyInLeft => self.left(),
+                    _ => self.right(),
+                };
+                let dir = join(root, path.parent().unwrap_or(Path::new("")));
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                format!("Only in {}: {name}", dir.display())
+            }
+            DifferenceKind::FileType => format!(
+                "File {} is a {} while file {} is a {}",
+                left.display(),
+                describe(difference, Side::Left),
+                right.display(),
+                describe(difference, Side::Right)
+            ),
+            DifferenceKind::RootName => return None,
+            _ => {
+                let symlinks = [Side::Left, Side::Right].iter().all(|side| {
+                    difference
+                        .metadata(*side)
+                        .map(|metadata| metadata.file_type() == FileType::Symlink)
+                        .unwrap_or(false)
+                });
+                let what = if symlinks { "Symbolic links" } else { "Files" };
+                format!("{what} {} and {} differ", left.display(), right.display())
+            }
+        };
+        Some(line)
+    }
+}
+
+/// `path` under `root`, leaving `root` as it is for the roots themselves.
+fn join(root: &Path, path: &Path) -> PathBuf {
+    if path.as_os_str().is_empty() {
+        root.to_owned()
+    } else {
+        root.join(path)
+    }
+}
+
+/// What `diff` calls the entry on `side`.
+fn describe(difference: &Difference, side: Side) -> &'static str {
+    let Some(metadata) = difference.metadata(side) else {
+        return "regular file";
+    };
+    match metadata.file_type() {
+        FileType::Dir => "directory",
+        FileType::Symlink => "symbolic link",
+        FileType::File if metadata.is_empty() => "regular empty file",
+        FileType::File => "regular file",
+        FileType::Other => "special file",
+    }
+}
@@ -0,0 +1,200 @@
+//! Rendering a [`DiffReport`] as a standalone HTML page.
+//!
+//! The page has no external resources, so it can be attached to CI runs as a single artifact.
+
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use crate::report::tree::Node;
+use crate::report::{DiffReport, DifferenceKind};
+use crate::textdiff::{self, Line};
+
+/// Files larger than this are not diffed inline.
+const MAX_INLINE_DIFF_LEN: u64 = 1024 * 1024;
+
+/// Unchanged lines shown around each change in inline diffs.
+const CONTEXT_LINES: usize = 3;
+
+const STYLE: &str = "
+body { font-family: sans-serif; margin: 2em; }
+ul.tree { list-style: none; padding-left: 1.5em; }
+summary { cursor: pointer; }
+.removed { color: #b31d28; }
+.added { color: #22863a; }
+.changed { color: #b08800; }
+pre.diff { background: #f6f8fa; color: #24292e; padding: 0.5em; overflow-x: auto; }
+pre.diff .removed { background: #ffeef0; }
+pre.diff .added { background: #e6ffed; }
+pre.diff .gap { color: #6a737d; }
+";
+
+/// How inline diffs find the lines that changed.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DiffAlgorithm {
+    /// Myers' algorithm, finding the fewest lines to add and remove.
+    #[default]
+    Myers,
+    /// Patience diff, which lines up unique lines first.  Its hunks follow the structure of the
+    /// text more closely, which is far more readable for code with many repeated lines such as
+    /// closing braces.
+    #[cfg(feature = "similar")]
+    Patience,
+    /// The longest common subsequence, found by dynamic programming.  Slow on large files.
+    #[cfg(feature = "similar")]
+    Lcs,
+}
+
+/// Render the report as a self-contained HTML page.
+///
+/// Differences are shown as a tree of collapsible directories.  Files whose contents differ are
+/// diffed inline when both sides are UTF-8 text of a reasonable size, reading them from the roots
+/// recorded in the report.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let report = dir_diff::diff("dir/a", "dir/b").unwrap();
+/// std::fs::write("report.html", dir_diff::report::html::render(&report)).unwrap();
+/// ```
+pub fn render(report: &DiffReport) -> String {
+    render_with_algorithm(report, DiffAlgorithm::default())
+}
+
+/// Render the report as [`render`] does, diffing files inline with `algorithm`.
+pub fn render_with_algorithm(report: &DiffReport, algorithm: DiffAlgorithm) -> String {
+    let left = escape(&report.left().display().to_string());
+    let right = escape(&report.right().display().to_string());
+
+    let mut out = String::new();
+    out.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    let _ = writeln!(out, "<title>dir-diff: {left} vs {right}</title>");
+    let _ = writeln!(out, "<style>{STYLE}</style>\n</head>\n<body>");
+    let _ = writeln!(
+        out,
+        "<h1><span class=\"removed\">{left}</span> vs <span class=\"added\">{right}</span></h1>"
+    );
+    match report.differences().len() {
+        0 => out.push_str("<p>No differences.</p>\n"),
+        1 => out.push_str("<p>1 difference.</p>\n"),
+        count => {
+            let _ = writeln!(out, "<p>{count} differences.</p>");
+        }
+    }
+
+    let tree = Node::from_report(report);
+    let mut path = PathBuf::new();
+    render_children(&mut out, report, &tree, &mut path, algorithm);
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+fn render_children(
+    out: &mut String,
+    report: &DiffReport,
+    node: &Node,
+    path: &mut PathBuf,
+    algorithm: DiffAlgorithm,
+) {
+    if node.children.is_empty() {
+        return;
+    }
+
+    out.push_str("<ul class=\"tree\">\n");
+    for (name, child) in &node.children {
+        path.push(name);
+        let name = escape(&name.to_string_lossy());
+        match child.kind {
+            Some(kind) => {
+                let marker = kind.marker();
+                let class = match kind {
+                    DifferenceKind::OnlyInLeft => "removed",
+                    DifferenceKind::OnlyInRight => "added",
+                    _ => "changed",
+                };
+                let label = format!("<span class=\"{class}\">{marker} {name}</span>");
+                match inline_diff(report, path, kind, algorithm) {
+                    Some(diff) => {
+                        let _ = writeln!(
+                            out,
+                            "<li><details><summary>{label}</summary>\n{diff}</details></li>"
+                        );
+                    }
+                    None => {
+                        let _ = writeln!(out, "<li>{label}</li>");
+                    }
+                }
+            }
+            None => {
+                let _ = writeln!(out, "<li><details open><summary>{name}</summary>");
+                render_children(out, report, child, path, algorithm);
+                out.push_str("</details></li>\n");
+            }
+        }
+        path.pop();
+    }
+    out.push_str("</ul>\n");
+}
+
+fn inline_diff(
+    report: &DiffReport,
+    path: &Path,
+    kind: DifferenceKind,
+    algorithm: DiffAlgorithm,
+) -> Option<String> {
+    if kind != DifferenceKind::Content {
+        return None;
+    }
+    let left = read_text(&report.left().join(path))?;
+    let right = read_text(&report.right().join(path))?;
+
+    let lines = textdiff::diff_lines_with(&left, &right, algorithm);
+    let mut out = String::from("<pre class=\"diff\">");
+    for (i, hunk) in textdiff::hunks(&lines, CONTEXT_LINES)
+        .into_iter()
+        .enumerate()
+    {
+        if i != 0 {
+            out.push_str("<span class=\"gap\">⋯</span>\n");
+        }
+        for line in hunk {
+            let (marker, class, text) = match line {
+                Line::Equal(text) => (" ", "", text),
+                Line::Removed(text) => ("-", "removed", text),
+                Line::Added(text) => ("+", "added", text),
+            };
+            let _ = writeln!(
+                out,
+                "<span class=\"{class}\">{marker}{}</span>",
+                escape(text)
+            );
+        }
+    }
+    out.push_str("</pre>\n");
+    Some(out)
+}
+
+fn read_text(path: &Path) -> Option<String> {
+    let metadata = std::fs::metadata(path).ok()?;
+    if MAX_INLINE_DIFF_LEN < metadata.len() {
+        return None;
+    }
+    String::from_utf8(std::fs::read(path).ok()?).ok()
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
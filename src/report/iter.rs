@@ -0,0 +1,99 @@
+//! Querying the differences in a report.
+
+use globset::{Glob, GlobMatcher};
+
+use crate::report::{Difference, DifferenceKind};
+use crate::Error;
+
+/// An iterator over some of the differences in a [`DiffReport`][crate::DiffReport], in relative
+/// path order.
+///
+/// Created by [`DiffReport::iter`][crate::DiffReport::iter] and the other query methods, and
+/// narrowed further with [`Iter::filter_paths`].
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let report = dir_diff::diff("out", "golden").unwrap();
+/// // Additions are fine, but nothing may be removed or changed.
+/// assert_eq!(report.only_in_left().count(), 0);
+/// assert_eq!(report.changed().filter_paths("**/*.rs").unwrap().count(), 0);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Iter<'r> {
+    differences: std::slice::Iter<'r, Difference>,
+    kinds: Kinds,
+    paths: Vec<GlobMatcher>,
+}
+
+/// Which kinds of differences an [`Iter`] keeps.
+#[derive(Copy, Clone, Debug)]
+pub(crate) enum Kinds {
+    All,
+    Only(&'static [DifferenceKind]),
+    Except(&'static [DifferenceKind]),
+}
+
+impl<'r> Iter<'r> {
+    pub(crate) fn new(differences: &'r [Difference], kinds: Kinds) -> Self {
+        Self {
+            differences: differences.iter(),
+            kinds,
+            paths: Vec::new(),
+        }
+    }
+
+    /// Only keep differences whose path, relative to the roots, matches `glob`.
+    ///
+    /// Globs are matched like [`DirDiffOptions::exclude`][crate::DirDiffOptions::exclude].
+    /// Calling this again keeps the differences matching every glob.
+    pub fn filter_paths(mut self, glob: &str) -> Result<Self, Error> {
+        self.paths.push(Glob::new(glob)?.compile_matcher());
+        Ok(self)
+    }
+
+    fn wanted(&self, difference: &Difference) -> bool {
+        let kind_matches = match self.kinds {
+            Kinds::All => true,
+            Kinds::Only(kinds) => kinds.contains(&difference.kind()),
+            Kinds::Except(kinds) => !kinds.contains(&difference.kind()),
+        };
+        kind_matches
+            && self
+                .paths
+                .iter()
+                .all(|glob| glob.is_match(difference.path()))
+    }
+}
+
+impl<'r> Iterator for Iter<'r> {
+    type Item = &'r Difference;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let difference = self.differences.next()?;
+            if self.wanted(difference) {
+                return Some(difference);
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.differences.size_hint().1)
+    }
+}
+
+impl DoubleEndedIterator for Iter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            let difference = self.differences.next_back()?;
+            if self.wanted(difference) {
+                return Some(difference);
+            }
+        }
+    }
+}
+
+impl std::iter::FusedIterator for Iter<'_> {}
@@ -0,0 +1,106 @@
+//! Ranking content differences by how large they are.
+
+use std::fs::File;
+
+use crate::compare::read_chunk;
+use crate::error::{Phase, ResultExt as _};
+use crate::report::{DiffReport, Difference, DifferenceKind, Side};
+use crate::Error;
+
+/// How [`DiffReport::largest`] measures a content difference.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Ranking {
+    /// The size of the larger of the two files.
+    Size,
+    /// The number of bytes that differ between the two files, counting every byte one file has
+    /// past the end of the other.
+    DifferingBytes,
+}
+
+impl DiffReport {
+    /// The `count` files whose contents differ the most, largest first, with their measure.
+    ///
+    /// Ties are broken by relative path.  [`Ranking::Size`] uses the metadata recorded in the
+    /// report.  [`Ranking::DifferingBytes`] reads the files again from the roots, so those must
+    /// still be local directories, unchanged since the comparison.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// extern crate dir_diff;
+    ///
+    /// use dir_diff::Ranking;
+    ///
+    /// let report = dir_diff::diff("out", "golden").unwrap();
+    /// for (difference, size) in report.largest(10, Ranking::Size).unwrap() {
+    ///     println!("{size:>12} {}", difference.path().display());
+    /// }
+    /// ```
+    pub fn largest(
+        &self,
+        count: usize,
+        ranking: Ranking,
+    ) -> Result<Vec<(&Difference, u64)>, Error> {
+        let mut ranked = Vec::new();
+        for difference in self.differences() {
+            if difference.kind() != DifferenceKind::Content {
+                continue;
+            }
+            let measure = match ranking {
+                Ranking::Size => [Side::Left, Side::Right]
+                    .into_iter()
+                    .filter_map(|side| difference.metadata(side))
+                    .map(|metadata| metadata.len())
+                    .max()
+                    .unwrap_or(0),
+                Ranking::DifferingBytes => self.differing_bytes(difference)?,
+            };
+            ranked.push((difference, measure));
+        }
+        // Differences are already sorted by path, which a stable sort keeps for ties.
+        ranked.sort_by(|(_, a), (_, b)| b.cmp(a));
+        ranked.truncate(count);
+        Ok(ranked)
+    }
+
+    fn differing_bytes(&self, difference: &Difference) -> Result<u64, Error> {
+        let left_path = self.resolve(difference.path(), Side::Left);
+        let right_path = self.resolve(difference.path(), Side::Right);
+        let mut left =
+            File::open(&left_path).context(Phase::Reading(Side::Left), || left_path.clone())?;
+        let mut right =
+            File::open(&right_path).context(Phase::Reading(Side::Right), || right_path.clone())?;
+        let mut left_buffer = vec![0; CHUNK];
+        let mut right_buffer = vec![0; CHUNK];
+        let mut differing = 0;
+        loop {
+            let left_len = read_chunk(&mut left, &mut left_buffer)
+                .context(Phase::Reading(Side::Left), || left_path.clone())?;
+            let right_len = read_chunk(&mut right, &mut right_buffer)
+                .context(Phase::Reading(Side::Right), || right_path.clone())?;
+            let common = left_len.min(right_len);
+            differing += left_buffer[..common]
+                .iter()
+                .zip(&right_buffer[..common])
+                .filter(|(left, right)| left != right)
+                .count() as u64;
+            differing += (left_len.max(right_len) - common) as u64;
+            if left_len < CHUNK || right_len < CHUNK {
+                break;
+            }
+        }
+        // One side ended, so whatever is left of the other differs.
+        for (file, path, side) in [
+            (&mut left, &left_path, Side::Left),
+            (&mut right, &right_path, Side::Right),
+        ] {
+            differing += std::io::copy(file, &mut std::io::sink())
+                .context(Phase::Reading(side), || path.clone())?;
+        }
+        Ok(differing)
+    }
+}
+
+/// How much of each file to read at a time.
+const CHUNK: usize = 64 * 1024;
@@ -0,0 +1,671 @@
+//! The result of a full comparison of two directories.
+
+mod delta;
+mod explain;
+mod gnu;
+#[cfg(feature = "html")]
+pub mod html;
+mod iter;
+mod largest;
+mod rel_path;
+mod rollup;
+pub(crate) mod tree;
+
+pub use self::delta::ReportDelta;
+pub use self::explain::{Check, ExplainedEntry, Explanation, Step};
+pub use self::iter::Iter;
+pub use self::largest::Ranking;
+pub use self::rel_path::{PathSeparator, RelPath};
+pub use self::rollup::{DiffCounts, Rollup};
+
+use std::path::{Path, PathBuf};
+
+use self::iter::Kinds;
+use crate::source::Metadata;
+
+/// Every difference found between two directory trees.
+///
+/// Entries are ordered by relative path, the same order the trees are walked in.  When an entry
+/// exists on only one side, or is a directory on one side and something else on the other, only
+/// that entry is reported and its contents are not descended into, similar to `diff -rq`.
+///
+/// # Ordering
+///
+/// Relative paths are compared component by component, and file names byte by byte, as
+/// [`Path`]'s [`Ord`] does.  So `a/z` comes before `a.txt`, and `B` before `a`.  This order doesn't
+/// depend on the platform, the file system or the order directories are listed in, so a report
+/// rendered or serialized from the same trees is always the same, and can itself be committed as
+/// a golden file.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiffReport {
+    left: PathBuf,
+    right: PathBuf,
+    differences: Vec<Difference>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    allowed: Vec<Difference>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    duplicates: Vec<DuplicateGroup>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Vec::is_empty")
+    )]
+    name_issues: Vec<NameIssue>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "DiffStats::is_empty")
+    )]
+    stats: DiffStats,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    explanation: Option<Explanation>,
+}
+
+impl DiffReport {
+    pub(crate) fn new(left: PathBuf, right: PathBuf, differences: Vec<Difference>) -> Self {
+        Self {
+            left,
+            right,
+            differences,
+            allowed: Vec::new(),
+            duplicates: Vec::new(),
+            name_issues: Vec::new(),
+            stats: DiffStats::default(),
+            explanation: None,
+        }
+    }
+
+    pub(crate) fn with_allowed(mut self, allowed: Vec<Difference>) -> Self {
+        self.allowed = allowed;
+        self
+    }
+
+    pub(crate) fn with_duplicates(mut self, duplicates: Vec<DuplicateGroup>) -> Self {
+        self.duplicates = duplicates;
+        self
+    }
+
+    pub(crate) fn with_name_issues(mut self, name_issues: Vec<NameIssue>) -> Self {
+        self.name_issues = name_issues;
+        self
+    }
+
+    pub(crate) fn with_stats(mut self, stats: DiffStats) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    pub(crate) fn with_explanation(mut self, explanation: Option<Explanation>) -> Self {
+        self.explanation = explanation;
+        self
+    }
+
+    /// This report with `separator` between the components of every relative path.
+    pub(crate) fn with_separator(mut self, separator: PathSeparator) -> Self {
+        let separate = |paths: Vec<RelPath>| -> Vec<RelPath> {
+            paths
+                .into_iter()
+                .map(|path| path.with_separator(separator))
+                .collect()
+        };
+        for difference in self.differences.iter_mut().chain(&mut self.allowed) {
+            difference.path = std::mem::take(&mut difference.path).with_separator(separator);
+        }
+        for group in &mut self.duplicates {
+            group.left = separate(std::mem::take(&mut group.left));
+            group.right = separate(std::mem::take(&mut group.right));
+        }
+        for issue in &mut self.name_issues {
+            issue.path = std::mem::take(&mut issue.path).with_separator(separator);
+        }
+        self.explanation = self
+            .explanation
+            .map(|explanation| explanation.with_separator(separator));
+        self
+    }
+
+    /// The root of the left-hand tree, as passed to the comparison.
+    pub fn left(&self) -> &Path {
+        &self.left
+    }
+
+    /// The root of the right-hand tree, as passed to the comparison.
+    pub fn right(&self) -> &Path {
+        &self.right
+    }
+
+    /// Where `path` is in the `side` tree.
+    pub fn resolve(&self, path: &RelPath, side: Side) -> PathBuf {
+        match side {
+            Side::Left => path.resolve(&self.left),
+            Side::Right => path.resolve(&self.right),
+        }
+    }
+
+    /// The differences found, ordered by relative path.
+    pub fn differences(&self) -> &[Difference] {
+        &self.differences
+    }
+
+    /// The differences that were found but are allowed by
+    /// [`DirDiffOptions::allow_different`][crate::DirDiffOptions::allow_different], ordered by
+    /// relative path.
+    ///
+    /// These don't make the trees different.
+    pub fn allowed(&self) -> &[Difference] {
+        &self.allowed
+    }
+
+    /// Files with identical contents, where at least one of them has no counterpart on the other
+    /// side.
+    ///
+    /// Only populated when [`DirDiffOptions::find_duplicates`][crate::DirDiffOptions::find_duplicates]
+    /// is set.  A group with several paths on one side points at a file that was copied rather
+    /// than added, and a group spanning both sides at a file that was moved.
+    pub fn duplicates(&self) -> &[DuplicateGroup] {
+        &self.duplicates
+    }
+
+    /// Entries in either tree whose names couldn't be created on Windows, ordered by relative
+    /// path.
+    ///
+    /// Only populated when
+    /// [`DirDiffOptions::check_portable_names`][crate::DirDiffOptions::check_portable_names] is
+    /// set.  These don't make the trees different.
+    pub fn name_issues(&self) -> &[NameIssue] {
+        &self.name_issues
+    }
+
+    /// How the comparison went, as opposed to what it found.
+    pub fn stats(&self) -> DiffStats {
+        self.stats
+    }
+
+    /// The checks run on every entry, with
+    /// [`DirDiffOptions::explain`][crate::DirDiffOptions::explain].
+    pub fn explanation(&self) -> Option<&Explanation> {
+        self.explanation.as_ref()
+    }
+
+    /// Are the two trees different?
+    pub fn is_different(&self) -> bool {
+        !self.differences.is_empty()
+    }
+
+    /// Were no differences found?
+    pub fn is_empty(&self) -> bool {
+        self.differences.is_empty()
+    }
+
+    /// Compare this report with an earlier `baseline`, such as one saved by a previous run.
+    ///
+    /// Differences are matched by path and kind, so a known difference can be tolerated while
+    /// any new one is flagged.  With the `serde` feature, reports can be saved and loaded as
+    /// JSON or any other format.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # #[cfg(feature = "serde")] {
+    /// extern crate dir_diff;
+    ///
+    /// let baseline: dir_diff::DiffReport =
+    ///     serde_json::from_str(&std::fs::read_to_string("baseline.json").unwrap()).unwrap();
+    /// let report = dir_diff::diff("out", "golden").unwrap();
+    /// assert!(!report.compare_to_baseline(&baseline).has_regressions());
+    /// # }
+    /// ```
+    pub fn compare_to_baseline(&self, baseline: &DiffReport) -> ReportDelta {
+        ReportDelta::new(self, baseline)
+    }
+
+    /// Iterate over the differences, ordered by relative path.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter::new(&self.differences, Kinds::All)
+    }
+
+    /// The entries that only exist in the left tree.
+    pub fn only_in_left(&self) -> Iter<'_> {
+        Iter::new(
+            &self.differences,
+            Kinds::Only(&[DifferenceKind::OnlyInLeft]),
+        )
+    }
+
+    /// The entries that only exist in the right tree.
+    pub fn only_in_right(&self) -> Iter<'_> {
+        Iter::new(
+            &self.differences,
+            Kinds::Only(&[DifferenceKind::OnlyInRight]),
+        )
+    }
+
+    /// The entries that exist in both trees but differ, in any way.
+    pub fn changed(&self) -> Iter<'_> {
+        Iter::new(
+            &self.differences,
+            Kinds::Except(&[DifferenceKind::OnlyInLeft, DifferenceKind::OnlyInRight]),
+        )
+    }
+
+    /// Replace the differences found under `dir` with a fresh comparison of that subtree.
+    #[cfg(feature = "watch")]
+    pub(crate) fn replace_subtree(
+        &mut self,
+        dir: &Path,
+        differences: Vec<Difference>,
+        allowed: Vec<Difference>,
+    ) {
+        for (existing, fresh) in [
+            (&mut self.differences, differences),
+            (&mut self.allowed, allowed),
+        ] {
+            existing
+                .retain(|difference| difference.path == dir || !difference.path.starts_with(dir));
+            existing.extend(fresh);
+            existing.sort_by(|a, b| a.path.cmp(&b.path));
+        }
+    }
+}
+
+impl<'r> IntoIterator for &'r DiffReport {
+    type Item = &'r Difference;
+    type IntoIter = Iter<'r>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// One of the two trees being compared.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+#[allow(clippy::exhaustive_enums)] // there are only ever two sides
+pub enum Side {
+    /// The first tree passed to the comparison.
+    Left,
+    /// The second tree passed to the comparison.
+    Right,
+}
+
+impl std::fmt::Display for Side {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Side::Left => write!(f, "left"),
+            Side::Right => write!(f, "right"),
+        }
+    }
+}
+
+/// A single entry that differs between the two trees.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Difference {
+    path: RelPath,
+    kind: DifferenceKind,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    left: Option<Metadata>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    right: Option<Metadata>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    similarity: Option<Similarity>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    blocks: Option<BlockSummary>,
+}
+
+impl Difference {
+    pub(crate) fn new(path: PathBuf, kind: DifferenceKind) -> Self {
+        Self {
+            path: RelPath::new(path),
+            kind,
+            left: None,
+            right: None,
+            similarity: None,
+            blocks: None,
+        }
+    }
+
+    pub(crate) fn with_separator(mut self, separator: PathSeparator) -> Self {
+        self.path = self.path.with_separator(separator);
+        self
+    }
+
+    pub(crate) fn with_metadata(mut self, left: Option<Metadata>, right: Option<Metadata>) -> Self {
+        self.left = left;
+        self.right = right;
+        self
+    }
+
+    pub(crate) fn with_similarity(mut self, similarity: Option<Similarity>) -> Self {
+        self.similarity = similarity;
+        self
+    }
+
+    pub(crate) fn with_blocks(mut self, blocks: Option<BlockSummary>) -> Self {
+        self.blocks = blocks;
+        self
+    }
+
+    /// The path of the entry, relative to the roots being compared.
+    pub fn path(&self) -> &RelPath {
+        &self.path
+    }
+
+    /// How the entry differs.
+    pub fn kind(&self) -> DifferenceKind {
+        self.kind
+    }
+
+    /// The metadata of the entry in the `side` tree, as it was when the entry was compared.
+    ///
+    /// `None` if the entry isn't in that tree.
+    pub fn metadata(&self, side: Side) -> Option<&Metadata> {
+        match side {
+            Side::Left => self.left.as_ref(),
+            Side::Right => self.right.as_ref(),
+        }
+    }
+
+    /// How similar the two versions of a text file are.
+    ///
+    /// Only measured for [`DifferenceKind::Content`] differences, with
+    /// [`DirDiffOptions::similarity`][crate::DirDiffOptions::similarity].
+    pub fn similarity(&self) -> Option<Similarity> {
+        self.similarity
+    }
+
+    /// How many blocks of the left version of a file are found in the right version.
+    ///
+    /// Only measured for [`DifferenceKind::Content`] differences, with
+    /// [`DirDiffOptions::summarize_blocks`][crate::DirDiffOptions::summarize_blocks].
+    pub fn blocks(&self) -> Option<BlockSummary> {
+        self.blocks
+    }
+}
+
+/// How many lines two versions of a text file have in common, according to a line diff.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Similarity {
+    common_lines: u64,
+    total_lines: u64,
+}
+
+impl Similarity {
+    pub(crate) fn new(common_lines: u64, total_lines: u64) -> Self {
+        Self {
+            common_lines,
+            total_lines,
+        }
+    }
+
+    /// The lines left unchanged by the diff, counted on both sides.
+    pub fn common_lines(&self) -> u64 {
+        self.common_lines
+    }
+
+    /// The lines of both versions together.
+    pub fn total_lines(&self) -> u64 {
+        self.total_lines
+    }
+
+    /// The share of lines the versions have in common, from 0.0 for completely different files
+    /// to 1.0 for files with the same lines.
+    ///
+    /// Like Python's `difflib.SequenceMatcher.ratio`: twice the lines in common over the lines of
+    /// both versions.  Files that differ only in line endings are 1.0.
+    pub fn ratio(&self) -> f64 {
+        if self.total_lines == 0 {
+            return 1.0;
+        }
+        self.common_lines as f64 / self.total_lines as f64
+    }
+}
+
+/// How many fixed-size blocks of the left version of a file turn up anywhere in the right
+/// version, as rsync would find them.
+///
+/// A file with a patched header has all but a block or two in common, while a corrupted or
+/// rewritten file has few or none.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlockSummary {
+    block_size: u64,
+    blocks: u64,
+    matching_blocks: u64,
+}
+
+impl BlockSummary {
+    pub(crate) fn new(block_size: u64, blocks: u64, matching_blocks: u64) -> Self {
+        Self {
+            block_size,
+            blocks,
+            matching_blocks,
+        }
+    }
+
+    /// The size of each block in bytes, except the last one, which may be shorter.
+    pub fn block_size(&self) -> u64 {
+        self.block_size
+    }
+
+    /// The blocks of the left version.
+    pub fn blocks(&self) -> u64 {
+        self.blocks
+    }
+
+    /// The blocks of the left version also found in the right version.
+    pub fn matching_blocks(&self) -> u64 {
+        self.matching_blocks
+    }
+
+    /// The blocks of the left version not found in the right version.
+    pub fn differing_blocks(&self) -> u64 {
+        self.blocks - self.matching_blocks
+    }
+}
+
+/// Counts of what happened during a comparison, in [`DiffReport::stats`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiffStats {
+    retries: u64,
+}
+
+impl DiffStats {
+    pub(crate) fn new(retries: u64) -> Self {
+        Self { retries }
+    }
+
+    #[cfg(feature = "serde")]
+    fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Reads retried after a transient error, with
+    /// [`DirDiffOptions::retry`][crate::DirDiffOptions::retry].
+    pub fn retries(&self) -> u64 {
+        self.retries
+    }
+}
+
+/// Files sharing the same contents, by relative path.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DuplicateGroup {
+    pub(crate) left: Vec<RelPath>,
+    pub(crate) right: Vec<RelPath>,
+}
+
+impl DuplicateGroup {
+    /// The files in the left tree, sorted.
+    pub fn left(&self) -> &[RelPath] {
+        &self.left
+    }
+
+    /// The files in the right tree, sorted.
+    pub fn right(&self) -> &[RelPath] {
+        &self.right
+    }
+
+    pub(crate) fn first(&self) -> Option<&RelPath> {
+        self.left.first().or_else(|| self.right.first())
+    }
+}
+
+/// An entry whose name couldn't be created on Windows.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NameIssue {
+    pub(crate) path: RelPath,
+    pub(crate) side: Side,
+    pub(crate) problem: NameProblem,
+}
+
+impl NameIssue {
+    /// The path of the entry, relative to its root.
+    pub fn path(&self) -> &RelPath {
+        &self.path
+    }
+
+    /// The tree the entry is in.
+    pub fn side(&self) -> Side {
+        self.side
+    }
+
+    /// What is wrong with the name.
+    pub fn problem(&self) -> NameProblem {
+        self.problem
+    }
+}
+
+/// Why a name couldn't be created on Windows.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+#[non_exhaustive]
+pub enum NameProblem {
+    /// The name is a device name like `CON`, `NUL` or `COM1`, with or without an extension.
+    Reserved,
+    /// The name ends with a dot or a space, which Windows strips.
+    TrailingDotOrSpace,
+    /// The name contains a control character or one of `<>:"/\|?*`.
+    InvalidCharacter,
+    /// The name isn't valid Unicode, so has no UTF-16 equivalent.
+    NotUnicode,
+    /// The relative path is longer than Windows' `MAX_PATH` of 260 characters, before even
+    /// adding the directory it is extracted to.  Only the outermost entry of such a path is
+    /// reported.
+    TooLong,
+    /// Another entry in the same directory has the same name but for case, which Windows treats
+    /// as the same name.  Only the entries after the first, in byte order, are reported.
+    CaseCollision,
+}
+
+/// How an entry differs between the two trees.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+#[non_exhaustive]
+pub enum DifferenceKind {
+    /// The entry only exists in the left tree.
+    OnlyInLeft,
+    /// The entry only exists in the right tree.
+    OnlyInRight,
+    /// The entry exists in both trees but with different file types.
+    FileType,
+    /// Both entries are files but their contents differ.
+    Content,
+    /// When following symlinks, the entry is a symlink that points outside of its tree on at
+    /// least one side, so it was not compared.
+    SymlinkEscape,
+    /// When following symlinks, the entry is a symlink that points to nothing in the `side`
+    /// tree, while it resolves in the other.
+    ///
+    /// Symlinks dangling in both trees are compared as links, by type alone.
+    BrokenSymlink {
+        /// The tree the symlink is dangling in.
+        side: Side,
+    },
+    /// The file changed in at least one tree while it was being compared, so whether the
+    /// contents differ is unknown.
+    ///
+    /// Only reported with [`DirDiffOptions::detect_volatile`][crate::DirDiffOptions::detect_volatile].
+    Volatile,
+    /// The roots themselves have different names.  Reported at the empty path, ahead of every
+    /// other difference.
+    ///
+    /// Only reported with [`DirDiffOptions::compare_root_names`][crate::DirDiffOptions::compare_root_names].
+    RootName,
+    /// Both entries are files, but comparing them took too long, so whether the contents differ
+    /// is unknown.
+    ///
+    /// Only reported with [`DirDiffOptions::read_timeout`][crate::DirDiffOptions::read_timeout]
+    /// or [`DirDiffOptions::deadline`][crate::DirDiffOptions::deadline].
+    Timeout,
+    /// The entries have the same contents but different permission bits.
+    ///
+    /// Only reported with
+    /// [`DirDiffOptions::compare_permissions`][crate::DirDiffOptions::compare_permissions].
+    Permissions,
+    /// The files have the same contents but different modification times.
+    ///
+    /// Only reported with
+    /// [`DirDiffOptions::compare_modified`][crate::DirDiffOptions::compare_modified].
+    Modified,
+    /// The entries have the same contents and permission bits but different access control
+    /// lists.
+    ///
+    /// Only reported with `DirDiffOptions::compare_acls`, with the `acl` feature.
+    Acl,
+    /// Both entries are files holding the same text, but in different encodings, like UTF-8 and
+    /// Latin-1.
+    ///
+    /// Only reported with
+    /// [`DirDiffOptions::detect_encoding_changes`][crate::DirDiffOptions::detect_encoding_changes];
+    /// otherwise these are [`DifferenceKind::Content`] differences.
+    Encoding,
+}
+
+impl DifferenceKind {
+    /// The marker the kind is shown with in rendered reports and by the `dir-diff` command.
+    ///
+    /// `-` and `+` for entries only on the left or right, and a letter for entries that differ
+    /// on both sides, like `M` for [`DifferenceKind::Content`], except `!` for
+    /// [`DifferenceKind::Timeout`].
+    pub fn marker(self) -> &'static str {
+        match self {
+            DifferenceKind::OnlyInLeft => "-",
+            DifferenceKind::OnlyInRight => "+",
+            DifferenceKind::FileType => "T",
+            DifferenceKind::Content => "M",
+            DifferenceKind::SymlinkEscape => "L",
+            DifferenceKind::BrokenSymlink { .. } => "B",
+            DifferenceKind::Volatile => "V",
+            DifferenceKind::RootName => "N",
+            DifferenceKind::Timeout => "!",
+            DifferenceKind::Permissions => "P",
+            DifferenceKind::Modified => "D",
+            DifferenceKind::Acl => "A",
+            DifferenceKind::Encoding => "E",
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! Paths within the trees being compared.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Which separator [`DirDiffOptions::path_separator`][crate::DirDiffOptions::path_separator]
+/// puts between the components of reported paths.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PathSeparator {
+    /// `/` on every platform, so a report from Windows is the same as one from Linux.
+    ///
+    /// Windows accepts `/` in paths too, so the paths still resolve there.
+    #[default]
+    Slash,
+    /// The platform's own separator, `\` on Windows.
+    Native,
+}
+
+/// A path relative to the roots being compared, naming the same entry in either tree.
+///
+/// Dereferences to a [`Path`], and can be turned into the entry's location in one tree with
+/// [`RelPath::resolve`] or [`DiffReport::resolve`][crate::DiffReport::resolve].  The empty path
+/// is the roots themselves.
+#[derive(Clone, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct RelPath(PathBuf);
+
+impl RelPath {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self(path)
+    }
+
+    /// This path with `separator` between its components.
+    pub(crate) fn with_separator(self, separator: PathSeparator) -> Self {
+        match separator {
+            PathSeparator::Slash => {
+                let mut path = OsString::new();
+                for (index, component) in self.0.iter().enumerate() {
+                    if index > 0 {
+                        path.push("/");
+                    }
+                    path.push(component);
+                }
+                Self(path.into())
+            }
+            PathSeparator::Native => Self(self.0.iter().collect()),
+        }
+    }
+
+    /// The relative path, as a [`Path`].
+    pub fn as_path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Where the entry is under `root`.
+    pub fn resolve(&self, root: impl AsRef<Path>) -> PathBuf {
+        let root = root.as_ref();
+        if self.0.as_os_str().is_empty() {
+            root.to_owned()
+        } else {
+            root.join(&self.0)
+        }
+    }
+}
+
+impl std::ops::Deref for RelPath {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for RelPath {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl std::borrow::Borrow<Path> for RelPath {
+    fn borrow(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl From<RelPath> for PathBuf {
+    fn from(path: RelPath) -> PathBuf {
+        path.0
+    }
+}
+
+impl PartialEq<Path> for RelPath {
+    fn eq(&self, other: &Path) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&Path> for RelPath {
+    fn eq(&self, other: &&Path) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<PathBuf> for RelPath {
+    fn eq(&self, other: &PathBuf) -> bool {
+        self.0 == *other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_separators() {
+        let native: PathBuf = ["dir", "sub", "file.txt"].iter().collect();
+        let slashed = RelPath::new(native.clone()).with_separator(PathSeparator::Slash);
+        assert_eq!(slashed.to_str(), Some("dir/sub/file.txt"));
+        assert_eq!(slashed, native);
+        let back = slashed.with_separator(PathSeparator::Native);
+        assert_eq!(back.as_os_str(), native.as_os_str());
+        assert_eq!(
+            RelPath::default()
+                .with_separator(PathSeparator::Slash)
+                .as_os_str(),
+            ""
+        );
+    }
+}
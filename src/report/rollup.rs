@@ -0,0 +1,173 @@
+//! Differences rolled up to the directories containing them.
+
+use std::path::{Path, PathBuf};
+
+use crate::report::tree::Node;
+use crate::report::{DiffReport, DifferenceKind, RelPath};
+
+/// How many differences of each kind there are at or under some entry.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DiffCounts {
+    removed: usize,
+    added: usize,
+    changed: usize,
+}
+
+impl DiffCounts {
+    fn add(&mut self, kind: DifferenceKind) {
+        match kind {
+            DifferenceKind::OnlyInLeft => self.removed += 1,
+            DifferenceKind::OnlyInRight => self.added += 1,
+            DifferenceKind::FileType
+            | DifferenceKind::Content
+            | DifferenceKind::SymlinkEscape
+            | DifferenceKind::BrokenSymlink { .. }
+            | DifferenceKind::Volatile
+            | DifferenceKind::RootName
+            | DifferenceKind::Timeout
+            | DifferenceKind::Permissions
+            | DifferenceKind::Modified
+            | DifferenceKind::Acl
+            | DifferenceKind::Encoding => self.changed += 1,
+        }
+    }
+
+    fn merge(&mut self, other: Self) {
+        self.removed += other.removed;
+        self.added += other.added;
+        self.changed += other.changed;
+    }
+
+    /// Entries only in the left tree.
+    pub fn removed(&self) -> usize {
+        self.removed
+    }
+
+    /// Entries only in the right tree.
+    pub fn added(&self) -> usize {
+        self.added
+    }
+
+    /// Entries in both trees that differ.
+    pub fn changed(&self) -> usize {
+        self.changed
+    }
+
+    /// Every difference, of any kind.
+    pub fn total(&self) -> usize {
+        self.removed + self.added + self.changed
+    }
+}
+
+/// Lists the non-zero counts, as in `3 changed, 1 added`.
+impl std::fmt::Display for DiffCounts {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let counts = [
+            (self.changed, "changed"),
+            (self.added, "added"),
+            (self.removed, "removed"),
+        ];
+        let mut first = true;
+        for (count, label) in counts {
+            if count == 0 {
+                continue;
+            }
+            if !first {
+                f.write_str(", ")?;
+            }
+            write!(f, "{count} {label}")?;
+            first = false;
+        }
+        if first {
+            f.write_str("no differences")?;
+        }
+        Ok(())
+    }
+}
+
+/// An entry of a [`DiffReport`] with the differences at and under it counted, and the entries
+/// under it that differ.
+///
+/// Created by [`DiffReport::rollup`], whose result is the root of both trees.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let report = dir_diff::diff("out", "golden").unwrap();
+/// for dir in report.rollup().children() {
+///     println!("{}: {}", dir.path().display(), dir.counts());
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rollup {
+    path: RelPath,
+    kind: Option<DifferenceKind>,
+    counts: DiffCounts,
+    children: Vec<Rollup>,
+}
+
+impl Rollup {
+    fn from_node(path: PathBuf, node: &Node) -> Self {
+        let mut counts = DiffCounts::default();
+        if let Some(kind) = node.kind {
+            counts.add(kind);
+        }
+        let mut children = Vec::with_capacity(node.children.len());
+        for (name, child) in &node.children {
+            let child = Rollup::from_node(path.join(name), child);
+            counts.merge(child.counts);
+            children.push(child);
+        }
+        Self {
+            path: RelPath::new(path),
+            kind: node.kind,
+            counts,
+            children,
+        }
+    }
+
+    /// The path of the entry, relative to the roots.  Empty for the roots themselves.
+    pub fn path(&self) -> &RelPath {
+        &self.path
+    }
+
+    /// How the entry itself differs, or `None` for a directory that only contains differences.
+    pub fn kind(&self) -> Option<DifferenceKind> {
+        self.kind
+    }
+
+    /// The differences at and under this entry.
+    pub fn counts(&self) -> DiffCounts {
+        self.counts
+    }
+
+    /// The entries directly under this one that differ or contain differences, sorted by name.
+    pub fn children(&self) -> &[Rollup] {
+        &self.children
+    }
+
+    /// The entry at `path`, relative to this one, if it differs or contains differences.
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<&Rollup> {
+        let mut rollup = self;
+        for component in path.as_ref().iter() {
+            rollup = rollup
+                .children
+                .iter()
+                .find(|child| child.path.file_name() == Some(component))?;
+        }
+        Some(rollup)
+    }
+}
+
+impl DiffReport {
+    /// The differences arranged by directory, with each directory counting the differences under
+    /// it.
+    ///
+    /// Differences are reported for the outermost differing entry only, so an added directory
+    /// counts as one addition no matter what it contains.
+    pub fn rollup(&self) -> Rollup {
+        Rollup::from_node(PathBuf::new(), &Node::from_report(self))
+    }
+}
@@ -0,0 +1,28 @@
+//! Differences arranged by directory, for renderers.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+
+use crate::report::{DiffReport, DifferenceKind};
+
+/// A directory containing differences, or a differing entry.
+#[derive(Default)]
+pub(crate) struct Node {
+    /// Set when this entry itself differs.
+    pub(crate) kind: Option<DifferenceKind>,
+    pub(crate) children: BTreeMap<OsString, Node>,
+}
+
+impl Node {
+    pub(crate) fn from_report(report: &DiffReport) -> Self {
+        let mut tree = Node::default();
+        for difference in report.differences() {
+            let mut node = &mut tree;
+            for component in difference.path().iter() {
+                node = node.children.entry(component.to_owned()).or_default();
+            }
+            node.kind = Some(difference.kind());
+        }
+        tree
+    }
+}
@@ -0,0 +1,113 @@
+//! Retrying reads that fail for reasons that may go away, as on flaky network filesystems.
+
+use std::time::Duration;
+
+use crate::Error;
+
+/// How often to retry a read that fails with a transient error, and how long to wait in between.
+///
+/// Reads failing as interrupted, timed out or, on Linux, with a stale NFS file handle are
+/// retried, with the wait doubling after each attempt.  Pass it to
+/// [`DirDiffOptions::retry`][crate::DirDiffOptions::retry].
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// let policy = dir_diff::RetryPolicy::new(3)
+///     .backoff(Duration::from_millis(100))
+///     .max_backoff(Duration::from_secs(2));
+/// let report = dir_diff::DirDiffOptions::new()
+///     .retry(policy)
+///     .diff("/mnt/nfs/backup", "data")
+///     .unwrap();
+/// println!("{} reads retried", report.stats().retries());
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    retries: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Retry a failing read up to `retries` times, first after 10ms.
+    pub fn new(retries: u32) -> Self {
+        Self {
+            retries,
+            backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+
+    /// Wait `backoff` before the first retry, doubling it for each one after.
+    pub fn backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Never wait longer than `max_backoff` between attempts.  1 second by default.
+    pub fn max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// How many times a failing read is retried.
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// How long to wait before the retry numbered `retry`, from 0.
+    pub(crate) fn delay(&self, retry: u32) -> Duration {
+        let factor = 1u32.checked_shl(retry).unwrap_or(u32::MAX);
+        self.backoff
+            .checked_mul(factor)
+            .unwrap_or(self.max_backoff)
+            .min(self.max_backoff)
+    }
+}
+
+/// Whether `err` may not happen again if the read is retried.
+pub(crate) fn is_transient(err: &Error) -> bool {
+    let Some(err) = err.io_error() else {
+        return false;
+    };
+    #[cfg(target_os = "linux")]
+    if err.raw_os_error() == Some(libc::ESTALE) {
+        return true;
+    }
+    matches!(
+        err.kind(),
+        std::io::ErrorKind::Interrupted | std::io::ErrorKind::TimedOut
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delay() {
+        let policy = RetryPolicy::new(5)
+            .backoff(Duration::from_millis(100))
+            .max_backoff(Duration::from_millis(500));
+        let delays: Vec<_> = (0..4)
+            .map(|retry| policy.delay(retry).as_millis())
+            .collect();
+        assert_eq!(delays, [100, 200, 400, 500]);
+        assert_eq!(policy.delay(40), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_is_transient() {
+        let io = |kind| Error::from(std::io::Error::from(kind));
+        assert!(is_transient(&io(std::io::ErrorKind::TimedOut)));
+        assert!(!is_transient(&io(std::io::ErrorKind::NotFound)));
+        assert!(!is_transient(&Error::Cancelled));
+        #[cfg(target_os = "linux")]
+        assert!(is_transient(&Error::from(
+            std::io::Error::from_raw_os_error(libc::ESTALE)
+        )));
+    }
+}
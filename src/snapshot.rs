@@ -0,0 +1,286 @@
+//! Copying live trees aside so they hold still while they are compared.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::compare::check_overlap;
+use crate::error::{Phase, ResultExt as _};
+use crate::options::Settings;
+use crate::report::Side;
+#[cfg(target_os = "linux")]
+use crate::source::DirFdSource;
+use crate::source::{FsSource, Metadata, SourceEntry, TreeSource};
+use crate::Error;
+
+/// How [`DirDiffOptions::snapshot`][crate::DirDiffOptions::snapshot] copies each tree.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Snapshot {
+    /// Hard-link each file into the snapshot, falling back to copying across file systems.
+    ///
+    /// This is cheap, and catches files being added, removed or replaced by renaming, which is
+    /// how most tools write files.  A file written in place still changes in the snapshot.
+    HardLink,
+    /// Copy each file into the snapshot.
+    ///
+    /// File systems that support it, like btrfs, XFS and APFS, clone the file rather than copy
+    /// its contents, making this nearly as cheap as hard links.  Copies are modified at the time
+    /// they are made.
+    Copy,
+}
+
+/// Directories on the local filesystem, snapshotted if the settings ask for it.
+pub(crate) fn local_trees(
+    a_base: &Path,
+    b_base: &Path,
+    settings: &Settings,
+) -> Result<(LocalTree, LocalTree), Error> {
+    let Some(mode) = settings.snapshot() else {
+        return Ok((
+            LocalTree::new(a_base, Side::Left, settings)?,
+            LocalTree::new(b_base, Side::Right, settings)?,
+        ));
+    };
+    // The snapshots can't overlap, so check the trees they are taken from.
+    check_overlap(&FsSource::new(a_base), &FsSource::new(b_base))?;
+    Ok((
+        LocalTree::snapshot(a_base, Side::Left, mode)?,
+        LocalTree::snapshot(b_base, Side::Right, mode)?,
+    ))
+}
+
+/// A directory on the local filesystem, or a snapshot of one standing in for it.
+///
+/// A snapshot is reported under the path of the directory it was taken from, and removed once
+/// the comparison is done.
+#[derive(Debug)]
+pub(crate) struct LocalTree {
+    root: PathBuf,
+    source: LocalSource,
+    snapshot: Option<SnapshotDir>,
+}
+
+/// How a local tree is read.
+#[derive(Debug)]
+enum LocalSource {
+    Path(FsSource),
+    #[cfg(target_os = "linux")]
+    Fd(DirFdSource),
+}
+
+impl LocalSource {
+    fn get(&self) -> &dyn TreeSource {
+        match self {
+            Self::Path(source) => source,
+            #[cfg(target_os = "linux")]
+            Self::Fd(source) => source,
+        }
+    }
+}
+
+impl LocalTree {
+    fn new(root: &Path, side: Side, settings: &Settings) -> Result<Self, Error> {
+        #[cfg(target_os = "linux")]
+        if settings.fd_traversal() {
+            let source = match DirFdSource::open(root) {
+                Ok(source) => source,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    return Err(Error::RootMissing {
+                        side,
+                        path: root.to_owned(),
+                    });
+                }
+                Err(err) => return Err(err).context(Phase::Walking(side), || root.to_owned()),
+            };
+            return Ok(Self {
+                root: root.to_owned(),
+                source: LocalSource::Fd(source),
+                snapshot: None,
+            });
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = (side, settings);
+        Ok(Self {
+            root: root.to_owned(),
+            source: LocalSource::Path(FsSource::new(root)),
+            snapshot: None,
+        })
+    }
+
+    fn snapshot(root: &Path, side: Side, mode: Snapshot) -> Result<Self, Error> {
+        match std::fs::metadata(root) {
+            Ok(_) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Err(Error::RootMissing {
+                    side,
+                    path: root.to_owned(),
+                });
+            }
+            Err(err) => return Err(err).context(Phase::Walking(side), || root.to_owned()),
+        }
+        let snapshot = SnapshotDir::create().context(Phase::Configuring, std::env::temp_dir)?;
+        copy_tree(root, &snapshot.path, mode, side)
+            .context(Phase::Walking(side), || root.to_owned())?;
+        Ok(Self {
+            root: root.to_owned(),
+            source: LocalSource::Path(FsSource::new(&snapshot.path)),
+            snapshot: Some(snapshot),
+        })
+    }
+
+    /// Point errors at the tree the snapshot was taken from.
+    fn unmap(&self, err: Error) -> Error {
+        let Some(snapshot) = &self.snapshot else {
+            return err;
+        };
+        match err {
+            Error::Io {
+                source,
+                phase,
+                path: Some(path),
+            } => {
+                let path = match path.strip_prefix(&snapshot.path) {
+                    Ok(relative) => crate::source::full_path(self, relative),
+                    Err(_) => path,
+                };
+                Error::Io {
+                    source,
+                    phase,
+                    path: Some(path),
+                }
+            }
+            err => err,
+        }
+    }
+}
+
+impl TreeSource for LocalTree {
+    fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<SourceEntry>, Error> {
+        self.source
+            .get()
+            .read_dir(path)
+            .map_err(|err| self.unmap(err))
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, Error> {
+        self.source
+            .get()
+            .metadata(path)
+            .map_err(|err| self.unmap(err))
+    }
+
+    fn open(&self, path: &Path) -> Result<Box<dyn Read + '_>, Error> {
+        self.source.get().open(path).map_err(|err| self.unmap(err))
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf, Error> {
+        self.source
+            .get()
+            .read_link(path)
+            .map_err(|err| self.unmap(err))
+    }
+
+    fn local_path(&self, path: &Path) -> Option<PathBuf> {
+        // Hashes of a snapshot are useless once it is removed, so keep them out of the cache.
+        match self.snapshot {
+            Some(_) => None,
+            None => self.source.get().local_path(path),
+        }
+    }
+
+    fn lists_local_dirs(&self) -> bool {
+        self.source.get().lists_local_dirs()
+    }
+}
+
+/// A uniquely named temporary directory, removed when dropped.
+#[derive(Debug)]
+struct SnapshotDir {
+    path: PathBuf,
+}
+
+impl SnapshotDir {
+    fn create() -> std::io::Result<Self> {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        loop {
+            let path = std::env::temp_dir().join(format!(
+                "dir-diff-snapshot-{}-{}",
+                std::process::id(),
+                NEXT.fetch_add(1, Ordering::Relaxed)
+            ));
+            match std::fs::DirBuilder::new().create(&path) {
+                Ok(()) => return Ok(Self { path }),
+                Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => {}
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl Drop for SnapshotDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Recreate the tree at `from` under the empty directory `to`.
+pub(crate) fn copy_tree(from: &Path, to: &Path, mode: Snapshot, side: Side) -> Result<(), Error> {
+    for entry in walkdir::WalkDir::new(from).min_depth(1) {
+        let entry = entry?;
+        let relative = entry
+            .path()
+            .strip_prefix(from)
+            .expect("walkdir yields paths under its root");
+        let target = to.join(relative);
+        let file_type = entry.file_type();
+        let copied = if file_type.is_dir() {
+            std::fs::create_dir_all(&target)
+        } else if file_type.is_symlink() {
+            copy_link(entry.path(), &target)
+        } else if file_type.is_file() {
+            copy_file(entry.path(), &target, mode)
+        } else {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "only files, directories and symlinks can be snapshotted",
+            ))
+        };
+        copied.context(Phase::Walking(side), || entry.path().to_owned())?;
+    }
+    Ok(())
+}
+
+pub(crate) fn copy_file(from: &Path, to: &Path, mode: Snapshot) -> std::io::Result<()> {
+    if mode == Snapshot::HardLink && std::fs::hard_link(from, to).is_ok() {
+        return Ok(());
+    }
+    std::fs::copy(from, to).map(|_| ())
+}
+
+#[cfg(unix)]
+pub(crate) fn copy_link(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(std::fs::read_link(from)?, to)
+}
+
+#[cfg(windows)]
+pub(crate) fn copy_link(from: &Path, to: &Path) -> std::io::Result<()> {
+    let link = std::fs::read_link(from)?;
+    if std::fs::metadata(from)?.is_dir() {
+        std::os::windows::fs::symlink_dir(link, to)
+    } else {
+        std::os::windows::fs::symlink_file(link, to)
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn copy_link(_from: &Path, _to: &Path) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "symlinks can't be snapshotted on this platform",
+    ))
+}
@@ -0,0 +1,278 @@
+//! Walking a local directory through file descriptors on Linux, so the walk can't be redirected
+//! by renames or symlinks swapped in while it runs.
+
+use std::ffi::{CStr, CString, OsStr};
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd as _, FromRawFd as _, IntoRawFd as _, OwnedFd};
+use std::os::unix::ffi::OsStrExt as _;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::source::{FileType, Metadata, SourceEntry, TreeSource};
+use crate::Error;
+
+/// A directory on the local filesystem, walked relative to a descriptor of its root.
+///
+/// The root is opened once, following symlinks, and every entry below it is reached from there
+/// one name at a time, with `openat` and friends, never following a symlink.  Renaming the root
+/// or any of its ancestors while the walk runs doesn't move the walk elsewhere, and a directory
+/// swapped for a symlink fails to open rather than leading outside the tree, so an attacker
+/// writing to the tree can't make the comparison read files outside of it.
+///
+/// Unlike [`FsSource`][crate::source::FsSource], the tree has no paths the engine could use to
+/// take shortcuts, so the hash cache, memory maps, ACLs,
+/// [`DirDiffOptions::same_file_system`][crate::DirDiffOptions::same_file_system] and listing
+/// directories ahead don't apply to it.  Only available on Linux.
+///
+/// # Examples
+///
+/// ```no_run
+/// use dir_diff::source::{DirFdSource, FsSource};
+///
+/// let uploaded = DirFdSource::open("/srv/uploads/1234").unwrap();
+/// let report = dir_diff::DirDiffOptions::new()
+///     .diff_sources(uploaded, FsSource::new("expected"))
+///     .unwrap();
+/// assert!(!report.is_different());
+/// ```
+#[derive(Debug)]
+pub struct DirFdSource {
+    root: PathBuf,
+    dir: OwnedFd,
+}
+
+impl DirFdSource {
+    /// Open the directory at `root`.
+    pub fn open(root: impl Into<PathBuf>) -> io::Result<Self> {
+        let root = root.into();
+        let path = CString::new(root.as_os_str().as_bytes())?;
+        // SAFETY: `path` is NUL-terminated.
+        let fd = unsafe {
+            libc::open(
+                path.as_ptr(),
+                libc::O_RDONLY | libc::O_DIRECTORY | libc::O_CLOEXEC,
+            )
+        };
+        Ok(Self {
+            root,
+            dir: owned(fd)?,
+        })
+    }
+
+    /// Open the directory reached through `names`, one at a time.
+    fn open_dir(&self, names: &[&OsStr]) -> io::Result<OwnedFd> {
+        let mut dir = self.dir.try_clone()?;
+        for name in names {
+            dir = open_at(&dir, name, libc::O_DIRECTORY)?;
+        }
+        Ok(dir)
+    }
+
+    /// The directory holding the entry at `path`, and the entry's name in it.
+    fn parent<'p>(&self, path: &'p Path) -> io::Result<(OwnedFd, &'p OsStr)> {
+        let mut names = names(path)?;
+        let name = names
+            .pop()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "the root has no parent"))?;
+        Ok((self.open_dir(&names)?, name))
+    }
+
+    fn list(&self, path: &Path) -> io::Result<Vec<SourceEntry>> {
+        let dir = self.open_dir(&names(path)?)?;
+        // SAFETY: the descriptor is open, and owned by the stream from here on.
+        let stream = unsafe { libc::fdopendir(dir.into_raw_fd()) };
+        if stream.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let stream = Stream(stream);
+        let mut entries = Vec::new();
+        loop {
+            // SAFETY: `readdir` only reports an error through `errno`.
+            unsafe { *libc::__errno_location() = 0 };
+            // SAFETY: the stream is open until `Stream` is dropped.
+            let entry = unsafe { libc::readdir(stream.0) };
+            if entry.is_null() {
+                let err = io::Error::last_os_error();
+                return match err.raw_os_error() {
+                    Some(0) => Ok(entries),
+                    _ => Err(err),
+                };
+            }
+            // SAFETY: the entry is valid until the next `readdir`, and its name NUL-terminated.
+            let (name, d_type) =
+                unsafe { (CStr::from_ptr((*entry).d_name.as_ptr()), (*entry).d_type) };
+            let name = OsStr::from_bytes(name.to_bytes());
+            if name == "." || name == ".." {
+                continue;
+            }
+            let file_type = match d_type {
+                libc::DT_REG => FileType::File,
+                libc::DT_DIR => FileType::Dir,
+                libc::DT_LNK => FileType::Symlink,
+                libc::DT_UNKNOWN => {
+                    // SAFETY: the stream's descriptor is open until `Stream` is dropped.
+                    let fd = unsafe { libc::dirfd(stream.0) };
+                    file_type(stat_at(fd, name)?.st_mode)
+                }
+                _ => FileType::Other,
+            };
+            entries.push(SourceEntry::new(name, file_type));
+        }
+    }
+
+    fn read_link_at(&self, path: &Path) -> io::Result<PathBuf> {
+        let (parent, name) = self.parent(path)?;
+        let name = c_name(name)?;
+        let mut buffer = vec![0_u8; 256];
+        loop {
+            // SAFETY: `name` is NUL-terminated, and at most `buffer.len()` bytes are written.
+            let len = unsafe {
+                libc::readlinkat(
+                    parent.as_raw_fd(),
+                    name.as_ptr(),
+                    buffer.as_mut_ptr().cast(),
+                    buffer.len(),
+                )
+            };
+            let len = usize::try_from(len).map_err(|_| io::Error::last_os_error())?;
+            // A link that fills the buffer may have been cut short.
+            if len < buffer.len() {
+                buffer.truncate(len);
+                return Ok(PathBuf::from(OsStr::from_bytes(&buffer)));
+            }
+            buffer.resize(buffer.len() * 2, 0);
+        }
+    }
+}
+
+impl TreeSource for DirFdSource {
+    fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<SourceEntry>, Error> {
+        Ok(self.list(path)?)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, Error> {
+        let stat = if path.as_os_str().is_empty() {
+            let mut stat = std::mem::MaybeUninit::<libc::stat>::uninit();
+            // SAFETY: `fstat` fills in `stat` when it succeeds.
+            let result = unsafe { libc::fstat(self.dir.as_raw_fd(), stat.as_mut_ptr()) };
+            if result != 0 {
+                return Err(io::Error::last_os_error().into());
+            }
+            // SAFETY: initialized by the successful `fstat`.
+            unsafe { stat.assume_init() }
+        } else {
+            let (parent, name) = self.parent(path)?;
+            stat_at(parent.as_raw_fd(), name)?
+        };
+        Ok(metadata(&stat))
+    }
+
+    fn open(&self, path: &Path) -> Result<Box<dyn Read + '_>, Error> {
+        let (parent, name) = self.parent(path)?;
+        Ok(Box::new(File::from(open_at(&parent, name, 0)?)))
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf, Error> {
+        Ok(self.read_link_at(path)?)
+    }
+}
+
+/// A directory stream, closed when dropped.
+struct Stream(*mut libc::DIR);
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        // SAFETY: the stream is open, and not used after this.
+        unsafe { libc::closedir(self.0) };
+    }
+}
+
+/// The names of the components of the relative `path`, which can't leave the tree.
+fn names(path: &Path) -> io::Result<Vec<&OsStr>> {
+    path.components()
+        .filter(|component| *component != Component::CurDir)
+        .map(|component| match component {
+            Component::Normal(name) => Ok(name),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("`{}` isn't a path inside the tree", path.display()),
+            )),
+        })
+        .collect()
+}
+
+fn c_name(name: &OsStr) -> io::Result<CString> {
+    Ok(CString::new(name.as_bytes())?)
+}
+
+/// Open the entry `name` of `dir`, failing if it is a symlink.
+fn open_at(dir: &OwnedFd, name: &OsStr, flags: libc::c_int) -> io::Result<OwnedFd> {
+    let name = c_name(name)?;
+    // SAFETY: `name` is NUL-terminated, and `dir` open while borrowed.
+    let fd = unsafe {
+        libc::openat(
+            dir.as_raw_fd(),
+            name.as_ptr(),
+            flags | libc::O_RDONLY | libc::O_NOFOLLOW | libc::O_CLOEXEC,
+        )
+    };
+    owned(fd)
+}
+
+fn owned(fd: libc::c_int) -> io::Result<OwnedFd> {
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: `fd` was just opened, and nothing else owns it.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+}
+
+/// Look up the entry `name` of the directory `dir`, without following symlinks.
+fn stat_at(dir: libc::c_int, name: &OsStr) -> io::Result<libc::stat> {
+    let name = c_name(name)?;
+    let mut stat = std::mem::MaybeUninit::<libc::stat>::uninit();
+    // SAFETY: `name` is NUL-terminated, and `fstatat` fills in `stat` when it succeeds.
+    let result = unsafe {
+        libc::fstatat(
+            dir,
+            name.as_ptr(),
+            stat.as_mut_ptr(),
+            libc::AT_SYMLINK_NOFOLLOW,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: initialized by the successful `fstatat`.
+    Ok(unsafe { stat.assume_init() })
+}
+
+fn file_type(mode: libc::mode_t) -> FileType {
+    match mode & libc::S_IFMT {
+        libc::S_IFREG => FileType::File,
+        libc::S_IFDIR => FileType::Dir,
+        libc::S_IFLNK => FileType::Symlink,
+        _ => FileType::Other,
+    }
+}
+
+fn metadata(stat: &libc::stat) -> Metadata {
+    let len = u64::try_from(stat.st_size).unwrap_or(0);
+    let metadata = Metadata::new(file_type(stat.st_mode), len).with_permissions(stat.st_mode);
+    let nanos = u32::try_from(stat.st_mtime_nsec).unwrap_or(0);
+    let modified = match u64::try_from(stat.st_mtime) {
+        Ok(secs) => SystemTime::UNIX_EPOCH.checked_add(Duration::new(secs, nanos)),
+        Err(_) => SystemTime::UNIX_EPOCH
+            .checked_sub(Duration::from_secs(stat.st_mtime.unsigned_abs()))
+            .and_then(|time| time.checked_add(Duration::from_nanos(nanos.into()))),
+    };
+    match modified {
+        Some(modified) => metadata.with_modified(modified),
+        None => metadata,
+    }
+}
@@ -0,0 +1,159 @@
+//! Comparing a tree committed to a git repository.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use git2::{ObjectType, Oid, Repository, Tree};
+
+use crate::source::{FileType, Metadata, SourceEntry, TreeSource};
+use crate::Error;
+
+/// A tree in a git repository, read straight from the object database without a checkout.
+///
+/// # Examples
+///
+/// ```no_run
+/// use dir_diff::source::{FsSource, GitTreeSource};
+///
+/// let golden = GitTreeSource::parse(".@HEAD:tests/golden").unwrap();
+/// let report = dir_diff::DirDiffOptions::new()
+///     .diff_sources(FsSource::new("target/out"), golden)
+///     .unwrap();
+/// assert!(!report.is_different());
+/// ```
+pub struct GitTreeSource {
+    repo: Repository,
+    tree: Oid,
+    root: PathBuf,
+}
+
+impl GitTreeSource {
+    /// The tree at `treeish` in the repository at `repo`.
+    ///
+    /// `treeish` is anything `git rev-parse` resolves to a tree, such as a branch, a commit or
+    /// `rev:path` for a subdirectory.
+    pub fn open(repo: impl AsRef<Path>, treeish: &str) -> Result<Self, Error> {
+        let root = PathBuf::from(format!("{}@{treeish}", repo.as_ref().display()));
+        let repo = Repository::open(repo).map_err(into_error)?;
+        let tree = repo
+            .revparse_single(treeish)
+            .and_then(|object| object.peel_to_tree())
+            .map_err(into_error)?
+            .id();
+        Ok(Self { repo, tree, root })
+    }
+
+    /// Open a tree described as `repo@treeish`, like `.@main:tests/golden`.
+    ///
+    /// See [`GitTreeSource::open`].
+    pub fn parse(spec: &str) -> Result<Self, Error> {
+        let (repo, treeish) = spec.rsplit_once('@').ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("expected `repo@treeish`, got `{spec}`"),
+            )
+        })?;
+        Self::open(repo, treeish)
+    }
+
+    fn tree(&self) -> Result<Tree<'_>, Error> {
+        self.repo.find_tree(self.tree).map_err(into_error)
+    }
+
+    /// The object at `path`, its type and its git file mode.
+    fn entry(&self, path: &Path) -> Result<(Oid, FileType, i32), Error> {
+        if path.as_os_str().is_empty() {
+            return Ok((self.tree, FileType::Dir, 0o040000));
+        }
+        let entry = self.tree()?.get_path(path).map_err(into_error)?;
+        Ok((entry.id(), file_type(entry.filemode()), entry.filemode()))
+    }
+}
+
+impl TreeSource for GitTreeSource {
+    fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<SourceEntry>, Error> {
+        let (id, _, _) = self.entry(path)?;
+        let tree = self.repo.find_tree(id).map_err(into_error)?;
+        Ok(tree
+            .iter()
+            .map(|entry| {
+                SourceEntry::new(
+                    bytes_to_name(entry.name_bytes()),
+                    file_type(entry.filemode()),
+                )
+            })
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, Error> {
+        let (id, file_type, mode) = self.entry(path)?;
+        let len = match file_type {
+            FileType::File | FileType::Symlink => {
+                let odb = self.repo.odb().map_err(into_error)?;
+                let (len, kind) = odb.read_header(id).map_err(into_error)?;
+                debug_assert_eq!(kind, ObjectType::Blob);
+                len as u64
+            }
+            _ => 0,
+        };
+        let metadata = Metadata::new(file_type, len);
+        Ok(match file_type {
+            FileType::File => metadata.with_permissions(mode as u32),
+            _ => metadata,
+        })
+    }
+
+    fn open(&self, path: &Path) -> Result<Box<dyn Read + '_>, Error> {
+        let (id, _, _) = self.entry(path)?;
+        let blob = self.repo.find_blob(id).map_err(into_error)?;
+        Ok(Box::new(std::io::Cursor::new(blob.content().to_vec())))
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf, Error> {
+        let (id, _, _) = self.entry(path)?;
+        let blob = self.repo.find_blob(id).map_err(into_error)?;
+        Ok(bytes_to_name(blob.content()).into())
+    }
+}
+
+impl std::fmt::Debug for GitTreeSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GitTreeSource")
+            .field("tree", &self.tree)
+            .field("root", &self.root)
+            .finish_non_exhaustive()
+    }
+}
+
+fn file_type(filemode: i32) -> FileType {
+    match filemode & 0o170000 {
+        0o040000 => FileType::Dir,
+        0o100000 => FileType::File,
+        0o120000 => FileType::Symlink,
+        // Submodules
+        _ => FileType::Other,
+    }
+}
+
+#[cfg(unix)]
+fn bytes_to_name(name: &[u8]) -> std::ffi::OsString {
+    use std::os::unix::ffi::OsStrExt as _;
+    std::ffi::OsStr::from_bytes(name).to_owned()
+}
+
+#[cfg(not(unix))]
+fn bytes_to_name(name: &[u8]) -> std::ffi::OsString {
+    String::from_utf8_lossy(name).into_owned().into()
+}
+
+fn into_error(err: git2::Error) -> Error {
+    let kind = match err.code() {
+        git2::ErrorCode::NotFound => std::io::ErrorKind::NotFound,
+        _ => std::io::ErrorKind::Other,
+    };
+    std::io::Error::new(kind, err).into()
+}
@@ -0,0 +1,179 @@
+//! Trees held in memory, for tests.
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::source::{FileType, Metadata, SourceEntry, TreeSource};
+use crate::Error;
+
+/// A tree held in memory.
+///
+/// Directories are listed in name order, and entries have no modification times or
+/// permissions, so comparisons involving it are the same from run to run.  The parents of each
+/// entry added are created as directories, replacing anything else in their place.
+///
+/// # Examples
+///
+/// ```
+/// use dir_diff::source::MemorySource;
+///
+/// let left = MemorySource::new("left")
+///     .file("src/lib.rs", "pub fn one() {}")
+///     .dir("target");
+/// let right = MemorySource::new("right").file("src/lib.rs", "pub fn two() {}");
+/// let report = dir_diff::DirDiffOptions::new()
+///     .diff_sources(&left, &right)
+///     .unwrap();
+/// assert_eq!(report.differences().len(), 2);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemorySource {
+    root: PathBuf,
+    entries: BTreeMap<PathBuf, Entry>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Entry {
+    File(Vec<u8>),
+    Dir,
+    Symlink(PathBuf),
+}
+
+impl MemorySource {
+    /// An empty tree, reported as `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            entries: BTreeMap::new(),
+        }
+    }
+
+    /// Add a file at `path` holding `contents`.
+    pub fn file(mut self, path: impl AsRef<Path>, contents: impl Into<Vec<u8>>) -> Self {
+        self.insert(path.as_ref(), Entry::File(contents.into()));
+        self
+    }
+
+    /// Add an empty directory at `path`.
+    pub fn dir(mut self, path: impl AsRef<Path>) -> Self {
+        self.insert(path.as_ref(), Entry::Dir);
+        self
+    }
+
+    /// Add a symlink at `path` pointing to `target`.
+    pub fn symlink(mut self, path: impl AsRef<Path>, target: impl Into<PathBuf>) -> Self {
+        self.insert(path.as_ref(), Entry::Symlink(target.into()));
+        self
+    }
+
+    /// Remove the entry at `path`, and everything under it.
+    pub fn remove(mut self, path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        self.entries.retain(|entry, _| !entry.starts_with(path));
+        self
+    }
+
+    /// The paths of every entry in the tree, in order.
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.entries.keys().map(PathBuf::as_path)
+    }
+
+    fn insert(&mut self, path: &Path, entry: Entry) {
+        if path.as_os_str().is_empty() {
+            return;
+        }
+        self.entries
+            .retain(|existing, _| !existing.starts_with(path));
+        for parent in path.ancestors().skip(1) {
+            if parent.as_os_str().is_empty() {
+                break;
+            }
+            self.entries.insert(parent.to_owned(), Entry::Dir);
+        }
+        self.entries.insert(path.to_owned(), entry);
+    }
+
+    fn get(&self, path: &Path) -> Result<Option<&Entry>, Error> {
+        if path.as_os_str().is_empty() {
+            return Ok(None);
+        }
+        match self.entries.get(path) {
+            Some(entry) => Ok(Some(entry)),
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("`{}` isn't in the tree", path.display()),
+            )
+            .into()),
+        }
+    }
+}
+
+impl Entry {
+    fn file_type(&self) -> FileType {
+        match self {
+            Entry::File(_) => FileType::File,
+            Entry::Dir => FileType::Dir,
+            Entry::Symlink(_) => FileType::Symlink,
+        }
+    }
+}
+
+impl TreeSource for MemorySource {
+    fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<SourceEntry>, Error> {
+        if let Some(entry) = self.get(path)? {
+            if *entry != Entry::Dir {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    format!("`{}` isn't a directory", path.display()),
+                )
+                .into());
+            }
+        }
+        let mut entries = Vec::new();
+        for (child, entry) in &self.entries {
+            if child.parent() == Some(path) {
+                let name = child.file_name().map(OsString::from).unwrap_or_default();
+                entries.push(SourceEntry::new(name, entry.file_type()));
+            }
+        }
+        Ok(entries)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, Error> {
+        Ok(match self.get(path)? {
+            None | Some(Entry::Dir) => Metadata::new(FileType::Dir, 0),
+            Some(Entry::File(contents)) => Metadata::new(FileType::File, contents.len() as u64),
+            Some(Entry::Symlink(target)) => {
+                Metadata::new(FileType::Symlink, target.as_os_str().len() as u64)
+            }
+        })
+    }
+
+    fn open(&self, path: &Path) -> Result<Box<dyn Read + '_>, Error> {
+        match self.get(path)? {
+            Some(Entry::File(contents)) => Ok(Box::new(contents.as_slice())),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                format!("`{}` isn't a file", path.display()),
+            )
+            .into()),
+        }
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf, Error> {
+        match self.get(path)? {
+            Some(Entry::Symlink(target)) => Ok(target.clone()),
+            _ => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("`{}` isn't a symlink", path.display()),
+            )
+            .into()),
+        }
+    }
+}
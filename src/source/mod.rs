@@ -0,0 +1,338 @@
+//! Where the trees being compared come from.
+//!
+//! The comparison engine only sees trees through [`TreeSource`], so in-memory trees, archives or
+//! remote storage can be compared by implementing it.  [`FsSource`] is the implementation for
+//! directories on the local filesystem, and [`MemorySource`] holds a tree in memory for tests.
+//!
+//! The engine only reaches the local filesystem through [`TreeSource::local_path`], for shortcuts
+//! like sharing hashes or listing directories ahead, and to resolve the name of a root like `.`
+//! for [`DirDiffOptions::compare_root_names`][crate::DirDiffOptions::compare_root_names].  Nor
+//! does it start threads or read the clock for sources without local paths, unless options like
+//! [`DirDiffOptions::deadline`][crate::DirDiffOptions::deadline] or
+//! [`DirDiffOptions::metrics`][crate::DirDiffOptions::metrics] ask for them.  So the same engine
+//! compares virtual trees where there is no filesystem, like in WebAssembly.
+
+#[cfg(target_os = "linux")]
+mod dirfd;
+#[cfg(feature = "git")]
+mod git;
+mod memory;
+#[cfg(feature = "object_store")]
+mod object_store;
+mod overlay;
+#[cfg(feature = "zip")]
+mod zip;
+
+#[cfg(target_os = "linux")]
+pub use self::dirfd::DirFdSource;
+#[cfg(feature = "git")]
+pub use self::git::GitTreeSource;
+pub use self::memory::MemorySource;
+#[cfg(feature = "object_store")]
+pub use self::object_store::ObjectStoreSource;
+pub use self::overlay::OverlaySource;
+#[cfg(feature = "zip")]
+pub use self::zip::ZipSource;
+
+use std::ffi::OsString;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use walkdir::WalkDir;
+
+use crate::Error;
+
+/// A tree of entries that can be compared.
+///
+/// All paths are relative to the root of the tree, with the root itself being the empty path.
+pub trait TreeSource {
+    /// A path describing the root of the tree, for use in reports.
+    fn root(&self) -> &Path;
+
+    /// List the entries directly inside the directory at `path`, in any order.
+    fn read_dir(&self, path: &Path) -> Result<Vec<SourceEntry>, Error>;
+
+    /// Look up the metadata of the entry at `path`, without following symlinks except for the
+    /// root.
+    fn metadata(&self, path: &Path) -> Result<Metadata, Error>;
+
+    /// Read the contents of the file at `path`.
+    fn open(&self, path: &Path) -> Result<Box<dyn Read + '_>, Error>;
+
+    /// Read where the symlink at `path` points, exactly as stored in the link.
+    ///
+    /// Only used when following symlinks.  By default symlinks can't be read.
+    fn read_link(&self, path: &Path) -> Result<PathBuf, Error> {
+        let _ = path;
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Unsupported,
+            "this source can't read symlinks",
+        )
+        .into())
+    }
+
+    /// The location of `path` on the local filesystem, if it has one.
+    ///
+    /// This lets the engine use shortcuts that only apply to local files, like sharing content
+    /// hashes between comparisons.
+    fn local_path(&self, path: &Path) -> Option<PathBuf> {
+        let _ = path;
+        None
+    }
+
+    /// Whether listing the [`local_path`][TreeSource::local_path] of a directory gives the same
+    /// entries as [`read_dir`][TreeSource::read_dir].
+    ///
+    /// This lets the engine list directories ahead on other threads, with
+    /// [`DirDiffOptions::walk_threads`][crate::DirDiffOptions::walk_threads].  By default
+    /// directories are only listed through `read_dir`.
+    fn lists_local_dirs(&self) -> bool {
+        false
+    }
+}
+
+impl<S: TreeSource + ?Sized> TreeSource for &S {
+    fn root(&self) -> &Path {
+        (**self).root()
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<SourceEntry>, Error> {
+        (**self).read_dir(path)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, Error> {
+        (**self).metadata(path)
+    }
+
+    fn open(&self, path: &Path) -> Result<Box<dyn Read + '_>, Error> {
+        (**self).open(path)
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf, Error> {
+        (**self).read_link(path)
+    }
+
+    fn local_path(&self, path: &Path) -> Option<PathBuf> {
+        (**self).local_path(path)
+    }
+
+    fn lists_local_dirs(&self) -> bool {
+        (**self).lists_local_dirs()
+    }
+}
+
+/// Where `path` is in `source`, for pointing users at it in errors.
+pub(crate) fn full_path<S: TreeSource + ?Sized>(source: &S, path: &Path) -> PathBuf {
+    if path.as_os_str().is_empty() {
+        source.root().to_owned()
+    } else {
+        source.root().join(path)
+    }
+}
+
+/// An entry listed by [`TreeSource::read_dir`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceEntry {
+    name: OsString,
+    file_type: FileType,
+}
+
+impl SourceEntry {
+    /// An entry named `name`, without any path separators.
+    pub fn new(name: impl Into<OsString>, file_type: FileType) -> Self {
+        Self {
+            name: name.into(),
+            file_type,
+        }
+    }
+
+    /// The file name of the entry.
+    pub fn name(&self) -> &std::ffi::OsStr {
+        &self.name
+    }
+
+    /// The type of the entry.
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+}
+
+/// The type of an entry in a tree.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+#[non_exhaustive]
+pub enum FileType {
+    /// A regular file.
+    File,
+    /// A directory.
+    Dir,
+    /// A symbolic link, which is not followed.
+    Symlink,
+    /// Anything else, like sockets or device nodes.
+    Other,
+}
+
+impl From<std::fs::FileType> for FileType {
+    fn from(file_type: std::fs::FileType) -> Self {
+        if file_type.is_symlink() {
+            FileType::Symlink
+        } else if file_type.is_dir() {
+            FileType::Dir
+        } else if file_type.is_file() {
+            FileType::File
+        } else {
+            FileType::Other
+        }
+    }
+}
+
+/// The metadata of an entry in a tree.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Metadata {
+    file_type: FileType,
+    len: u64,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    modified: Option<SystemTime>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    permissions: Option<u32>,
+}
+
+impl Metadata {
+    /// Metadata for an entry of `len` bytes.
+    pub fn new(file_type: FileType, len: u64) -> Self {
+        Self {
+            file_type,
+            len,
+            modified: None,
+            permissions: None,
+        }
+    }
+
+    /// Record when the entry was last modified.
+    pub fn with_modified(mut self, modified: SystemTime) -> Self {
+        self.modified = Some(modified);
+        self
+    }
+
+    /// Record the entry's permission bits, as in a Unix file mode.
+    pub fn with_permissions(mut self, permissions: u32) -> Self {
+        self.permissions = Some(permissions & 0o7777);
+        self
+    }
+
+    /// The type of the entry.
+    pub fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    /// The size of the entry in bytes.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Whether the entry has no contents.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// When the entry was last modified, if known.
+    pub fn modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+
+    /// The entry's permission bits, as in a Unix file mode, if known.
+    pub fn permissions(&self) -> Option<u32> {
+        self.permissions
+    }
+}
+
+impl From<std::fs::Metadata> for Metadata {
+    fn from(metadata: std::fs::Metadata) -> Self {
+        #[cfg(unix)]
+        let permissions = {
+            use std::os::unix::fs::PermissionsExt as _;
+            Some(metadata.permissions().mode() & 0o7777)
+        };
+        #[cfg(not(unix))]
+        let permissions = None;
+        Self {
+            file_type: metadata.file_type().into(),
+            len: metadata.len(),
+            modified: metadata.modified().ok(),
+            permissions,
+        }
+    }
+}
+
+/// A directory on the local filesystem.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FsSource {
+    root: PathBuf,
+}
+
+impl FsSource {
+    /// The directory at `root`.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, path: &Path) -> PathBuf {
+        if path.as_os_str().is_empty() {
+            self.root.clone()
+        } else {
+            self.root.join(path)
+        }
+    }
+}
+
+impl TreeSource for FsSource {
+    fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<SourceEntry>, Error> {
+        let mut entries = Vec::new();
+        for entry in WalkDir::new(self.path(path)).min_depth(1).max_depth(1) {
+            let entry = entry?;
+            entries.push(SourceEntry::new(
+                entry.file_name(),
+                entry.file_type().into(),
+            ));
+        }
+        Ok(entries)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, Error> {
+        let metadata = if path.as_os_str().is_empty() {
+            std::fs::metadata(&self.root)?
+        } else {
+            std::fs::symlink_metadata(self.root.join(path))?
+        };
+        Ok(metadata.into())
+    }
+
+    fn open(&self, path: &Path) -> Result<Box<dyn Read + '_>, Error> {
+        Ok(Box::new(std::fs::File::open(self.path(path))?))
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf, Error> {
+        Ok(std::fs::read_link(self.path(path))?)
+    }
+
+    fn local_path(&self, path: &Path) -> Option<PathBuf> {
+        Some(self.path(path))
+    }
+
+    fn lists_local_dirs(&self) -> bool {
+        true
+    }
+}
@@ -0,0 +1,145 @@
+//! Comparing a prefix in an [`ObjectStore`].
+
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use ::object_store::path::Path as StorePath;
+use ::object_store::ObjectStore;
+use futures_util::TryStreamExt as _;
+
+use crate::source::{FileType, Metadata, SourceEntry, TreeSource};
+use crate::Error;
+
+/// The objects under a prefix in an [`ObjectStore`], such as S3, GCS or Azure Blob Storage.
+///
+/// Object keys are split on `/` into directories.  The objects under the prefix are listed once,
+/// when the source is created, and each object is fetched when its contents are compared, so a
+/// remote tree can be checked without downloading it first.
+///
+/// Requests are made on a private single-threaded `tokio` runtime, so the comparison must not be
+/// run from within another `tokio` runtime.
+///
+/// # Examples
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// use dir_diff::source::{FsSource, ObjectStoreSource};
+/// use object_store::memory::InMemory;
+///
+/// let store = Arc::new(InMemory::new());
+/// let uploaded = ObjectStoreSource::new(store, "artifacts/v1".into()).unwrap();
+/// let report = dir_diff::DirDiffOptions::new()
+///     .diff_sources(uploaded, FsSource::new("target/dist"))
+///     .unwrap();
+/// assert!(!report.is_different());
+/// ```
+pub struct ObjectStoreSource {
+    store: Arc<dyn ObjectStore>,
+    root: PathBuf,
+    runtime: tokio::runtime::Runtime,
+    dirs: HashMap<PathBuf, BTreeMap<OsString, FileType>>,
+    files: HashMap<PathBuf, (StorePath, Metadata)>,
+}
+
+impl ObjectStoreSource {
+    /// List the objects in `store` under `prefix`.
+    ///
+    /// An object whose key is also the prefix of other objects is treated as a directory.
+    pub fn new(store: Arc<dyn ObjectStore>, prefix: StorePath) -> Result<Self, Error> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let objects: Vec<_> = runtime
+            .block_on(store.list(Some(&prefix)).try_collect())
+            .map_err(std::io::Error::from)?;
+
+        let mut dirs: HashMap<PathBuf, BTreeMap<OsString, FileType>> = HashMap::new();
+        dirs.insert(PathBuf::new(), BTreeMap::new());
+        let mut files = HashMap::new();
+        for object in objects {
+            let Some(parts) = object.location.prefix_match(&prefix) else {
+                continue;
+            };
+            let relative: PathBuf = parts.map(|part| part.as_ref().to_owned()).collect();
+            let mut parent = PathBuf::new();
+            for component in relative.iter() {
+                let path = parent.join(component);
+                let file_type = if path == relative {
+                    FileType::File
+                } else {
+                    FileType::Dir
+                };
+                let siblings = dirs.entry(parent).or_default();
+                let existing = siblings.entry(component.to_owned()).or_insert(file_type);
+                if file_type == FileType::Dir {
+                    *existing = FileType::Dir;
+                }
+                parent = path;
+            }
+
+            let metadata = Metadata::new(FileType::File, object.size)
+                .with_modified(SystemTime::from(object.last_modified));
+            files.insert(relative, (object.location, metadata));
+        }
+
+        Ok(Self {
+            store,
+            root: PathBuf::from(prefix.as_ref()),
+            runtime,
+            dirs,
+            files,
+        })
+    }
+
+    fn not_found(path: &Path) -> Error {
+        std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("no object or prefix `{}`", path.display()),
+        )
+        .into()
+    }
+}
+
+impl TreeSource for ObjectStoreSource {
+    fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<SourceEntry>, Error> {
+        let entries = self.dirs.get(path).ok_or_else(|| Self::not_found(path))?;
+        Ok(entries
+            .iter()
+            .map(|(name, file_type)| SourceEntry::new(name.clone(), *file_type))
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, Error> {
+        if self.dirs.contains_key(path) {
+            return Ok(Metadata::new(FileType::Dir, 0));
+        }
+        let (_, metadata) = self.files.get(path).ok_or_else(|| Self::not_found(path))?;
+        Ok(metadata.clone())
+    }
+
+    fn open(&self, path: &Path) -> Result<Box<dyn Read + '_>, Error> {
+        let (location, _) = self.files.get(path).ok_or_else(|| Self::not_found(path))?;
+        let contents = self
+            .runtime
+            .block_on(async { self.store.get(location).await?.bytes().await })
+            .map_err(std::io::Error::from)?;
+        Ok(Box::new(std::io::Cursor::new(contents)))
+    }
+}
+
+impl std::fmt::Debug for ObjectStoreSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ObjectStoreSource")
+            .field("store", &self.store)
+            .field("root", &self.root)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,121 @@
+//! Comparing several trees layered on top of each other, as with an overlay filesystem.
+
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::source::{FileType, Metadata, SourceEntry, TreeSource};
+use crate::Error;
+
+/// The union of several trees, with later layers overriding earlier ones.
+///
+/// An entry is read from the last layer that has it.  A directory lists the entries of every
+/// layer that has a directory there, down to the first layer with something else in its place,
+/// which hides the layers below.  Whiteouts, for deleting entries of lower layers, aren't
+/// supported.
+///
+/// The root reported is the last layer's.
+///
+/// # Examples
+///
+/// ```no_run
+/// use dir_diff::source::{FsSource, OverlaySource};
+///
+/// let layered = OverlaySource::new([
+///     FsSource::new("fixtures/base"),
+///     FsSource::new("fixtures/patch"),
+/// ]);
+/// let report = dir_diff::DirDiffOptions::new()
+///     .diff_sources(layered, FsSource::new("target/flattened"))
+///     .unwrap();
+/// assert!(!report.is_different());
+/// ```
+#[derive(Clone, Debug)]
+pub struct OverlaySource<S> {
+    layers: Vec<S>,
+}
+
+impl<S: TreeSource> OverlaySource<S> {
+    /// The union of `layers`, from the bottom up.
+    pub fn new(layers: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            layers: layers.into_iter().collect(),
+        }
+    }
+
+    /// The last layer with an entry at `path`, and its metadata.
+    fn top(&self, path: &Path) -> Result<(&S, Metadata), Error> {
+        for layer in self.layers.iter().rev() {
+            match layer.metadata(path) {
+                Ok(metadata) => return Ok((layer, metadata)),
+                Err(err) if is_not_found(&err) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Err(std::io::Error::new(
+            std::io::ErrorKind::NotFound,
+            format!("no layer has `{}`", path.display()),
+        )
+        .into())
+    }
+}
+
+impl<S: TreeSource> TreeSource for OverlaySource<S> {
+    fn root(&self) -> &Path {
+        self.layers
+            .last()
+            .map(|layer| layer.root())
+            .unwrap_or(Path::new(""))
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<SourceEntry>, Error> {
+        let mut visible = Vec::new();
+        for layer in self.layers.iter().rev() {
+            match layer.metadata(path) {
+                Ok(metadata) if metadata.file_type() == FileType::Dir => visible.push(layer),
+                Ok(_) => break,
+                Err(err) if is_not_found(&err) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        if visible.is_empty() {
+            // For the error the caller expects.
+            self.top(path)?;
+        }
+
+        let mut entries = BTreeMap::new();
+        for layer in visible.into_iter().rev() {
+            for entry in layer.read_dir(path)? {
+                entries.insert(entry.name().to_owned(), entry);
+            }
+        }
+        Ok(entries.into_values().collect())
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, Error> {
+        Ok(self.top(path)?.1)
+    }
+
+    fn open(&self, path: &Path) -> Result<Box<dyn Read + '_>, Error> {
+        self.top(path)?.0.open(path)
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf, Error> {
+        self.top(path)?.0.read_link(path)
+    }
+
+    fn local_path(&self, path: &Path) -> Option<PathBuf> {
+        // A directory is the union of the layers, so it isn't anywhere on its own.
+        let (layer, metadata) = self.top(path).ok()?;
+        if metadata.file_type() == FileType::Dir {
+            return None;
+        }
+        layer.local_path(path)
+    }
+}
+
+fn is_not_found(err: &Error) -> bool {
+    err.io_error()
+        .map(|err| err.kind() == std::io::ErrorKind::NotFound)
+        .unwrap_or(false)
+}
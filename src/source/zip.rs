@@ -0,0 +1,173 @@
+//! Comparing the members of a zip archive, such as a jar.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::io::{Cursor, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use ::zip::result::ZipError;
+use ::zip::ZipArchive;
+
+use crate::source::{FileType, Metadata, SourceEntry, TreeSource};
+use crate::Error;
+
+/// The members of a zip archive, as a tree.
+///
+/// Member timestamps aren't recorded, so archives built from the same files at different times
+/// compare equal.  Directories are implied by the members' paths, whether or not the archive has
+/// entries for them, and members with unsafe paths, like ones containing `..`, are skipped.
+///
+/// # Examples
+///
+/// ```no_run
+/// use dir_diff::source::ZipSource;
+///
+/// let report = dir_diff::DirDiffOptions::new()
+///     .diff_sources(
+///         ZipSource::open("target/app.jar").unwrap(),
+///         ZipSource::open("golden/app.jar").unwrap(),
+///     )
+///     .unwrap();
+/// assert!(!report.is_different());
+/// ```
+pub struct ZipSource {
+    root: PathBuf,
+    archive: Mutex<ZipArchive<Cursor<Vec<u8>>>>,
+    dirs: HashMap<PathBuf, BTreeMap<OsString, FileType>>,
+    files: HashMap<PathBuf, (usize, Metadata)>,
+}
+
+impl ZipSource {
+    /// Read the archive at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        Self::from_bytes(path, std::fs::read(path)?)
+    }
+
+    /// The archive in `contents`, reported as being at `root`.
+    pub fn from_bytes(root: impl Into<PathBuf>, contents: Vec<u8>) -> Result<Self, Error> {
+        let mut archive = ZipArchive::new(Cursor::new(contents)).map_err(into_error)?;
+        let mut dirs: HashMap<PathBuf, BTreeMap<OsString, FileType>> = HashMap::new();
+        dirs.insert(PathBuf::new(), BTreeMap::new());
+        let mut files = HashMap::new();
+        for index in 0..archive.len() {
+            let member = archive.by_index_raw(index).map_err(into_error)?;
+            let Some(relative) = member.enclosed_name().map(Path::to_owned) else {
+                continue;
+            };
+            let file_type = if member.is_dir() {
+                FileType::Dir
+            } else if member.unix_mode().map(|mode| mode & 0o170000) == Some(0o120000) {
+                FileType::Symlink
+            } else {
+                FileType::File
+            };
+            let mut parent = PathBuf::new();
+            for component in relative.iter() {
+                let path = parent.join(component);
+                let file_type = if path == relative {
+                    file_type
+                } else {
+                    FileType::Dir
+                };
+                let siblings = dirs.entry(parent).or_default();
+                let existing = siblings.entry(component.to_owned()).or_insert(file_type);
+                if file_type == FileType::Dir {
+                    *existing = FileType::Dir;
+                }
+                parent = path;
+            }
+
+            if file_type == FileType::Dir {
+                dirs.entry(relative).or_default();
+            } else {
+                let mut metadata = Metadata::new(file_type, member.size());
+                if let Some(mode) = member.unix_mode() {
+                    metadata = metadata.with_permissions(mode);
+                }
+                files.insert(relative, (index, metadata));
+            }
+        }
+
+        Ok(Self {
+            root: root.into(),
+            archive: Mutex::new(archive),
+            dirs,
+            files,
+        })
+    }
+
+    fn member(&self, path: &Path) -> Result<&(usize, Metadata), Error> {
+        self.files.get(path).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no member `{}`", path.display()),
+            )
+            .into()
+        })
+    }
+
+    fn read(&self, path: &Path) -> Result<Vec<u8>, Error> {
+        let (index, metadata) = self.member(path)?;
+        let mut archive = self.archive.lock().unwrap_or_else(|err| err.into_inner());
+        let mut member = archive.by_index(*index).map_err(into_error)?;
+        let mut contents = Vec::with_capacity(metadata.len() as usize);
+        member.read_to_end(&mut contents)?;
+        Ok(contents)
+    }
+}
+
+impl TreeSource for ZipSource {
+    fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<SourceEntry>, Error> {
+        let entries = self.dirs.get(path).ok_or_else(|| {
+            std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("no directory `{}`", path.display()),
+            )
+        })?;
+        Ok(entries
+            .iter()
+            .map(|(name, file_type)| SourceEntry::new(name.clone(), *file_type))
+            .collect())
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, Error> {
+        if self.dirs.contains_key(path) {
+            return Ok(Metadata::new(FileType::Dir, 0));
+        }
+        let (_, metadata) = self.member(path)?;
+        Ok(metadata.clone())
+    }
+
+    fn open(&self, path: &Path) -> Result<Box<dyn Read + '_>, Error> {
+        Ok(Box::new(Cursor::new(self.read(path)?)))
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf, Error> {
+        let target = String::from_utf8(self.read(path)?)
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?;
+        Ok(target.into())
+    }
+}
+
+impl std::fmt::Debug for ZipSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ZipSource")
+            .field("root", &self.root)
+            .finish_non_exhaustive()
+    }
+}
+
+fn into_error(err: ZipError) -> Error {
+    let err = match err {
+        ZipError::Io(err) => err,
+        ZipError::FileNotFound => std::io::Error::new(std::io::ErrorKind::NotFound, err),
+        _ => std::io::Error::new(std::io::ErrorKind::InvalidData, err),
+    };
+    err.into()
+}
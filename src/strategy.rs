@@ -0,0 +1,101 @@
+//! Strategies generating random trees, for property-testing comparisons with `proptest`.
+//!
+//! The trees are [`MemorySource`]s built from a handful of names, so the same paths come up
+//! often, as files in one tree and directories in another.
+//!
+//! # Examples
+//!
+//! ```
+//! use proptest::prelude::*;
+//!
+//! proptest!(|((left, right) in dir_diff::strategy::tree_pair())| {
+//!     let options = dir_diff::DirDiffOptions::new();
+//!     let report = options.diff_sources(&left, &right).unwrap();
+//!     let different = options.is_different_sources(&left, &right).unwrap();
+//!     prop_assert_eq!(report.is_different(), different);
+//! });
+//! ```
+
+use std::path::PathBuf;
+
+use proptest::collection::vec;
+use proptest::prop_oneof;
+use proptest::sample::select;
+use proptest::strategy::{Just, Strategy};
+
+use crate::source::MemorySource;
+
+/// The names entries are built from.
+const NAMES: &[&str] = &["a", "b", "c.txt", "d.txt"];
+
+/// The most entries added to a tree.
+const MAX_ENTRIES: usize = 16;
+
+/// The most changes between the trees of a pair.
+const MAX_CHANGES: usize = 4;
+
+#[derive(Clone, Debug)]
+enum Change {
+    File(PathBuf, Vec<u8>),
+    Dir(PathBuf),
+    Symlink(PathBuf, PathBuf),
+    Remove(PathBuf),
+}
+
+/// Random trees of files, directories and symlinks, reported as `memory`.
+pub fn tree() -> impl Strategy<Value = MemorySource> {
+    vec(entry(), 0..=MAX_ENTRIES).prop_map(|entries| apply(MemorySource::new("memory"), entries))
+}
+
+/// Random trees, reported as `left`, each with a copy that has up to a few entries added,
+/// replaced or removed, reported as `right`.
+///
+/// The copy is sometimes the same as the tree, when there are no changes or they undo each
+/// other.
+pub fn tree_pair() -> impl Strategy<Value = (MemorySource, MemorySource)> {
+    (
+        vec(entry(), 0..=MAX_ENTRIES),
+        vec(change(), 0..=MAX_CHANGES),
+    )
+        .prop_map(|(entries, changes)| {
+            let left = apply(MemorySource::new("left"), entries.clone());
+            let right = apply(apply(MemorySource::new("right"), entries), changes);
+            (left, right)
+        })
+}
+
+fn path() -> impl Strategy<Value = PathBuf> {
+    vec(select(NAMES), 1..=3).prop_map(|names| names.iter().collect())
+}
+
+fn contents() -> impl Strategy<Value = Vec<u8>> {
+    prop_oneof![Just(Vec::new()), vec(select(&b"ab\n"[..]), 1..8)]
+}
+
+fn entry() -> impl Strategy<Value = Change> {
+    prop_oneof![
+        4 => (path(), contents()).prop_map(|(path, contents)| Change::File(path, contents)),
+        1 => path().prop_map(Change::Dir),
+        1 => (path(), path()).prop_map(|(path, target)| Change::Symlink(path, target)),
+    ]
+}
+
+fn change() -> impl Strategy<Value = Change> {
+    prop_oneof![
+        3 => entry(),
+        1 => path().prop_map(Change::Remove),
+    ]
+}
+
+fn apply(tree: MemorySource, changes: Vec<Change>) -> MemorySource {
+    let mut tree = tree;
+    for change in changes {
+        tree = match change {
+            Change::File(path, contents) => tree.file(path, contents),
+            Change::Dir(path) => tree.dir(path),
+            Change::Symlink(path, target) => tree.symlink(path, target),
+            Change::Remove(path) => tree.remove(path),
+        };
+    }
+    tree
+}
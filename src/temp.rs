@@ -0,0 +1,180 @@
+//! Comparing directories inside temporary directories, reported relative to them.
+
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::report::DiffReport;
+use crate::source::{FsSource, Metadata, SourceEntry, TreeSource};
+use crate::{DirDiffOptions, Error};
+
+/// A temporary directory that comparisons can be run in, with [`diff_temp`].
+///
+/// Implemented for `tempfile::TempDir` with the `tempfile` feature, and for
+/// `assert_fs::TempDir` with the `assert_fs` feature.
+pub trait TempRoot {
+    /// Where the temporary directory is.
+    fn temp_root(&self) -> &Path;
+}
+
+#[cfg(feature = "tempfile")]
+impl TempRoot for tempfile::TempDir {
+    fn temp_root(&self) -> &Path {
+        self.path()
+    }
+}
+
+#[cfg(feature = "assert_fs")]
+impl TempRoot for assert_fs::TempDir {
+    fn temp_root(&self) -> &Path {
+        self.path()
+    }
+}
+
+impl<T: TempRoot + ?Sized> TempRoot for &T {
+    fn temp_root(&self) -> &Path {
+        (**self).temp_root()
+    }
+}
+
+/// Find every difference between two directories, where they are in `temp`.
+///
+/// See [`DirDiffOptions::diff_temp`].
+///
+/// # Examples
+///
+/// ```no_run
+/// # #[cfg(feature = "tempfile")] {
+/// extern crate dir_diff;
+///
+/// let temp = tempfile::tempdir().unwrap();
+/// std::fs::create_dir_all(temp.path().join("out")).unwrap();
+/// let golden = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+/// let report = dir_diff::diff_temp(&temp, "out", golden).unwrap();
+/// // Prints `out (-) vs /…/tests/golden (+)`, and the differences.
+/// println!("{report}");
+/// # }
+/// ```
+pub fn diff_temp<T: TempRoot + ?Sized, A: AsRef<Path>, B: AsRef<Path>>(
+    temp: &T,
+    a_base: A,
+    b_base: B,
+) -> Result<DiffReport, Error> {
+    DirDiffOptions::new().diff_temp(temp, a_base, b_base)
+}
+
+impl DirDiffOptions {
+    /// Find every difference between two directories, where they are in `temp`.
+    ///
+    /// Relative paths are taken from the root of `temp`, and paths under it are reported
+    /// relative to it, so reports and errors don't start with a random temporary path.  Paths
+    /// outside of `temp`, like a golden tree in the crate, are taken and reported as they are.
+    /// The roots of the report are then relative to `temp`, so [`DiffReport::resolve`] gives
+    /// paths to join onto it.
+    ///
+    /// Reads the trees as [`DirDiffOptions::diff_sources`] does, so
+    /// [`DirDiffOptions::snapshot`] doesn't apply.
+    pub fn diff_temp<T: TempRoot + ?Sized, A: AsRef<Path>, B: AsRef<Path>>(
+        &self,
+        temp: &T,
+        a_base: A,
+        b_base: B,
+    ) -> Result<DiffReport, Error> {
+        let root = temp.temp_root();
+        self.diff_sources(
+            TempSource::new(root, a_base.as_ref()),
+            TempSource::new(root, b_base.as_ref()),
+        )
+    }
+
+    /// Are the contents of two directories, where they are in `temp`, different?
+    ///
+    /// Stops at the first difference found, as [`DirDiffOptions::is_different`] does.  Paths are
+    /// taken as by [`DirDiffOptions::diff_temp`].
+    pub fn is_different_temp<T: TempRoot + ?Sized, A: AsRef<Path>, B: AsRef<Path>>(
+        &self,
+        temp: &T,
+        a_base: A,
+        b_base: B,
+    ) -> Result<bool, Error> {
+        let root = temp.temp_root();
+        self.is_different_sources(
+            TempSource::new(root, a_base.as_ref()),
+            TempSource::new(root, b_base.as_ref()),
+        )
+    }
+}
+
+/// A directory on the local filesystem, reported relative to the temporary directory it is in.
+#[derive(Debug)]
+struct TempSource {
+    /// The root as reported.
+    label: PathBuf,
+    source: FsSource,
+}
+
+impl TempSource {
+    fn new(temp: &Path, path: &Path) -> Self {
+        let path = temp.join(path);
+        let label = match path.strip_prefix(temp) {
+            Ok(relative) if relative.as_os_str().is_empty() => PathBuf::from("."),
+            Ok(relative) => relative.to_owned(),
+            Err(_) => path.clone(),
+        };
+        Self {
+            label,
+            source: FsSource::new(path),
+        }
+    }
+
+    /// Point errors at the reported root.
+    fn relabel(&self, err: Error) -> Error {
+        match err {
+            Error::Io {
+                source,
+                phase,
+                path: Some(path),
+            } => {
+                let path = match path.strip_prefix(self.source.root()) {
+                    Ok(relative) => crate::source::full_path(self, relative),
+                    Err(_) => path,
+                };
+                Error::Io {
+                    source,
+                    phase,
+                    path: Some(path),
+                }
+            }
+            err => err,
+        }
+    }
+}
+
+impl TreeSource for TempSource {
+    fn root(&self) -> &Path {
+        &self.label
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<SourceEntry>, Error> {
+        self.source.read_dir(path).map_err(|err| self.relabel(err))
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, Error> {
+        self.source.metadata(path).map_err(|err| self.relabel(err))
+    }
+
+    fn open(&self, path: &Path) -> Result<Box<dyn Read + '_>, Error> {
+        self.source.open(path).map_err(|err| self.relabel(err))
+    }
+
+    fn read_link(&self, path: &Path) -> Result<PathBuf, Error> {
+        self.source.read_link(path).map_err(|err| self.relabel(err))
+    }
+
+    fn local_path(&self, path: &Path) -> Option<PathBuf> {
+        self.source.local_path(path)
+    }
+
+    fn lists_local_dirs(&self) -> bool {
+        self.source.lists_local_dirs()
+    }
+}
@@ -0,0 +1,227 @@
+//! Normalizing text files before comparing them, so differences that don't matter are ignored.
+
+use std::borrow::Cow;
+
+/// How [`DirDiffOptions::normalize_encoding`][crate::DirDiffOptions::normalize_encoding] treats
+/// the encoding of files.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum EncodingNormalization {
+    /// Ignore a UTF-8 byte order mark at the start of a file.
+    StripBom,
+    /// Ignore a UTF-8 byte order mark, and decode files starting with a UTF-16 byte order mark,
+    /// either little or big endian, to UTF-8.
+    ///
+    /// A UTF-16 file that doesn't decode is compared as it is.
+    TranscodeUtf16,
+}
+
+/// Which whitespace [`DirDiffOptions::ignore_whitespace`][crate::DirDiffOptions::ignore_whitespace]
+/// ignores.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Whitespace {
+    /// Ignore whitespace at the end of each line, including the `\r` of Windows line endings.
+    Trailing,
+    /// Ignore changes in the amount of whitespace, treating each run of spaces and tabs as a
+    /// single space, as well as whitespace at the end of each line, like `diff -b`.
+    Runs,
+}
+
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+const UTF16_LE_BOM: &[u8] = b"\xFF\xFE";
+const UTF16_BE_BOM: &[u8] = b"\xFE\xFF";
+
+/// What to normalize in a file before comparing it.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct TextRules {
+    pub(crate) encoding: Option<EncodingNormalization>,
+    pub(crate) whitespace: Option<Whitespace>,
+    /// Whether to compare the lines in any order.
+    pub(crate) sort_lines: bool,
+}
+
+impl TextRules {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.encoding.is_none() && self.whitespace.is_none() && !self.sort_lines
+    }
+
+    /// The contents of a file, as they are compared under these rules.
+    pub(crate) fn normalize<'a>(&self, contents: &'a [u8]) -> Cow<'a, [u8]> {
+        let decoded = self.decode(contents);
+        let normalized = match self.whitespace {
+            Some(whitespace) => Cow::Owned(normalize_whitespace(&decoded, whitespace)),
+            None => decoded,
+        };
+        if self.sort_lines {
+            Cow::Owned(sort_lines(&normalized))
+        } else {
+            normalized
+        }
+    }
+
+    fn decode<'a>(&self, contents: &'a [u8]) -> Cow<'a, [u8]> {
+        match self.encoding {
+            Some(EncodingNormalization::StripBom) => Cow::Borrowed(strip_bom(contents)),
+            Some(EncodingNormalization::TranscodeUtf16) => match decode_utf16(contents) {
+                Some(decoded) => Cow::Owned(decoded.into_bytes()),
+                None => Cow::Borrowed(strip_bom(contents)),
+            },
+            None => Cow::Borrowed(contents),
+        }
+    }
+}
+
+fn normalize_whitespace(contents: &[u8], whitespace: Whitespace) -> Vec<u8> {
+    let mut normalized = Vec::with_capacity(contents.len());
+    let mut lines = contents.split(|&byte| byte == b'\n').peekable();
+    while let Some(line) = lines.next() {
+        let line = match line.iter().rposition(|byte| !byte.is_ascii_whitespace()) {
+            Some(last) => &line[..=last],
+            None => &[],
+        };
+        match whitespace {
+            Whitespace::Trailing => normalized.extend_from_slice(line),
+            Whitespace::Runs => {
+                let mut in_run = false;
+                for &byte in line {
+                    let blank = byte == b' ' || byte == b'\t';
+                    if !(blank && in_run) {
+                        normalized.push(if blank { b' ' } else { byte });
+                    }
+                    in_run = blank;
+                }
+            }
+        }
+        if lines.peek().is_some() {
+            normalized.push(b'\n');
+        }
+    }
+    normalized
+}
+
+/// The lines of `contents` in sorted order, each ended by a newline, so a missing newline at the
+/// end doesn't matter either.
+fn sort_lines(contents: &[u8]) -> Vec<u8> {
+    let contents = contents.strip_suffix(b"\n").unwrap_or(contents);
+    let mut lines: Vec<_> = contents.split(|&byte| byte == b'\n').collect();
+    if contents.is_empty() {
+        lines.clear();
+    }
+    lines.sort_unstable();
+    let mut sorted = Vec::with_capacity(contents.len() + 1);
+    for line in lines {
+        sorted.extend_from_slice(line);
+        sorted.push(b'\n');
+    }
+    sorted
+}
+
+/// Whether two files hold the same text, each in UTF-8 or UTF-16 with a byte order mark, or
+/// failing those, Latin-1.
+pub(crate) fn same_text(left: &[u8], right: &[u8]) -> bool {
+    decode_any(left) == decode_any(right)
+}
+
+fn decode_any(contents: &[u8]) -> Cow<'_, str> {
+    if let Some(text) = decode_utf16(contents) {
+        return Cow::Owned(text);
+    }
+    match std::str::from_utf8(strip_bom(contents)) {
+        Ok(text) => Cow::Borrowed(text),
+        // Every byte is a Latin-1 character, with the same code point.
+        Err(_) => Cow::Owned(contents.iter().copied().map(char::from).collect()),
+    }
+}
+
+fn strip_bom(contents: &[u8]) -> &[u8] {
+    contents.strip_prefix(UTF8_BOM).unwrap_or(contents)
+}
+
+/// The text of a file with a UTF-16 byte order mark, or `None` if it hasn't one or doesn't
+/// decode.
+fn decode_utf16(contents: &[u8]) -> Option<String> {
+    let (units, big_endian) = if let Some(units) = contents.strip_prefix(UTF16_LE_BOM) {
+        (units, false)
+    } else if let Some(units) = contents.strip_prefix(UTF16_BE_BOM) {
+        (units, true)
+    } else {
+        return None;
+    };
+    if units.len() % 2 != 0 {
+        return None;
+    }
+    let units = units.chunks_exact(2).map(|unit| {
+        if big_endian {
+            u16::from_be_bytes([unit[0], unit[1]])
+        } else {
+            u16::from_le_bytes([unit[0], unit[1]])
+        }
+    });
+    char::decode_utf16(units).collect::<Result<_, _>>().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize() {
+        let strip = TextRules {
+            encoding: Some(EncodingNormalization::StripBom),
+            whitespace: None,
+            sort_lines: false,
+        };
+        assert_eq!(strip.normalize(b"\xEF\xBB\xBFtext"), &b"text"[..]);
+        assert_eq!(strip.normalize(b"text"), &b"text"[..]);
+        assert_eq!(strip.normalize(b"\xFF\xFEt\0"), &b"\xFF\xFEt\0"[..]);
+
+        let transcode = TextRules {
+            encoding: Some(EncodingNormalization::TranscodeUtf16),
+            whitespace: None,
+            sort_lines: false,
+        };
+        assert_eq!(transcode.normalize(b"\xFF\xFEt\0\xe9\0"), "té".as_bytes());
+        assert_eq!(transcode.normalize(b"\xFE\xFF\0t\0\xe9"), "té".as_bytes());
+        assert_eq!(transcode.normalize(b"\xEF\xBB\xBFt"), &b"t"[..]);
+        // An odd length or unpaired surrogate isn't UTF-16.
+        assert_eq!(transcode.normalize(b"\xFF\xFEt"), &b"\xFF\xFEt"[..]);
+        assert_eq!(
+            transcode.normalize(b"\xFF\xFE\0\xD8"),
+            &b"\xFF\xFE\0\xD8"[..]
+        );
+
+        assert_eq!(TextRules::default().normalize(b"\xEF\xBB\xBF"), UTF8_BOM);
+    }
+
+    #[test]
+    fn test_normalize_whitespace() {
+        let text = b"a  b\t \r\n\tc \n\n";
+        assert_eq!(
+            normalize_whitespace(text, Whitespace::Trailing),
+            b"a  b\n\tc\n\n"
+        );
+        assert_eq!(normalize_whitespace(text, Whitespace::Runs), b"a b\n c\n\n");
+        assert_eq!(normalize_whitespace(b"", Whitespace::Runs), b"");
+    }
+
+    #[test]
+    fn test_same_text() {
+        assert!(same_text("café".as_bytes(), b"caf\xe9"));
+        assert!(same_text(
+            b"\xEF\xBB\xBFcaf\xC3\xA9",
+            b"\xFF\xFEc\0a\0f\0\xe9\0"
+        ));
+        assert!(!same_text("café".as_bytes(), b"cafe"));
+        assert!(!same_text(b"\xe9", b"\xe8"));
+    }
+
+    #[test]
+    fn test_sort_lines() {
+        assert_eq!(sort_lines(b"b\na\nb\n"), b"a\nb\nb\n");
+        assert_eq!(sort_lines(b"b\na"), b"a\nb\n");
+        assert_eq!(sort_lines(b"\n\nx"), b"\n\nx\n");
+        assert_eq!(sort_lines(b""), b"");
+        assert_eq!(sort_lines(b"\n"), b"");
+    }
+}
@@ -0,0 +1,312 @@
+//! Line-based text diffs of differing files.
+
+use std::fmt::Write as _;
+use std::ops::Range;
+
+#[cfg(feature = "html")]
+use crate::report::html::DiffAlgorithm;
+
+/// A line of a text diff.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Line<'a> {
+    /// The line is present on both sides.
+    Equal(&'a str),
+    /// The line is only present on the left.
+    Removed(&'a str),
+    /// The line is only present on the right.
+    Added(&'a str),
+}
+
+impl Line<'_> {
+    pub(crate) fn is_change(&self) -> bool {
+        !matches!(self, Line::Equal(_))
+    }
+}
+
+/// Diff two texts line by line with `algorithm`.
+#[cfg(feature = "html")]
+pub(crate) fn diff_lines_with<'a>(
+    left: &'a str,
+    right: &'a str,
+    algorithm: DiffAlgorithm,
+) -> Vec<Line<'a>> {
+    match algorithm {
+        DiffAlgorithm::Myers => diff_lines(left, right),
+        #[cfg(feature = "similar")]
+        DiffAlgorithm::Patience => diff_lines_similar(left, right, similar::Algorithm::Patience),
+        #[cfg(feature = "similar")]
+        DiffAlgorithm::Lcs => diff_lines_similar(left, right, similar::Algorithm::Lcs),
+    }
+}
+
+#[cfg(feature = "similar")]
+fn diff_lines_similar<'a>(
+    left: &'a str,
+    right: &'a str,
+    algorithm: similar::Algorithm,
+) -> Vec<Line<'a>> {
+    let diff = similar::TextDiff::configure()
+        .algorithm(algorithm)
+        .diff_lines(left, right);
+    diff.iter_all_changes()
+        .map(|change| {
+            // Like `str::lines`, drop the line ending.
+            let line = change.value();
+            let line = line.strip_suffix('\n').unwrap_or(line);
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            match change.tag() {
+                similar::ChangeTag::Equal => Line::Equal(line),
+                similar::ChangeTag::Delete => Line::Removed(line),
+                similar::ChangeTag::Insert => Line::Added(line),
+            }
+        })
+        .collect()
+}
+
+/// Diff two texts line by line with Myers' algorithm.
+#[cfg(feature = "html")]
+pub(crate) fn diff_lines<'a>(left: &'a str, right: &'a str) -> Vec<Line<'a>> {
+    let left: Vec<_> = left.lines().collect();
+    let right: Vec<_> = right.lines().collect();
+    diff_slices(&left, &right)
+}
+
+/// Diff two sequences of lines with Myers' algorithm.
+pub(crate) fn diff_slices<'a>(a: &[&'a str], b: &[&'a str]) -> Vec<Line<'a>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = n + m;
+    let index = |k: isize| (k + max) as usize;
+
+    // `v[k]` is the furthest `x` reached on diagonal `k = x - y`; `trace[d]` is `v` before
+    // searching with `d` edits.
+    let mut v = vec![0_isize; 2 * max as usize + 2];
+    let mut trace = Vec::new();
+    'search: for d in 0..=max {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+                v[index(k + 1)]
+            } else {
+                v[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index(k)] = x;
+            if n <= x && m <= y {
+                break 'search;
+            }
+        }
+    }
+
+    let mut lines = Vec::with_capacity(a.len().max(b.len()));
+    let (mut x, mut y) = (n, m);
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[index(prev_k)];
+        let prev_y = prev_x - prev_k;
+        while prev_x < x && prev_y < y {
+            lines.push(Line::Equal(a[x as usize - 1]));
+            x -= 1;
+            y -= 1;
+        }
+        if 0 < d {
+            if x == prev_x {
+                lines.push(Line::Added(b[y as usize - 1]));
+            } else {
+                lines.push(Line::Removed(a[x as usize - 1]));
+            }
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    lines.reverse();
+    lines
+}
+
+/// The number of lines removed from `left` and added from `right` by the shortest line diff
+/// between them.
+///
+/// The same search as [`diff_lines`], without keeping what is needed to recover the diff.
+pub(crate) fn edit_distance(left: &[&str], right: &[&str]) -> usize {
+    let (n, m) = (left.len() as isize, right.len() as isize);
+    let max = n + m;
+    let index = |k: isize| (k + max) as usize;
+    let mut v = vec![0_isize; 2 * max as usize + 2];
+    for d in 0..=max {
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[index(k - 1)] < v[index(k + 1)]) {
+                v[index(k + 1)]
+            } else {
+                v[index(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && left[x as usize] == right[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index(k)] = x;
+            if n <= x && m <= y {
+                return d as usize;
+            }
+        }
+    }
+    unreachable!("removing every line and adding every line is always a diff")
+}
+
+/// Group lines into hunks of changes surrounded by up to `context` unchanged lines.
+#[cfg(feature = "html")]
+pub(crate) fn hunks<'d, 'a>(lines: &'d [Line<'a>], context: usize) -> Vec<&'d [Line<'a>]> {
+    hunk_ranges(lines, context)
+        .into_iter()
+        .map(|range| &lines[range])
+        .collect()
+}
+
+/// The ranges of `lines` holding hunks of changes surrounded by up to `context` unchanged lines.
+pub(crate) fn hunk_ranges(lines: &[Line<'_>], context: usize) -> Vec<Range<usize>> {
+    let mut hunks: Vec<Range<usize>> = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if !line.is_change() {
+            continue;
+        }
+        let start = i.saturating_sub(context);
+        let end = (i + context + 1).min(lines.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.end => last.end = end,
+            _ => hunks.push(start..end),
+        }
+    }
+    hunks
+}
+
+/// The hunks of a unified diff from `old` to `new`, with up to `context` unchanged lines around
+/// each change, without the file headers.  Empty when the texts are the same.
+pub(crate) fn unified(old: &str, new: &str, context: usize) -> String {
+    let old_lines: Vec<_> = old.split_inclusive('\n').collect();
+    let new_lines: Vec<_> = new.split_inclusive('\n').collect();
+    let lines = diff_slices(&old_lines, &new_lines);
+
+    let mut out = String::new();
+    // Line numbers, counted from 1, of `lines[counted]` on each side.
+    let (mut old_line, mut new_line) = (1, 1);
+    let mut counted = 0;
+    for range in hunk_ranges(&lines, context) {
+        for line in &lines[counted..range.start] {
+            match line {
+                Line::Equal(_) => {
+                    old_line += 1;
+                    new_line += 1;
+                }
+                Line::Removed(_) => old_line += 1,
+                Line::Added(_) => new_line += 1,
+            }
+        }
+        counted = range.start;
+        let hunk = &lines[range];
+        let old_len = hunk
+            .iter()
+            .filter(|line| !matches!(line, Line::Added(_)))
+            .count();
+        let new_len = hunk
+            .iter()
+            .filter(|line| !matches!(line, Line::Removed(_)))
+            .count();
+        let _ = writeln!(
+            out,
+            "@@ -{} +{} @@",
+            hunk_range(old_line, old_len),
+            hunk_range(new_line, new_len)
+        );
+        for line in hunk {
+            let (marker, text) = match line {
+                Line::Equal(text) => (' ', text),
+                Line::Removed(text) => ('-', text),
+                Line::Added(text) => ('+', text),
+            };
+            out.push(marker);
+            out.push_str(text);
+            if !text.ends_with('\n') {
+                out.push_str("\n\\ No newline at end of file\n");
+            }
+        }
+    }
+    out
+}
+
+/// The `start,len` of one side of a hunk header, where an empty side starts at the line before.
+fn hunk_range(start: usize, len: usize) -> String {
+    if len == 0 {
+        format!("{},0", start - 1)
+    } else {
+        format!("{start},{len}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance(&["a", "b", "c"], &["a", "c", "d"]), 2);
+        assert_eq!(edit_distance(&[], &[]), 0);
+        assert_eq!(edit_distance(&["a"], &["b"]), 2);
+        assert_eq!(edit_distance(&["a", "b"], &["a", "b"]), 0);
+    }
+
+    #[test]
+    #[cfg(feature = "html")]
+    fn test_diff_lines() {
+        assert_eq!(
+            diff_lines("a\nb\nc\n", "a\nc\nd\n"),
+            vec![
+                Line::Equal("a"),
+                Line::Removed("b"),
+                Line::Equal("c"),
+                Line::Added("d"),
+            ]
+        );
+        assert_eq!(diff_lines("", ""), vec![]);
+        assert_eq!(diff_lines("", "a"), vec![Line::Added("a")]);
+        assert_eq!(diff_lines("a", ""), vec![Line::Removed("a")]);
+    }
+
+    #[test]
+    #[cfg(feature = "html")]
+    fn test_hunks() {
+        let lines = diff_lines("1\n2\n3\n4\n5\n6\n7\n8\n", "1\n2\n3\n4\n5\n6\n7\nx\n");
+        assert_eq!(
+            hunks(&lines, 1),
+            vec![&[Line::Equal("7"), Line::Removed("8"), Line::Added("x")][..]]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "similar")]
+    fn test_diff_lines_with() {
+        for algorithm in [
+            DiffAlgorithm::Myers,
+            DiffAlgorithm::Patience,
+            DiffAlgorithm::Lcs,
+        ] {
+            assert_eq!(
+                diff_lines_with("a\r\nb\nc", "a\r\nx\nc", algorithm),
+                vec![
+                    Line::Equal("a"),
+                    Line::Removed("b"),
+                    Line::Added("x"),
+                    Line::Equal("c"),
+                ]
+            );
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! Reading local files through `io_uring` on Linux.
+//!
+//! Each chunk of both files is read with a single system call, rather than one read per file,
+//! which adds up when comparing many small files.
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd as _;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use io_uring::{opcode, types, IoUring};
+
+use crate::report::Side;
+
+/// Set once creating a ring fails, such as when `io_uring` is disabled or blocked by a sandbox,
+/// to stop trying.
+static UNAVAILABLE: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static RING: RefCell<Option<IoUring>> = const { RefCell::new(None) };
+}
+
+/// Compare two local files chunk by chunk, stopping at the first chunk that differs.
+///
+/// Returns `None` if `io_uring` can't be used, in which case the files should be read the usual
+/// way.  Errors are returned with the side they happened on.
+pub(crate) fn same_files(
+    left: &Path,
+    right: &Path,
+    buffer_size: usize,
+) -> Option<Result<bool, (Side, io::Error)>> {
+    if UNAVAILABLE.load(Ordering::Relaxed) {
+        return None;
+    }
+    RING.with(|ring| {
+        let mut ring = ring.borrow_mut();
+        if ring.is_none() {
+            match IoUring::new(2) {
+                Ok(created) => *ring = Some(created),
+                Err(_) => {
+                    UNAVAILABLE.store(true, Ordering::Relaxed);
+                    return None;
+                }
+            }
+        }
+        let result = compare(ring.as_mut()?, left, right, buffer_size);
+        if let Err((_, Failure::Broken)) = &result {
+            // The kernel may still write into buffers we no longer own, so never reuse the ring.
+            *ring = None;
+        }
+        Some(result.map_err(|(side, err)| (side, err.into_io())))
+    })
+}
+
+enum Failure {
+    Io(io::Error),
+    /// The ring can't be trusted any more, after failing with reads in flight.
+    Broken,
+}
+
+impl Failure {
+    fn into_io(self) -> io::Error {
+        match self {
+            Failure::Io(err) => err,
+            Failure::Broken => io::Error::new(io::ErrorKind::Other, "io_uring failed"),
+        }
+    }
+}
+
+fn compare(
+    ring: &mut IoUring,
+    left: &Path,
+    right: &Path,
+    buffer_size: usize,
+) -> Result<bool, (Side, Failure)> {
+    let files = [
+        File::open(left).map_err(|err| (Side::Left, Failure::Io(err)))?,
+        File::open(right).map_err(|err| (Side::Right, Failure::Io(err)))?,
+    ];
+    let mut buffers = [vec![0; buffer_size], vec![0; buffer_size]];
+    let mut offset = 0;
+    loop {
+        let lens = match read_chunks(ring, &files, &mut buffers, offset) {
+            Ok(lens) => lens,
+            Err(err @ (_, Failure::Broken)) => {
+                // Reads may still land in the buffers.
+                for buffer in buffers {
+                    buffer.leak();
+                }
+                return Err(err);
+            }
+            Err(err) => return Err(err),
+        };
+        if buffers[0][..lens[0]] != buffers[1][..lens[1]] {
+            return Ok(false);
+        }
+        if lens[0] == 0 {
+            return Ok(true);
+        }
+        offset += lens[0] as u64;
+    }
+}
+
+/// Fill both buffers from `offset`, unless the end of a file is reached first, returning the
+/// number of bytes read into each.
+fn read_chunks(
+    ring: &mut IoUring,
+    files: &[File; 2],
+    buffers: &mut [Vec<u8>; 2],
+    offset: u64,
+) -> Result<[usize; 2], (Side, Failure)> {
+    let sides = [Side::Left, Side::Right];
+    let mut filled = [0; 2];
+    let mut ended = [false; 2];
+    loop {
+        let mut in_flight = 0;
+        for i in 0..2 {
+            if ended[i] || filled[i] == buffers[i].len() {
+                continue;
+            }
+            let remaining = &mut buffers[i][filled[i]..];
+            let read = opcode::Read::new(
+                types::Fd(files[i].as_raw_fd()),
+                remaining.as_mut_ptr(),
+                remaining.len().min(u32::MAX as usize) as u32,
+            )
+            .offset(offset + filled[i] as u64)
+            .build()
+            .user_data(i as u64);
+            // SAFETY: the file and buffer outlive the read, as every read is waited for below,
+            // and the ring is discarded if that fails.
+            unsafe {
+                ring.submission()
+                    .push(&read)
+                    .map_err(|_| (sides[i], Failure::Broken))?;
+            }
+            in_flight += 1;
+        }
+        if in_flight == 0 {
+            return Ok(filled);
+        }
+
+        let mut completed = 0;
+        while completed < in_flight {
+            match ring.submit_and_wait(in_flight - completed) {
+                Ok(_) => {}
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => return Err((Side::Left, Failure::Broken)),
+            }
+            let results: Vec<_> = ring
+                .completion()
+                .map(|completion| (completion.user_data() as usize, completion.result()))
+                .collect();
+            completed += results.len();
+            let mut failed = None;
+            for (i, result) in results {
+                match result {
+                    0 => ended[i] = true,
+                    read if read > 0 => filled[i] += read as usize,
+                    err if -err == libc::EINTR || -err == libc::EAGAIN => {}
+                    err => failed = Some((sides[i], io::Error::from_raw_os_error(-err))),
+                }
+            }
+            if let Some((side, err)) = failed {
+                // Wait for the other side before its buffer can be given up.
+                drain(ring, in_flight - completed).map_err(|()| (side, Failure::Broken))?;
+                return Err((side, Failure::Io(err)));
+            }
+        }
+    }
+}
+
+/// Wait for `count` more reads to complete, ignoring their results.
+fn drain(ring: &mut IoUring, mut count: usize) -> Result<(), ()> {
+    while count > 0 {
+        match ring.submit_and_wait(count) {
+            Ok(_) => {}
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => return Err(()),
+        }
+        count -= ring.completion().count();
+    }
+    Ok(())
+}
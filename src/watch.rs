@@ -0,0 +1,255 @@
+//! Keeping a report up to date as the trees change.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::Duration;
+
+use notify::{RecommendedWatcher, RecursiveMode, Watcher as _};
+
+use crate::compare::Differences;
+use crate::error::{Phase, ResultExt as _};
+use crate::options::{DirDiffOptions, Settings};
+use crate::report::{DiffReport, Side};
+use crate::source::FsSource;
+use crate::Error;
+
+/// How long the trees must be quiet before re-diffing, so bursts of changes are handled at once.
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// Compare two directories, then compare them again whenever they change.
+///
+/// `callback` is called with the initial report, and then with an updated report after every
+/// batch of changes.  Only the subtrees containing changes are compared again.
+///
+/// The callback runs on a background thread until the returned [`WatchHandle`] is dropped.
+///
+/// # Examples
+///
+/// ```no_run
+/// extern crate dir_diff;
+///
+/// let _watch = dir_diff::watch("out", "golden", |report| match report {
+///     Ok(report) => println!("{report}"),
+///     Err(err) => eprintln!("{err}"),
+/// })
+/// .unwrap();
+/// std::thread::park();
+/// ```
+pub fn watch<A, B, F>(a_base: A, b_base: B, callback: F) -> Result<WatchHandle, Error>
+where
+    A: AsRef<Path>,
+    B: AsRef<Path>,
+    F: FnMut(Result<&DiffReport, Error>) + Send + 'static,
+{
+    DirDiffOptions::new().watch(a_base, b_base, callback)
+}
+
+impl DirDiffOptions {
+    /// Compare two directories, then compare them again whenever they change.
+    ///
+    /// See [`crate::watch`].
+    pub fn watch<A, B, F>(
+        &self,
+        a_base: A,
+        b_base: B,
+        mut callback: F,
+    ) -> Result<WatchHandle, Error>
+    where
+        A: AsRef<Path>,
+        B: AsRef<Path>,
+        F: FnMut(Result<&DiffReport, Error>) + Send + 'static,
+    {
+        let settings = self.settings()?;
+        // Events are reported under the paths being watched; canonical roots make them easy to
+        // relate back to either tree.
+        let roots = [
+            std::fs::canonicalize(a_base.as_ref())
+                .context(Phase::Walking(Side::Left), || a_base.as_ref().to_owned())?,
+            std::fs::canonicalize(b_base.as_ref())
+                .context(Phase::Walking(Side::Right), || b_base.as_ref().to_owned())?,
+        ];
+
+        let (sender, receiver) = mpsc::channel();
+        let mut watcher = notify::recommended_watcher(sender).map_err(into_error)?;
+        for root in &roots {
+            watcher
+                .watch(root, RecursiveMode::Recursive)
+                .map_err(into_error)
+                .context(Phase::Watching, || root.clone())?;
+        }
+
+        let rediff = Rediff {
+            left: a_base.as_ref().to_owned(),
+            right: b_base.as_ref().to_owned(),
+            roots,
+            settings,
+        };
+        let thread = std::thread::spawn(move || {
+            let mut report = deliver(&mut callback, rediff.full());
+            while let Some(changed) = wait_for_changes(&receiver) {
+                let result = match (report.take(), changed) {
+                    // Duplicates span the whole tree, and name issues aren't tracked by subtree,
+                    // so neither can be updated one subtree at a time.
+                    (Some(mut report), Some(changed))
+                        if !rediff.settings.find_duplicates()
+                            && !rediff.settings.check_portable_names() =>
+                    {
+                        rediff
+                            .subtrees(&mut report, &changed)
+                            .map(|()| report)
+                            // The trees may have changed again mid-comparison; start over.
+                            .or_else(|_| rediff.full())
+                    }
+                    _ => rediff.full(),
+                };
+                report = deliver(&mut callback, result);
+            }
+        });
+
+        Ok(WatchHandle {
+            watcher: Some(watcher),
+            thread: Some(thread),
+        })
+    }
+}
+
+/// Pass `result` to the callback, keeping the report to update it later.
+fn deliver<F>(callback: &mut F, result: Result<DiffReport, Error>) -> Option<DiffReport>
+where
+    F: FnMut(Result<&DiffReport, Error>),
+{
+    match result {
+        Ok(report) => {
+            callback(Ok(&report));
+            Some(report)
+        }
+        Err(err) => {
+            callback(Err(err));
+            None
+        }
+    }
+}
+
+/// Stops watching when dropped.
+pub struct WatchHandle {
+    watcher: Option<RecommendedWatcher>,
+    thread: Option<std::thread::JoinHandle<()>>,
+}
+
+impl Drop for WatchHandle {
+    fn drop(&mut self) {
+        // Dropping the watcher closes the channel, which ends the background thread.
+        drop(self.watcher.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl std::fmt::Debug for WatchHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("WatchHandle").finish_non_exhaustive()
+    }
+}
+
+struct Rediff {
+    left: PathBuf,
+    right: PathBuf,
+    roots: [PathBuf; 2],
+    settings: Settings,
+}
+
+impl Rediff {
+    fn full(&self) -> Result<DiffReport, Error> {
+        crate::diff_with(
+            FsSource::new(&self.left),
+            FsSource::new(&self.right),
+            self.settings.clone(),
+            None,
+        )
+    }
+
+    /// Compare the subtrees affected by `changed` again, updating `report` in place.
+    fn subtrees(&self, report: &mut DiffReport, changed: &[PathBuf]) -> Result<(), Error> {
+        let mut dirs: Vec<PathBuf> = Vec::new();
+        for path in changed {
+            let relative = self
+                .roots
+                .iter()
+                .find_map(|root| path.strip_prefix(root).ok())
+                .ok_or_else(|| {
+                    std::io::Error::new(std::io::ErrorKind::Other, "change outside of the trees")
+                })
+                .context(Phase::Watching, || path.clone())?;
+            dirs.push(self.common_dir(relative));
+        }
+        dirs.sort();
+        dirs.dedup_by(|dir, ancestor| dir.starts_with(ancestor));
+
+        for dir in dirs {
+            let mut walk = Differences::new_at(
+                FsSource::new(&self.left),
+                FsSource::new(&self.right),
+                self.settings.clone(),
+                None,
+                &dir,
+            )?;
+            let differences = walk.by_ref().collect::<Result<_, _>>()?;
+            report.replace_subtree(&dir, differences, walk.into_allowed());
+        }
+        Ok(())
+    }
+
+    /// The closest directory containing `relative` that is a directory in both trees.
+    ///
+    /// A change can add or remove an entry, so its parent is compared again rather than the
+    /// entry itself.
+    fn common_dir(&self, relative: &Path) -> PathBuf {
+        let mut dir = relative.parent().unwrap_or(Path::new(""));
+        loop {
+            let in_both = self.left.join(dir).is_dir() && self.right.join(dir).is_dir();
+            if dir.as_os_str().is_empty() || in_both {
+                return dir.to_owned();
+            }
+            dir = dir.parent().unwrap_or(Path::new(""));
+        }
+    }
+}
+
+/// Block until the trees change, then collect changes until they are quiet.
+///
+/// Returns `None` once the watcher is gone, and `Some(None)` when the changes are unknown and
+/// everything needs to be compared again.
+fn wait_for_changes(
+    receiver: &Receiver<notify::Result<notify::Event>>,
+) -> Option<Option<Vec<PathBuf>>> {
+    let mut changed = Some(Vec::new());
+    let mut event = receiver.recv().ok()?;
+    loop {
+        match event {
+            Ok(event) if !event.need_rescan() => {
+                if let Some(changed) = &mut changed {
+                    changed.extend(event.paths);
+                }
+            }
+            _ => changed = None,
+        }
+        event = match receiver.recv_timeout(DEBOUNCE) {
+            Ok(event) => event,
+            Err(RecvTimeoutError::Timeout) => return Some(changed),
+            Err(RecvTimeoutError::Disconnected) => return None,
+        };
+    }
+}
+
+fn into_error(err: notify::Error) -> Error {
+    let path = err.paths.first().cloned();
+    let mut err: Error = match err.kind {
+        notify::ErrorKind::Io(err) => err.into(),
+        _ => std::io::Error::new(std::io::ErrorKind::Other, err).into(),
+    };
+    if let Some(path) = path {
+        err = err.context(Phase::Watching, || path);
+    }
+    err
+}
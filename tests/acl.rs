@@ -0,0 +1,42 @@
+#![cfg(all(feature = "acl", target_os = "linux"))]
+
+use std::fs::create_dir_all;
+use std::path::PathBuf;
+use std::process::Command;
+
+use dir_diff::{DifferenceKind, DirDiffOptions};
+
+#[test]
+fn compare_acls() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("compare_acls");
+    let _ = std::fs::remove_dir_all(&dir);
+    for side in ["left", "right"] {
+        create_dir_all(dir.join(side).join("sub")).unwrap();
+        std::fs::write(dir.join(side).join("sub/a.txt"), "same").unwrap();
+    }
+    let options = DirDiffOptions::new().compare_acls(true);
+    assert!(!options
+        .is_different(dir.join("left"), dir.join("right"))
+        .unwrap());
+
+    // Not every file system supports ACLs, nor every system `setfacl`.
+    let set = Command::new("setfacl")
+        .args(["-m", "u:nobody:r"])
+        .arg(dir.join("right/sub/a.txt"))
+        .status();
+    if !set.map(|status| status.success()).unwrap_or(false) {
+        return;
+    }
+    let report = options.diff(dir.join("left"), dir.join("right")).unwrap();
+    let differences: Vec<_> = report
+        .iter()
+        .map(|d| (d.path().to_path_buf(), d.kind()))
+        .collect();
+    assert_eq!(
+        differences,
+        [(PathBuf::from("sub/a.txt"), DifferenceKind::Acl)]
+    );
+    assert!(!DirDiffOptions::new()
+        .is_different(dir.join("left"), dir.join("right"))
+        .unwrap());
+}
@@ -0,0 +1,74 @@
+use std::path::PathBuf;
+
+fn cache_dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+#[test]
+fn persists_hashes() {
+    let dir = cache_dir("persists_hashes");
+    let options = dir_diff::DirDiffOptions::new().cache_dir(&dir);
+
+    assert!(!options
+        .is_different("tests/binary/good/dir1", "tests/binary/good/dir2")
+        .unwrap());
+    let cache = std::fs::read_dir(&dir).unwrap().next().unwrap().unwrap();
+    let contents = std::fs::read_to_string(cache.path()).unwrap();
+    assert_eq!(contents.lines().count(), 2);
+
+    assert!(!options
+        .is_different("tests/binary/good/dir1", "tests/binary/good/dir2")
+        .unwrap());
+    assert!(options
+        .is_different("tests/binary/bad/dir1", "tests/binary/bad/dir2")
+        .unwrap());
+}
+
+#[test]
+fn ignores_corrupt_cache() {
+    let dir = cache_dir("ignores_corrupt_cache");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("dir-diff-hashes-v1"), "not a cache\n").unwrap();
+
+    let options = dir_diff::DirDiffOptions::new().cache_dir(&dir);
+    assert!(options
+        .is_different("tests/easy/bad/dir1", "tests/easy/bad/dir2")
+        .unwrap());
+}
+
+#[test]
+fn explains_cache_hits() {
+    let dir = cache_dir("explains_cache_hits");
+    let options = dir_diff::DirDiffOptions::new()
+        .cache_dir(&dir)
+        .explain(true);
+    let checks = |report: &dir_diff::DiffReport| -> Vec<dir_diff::Check> {
+        let explanation = report.explanation().unwrap();
+        let entry = &explanation.entries()[0];
+        assert!(entry.is_same());
+        entry.steps().iter().map(|step| step.check()).collect()
+    };
+
+    let report = options
+        .diff("tests/binary/good/dir1", "tests/binary/good/dir2")
+        .unwrap();
+    assert_eq!(
+        checks(&report),
+        [
+            dir_diff::Check::Name,
+            dir_diff::Check::Hash { cached: false }
+        ]
+    );
+    let report = options
+        .diff("tests/binary/good/dir1", "tests/binary/good/dir2")
+        .unwrap();
+    assert_eq!(
+        checks(&report),
+        [
+            dir_diff::Check::Name,
+            dir_diff::Check::Hash { cached: true }
+        ]
+    );
+}
@@ -0,0 +1,35 @@
+#![cfg(all(feature = "capi", unix))]
+
+use std::ffi::{CStr, CString, OsStr};
+use std::fs::create_dir_all;
+use std::os::unix::ffi::OsStrExt as _;
+
+use dir_diff::capi::{
+    dir_diff_report_free, dir_diff_report_len, dir_diff_report_new, dir_diff_report_path,
+};
+
+#[test]
+fn non_utf8_path() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("capi_non_utf8");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left")).unwrap();
+    create_dir_all(dir.join("right")).unwrap();
+    let name = OsStr::from_bytes(b"caf\xe9.txt");
+    std::fs::write(dir.join("left").join(name), "left").unwrap();
+    std::fs::write(dir.join("right").join(name), "right").unwrap();
+
+    let left = CString::new(dir.join("left").as_os_str().as_bytes()).unwrap();
+    let right = CString::new(dir.join("right").as_os_str().as_bytes()).unwrap();
+    // SAFETY: the strings are NUL-terminated, and the report is freed once, after its path is
+    // read.
+    unsafe {
+        let report = dir_diff_report_new(left.as_ptr(), right.as_ptr());
+        assert!(!report.is_null());
+        assert_eq!(dir_diff_report_len(report), 1);
+        assert_eq!(
+            CStr::from_ptr(dir_diff_report_path(report, 0)).to_bytes(),
+            b"caf\xe9.txt"
+        );
+        dir_diff_report_free(report);
+    }
+}
@@ -0,0 +1,68 @@
+#![cfg(feature = "checksums")]
+
+use std::fs::create_dir_all;
+use std::path::PathBuf;
+
+use dir_diff::{DifferenceKind, DirDiffOptions};
+
+/// The SHA-256 hash of `hello\n`.
+const HELLO: &str = "5891b5b522d5df086d0ff0b110fbd9d21bb4fc7163af34d08286a2e846f6be03";
+
+fn dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("release/bin")).unwrap();
+    std::fs::write(dir.join("release/README"), "hello\n").unwrap();
+    std::fs::write(dir.join("release/bin/tool"), "hello\n").unwrap();
+    dir
+}
+
+#[test]
+fn write_checksums() {
+    let dir = dir("write_checksums");
+    let mut sums = Vec::new();
+    dir_diff::write_checksums(dir.join("release"), &mut sums).unwrap();
+    assert_eq!(
+        String::from_utf8(sums).unwrap(),
+        format!("{HELLO}  README\n{HELLO}  bin/tool\n")
+    );
+}
+
+#[test]
+fn verify_checksums() {
+    let dir = dir("verify_checksums");
+    let sums = dir.join("SHA256SUMS");
+    let mut file = std::fs::File::create(&sums).unwrap();
+    dir_diff::write_checksums(dir.join("release"), &mut file).unwrap();
+    drop(file);
+    assert!(!dir_diff::verify_checksums(&sums, dir.join("release"))
+        .unwrap()
+        .is_different());
+
+    std::fs::write(dir.join("release/README"), "goodbye\n").unwrap();
+    std::fs::remove_file(dir.join("release/bin/tool")).unwrap();
+    std::fs::write(dir.join("release/NEWS"), "new\n").unwrap();
+    let report = dir_diff::verify_checksums(&sums, dir.join("release")).unwrap();
+    let differences: Vec<_> = report
+        .iter()
+        .map(|d| (d.path().to_path_buf(), d.kind()))
+        .collect();
+    assert_eq!(
+        differences,
+        [
+            (PathBuf::from("NEWS"), DifferenceKind::OnlyInRight),
+            (PathBuf::from("README"), DifferenceKind::Content),
+            (PathBuf::from("bin/tool"), DifferenceKind::OnlyInLeft),
+        ]
+    );
+
+    let report = DirDiffOptions::new()
+        .exclude("bin")
+        .exclude("NEWS")
+        .verify_checksums(&sums, dir.join("release"))
+        .unwrap();
+    assert_eq!(report.differences().len(), 1);
+
+    std::fs::write(&sums, "not a checksum\n").unwrap();
+    assert!(dir_diff::verify_checksums(&sums, dir.join("release")).is_err());
+}
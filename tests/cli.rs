@@ -0,0 +1,211 @@
+#![cfg(feature = "cli")]
+
+use std::process::Command;
+
+fn dir_diff() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_dir-diff"))
+}
+
+#[test]
+fn same() {
+    let output = dir_diff()
+        .args(["tests/easy/good/dir1", "tests/easy/good/dir2"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn different() {
+    let output = dir_diff()
+        .args(["tests/dirs_differ/dir1", "tests/dirs_differ/dir2"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "- dirA\n+ dirB\n"
+    );
+}
+
+#[test]
+fn similarity() {
+    let output = dir_diff()
+        .args(["--similarity", "tests/easy/bad/dir1", "tests/easy/bad/dir2"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "M test.txt (0% similar)\n"
+    );
+}
+
+#[test]
+fn exclude() {
+    let output = dir_diff()
+        .args(["tests/dirs_differ/dir1", "tests/dirs_differ/dir2"])
+        .args(["--exclude", "dirA", "--exclude", "dirB"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+}
+
+#[test]
+fn brief() {
+    let output = dir_diff()
+        .args(["--brief", "tests/easy/bad/dir1", "tests/easy/bad/dir2"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "Directories tests/easy/bad/dir1 and tests/easy/bad/dir2 differ\n"
+    );
+}
+
+#[test]
+fn diff_rq() {
+    let output = dir_diff()
+        .args([
+            "--diff-rq",
+            "tests/dirs_differ/dir1",
+            "tests/dirs_differ/dir2",
+        ])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "Only in tests/dirs_differ/dir1: dirA\nOnly in tests/dirs_differ/dir2: dirB\n"
+    );
+}
+
+#[test]
+fn json() {
+    let output = dir_diff()
+        .args(["--json", "tests/easy/bad/dir1", "tests/easy/bad/dir2"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["differences"][0]["path"], "test.txt");
+    assert_eq!(report["differences"][0]["kind"], "content");
+}
+
+#[test]
+fn ndjson() {
+    let output = dir_diff()
+        .args(["--ndjson", "tests/easy/bad/dir1", "tests/easy/bad/dir2"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let lines: Vec<serde_json::Value> = output
+        .stdout
+        .split(|byte| *byte == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| serde_json::from_slice(line).unwrap())
+        .collect();
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0]["record"], "difference");
+    assert_eq!(lines[0]["path"], "test.txt");
+
+    let output = dir_diff()
+        .args(["--ndjson", "tests/easy/good/dir1", "tests/easy/good/dir2"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+#[cfg(feature = "cbor")]
+fn compact() {
+    let output = dir_diff()
+        .args([
+            "--compact",
+            "cbor",
+            "tests/easy/bad/dir1",
+            "tests/easy/bad/dir2",
+        ])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let report =
+        dir_diff::DiffReport::read_compact(dir_diff::CompactFormat::Cbor, &output.stdout[..])
+            .unwrap();
+    assert_eq!(
+        report.differences()[0].path(),
+        std::path::Path::new("test.txt")
+    );
+}
+
+#[test]
+fn error() {
+    let output = dir_diff()
+        .args(["does_not_exist", "tests/easy/good/dir1"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn first_only() {
+    let output = dir_diff()
+        .args([
+            "--first-only",
+            "tests/dirs_differ/dir1",
+            "tests/dirs_differ/dir2",
+        ])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "- dirA\n");
+
+    let output = dir_diff()
+        .args([
+            "--first-only",
+            "tests/easy/good/dir1",
+            "tests/easy/good/dir2",
+        ])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn usage_error() {
+    let output = dir_diff().arg("tests/easy/good/dir1").output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn baseline() {
+    let baseline = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("baseline.json");
+    let output = dir_diff()
+        .args(["--json", "tests/easy/bad/dir1", "tests/easy/bad/dir2"])
+        .output()
+        .unwrap();
+    std::fs::write(&baseline, output.stdout).unwrap();
+
+    let output = dir_diff()
+        .args(["tests/easy/bad/dir1", "tests/easy/bad/dir2", "--baseline"])
+        .arg(&baseline)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+
+    let output = dir_diff()
+        .args(["tests/reflexive/dir1", "tests/reflexive/dir2", "--baseline"])
+        .arg(&baseline)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "+ subdir/three.txt\n"
+    );
+}
@@ -0,0 +1,46 @@
+#![cfg(all(feature = "cbor", feature = "msgpack"))]
+
+use dir_diff::{CompactFormat, DiffReport, MerkleTree};
+
+const FORMATS: [CompactFormat; 2] = [CompactFormat::Cbor, CompactFormat::MessagePack];
+
+#[test]
+fn report() {
+    let report = dir_diff::DirDiffOptions::new()
+        .similarity(true)
+        .diff("tests/easy/bad/dir1", "tests/easy/bad/dir2")
+        .unwrap();
+    let json = serde_json::to_vec(&report).unwrap();
+    for format in FORMATS {
+        let mut compact = Vec::new();
+        report.write_compact(format, &mut compact).unwrap();
+        assert!(compact.len() < json.len(), "{format:?}");
+        assert_eq!(
+            DiffReport::read_compact(format, &compact[..]).unwrap(),
+            report,
+            "{format:?}"
+        );
+    }
+}
+
+#[test]
+fn merkle_tree() {
+    let tree = dir_diff::merkle_tree("tests/easy/good/dir1").unwrap();
+    for format in FORMATS {
+        let mut compact = Vec::new();
+        tree.write_compact(format, &mut compact).unwrap();
+        assert_eq!(
+            MerkleTree::read_compact(format, &compact[..]).unwrap(),
+            tree,
+            "{format:?}"
+        );
+    }
+}
+
+#[test]
+fn invalid() {
+    for format in FORMATS {
+        let err = DiffReport::read_compact(format, &b"\xc1 not a report"[..]).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData, "{format:?}");
+    }
+}
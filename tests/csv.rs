@@ -0,0 +1,50 @@
+#![cfg(feature = "csv")]
+
+use std::path::PathBuf;
+
+#[test]
+fn numeric_tolerance() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("numeric_tolerance");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("left")).unwrap();
+    std::fs::create_dir_all(dir.join("right")).unwrap();
+    std::fs::write(dir.join("left/energy.csv"), "step,energy\n1,-76.02668013\n").unwrap();
+    std::fs::write(
+        dir.join("right/energy.csv"),
+        "step,energy\n1,-76.0266801300001\n",
+    )
+    .unwrap();
+    std::fs::write(dir.join("left/forces.tsv"), "x\ty\n0.5\t1e-3\n").unwrap();
+    std::fs::write(dir.join("right/forces.tsv"), "x\ty\n0.50000000001\t0.001\n").unwrap();
+    std::fs::write(dir.join("left/notes.csv"), "label\nwater\n").unwrap();
+    std::fs::write(dir.join("right/notes.csv"), "label\nice\n").unwrap();
+
+    assert!(dir_diff::DirDiffOptions::new()
+        .exclude("notes.csv")
+        .is_different(dir.join("left"), dir.join("right"))
+        .unwrap());
+
+    let report = dir_diff::DirDiffOptions::new()
+        .compare_csv("*.{csv,tsv}", 1e-9)
+        .diff(dir.join("left"), dir.join("right"))
+        .unwrap();
+    let differences: Vec<_> = report
+        .differences()
+        .iter()
+        .map(|d| (d.path().to_path_buf(), d.kind()))
+        .collect();
+    assert_eq!(
+        differences,
+        [(
+            PathBuf::from("notes.csv"),
+            dir_diff::DifferenceKind::Content
+        )]
+    );
+
+    assert!(dir_diff::DirDiffOptions::new()
+        .compare_csv("*.csv", 1e-9)
+        .compare_csv("energy.csv", 1e-20)
+        .exclude("notes.csv")
+        .is_different(dir.join("left"), dir.join("right"))
+        .unwrap());
+}
@@ -0,0 +1,42 @@
+use std::path::{Path, PathBuf};
+
+fn fixture(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    for side in ["left", "right"] {
+        std::fs::create_dir_all(dir.join(side).join("lib")).unwrap();
+        std::fs::write(dir.join(side).join("lib/a.so"), "library").unwrap();
+        std::fs::write(dir.join(side).join("readme"), "unrelated").unwrap();
+    }
+    dir
+}
+
+#[test]
+fn copied_file() {
+    let dir = fixture("copied_file");
+    std::fs::write(dir.join("right/lib/b.so"), "library").unwrap();
+    std::fs::write(dir.join("right/new"), "unrelated too").unwrap();
+
+    let report = dir_diff::DirDiffOptions::new()
+        .find_duplicates(true)
+        .diff(dir.join("left"), dir.join("right"))
+        .unwrap();
+    assert_eq!(report.differences().len(), 2);
+    let duplicates = report.duplicates();
+    assert_eq!(duplicates.len(), 1);
+    assert_eq!(duplicates[0].left(), [Path::new("lib/a.so")]);
+    assert_eq!(
+        duplicates[0].right(),
+        [Path::new("lib/a.so"), Path::new("lib/b.so")]
+    );
+}
+
+#[test]
+fn off_by_default() {
+    let dir = fixture("duplicates_off_by_default");
+    std::fs::write(dir.join("right/lib/b.so"), "library").unwrap();
+
+    let report = dir_diff::diff(dir.join("left"), dir.join("right")).unwrap();
+    assert!(report.is_different());
+    assert!(report.duplicates().is_empty());
+}
@@ -0,0 +1,40 @@
+#![cfg(feature = "git")]
+
+use std::path::PathBuf;
+
+use dir_diff::source::{FsSource, GitTreeSource};
+use dir_diff::{DifferenceKind, DirDiffOptions};
+
+#[test]
+fn matches_checkout() {
+    let committed = GitTreeSource::parse(".@HEAD:tests/reflexive/dir1").unwrap();
+    assert!(!DirDiffOptions::new()
+        .is_different_sources(&committed, FsSource::new("tests/reflexive/dir1"))
+        .unwrap());
+}
+
+#[test]
+fn differs_from_checkout() {
+    let committed = GitTreeSource::open(".", "HEAD:tests/reflexive/dir1").unwrap();
+    let report = DirDiffOptions::new()
+        .diff_sources(&committed, FsSource::new("tests/reflexive/dir2"))
+        .unwrap();
+    let differences: Vec<_> = report
+        .differences()
+        .iter()
+        .map(|d| (d.path().to_path_buf(), d.kind()))
+        .collect();
+    assert_eq!(
+        differences,
+        [(
+            PathBuf::from("subdir/three.txt"),
+            DifferenceKind::OnlyInRight
+        )]
+    );
+}
+
+#[test]
+fn bad_spec() {
+    assert!(GitTreeSource::parse("no-at-sign").is_err());
+    assert!(GitTreeSource::parse(".@does-not-exist").is_err());
+}
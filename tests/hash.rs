@@ -0,0 +1,115 @@
+#![cfg(all(feature = "sha256", feature = "xxhash"))]
+
+use std::fs::create_dir_all;
+use std::path::PathBuf;
+
+use dir_diff::{DirDiffOptions, HashAlgorithm};
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+const ALGORITHMS: [HashAlgorithm; 3] = [
+    HashAlgorithm::Blake3,
+    HashAlgorithm::Sha256,
+    HashAlgorithm::Xxh3,
+];
+
+#[test]
+fn tree_digest() {
+    let mut digests = Vec::new();
+    for algorithm in ALGORITHMS {
+        let options = DirDiffOptions::new().hash_algorithm(algorithm);
+        let digest = options.tree_digest("tests/easy/good/dir1").unwrap();
+        assert_eq!(
+            digest,
+            options.tree_digest("tests/easy/good/dir2").unwrap(),
+            "{algorithm:?}"
+        );
+        assert_ne!(
+            options.tree_digest("tests/easy/bad/dir1").unwrap(),
+            options.tree_digest("tests/easy/bad/dir2").unwrap(),
+            "{algorithm:?}"
+        );
+        digests.push(digest);
+    }
+    assert_eq!(
+        digests[0],
+        dir_diff::tree_digest("tests/easy/good/dir1").unwrap()
+    );
+    assert_ne!(digests[0], digests[1]);
+    assert_ne!(digests[1], digests[2]);
+    assert_eq!(digests[2][16..], [0; 16]);
+}
+
+#[test]
+fn merkle_tree() {
+    let dir = temp_dir("hash_merkle_tree");
+    create_dir_all(dir.join("docs")).unwrap();
+    std::fs::write(dir.join("docs/index.html"), "<p>hello</p>").unwrap();
+
+    let sha256 = DirDiffOptions::new().hash_algorithm(HashAlgorithm::Sha256);
+    let mut tree = dir_diff::merkle_tree(&dir).unwrap();
+    assert_eq!(tree.algorithm(), HashAlgorithm::Blake3);
+
+    // A tree of another algorithm is recomputed in full, whatever changed.
+    sha256
+        .update_merkle_tree(&mut tree, &dir, ["docs"])
+        .unwrap();
+    assert_eq!(tree.algorithm(), HashAlgorithm::Sha256);
+    assert_eq!(tree.digest(), sha256.tree_digest(&dir).unwrap());
+
+    let mut table = Vec::new();
+    tree.write_to(&mut table).unwrap();
+    assert!(table.starts_with(b"# sha256\n"));
+    assert_eq!(dir_diff::MerkleTree::read_from(&table[..]).unwrap(), tree);
+}
+
+#[test]
+fn cache_per_algorithm() {
+    let dir = temp_dir("hash_cache_per_algorithm");
+    for algorithm in ALGORITHMS {
+        let options = DirDiffOptions::new()
+            .cache_dir(&dir)
+            .hash_algorithm(algorithm);
+        assert!(!options
+            .is_different("tests/binary/good/dir1", "tests/binary/good/dir2")
+            .unwrap());
+        assert!(options
+            .is_different("tests/binary/bad/dir1", "tests/binary/bad/dir2")
+            .unwrap());
+    }
+    let mut files: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    files.sort();
+    assert_eq!(
+        files,
+        [
+            "dir-diff-hashes-v1",
+            "dir-diff-hashes-v1-sha256",
+            "dir-diff-hashes-v1-xxh3-128"
+        ]
+    );
+}
+
+#[test]
+fn duplicates() {
+    let dir = temp_dir("hash_duplicates");
+    create_dir_all(dir.join("left")).unwrap();
+    create_dir_all(dir.join("right")).unwrap();
+    std::fs::write(dir.join("left/a.txt"), "same").unwrap();
+    std::fs::write(dir.join("right/b.txt"), "same").unwrap();
+
+    for algorithm in ALGORITHMS {
+        let report = DirDiffOptions::new()
+            .find_duplicates(true)
+            .hash_algorithm(algorithm)
+            .diff(dir.join("left"), dir.join("right"))
+            .unwrap();
+        assert_eq!(report.duplicates().len(), 1, "{algorithm:?}");
+    }
+}
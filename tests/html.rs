@@ -0,0 +1,19 @@
+#![cfg(feature = "html")]
+
+#[test]
+fn inline_diff() {
+    let report = dir_diff::diff("tests/easy/bad/dir1", "tests/easy/bad/dir2").unwrap();
+    let html = dir_diff::report::html::render(&report);
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("<p>1 difference.</p>"));
+    assert!(html.contains("<summary><span class=\"changed\">M test.txt</span></summary>"));
+    assert!(html.contains("<pre class=\"diff\">"));
+}
+
+#[test]
+fn collapsible_tree() {
+    let report = dir_diff::diff("tests/reflexive/dir1", "tests/reflexive/dir2").unwrap();
+    let html = dir_diff::report::html::render(&report);
+    assert!(html.contains("<li><details open><summary>subdir</summary>"));
+    assert!(html.contains("<li><span class=\"added\">+ three.txt</span></li>"));
+}
@@ -0,0 +1,106 @@
+use std::fs::create_dir_all;
+use std::path::PathBuf;
+
+use dir_diff::{DifferenceKind, DirDiffOptions};
+
+fn dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("release/bin")).unwrap();
+    create_dir_all(dir.join("release/share/empty")).unwrap();
+    std::fs::write(dir.join("release/README"), "hello\n").unwrap();
+    std::fs::write(dir.join("release/bin/my tool"), "hello\n").unwrap();
+    dir
+}
+
+fn kinds(report: &dir_diff::DiffReport) -> Vec<(PathBuf, DifferenceKind)> {
+    report
+        .iter()
+        .map(|d| (d.path().to_path_buf(), d.kind()))
+        .collect()
+}
+
+#[test]
+fn write_mtree() {
+    let dir = dir("write_mtree");
+    let mut spec = Vec::new();
+    dir_diff::write_mtree(dir.join("release"), &mut spec).unwrap();
+    let spec = String::from_utf8(spec).unwrap();
+    let names: Vec<_> = spec
+        .lines()
+        .map(|line| line.split(' ').next().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "#mtree",
+            ".",
+            "./README",
+            "./bin",
+            "./bin/my\\040tool",
+            "./share",
+            "./share/empty"
+        ]
+    );
+    assert!(spec.contains("./README type=file"), "{spec}");
+    assert!(spec.contains(" size=6"), "{spec}");
+}
+
+#[test]
+fn diff_mtree() {
+    let dir = dir("diff_mtree");
+    let spec = dir.join("release.mtree");
+    let file = std::fs::File::create(&spec).unwrap();
+    dir_diff::write_mtree(dir.join("release"), file).unwrap();
+    let options = DirDiffOptions::new()
+        .compare_permissions(true)
+        .compare_modified(true);
+    assert!(!options
+        .diff_mtree(&spec, dir.join("release"))
+        .unwrap()
+        .is_different());
+
+    std::fs::write(dir.join("release/README"), "goodbye\n").unwrap();
+    std::fs::remove_dir_all(dir.join("release/share")).unwrap();
+    std::fs::write(dir.join("release/NEWS"), "new\n").unwrap();
+    let report = dir_diff::diff_mtree(&spec, dir.join("release")).unwrap();
+    assert_eq!(
+        kinds(&report),
+        [
+            (PathBuf::from("NEWS"), DifferenceKind::OnlyInRight),
+            (PathBuf::from("README"), DifferenceKind::Content),
+            (PathBuf::from("share"), DifferenceKind::OnlyInLeft),
+        ]
+    );
+}
+
+#[test]
+fn diff_mtree_relative_form() {
+    let dir = dir("diff_mtree_relative_form");
+    let spec = dir.join("release.mtree");
+    std::fs::write(
+        &spec,
+        "\
+#mtree
+/set type=file uid=0 gid=0
+. type=dir
+README size=6
+bin type=dir
+    my\\040tool size=6
+..
+share type=dir
+    empty type=file
+..
+",
+    )
+    .unwrap();
+    let report = dir_diff::diff_mtree(&spec, dir.join("release")).unwrap();
+    assert_eq!(
+        kinds(&report),
+        [(PathBuf::from("share/empty"), DifferenceKind::FileType)]
+    );
+
+    std::fs::write(&spec, "#mtree\n./README size\n").unwrap();
+    let err = dir_diff::diff_mtree(&spec, dir.join("release")).unwrap_err();
+    assert!(err.to_string().contains("size"), "{err}");
+}
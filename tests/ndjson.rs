@@ -0,0 +1,50 @@
+#![cfg(feature = "ndjson")]
+
+fn lines(ndjson: &[u8]) -> Vec<serde_json::Value> {
+    std::str::from_utf8(ndjson)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn report() {
+    let report = dir_diff::DirDiffOptions::new()
+        .allow_different("*.txt")
+        .diff("tests/reflexive/dir1", "tests/reflexive/dir2")
+        .unwrap();
+    let mut ndjson = Vec::new();
+    report.write_ndjson(&mut ndjson).unwrap();
+
+    let lines = lines(&ndjson);
+    assert!(!lines.is_empty());
+    assert_eq!(lines.len(), report.allowed().len());
+    for (line, allowed) in lines.iter().zip(report.allowed()) {
+        assert_eq!(line["record"], "allowed");
+        // The extra `record` field is ignored.
+        let difference: dir_diff::Difference = serde_json::from_value(line.clone()).unwrap();
+        assert_eq!(&difference, allowed);
+    }
+}
+
+#[test]
+fn streamed() {
+    let mut ndjson = Vec::new();
+    let count = dir_diff::differences("tests/easy/bad/dir1", "tests/easy/bad/dir2")
+        .write_ndjson(&mut ndjson)
+        .unwrap();
+    assert_eq!(count, 1);
+
+    let report = dir_diff::diff("tests/easy/bad/dir1", "tests/easy/bad/dir2").unwrap();
+    let mut expected = Vec::new();
+    report.write_ndjson(&mut expected).unwrap();
+    assert_eq!(ndjson, expected);
+    assert_eq!(lines(&ndjson)[0]["record"], "difference");
+
+    assert!(
+        dir_diff::differences("tests/easy/bad/dir1", "does_not_exist")
+            .write_ndjson(std::io::sink())
+            .is_err()
+    );
+}
@@ -0,0 +1,63 @@
+#![cfg(feature = "object_store")]
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use dir_diff::source::{FsSource, ObjectStoreSource};
+use dir_diff::{DifferenceKind, DirDiffOptions};
+use object_store::memory::InMemory;
+use object_store::path::Path as StorePath;
+use object_store::ObjectStore;
+
+fn upload(files: &[(&str, &'static [u8])]) -> Arc<InMemory> {
+    let store = Arc::new(InMemory::new());
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+    for (path, contents) in files {
+        runtime
+            .block_on(store.put(&StorePath::from(*path), (*contents).into()))
+            .unwrap();
+    }
+    store
+}
+
+#[test]
+fn matches_local() {
+    let store = upload(&[
+        ("upload/one.txt", b"one"),
+        ("upload/subdir/two.txt", b"two"),
+        ("elsewhere/three.txt", b"three"),
+    ]);
+    let source = ObjectStoreSource::new(store, "upload".into()).unwrap();
+    assert!(!DirDiffOptions::new()
+        .is_different_sources(&source, FsSource::new("tests/reflexive/dir1"))
+        .unwrap());
+}
+
+#[test]
+fn differs_from_local() {
+    let store = upload(&[
+        ("upload/one.txt", b"uno"),
+        ("upload/subdir/two.txt", b"two"),
+    ]);
+    let source = ObjectStoreSource::new(store, "upload".into()).unwrap();
+    let report = DirDiffOptions::new()
+        .diff_sources(&source, FsSource::new("tests/reflexive/dir2"))
+        .unwrap();
+    let differences: Vec<_> = report
+        .differences()
+        .iter()
+        .map(|d| (d.path().to_path_buf(), d.kind()))
+        .collect();
+    assert_eq!(
+        differences,
+        [
+            (PathBuf::from("one.txt"), DifferenceKind::Content),
+            (
+                PathBuf::from("subdir/three.txt"),
+                DifferenceKind::OnlyInRight
+            ),
+        ]
+    );
+}
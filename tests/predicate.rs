@@ -0,0 +1,36 @@
+#![cfg(feature = "predicates")]
+
+use std::path::Path;
+
+use dir_diff::DirMatchesPredicate;
+use predicates_core::Predicate;
+
+#[test]
+fn eval() {
+    let predicate = DirMatchesPredicate::new("tests/easy/good/dir2");
+    assert!(predicate.eval(Path::new("tests/easy/good/dir1")));
+
+    let predicate = DirMatchesPredicate::new("tests/easy/bad/dir2");
+    assert!(!predicate.eval(Path::new("tests/easy/bad/dir1")));
+    assert!(!predicate.eval(Path::new("does_not_exist")));
+}
+
+#[test]
+fn find_case() {
+    let predicate = DirMatchesPredicate::new("tests/easy/bad/dir2");
+    let case = predicate
+        .find_case(false, Path::new("tests/easy/bad/dir1"))
+        .unwrap();
+    let products: Vec<_> = case.products().map(|p| p.name().to_owned()).collect();
+    assert_eq!(products, ["differences"]);
+    assert!(predicate
+        .find_case(true, Path::new("tests/easy/bad/dir1"))
+        .is_none());
+}
+
+#[test]
+fn options() {
+    let predicate = DirMatchesPredicate::new("tests/reflexive/dir2")
+        .options(dir_diff::DirDiffOptions::new().exclude("subdir/three.txt"));
+    assert!(predicate.eval(Path::new("tests/reflexive/dir1")));
+}
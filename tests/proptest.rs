@@ -0,0 +1,48 @@
+#![cfg(feature = "proptest")]
+
+use dir_diff::{DifferenceKind, DirDiffOptions};
+use proptest::prelude::*;
+
+proptest! {
+    #[test]
+    fn tree_is_same_as_itself(tree in dir_diff::strategy::tree()) {
+        let report = DirDiffOptions::new().diff_sources(&tree, tree.clone()).unwrap();
+        prop_assert!(!report.is_different(), "{report}");
+    }
+
+    #[test]
+    fn is_different_agrees_with_diff((left, right) in dir_diff::strategy::tree_pair()) {
+        let options = DirDiffOptions::new();
+        let report = options.diff_sources(&left, &right).unwrap();
+        let different = options.is_different_sources(&left, &right).unwrap();
+        prop_assert_eq!(report.is_different(), different);
+        if left.paths().ne(right.paths()) {
+            prop_assert!(different);
+        }
+    }
+
+    #[test]
+    fn diff_is_symmetric((left, right) in dir_diff::strategy::tree_pair()) {
+        let options = DirDiffOptions::new();
+        let forward = options.diff_sources(&left, &right).unwrap();
+        let backward = options.diff_sources(&right, &left).unwrap();
+        let swapped: Vec<_> = backward
+            .differences()
+            .iter()
+            .map(|d| {
+                let kind = match d.kind() {
+                    DifferenceKind::OnlyInLeft => DifferenceKind::OnlyInRight,
+                    DifferenceKind::OnlyInRight => DifferenceKind::OnlyInLeft,
+                    kind => kind,
+                };
+                (d.path().to_path_buf(), kind)
+            })
+            .collect();
+        let forward: Vec<_> = forward
+            .differences()
+            .iter()
+            .map(|d| (d.path().to_path_buf(), d.kind()))
+            .collect();
+        prop_assert_eq!(forward, swapped);
+    }
+}
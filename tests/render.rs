@@ -0,0 +1,129 @@
+use dir_diff::ColorChoice;
+
+#[test]
+fn tree() {
+    let report = dir_diff::diff("tests/reflexive/dir1", "tests/reflexive/dir2").unwrap();
+    assert_eq!(
+        report.render(ColorChoice::Never),
+        "\
+tests/reflexive/dir1 (-) vs tests/reflexive/dir2 (+)
+└── subdir
+    └── + three.txt
+"
+    );
+}
+
+#[test]
+fn siblings() {
+    let report = dir_diff::diff("tests/dirs_differ/dir1", "tests/dirs_differ/dir2").unwrap();
+    assert_eq!(
+        report.to_string(),
+        "\
+tests/dirs_differ/dir1 (-) vs tests/dirs_differ/dir2 (+)
+├── - dirA
+└── + dirB
+"
+    );
+}
+
+#[test]
+fn colored() {
+    let report = dir_diff::diff("tests/easy/bad/dir1", "tests/easy/bad/dir2").unwrap();
+    assert_eq!(
+        report.render(ColorChoice::Always),
+        "\
+tests/easy/bad/dir1 (-) vs tests/easy/bad/dir2 (+)
+└── \u{1b}[33mM test.txt\u{1b}[0m
+"
+    );
+}
+
+#[test]
+fn no_differences() {
+    let report = dir_diff::diff("tests/easy/good/dir1", "tests/easy/good/dir2").unwrap();
+    assert_eq!(
+        report.to_string(),
+        "\
+tests/easy/good/dir1 (-) vs tests/easy/good/dir2 (+)
+(no differences)
+"
+    );
+}
+
+#[test]
+fn rollup() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("rollup");
+    let _ = std::fs::remove_dir_all(&dir);
+    for side in ["left", "right"] {
+        std::fs::create_dir_all(dir.join(side).join("src/nested")).unwrap();
+        for file in ["src/a", "src/nested/b", "src/nested/c"] {
+            std::fs::write(dir.join(side).join(file), side).unwrap();
+        }
+    }
+    std::fs::write(dir.join("left/removed"), "").unwrap();
+    std::fs::write(dir.join("right/src/added"), "").unwrap();
+
+    let report = dir_diff::diff(dir.join("left"), dir.join("right")).unwrap();
+    let rollup = report.rollup();
+    assert_eq!(rollup.counts().total(), 5);
+    let src = rollup.get("src").unwrap();
+    assert_eq!(
+        (
+            src.counts().changed(),
+            src.counts().added(),
+            src.counts().removed()
+        ),
+        (3, 1, 0)
+    );
+    assert_eq!(src.counts().to_string(), "3 changed, 1 added");
+    assert_eq!(
+        rollup.get("src/nested/b").unwrap().kind(),
+        Some(dir_diff::DifferenceKind::Content)
+    );
+    assert!(rollup.get("src/missing").is_none());
+
+    let header = format!(
+        "{} (-) vs {} (+)\n",
+        dir.join("left").display(),
+        dir.join("right").display()
+    );
+    assert_eq!(
+        report
+            .render_rollup(2, ColorChoice::Never)
+            .strip_prefix(&header),
+        Some(
+            "\
+├── - removed
+└── src/ (3 changed, 1 added)
+    ├── M a
+    ├── + added
+    └── nested/ (2 changed)
+"
+        )
+    );
+}
+
+#[test]
+fn diff_rq() {
+    let report = dir_diff::diff("tests/dirs_differ/dir1", "tests/dirs_differ/dir2").unwrap();
+    assert_eq!(
+        report.render_diff_rq(),
+        "\
+Only in tests/dirs_differ/dir1: dirA
+Only in tests/dirs_differ/dir2: dirB
+"
+    );
+
+    let report = dir_diff::diff("tests/easy/bad/dir1", "tests/easy/bad/dir2").unwrap();
+    assert_eq!(
+        report.render_diff_rq(),
+        "Files tests/easy/bad/dir1/test.txt and tests/easy/bad/dir2/test.txt differ\n"
+    );
+
+    let report = dir_diff::diff("tests/fileanddir/dir1", "tests/fileanddir/dir2").unwrap();
+    assert_eq!(
+        report.render_diff_rq(),
+        "File tests/fileanddir/dir1/name is a directory while file tests/fileanddir/dir2/name is \
+         a regular file\n"
+    );
+}
@@ -0,0 +1,18 @@
+#![cfg(feature = "serde")]
+
+#[test]
+fn report_round_trip() {
+    let report = dir_diff::DirDiffOptions::new()
+        .allow_different("*.txt")
+        .diff("tests/reflexive/dir1", "tests/reflexive/dir2")
+        .unwrap();
+    let json = serde_json::to_string(&report).unwrap();
+    let loaded: dir_diff::DiffReport = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded, report);
+
+    let report = dir_diff::diff("tests/easy/bad/dir1", "tests/easy/bad/dir2").unwrap();
+    let json = serde_json::to_string(&report).unwrap();
+    let loaded: dir_diff::DiffReport = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded, report);
+    assert!(!report.compare_to_baseline(&loaded).has_regressions());
+}
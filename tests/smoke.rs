@@ -70,3 +70,2344 @@ fn filedepth() {
         dir_diff::is_different("tests/filedepth/desc/dir1", "tests/filedepth/desc/dir2").unwrap()
     );
 }
+
+#[test]
+fn diff_reports_every_difference() {
+    let report = dir_diff::diff("tests/reflexive/dir1", "tests/reflexive/dir2").unwrap();
+    let differences: Vec<_> = report
+        .differences()
+        .iter()
+        .map(|d| (d.path().to_path_buf(), d.kind()))
+        .collect();
+    assert_eq!(
+        differences,
+        vec![(
+            std::path::PathBuf::from("subdir/three.txt"),
+            dir_diff::DifferenceKind::OnlyInRight
+        )]
+    );
+}
+
+#[test]
+fn diff_does_not_descend_into_mismatched_dirs() {
+    let report = dir_diff::diff("tests/dirs_differ/dir1", "tests/dirs_differ/dir2").unwrap();
+    let differences: Vec<_> = report
+        .differences()
+        .iter()
+        .map(|d| (d.path().to_str().unwrap().to_owned(), d.kind()))
+        .collect();
+    assert_eq!(
+        differences,
+        vec![
+            ("dirA".to_owned(), dir_diff::DifferenceKind::OnlyInLeft),
+            ("dirB".to_owned(), dir_diff::DifferenceKind::OnlyInRight),
+        ]
+    );
+}
+
+#[test]
+fn compare_many_preserves_order() {
+    let reports = dir_diff::compare_many(vec![
+        ("tests/easy/good/dir1".into(), "tests/easy/good/dir2".into()),
+        ("tests/easy/bad/dir1".into(), "tests/easy/bad/dir2".into()),
+        ("does_not_exist".into(), "tests/easy/good/dir1".into()),
+        (
+            "tests/binary/good/dir1".into(),
+            "tests/binary/good/dir2".into(),
+        ),
+    ]);
+    assert_eq!(reports.len(), 4);
+    assert!(!reports[0].as_ref().unwrap().is_different());
+    assert!(reports[1].as_ref().unwrap().is_different());
+    assert!(reports[2].is_err());
+    assert!(!reports[3].as_ref().unwrap().is_different());
+}
+
+#[test]
+fn compare_many_without_cache() {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Default)]
+    struct Misses(AtomicU64);
+
+    impl dir_diff::Metrics for Misses {
+        fn cache_miss(&self) {
+            self.0.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let dir =
+        std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("compare_many_without_cache");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left")).unwrap();
+    create_dir_all(dir.join("right")).unwrap();
+    let mut contents = vec![7; 4 * 1024 * 1024];
+    std::fs::write(dir.join("left/large.bin"), &contents).unwrap();
+    contents[0] = 8;
+    std::fs::write(dir.join("right/large.bin"), &contents).unwrap();
+
+    let misses = Arc::new(Misses::default());
+    let reports = dir_diff::DirDiffOptions::new()
+        .metrics(misses.clone())
+        .compare_many(vec![(dir.join("left"), dir.join("right"))])
+        .unwrap();
+    assert!(reports[0].as_ref().unwrap().is_different());
+    // Without a cache directory the files are compared chunk by chunk, not hashed in full.
+    assert_eq!(misses.0.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn compare_many_explain() {
+    let reports = dir_diff::DirDiffOptions::new()
+        .explain(true)
+        // Keeps the second pair half explained while the first finishes, given two threads.
+        .on_enter_dir(|path| {
+            if path == std::path::Path::new("subdir") {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            }
+            dir_diff::Visit::Continue
+        })
+        .compare_many(vec![
+            ("tests/easy/bad/dir1".into(), "tests/easy/bad/dir2".into()),
+            ("tests/reflexive/dir1".into(), "tests/reflexive/dir2".into()),
+        ])
+        .unwrap();
+    let explained: Vec<Vec<_>> = reports
+        .iter()
+        .map(|report| {
+            let report = report.as_ref().unwrap();
+            assert!(report.is_different());
+            report
+                .explanation()
+                .unwrap()
+                .entries()
+                .iter()
+                .map(|entry| entry.path().to_str().unwrap().to_owned())
+                .collect()
+        })
+        .collect();
+    assert_eq!(
+        explained,
+        [
+            vec!["test.txt"],
+            vec!["one.txt", "subdir", "subdir/three.txt", "subdir/two.txt"],
+        ]
+    );
+}
+
+#[test]
+fn exclude() {
+    let options = dir_diff::DirDiffOptions::new().exclude("subdir/three.txt");
+    assert!(!options
+        .is_different("tests/reflexive/dir1", "tests/reflexive/dir2")
+        .unwrap());
+
+    let options = dir_diff::DirDiffOptions::new().exclude("dir*");
+    assert!(!options
+        .is_different("tests/dirs_differ/dir1", "tests/dirs_differ/dir2")
+        .unwrap());
+}
+
+#[test]
+fn exclude_invalid_glob() {
+    let options = dir_diff::DirDiffOptions::new().exclude("[");
+    assert!(matches!(
+        options.diff("tests/easy/good/dir1", "tests/easy/good/dir2"),
+        Err(dir_diff::Error::Glob { .. })
+    ));
+}
+
+#[test]
+fn small_buffer() {
+    let options = dir_diff::DirDiffOptions::new().max_buffer_size(7);
+    assert!(!options
+        .is_different("tests/binary/good/dir1", "tests/binary/good/dir2")
+        .unwrap());
+    assert!(options
+        .is_different("tests/binary/bad/dir1", "tests/binary/bad/dir2")
+        .unwrap());
+}
+
+#[test]
+fn sequential_reads() {
+    let options = dir_diff::DirDiffOptions::new().sequential_reads(true);
+    assert!(!options
+        .is_different("tests/binary/good/dir1", "tests/binary/good/dir2")
+        .unwrap());
+    assert!(options
+        .is_different("tests/binary/bad/dir1", "tests/binary/bad/dir2")
+        .unwrap());
+
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("sequential_reads");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left")).unwrap();
+    create_dir_all(dir.join("right")).unwrap();
+    let mut contents = vec![7; 9 * 1024 * 1024 + 5];
+    std::fs::write(dir.join("left/large"), &contents).unwrap();
+    std::fs::write(dir.join("right/large"), &contents).unwrap();
+    assert!(!options
+        .is_different(dir.join("left"), dir.join("right"))
+        .unwrap());
+    *contents.last_mut().unwrap() = 8;
+    std::fs::write(dir.join("right/large"), &contents).unwrap();
+    assert!(options
+        .is_different(dir.join("left"), dir.join("right"))
+        .unwrap());
+}
+
+#[test]
+fn names_only_diff() {
+    assert!(
+        !dir_diff::names_only_diff("tests/binary/bad/dir1", "tests/binary/bad/dir2")
+            .unwrap()
+            .is_different()
+    );
+
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("names_only_diff");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left/same")).unwrap();
+    create_dir_all(dir.join("left/gone/deeper")).unwrap();
+    create_dir_all(dir.join("left/was_dir/inside")).unwrap();
+    create_dir_all(dir.join("right/same")).unwrap();
+    create_dir_all(dir.join("right/new")).unwrap();
+    std::fs::write(dir.join("left/same/file"), "left").unwrap();
+    std::fs::write(dir.join("right/same/file"), "right").unwrap();
+    std::fs::write(dir.join("right/new/file"), "").unwrap();
+    std::fs::write(dir.join("right/was_dir"), "").unwrap();
+    std::fs::write(dir.join("left/excluded"), "").unwrap();
+
+    let names = dir_diff::DirDiffOptions::new()
+        .exclude("excluded")
+        .names_only_diff(dir.join("left"), dir.join("right"))
+        .unwrap();
+    assert!(names.is_different());
+    let paths = |paths: &[&str]| paths.iter().map(std::path::PathBuf::from).collect();
+    assert_eq!(*names.added(), paths(&["new", "new/file"]));
+    assert_eq!(*names.removed(), paths(&["gone", "gone/deeper"]));
+    assert_eq!(*names.type_changed(), paths(&["was_dir"]));
+
+    let names = dir_diff::DirDiffOptions::new()
+        .ignore_extra(dir_diff::Side::Right, "new")
+        .names_only_diff(dir.join("left"), dir.join("right"))
+        .unwrap();
+    assert!(names.added().is_empty());
+    assert!(matches!(
+        dir_diff::names_only_diff(dir.join("missing"), dir.join("right")),
+        Err(dir_diff::Error::RootMissing { .. })
+    ));
+}
+
+#[test]
+fn size_tolerance() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("size_tolerance");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left")).unwrap();
+    create_dir_all(dir.join("right")).unwrap();
+    std::fs::write(dir.join("left/run.log"), "started\n").unwrap();
+    std::fs::write(dir.join("right/run.log"), "started\nfinished\n").unwrap();
+    std::fs::write(dir.join("left/data"), "same").unwrap();
+    std::fs::write(dir.join("right/data"), "same").unwrap();
+
+    let options = dir_diff::DirDiffOptions::new();
+    assert!(options
+        .is_different(dir.join("left"), dir.join("right"))
+        .unwrap());
+    assert!(!options
+        .clone()
+        .size_tolerance("*.log", 9)
+        .is_different(dir.join("left"), dir.join("right"))
+        .unwrap());
+    let report = options
+        .size_tolerance("*.log", 1024)
+        .size_tolerance("run.log", 8)
+        .diff(dir.join("left"), dir.join("right"))
+        .unwrap();
+    let differences: Vec<_> = report
+        .differences()
+        .iter()
+        .map(|d| (d.path().to_path_buf(), d.kind()))
+        .collect();
+    assert_eq!(
+        differences,
+        [(
+            std::path::PathBuf::from("run.log"),
+            dir_diff::DifferenceKind::Content
+        )]
+    );
+
+    // Contents within the tolerance aren't read.
+    std::fs::write(dir.join("right/data"), "diff").unwrap();
+    assert!(!dir_diff::DirDiffOptions::new()
+        .size_tolerance("*.log", 9)
+        .size_tolerance("data", 0)
+        .is_different(dir.join("left"), dir.join("right"))
+        .unwrap());
+}
+
+#[test]
+fn compare_record_counts() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("compare_record_counts");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left")).unwrap();
+    create_dir_all(dir.join("right")).unwrap();
+    std::fs::write(dir.join("left/rows.jsonl"), "{\"id\":1}\n{\"id\":22}\n").unwrap();
+    std::fs::write(dir.join("right/rows.jsonl"), "{\"id\":22}\n{\"id\":1}").unwrap();
+    std::fs::write(dir.join("left/ids"), "1;2;3").unwrap();
+    std::fs::write(dir.join("right/ids"), "3;1;2;").unwrap();
+
+    let options = dir_diff::DirDiffOptions::new().compare_record_counts("*.jsonl", b'\n');
+    let report = options
+        .clone()
+        .diff(dir.join("left"), dir.join("right"))
+        .unwrap();
+    let differences: Vec<_> = report
+        .differences()
+        .iter()
+        .map(|d| d.path().to_path_buf())
+        .collect();
+    assert_eq!(differences, [std::path::PathBuf::from("ids")]);
+    assert!(!options
+        .compare_record_counts("ids", b';')
+        .is_different(dir.join("left"), dir.join("right"))
+        .unwrap());
+
+    std::fs::write(dir.join("right/rows.jsonl"), "{\"id\":1}\n").unwrap();
+    assert!(dir_diff::DirDiffOptions::new()
+        .compare_record_counts("**", b'\n')
+        .exclude("ids")
+        .is_different(dir.join("left"), dir.join("right"))
+        .unwrap());
+}
+
+#[test]
+fn comparator() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("comparator");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left")).unwrap();
+    create_dir_all(dir.join("right")).unwrap();
+    // The same message, with its two fields written in either order.
+    std::fs::write(
+        dir.join("left/events.pb"),
+        [5, 0x08, 0x01, 0x10, 0x96, 0x01],
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("right/events.pb"),
+        [5, 0x10, 0x96, 0x01, 0x08, 0x01],
+    )
+    .unwrap();
+    std::fs::write(dir.join("left/notes.txt"), "Hello").unwrap();
+    std::fs::write(dir.join("right/notes.txt"), "HELLO").unwrap();
+
+    let report = dir_diff::DirDiffOptions::new()
+        .comparator("pb", dir_diff::DelimitedProtobuf)
+        .diff(dir.join("left"), dir.join("right"))
+        .unwrap();
+    let differences: Vec<_> = report
+        .differences()
+        .iter()
+        .map(|d| d.path().to_path_buf())
+        .collect();
+    assert_eq!(differences, [std::path::PathBuf::from("notes.txt")]);
+
+    let compared = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let counter = compared.clone();
+    assert!(!dir_diff::DirDiffOptions::new()
+        .comparator("pb", dir_diff::DelimitedProtobuf)
+        .comparator(".txt", move |left: &[u8], right: &[u8]| {
+            counter.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            left.eq_ignore_ascii_case(right)
+        })
+        .is_different(dir.join("left"), dir.join("right"))
+        .unwrap());
+    assert_eq!(compared.load(std::sync::atomic::Ordering::Relaxed), 1);
+}
+
+#[test]
+#[cfg(unix)]
+fn compare_with_command() {
+    let options = dir_diff::DirDiffOptions::new().compare_with_command("*.png", "cmp -s %L %R");
+    assert!(!options
+        .is_different("tests/binary/good/dir1", "tests/binary/good/dir2")
+        .unwrap());
+    assert!(options
+        .is_different("tests/binary/bad/dir1", "tests/binary/bad/dir2")
+        .unwrap());
+
+    // The command has the last word, even on files of different sizes.
+    let options = options.compare_with_command("**/rust-logo.png", "true");
+    assert!(!options
+        .is_different("tests/binary/bad/dir1", "tests/binary/bad/dir2")
+        .unwrap());
+
+    let err = dir_diff::DirDiffOptions::new()
+        .compare_with_command("*.png", "cmp %L %R.missing")
+        .diff("tests/binary/good/dir1", "tests/binary/good/dir2")
+        .unwrap_err();
+    let dir_diff::Error::Command {
+        command,
+        path,
+        status,
+        stderr,
+    } = err
+    else {
+        panic!("unexpected error: {err}");
+    };
+    assert_eq!(command, "cmp %L %R.missing");
+    assert!(path.ends_with("rust-logo.png"), "{}", path.display());
+    assert_eq!(status.code(), Some(2));
+    assert!(stderr.contains("missing"), "{stderr}");
+
+    let err = dir_diff::DirDiffOptions::new()
+        .compare_with_command("*.png", "dir-diff-no-such-command %L %R")
+        .diff("tests/binary/good/dir1", "tests/binary/good/dir2")
+        .unwrap_err();
+    assert_eq!(
+        err.io_error().map(std::io::Error::kind),
+        Some(std::io::ErrorKind::NotFound)
+    );
+}
+
+#[test]
+fn mmap_threshold() {
+    let options = dir_diff::DirDiffOptions::new().mmap_threshold(0);
+    assert!(!options
+        .is_different("tests/binary/good/dir1", "tests/binary/good/dir2")
+        .unwrap());
+    assert!(options
+        .is_different("tests/binary/bad/dir1", "tests/binary/bad/dir2")
+        .unwrap());
+
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("mmap_threshold");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left")).unwrap();
+    create_dir_all(dir.join("right")).unwrap();
+    let mut contents = vec![7; 3 * 1024 * 1024 + 5];
+    std::fs::write(dir.join("left/large"), &contents).unwrap();
+    std::fs::write(dir.join("right/large"), &contents).unwrap();
+    assert!(!options
+        .is_different(dir.join("left"), dir.join("right"))
+        .unwrap());
+    *contents.last_mut().unwrap() = 8;
+    std::fs::write(dir.join("right/large"), &contents).unwrap();
+    assert!(options
+        .is_different(dir.join("left"), dir.join("right"))
+        .unwrap());
+}
+
+#[test]
+fn compare_root_names() {
+    let options = dir_diff::DirDiffOptions::new().compare_root_names(true);
+    assert!(!dir_diff::is_different("tests/easy/good/dir1", "tests/easy/good/dir2").unwrap());
+    let report = options
+        .diff("tests/easy/good/dir1", "tests/easy/good/dir2")
+        .unwrap();
+    let differences: Vec<_> = report
+        .iter()
+        .map(|d| (d.path().to_path_buf(), d.kind()))
+        .collect();
+    assert_eq!(
+        differences,
+        [(
+            std::path::PathBuf::new(),
+            dir_diff::DifferenceKind::RootName
+        )]
+    );
+
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("compare_root_names");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("out/foo-v1")).unwrap();
+    create_dir_all(dir.join("golden/foo-v1")).unwrap();
+    assert!(!options
+        .is_different(dir.join("out/foo-v1"), dir.join("golden/foo-v1"))
+        .unwrap());
+    assert!(!options
+        .is_different(dir.join("out/foo-v1/."), dir.join("golden/foo-v1"))
+        .unwrap());
+}
+
+#[test]
+#[cfg(unix)]
+fn check_portable_names() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("check_portable_names");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left/aux")).unwrap();
+    create_dir_all(dir.join("right")).unwrap();
+    for file in ["left/aux/file", "right/nul.txt", "right/trailing."] {
+        std::fs::write(dir.join(file), "").unwrap();
+    }
+
+    let report = dir_diff::DirDiffOptions::new()
+        .check_portable_names(true)
+        .diff(dir.join("left"), dir.join("right"))
+        .unwrap();
+    let issues: Vec<_> = report
+        .name_issues()
+        .iter()
+        .map(|issue| {
+            (
+                issue.path().to_str().unwrap(),
+                issue.side(),
+                issue.problem(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        issues,
+        [
+            ("aux", dir_diff::Side::Left, dir_diff::NameProblem::Reserved),
+            (
+                "nul.txt",
+                dir_diff::Side::Right,
+                dir_diff::NameProblem::Reserved
+            ),
+            (
+                "trailing.",
+                dir_diff::Side::Right,
+                dir_diff::NameProblem::TrailingDotOrSpace
+            ),
+        ]
+    );
+
+    let report = dir_diff::diff(dir.join("left"), dir.join("right")).unwrap();
+    assert!(report.name_issues().is_empty());
+}
+
+#[test]
+fn normalize_encoding() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("normalize_encoding");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left")).unwrap();
+    create_dir_all(dir.join("right")).unwrap();
+    std::fs::write(dir.join("left/bom"), "\u{feff}text").unwrap();
+    std::fs::write(dir.join("right/bom"), "text").unwrap();
+    let utf16: Vec<u8> = "\u{feff}text"
+        .encode_utf16()
+        .flat_map(u16::to_le_bytes)
+        .collect();
+    std::fs::write(dir.join("left/utf16"), utf16).unwrap();
+    std::fs::write(dir.join("right/utf16"), "text").unwrap();
+
+    let changed = |options: dir_diff::DirDiffOptions| -> Vec<_> {
+        options
+            .diff(dir.join("left"), dir.join("right"))
+            .unwrap()
+            .iter()
+            .map(|d| d.path().to_str().unwrap().to_owned())
+            .collect()
+    };
+    assert_eq!(changed(dir_diff::DirDiffOptions::new()), ["bom", "utf16"]);
+    assert_eq!(
+        changed(
+            dir_diff::DirDiffOptions::new()
+                .normalize_encoding(dir_diff::EncodingNormalization::StripBom)
+        ),
+        ["utf16"]
+    );
+    assert!(changed(
+        dir_diff::DirDiffOptions::new()
+            .normalize_encoding(dir_diff::EncodingNormalization::TranscodeUtf16)
+    )
+    .is_empty());
+}
+
+#[test]
+fn ignore_whitespace() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("ignore_whitespace");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left/reports")).unwrap();
+    create_dir_all(dir.join("right/reports")).unwrap();
+    for (file, left, right) in [
+        ("reports/trailing.txt", "total: 3\n", "total: 3  \r\n"),
+        ("reports/runs.txt", "total: 3\n", "total:\t 3\n"),
+        ("other.txt", "total: 3\n", "total: 3 \n"),
+    ] {
+        std::fs::write(dir.join("left").join(file), left).unwrap();
+        std::fs::write(dir.join("right").join(file), right).unwrap();
+    }
+
+    let changed = |options: dir_diff::DirDiffOptions| -> Vec<_> {
+        options
+            .diff(dir.join("left"), dir.join("right"))
+            .unwrap()
+            .iter()
+            .map(|d| d.path().to_str().unwrap().to_owned())
+            .collect()
+    };
+    assert_eq!(
+        changed(
+            dir_diff::DirDiffOptions::new()
+                .ignore_whitespace("reports/*", dir_diff::Whitespace::Trailing)
+        ),
+        ["other.txt", "reports/runs.txt"]
+    );
+    assert_eq!(
+        changed(
+            dir_diff::DirDiffOptions::new()
+                .ignore_whitespace("reports/*", dir_diff::Whitespace::Trailing)
+                .ignore_whitespace("*/runs.txt", dir_diff::Whitespace::Runs)
+        ),
+        ["other.txt"]
+    );
+    assert!(matches!(
+        dir_diff::DirDiffOptions::new()
+            .ignore_whitespace("[", dir_diff::Whitespace::Runs)
+            .diff(dir.join("left"), dir.join("right")),
+        Err(dir_diff::Error::Glob { .. })
+    ));
+}
+
+#[test]
+fn ignore_line_order() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("ignore_line_order");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left")).unwrap();
+    create_dir_all(dir.join("right")).unwrap();
+    for (file, left, right) in [
+        ("shuffled.txt", "b = 2\na = 1\n", "a = 1  \nb = 2"),
+        ("repeated.txt", "a\na\nb\n", "a\nb\nb\n"),
+    ] {
+        std::fs::write(dir.join("left").join(file), left).unwrap();
+        std::fs::write(dir.join("right").join(file), right).unwrap();
+    }
+
+    let report = dir_diff::DirDiffOptions::new()
+        .ignore_line_order("*.txt")
+        .ignore_whitespace("shuffled.txt", dir_diff::Whitespace::Trailing)
+        .diff(dir.join("left"), dir.join("right"))
+        .unwrap();
+    let differences: Vec<_> = report
+        .differences()
+        .iter()
+        .map(|d| d.path().to_path_buf())
+        .collect();
+    assert_eq!(differences, [std::path::PathBuf::from("repeated.txt")]);
+}
+
+#[test]
+fn detect_encoding_changes() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("detect_encoding_changes");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left")).unwrap();
+    create_dir_all(dir.join("right")).unwrap();
+    std::fs::write(dir.join("left/latin1.txt"), "café\n").unwrap();
+    std::fs::write(dir.join("right/latin1.txt"), b"caf\xe9\n").unwrap();
+    std::fs::write(dir.join("left/changed.txt"), "café\n").unwrap();
+    std::fs::write(dir.join("right/changed.txt"), b"cafe\n").unwrap();
+
+    let kinds = |options: dir_diff::DirDiffOptions| -> Vec<_> {
+        options
+            .diff(dir.join("left"), dir.join("right"))
+            .unwrap()
+            .differences()
+            .iter()
+            .map(|d| (d.path().to_path_buf(), d.kind()))
+            .collect()
+    };
+    assert_eq!(
+        kinds(dir_diff::DirDiffOptions::new()),
+        [
+            (
+                std::path::PathBuf::from("changed.txt"),
+                dir_diff::DifferenceKind::Content
+            ),
+            (
+                std::path::PathBuf::from("latin1.txt"),
+                dir_diff::DifferenceKind::Content
+            ),
+        ]
+    );
+    assert_eq!(
+        kinds(dir_diff::DirDiffOptions::new().detect_encoding_changes(true)),
+        [
+            (
+                std::path::PathBuf::from("changed.txt"),
+                dir_diff::DifferenceKind::Content
+            ),
+            (
+                std::path::PathBuf::from("latin1.txt"),
+                dir_diff::DifferenceKind::Encoding
+            ),
+        ]
+    );
+}
+
+#[test]
+fn compare_many_with_open_file_limit() {
+    let reports = dir_diff::DirDiffOptions::new()
+        .max_open_files(2)
+        .compare_many(vec![
+            ("tests/easy/good/dir1".into(), "tests/easy/good/dir2".into()),
+            ("tests/easy/bad/dir1".into(), "tests/easy/bad/dir2".into()),
+            (
+                "tests/binary/good/dir1".into(),
+                "tests/binary/good/dir2".into(),
+            ),
+        ])
+        .unwrap();
+    assert!(!reports[0].as_ref().unwrap().is_different());
+    assert!(reports[1].as_ref().unwrap().is_different());
+    assert!(!reports[2].as_ref().unwrap().is_different());
+}
+
+#[test]
+fn overlapping_roots() {
+    assert!(matches!(
+        dir_diff::is_different("tests/reflexive/dir1", "tests/reflexive/dir1/"),
+        Err(dir_diff::Error::OverlappingRoots { .. })
+    ));
+    assert!(matches!(
+        dir_diff::diff("tests/reflexive", "tests/reflexive/dir1"),
+        Err(dir_diff::Error::OverlappingRoots { .. })
+    ));
+    assert!(matches!(
+        dir_diff::diff("tests/reflexive/dir2/subdir", "tests/reflexive/dir2"),
+        Err(dir_diff::Error::OverlappingRoots { .. })
+    ));
+}
+
+#[test]
+fn contains() {
+    assert!(dir_diff::contains("tests/reflexive/dir2", "tests/reflexive/dir1").unwrap());
+    assert!(!dir_diff::contains("tests/reflexive/dir1", "tests/reflexive/dir2").unwrap());
+    assert!(!dir_diff::contains("tests/easy/bad/dir1", "tests/easy/bad/dir2").unwrap());
+}
+
+#[test]
+fn allow_different() {
+    let options = dir_diff::DirDiffOptions::new().allow_different("subdir/three.txt");
+    assert!(!options
+        .is_different("tests/reflexive/dir1", "tests/reflexive/dir2")
+        .unwrap());
+    let report = options
+        .diff("tests/reflexive/dir1", "tests/reflexive/dir2")
+        .unwrap();
+    assert!(!report.is_different());
+    assert_eq!(report.allowed().len(), 1);
+    assert_eq!(
+        report.allowed()[0].path(),
+        std::path::Path::new("subdir/three.txt")
+    );
+
+    let options = dir_diff::DirDiffOptions::new().allow_different("*.txt");
+    assert!(!options
+        .is_different("tests/easy/bad/dir1", "tests/easy/bad/dir2")
+        .unwrap());
+    assert!(options
+        .is_different("tests/dirs_differ/dir1", "tests/dirs_differ/dir2")
+        .unwrap());
+}
+
+#[test]
+fn query_report() {
+    let report = dir_diff::diff("tests/reflexive/dir1", "tests/reflexive/dir2").unwrap();
+    assert!(!report.is_empty());
+    assert_eq!(report.iter().count(), 1);
+    assert_eq!(report.only_in_left().count(), 0);
+    assert_eq!(report.changed().count(), 0);
+    let added: Vec<_> = report.only_in_right().map(|d| d.path()).collect();
+    assert_eq!(added, [std::path::Path::new("subdir/three.txt")]);
+    assert_eq!(report.iter().filter_paths("subdir/*").unwrap().count(), 1);
+    assert_eq!(report.iter().filter_paths("*.rs").unwrap().count(), 0);
+    assert!(report.iter().filter_paths("[").is_err());
+    assert_eq!(
+        report.resolve(added[0], dir_diff::Side::Right),
+        std::path::Path::new("tests/reflexive/dir2/subdir/three.txt")
+    );
+    assert!(!added[0].resolve(report.left()).exists());
+
+    let report = dir_diff::diff("tests/easy/bad/dir1", "tests/easy/bad/dir2").unwrap();
+    assert_eq!(report.changed().count(), 1);
+    assert!(
+        dir_diff::diff("tests/easy/good/dir1", "tests/easy/good/dir2")
+            .unwrap()
+            .is_empty()
+    );
+}
+
+#[test]
+fn largest() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("largest");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left")).unwrap();
+    create_dir_all(dir.join("right")).unwrap();
+    let files: [(&str, &[u8], &[u8]); 4] = [
+        ("big", &[0; 1000], &[1; 1000]),
+        ("grown", b"abc", b"abcdefgh"),
+        ("same", b"same", b"same"),
+        ("tweaked", &[0; 5000], &[0; 4999]),
+    ];
+    for (name, left, right) in files {
+        std::fs::write(dir.join("left").join(name), left).unwrap();
+        std::fs::write(dir.join("right").join(name), right).unwrap();
+    }
+    std::fs::write(dir.join("left/only"), [0; 9000]).unwrap();
+
+    let report = dir_diff::diff(dir.join("left"), dir.join("right")).unwrap();
+    let ranked = |ranking| -> Vec<_> {
+        report
+            .largest(2, ranking)
+            .unwrap()
+            .into_iter()
+            .map(|(d, measure)| (d.path().to_str().unwrap(), measure))
+            .collect()
+    };
+    assert_eq!(
+        ranked(dir_diff::Ranking::Size),
+        [("tweaked", 5000), ("big", 1000)]
+    );
+    assert_eq!(
+        ranked(dir_diff::Ranking::DifferingBytes),
+        [("big", 1000), ("grown", 5)]
+    );
+}
+
+#[test]
+fn similarity() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("similarity");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left")).unwrap();
+    create_dir_all(dir.join("right")).unwrap();
+    let files: [(&str, &[u8], &[u8]); 3] = [
+        ("binary", &[0, 159, 146, 150], &[0, 159, 146, 151]),
+        ("rewritten", b"a\nb\n", b"c\nd\n"),
+        ("tweaked", b"1\n2\n3\n4\n", b"1\n2\nthree\n4\n"),
+    ];
+    for (name, left, right) in files {
+        std::fs::write(dir.join("left").join(name), left).unwrap();
+        std::fs::write(dir.join("right").join(name), right).unwrap();
+    }
+
+    let report = dir_diff::DirDiffOptions::new()
+        .similarity(true)
+        .diff(dir.join("left"), dir.join("right"))
+        .unwrap();
+    let ratios: Vec<_> = report
+        .iter()
+        .map(|d| d.similarity().map(|similarity| similarity.ratio()))
+        .collect();
+    assert_eq!(ratios, [None, Some(0.0), Some(0.75)]);
+    let tweaked = report.differences()[2].similarity().unwrap();
+    assert_eq!((tweaked.common_lines(), tweaked.total_lines()), (6, 8));
+
+    let report = dir_diff::diff(dir.join("left"), dir.join("right")).unwrap();
+    assert!(report.iter().all(|d| d.similarity().is_none()));
+}
+
+#[test]
+fn summarize_blocks() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("summarize_blocks");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left")).unwrap();
+    create_dir_all(dir.join("right")).unwrap();
+    // Pseudo-random, so no block turns up anywhere but where it belongs.
+    let mut state = 1u32;
+    let original: Vec<u8> = (0..10_000)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (state >> 16) as u8
+        })
+        .collect();
+    let mut patched = original.clone();
+    patched[3] = 0xff;
+    let corrupted: Vec<u8> = original.iter().map(|byte| byte ^ 0x55).collect();
+    std::fs::write(dir.join("left/corrupted"), &original).unwrap();
+    std::fs::write(dir.join("right/corrupted"), corrupted).unwrap();
+    std::fs::write(dir.join("left/patched"), &original).unwrap();
+    std::fs::write(dir.join("right/patched"), patched).unwrap();
+
+    let report = dir_diff::DirDiffOptions::new()
+        .summarize_blocks(1000)
+        .diff(dir.join("left"), dir.join("right"))
+        .unwrap();
+    let blocks: Vec<_> = report
+        .iter()
+        .map(|d| {
+            let blocks = d.blocks().unwrap();
+            (blocks.blocks(), blocks.matching_blocks())
+        })
+        .collect();
+    assert_eq!(blocks, [(10, 0), (10, 9)]);
+
+    let report = dir_diff::diff(dir.join("left"), dir.join("right")).unwrap();
+    assert!(report.iter().all(|d| d.blocks().is_none()));
+}
+
+#[test]
+fn estimate() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("estimate");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left/sub")).unwrap();
+    create_dir_all(dir.join("right/sub")).unwrap();
+    for (file, contents) in [
+        ("left/a.txt", "hello"),
+        ("left/sub/b.txt", "xy"),
+        ("left/c.txt", "1234"),
+        ("right/a.txt", "HELLO"),
+        ("right/sub/b.txt", "xyz"),
+    ] {
+        std::fs::write(dir.join(file), contents).unwrap();
+    }
+
+    let estimate = dir_diff::estimate(dir.join("left"), dir.join("right")).unwrap();
+    let counts = |side| (estimate.entries(side), estimate.bytes(side));
+    assert_eq!(counts(dir_diff::Side::Left), (4, 11));
+    assert_eq!(counts(dir_diff::Side::Right), (3, 8));
+    assert_eq!(estimate.bytes_to_compare(), 10);
+
+    let estimate = dir_diff::DirDiffOptions::new()
+        .exclude("c.txt")
+        .estimate(dir.join("left"), dir.join("right"))
+        .unwrap();
+    assert_eq!(estimate.entries(dir_diff::Side::Left), 3);
+    assert!(matches!(
+        dir_diff::estimate(dir.join("missing"), dir.join("right")),
+        Err(dir_diff::Error::RootMissing { .. })
+    ));
+}
+
+#[test]
+fn compare_to_baseline() {
+    let baseline = dir_diff::diff("tests/reflexive/dir1", "tests/reflexive/dir2").unwrap();
+    let report = dir_diff::diff("tests/easy/bad/dir1", "tests/easy/bad/dir2").unwrap();
+
+    let delta = report.compare_to_baseline(&baseline);
+    assert!(delta.has_regressions());
+    assert_eq!(delta.introduced(), report.differences());
+    assert_eq!(delta.resolved(), baseline.differences());
+
+    assert!(!baseline.compare_to_baseline(&baseline).has_regressions());
+}
+
+#[test]
+fn error_context() {
+    let err = dir_diff::diff("tests/easy/good/dir1", "does_not_exist").unwrap_err();
+    assert!(matches!(
+        err,
+        dir_diff::Error::RootMissing {
+            side: dir_diff::Side::Right,
+            ..
+        }
+    ));
+    assert_eq!(err.path(), Some(std::path::Path::new("does_not_exist")));
+    assert_eq!(
+        err.phase(),
+        Some(dir_diff::Phase::Walking(dir_diff::Side::Right))
+    );
+    assert_eq!(err.side(), Some(dir_diff::Side::Right));
+    assert_eq!(
+        err.to_string(),
+        "The right root does_not_exist doesn't exist"
+    );
+
+    let err = dir_diff::DirDiffOptions::new()
+        .exclude("[")
+        .diff("tests/easy/good/dir1", "tests/easy/good/dir2")
+        .unwrap_err();
+    assert_eq!(err.phase(), Some(dir_diff::Phase::Configuring));
+    assert!(err.to_string().starts_with("Glob error in `[`"));
+    assert!(std::error::Error::source(&err).is_some());
+}
+
+#[test]
+fn cancelled() {
+    let token = dir_diff::CancelToken::new();
+    let options = dir_diff::DirDiffOptions::new().cancel_token(token.clone());
+    assert!(options
+        .is_different("tests/easy/bad/dir1", "tests/easy/bad/dir2")
+        .unwrap());
+
+    token.cancel();
+    assert!(matches!(
+        options.diff("tests/easy/bad/dir1", "tests/easy/bad/dir2"),
+        Err(dir_diff::Error::Cancelled)
+    ));
+}
+
+#[test]
+fn validate() {
+    let options = dir_diff::DirDiffOptions::new();
+    options
+        .validate("tests/easy/good/dir1", "tests/easy/good/dir2")
+        .unwrap();
+    assert!(matches!(
+        options.validate("tests/easy/good/dir1", "does_not_exist"),
+        Err(dir_diff::Error::RootMissing {
+            side: dir_diff::Side::Right,
+            ..
+        })
+    ));
+    assert!(matches!(
+        options.validate("tests/easy", "tests/easy/good"),
+        Err(dir_diff::Error::OverlappingRoots { .. })
+    ));
+    let err = options
+        .validate("tests/easy/good/dir1/test.txt", "tests/easy/good/dir2")
+        .unwrap_err();
+    assert_eq!(
+        err.path(),
+        Some(std::path::Path::new("tests/easy/good/dir1/test.txt"))
+    );
+
+    assert!(matches!(
+        options
+            .clone()
+            .exclude("[")
+            .validate("tests/easy/good/dir1", "tests/easy/good/dir2"),
+        Err(dir_diff::Error::Glob { .. })
+    ));
+    assert!(options
+        .cache_dir("tests/easy/good/dir1/test.txt")
+        .validate("tests/easy/good/dir1", "tests/easy/good/dir2")
+        .is_err());
+}
+
+#[test]
+fn validate_conflicts() {
+    let validate = |options: dir_diff::DirDiffOptions| match options
+        .validate("tests/easy/good/dir1", "tests/easy/good/dir2")
+    {
+        Err(dir_diff::Error::ConflictingOptions {
+            option,
+            other,
+            glob,
+        }) => Some((option, other, glob)),
+        Err(err) => panic!("{err}"),
+        Ok(()) => None,
+    };
+    let tolerant = || dir_diff::DirDiffOptions::new().size_tolerance("*.log", 16);
+
+    assert_eq!(
+        validate(tolerant().ignore_whitespace("*.log", dir_diff::Whitespace::Runs)),
+        Some(("size_tolerance", "ignore_whitespace", "*.log".to_owned()))
+    );
+    assert_eq!(
+        validate(tolerant().normalize_encoding(dir_diff::EncodingNormalization::StripBom)),
+        Some(("size_tolerance", "normalize_encoding", "*.log".to_owned()))
+    );
+    assert_eq!(
+        validate(tolerant().compare_with_command("*.log", "cmp -s %L %R")),
+        Some(("size_tolerance", "compare_with_command", "*.log".to_owned()))
+    );
+    assert_eq!(
+        validate(tolerant().placeholder("*.log", "{{PID}}", r"\d+")),
+        Some(("size_tolerance", "placeholder", "*.log".to_owned()))
+    );
+    assert_eq!(
+        validate(
+            dir_diff::DirDiffOptions::new()
+                .ignore_line_order("*.txt")
+                .compare_with_command("*.txt", "cmp -s %L %R")
+        ),
+        Some((
+            "ignore_line_order",
+            "compare_with_command",
+            "*.txt".to_owned()
+        ))
+    );
+    assert_eq!(
+        validate(tolerant().placeholder("**/*.log", "{{PID}}", r"\d+")),
+        Some(("size_tolerance", "placeholder", "*.log".to_owned()))
+    );
+    assert_eq!(
+        validate(
+            dir_diff::DirDiffOptions::new()
+                .compare_record_counts("export/*", b'\n')
+                .ignore_whitespace("export/rows.txt", dir_diff::Whitespace::Runs)
+        ),
+        Some((
+            "compare_record_counts",
+            "ignore_whitespace",
+            "export/*".to_owned()
+        ))
+    );
+    assert_eq!(
+        validate(tolerant().comparator("log", |_: &[u8], _: &[u8]| true)),
+        Some(("size_tolerance", "comparator", "*.log".to_owned()))
+    );
+
+    assert_eq!(
+        validate(
+            tolerant()
+                .ignore_whitespace("*.txt", dir_diff::Whitespace::Runs)
+                .compare_with_command("*.bin", "cmp -s %L %R")
+                .ignore_line_order("*.txt")
+        ),
+        None
+    );
+    let err = tolerant()
+        .placeholder("*.log", "{{PID}}", r"\d+")
+        .validate("tests/easy/good/dir1", "tests/easy/good/dir2")
+        .unwrap_err();
+    assert_eq!(err.phase(), Some(dir_diff::Phase::Configuring));
+    assert_eq!(
+        err.to_string(),
+        "Options `size_tolerance` and `placeholder` can't both apply to `*.log`"
+    );
+}
+
+#[test]
+fn metadata_snapshot() {
+    let report = dir_diff::diff("tests/easy/bad/dir1", "tests/easy/bad/dir2").unwrap();
+    let difference = &report.differences()[0];
+    for side in [dir_diff::Side::Left, dir_diff::Side::Right] {
+        let metadata = difference.metadata(side).unwrap();
+        let actual = std::fs::metadata(report.resolve(difference.path(), side)).unwrap();
+        assert_eq!(metadata.file_type(), dir_diff::source::FileType::File);
+        assert_eq!(metadata.len(), actual.len());
+        assert_eq!(metadata.modified(), actual.modified().ok());
+        #[cfg(unix)]
+        assert_eq!(metadata.permissions(), {
+            use std::os::unix::fs::PermissionsExt as _;
+            Some(actual.permissions().mode() & 0o7777)
+        });
+    }
+
+    let report = dir_diff::diff("tests/reflexive/dir1", "tests/reflexive/dir2").unwrap();
+    let difference = &report.differences()[0];
+    assert!(difference.metadata(dir_diff::Side::Left).is_none());
+    assert_eq!(
+        difference
+            .metadata(dir_diff::Side::Right)
+            .unwrap()
+            .file_type(),
+        dir_diff::source::FileType::File
+    );
+}
+
+#[test]
+fn snapshot() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("snapshot");
+    let live = dir.join("left/sub/live.txt");
+    for mode in [dir_diff::Snapshot::HardLink, dir_diff::Snapshot::Copy] {
+        let _ = std::fs::remove_dir_all(&dir);
+        for side in ["left", "right"] {
+            create_dir_all(dir.join(side).join("sub")).unwrap();
+            std::fs::write(dir.join(side).join("sub/a.txt"), "a").unwrap();
+        }
+        // Another process writing to the tree once the comparison has started.
+        let write_live = {
+            let live = live.clone();
+            move |_: &std::path::Path| {
+                std::fs::write(&live, "live").unwrap();
+                dir_diff::Visit::Continue
+            }
+        };
+        let report = dir_diff::DirDiffOptions::new()
+            .snapshot(mode)
+            .on_enter_dir(write_live)
+            .diff(dir.join("left"), dir.join("right"))
+            .unwrap();
+        assert!(live.exists());
+        assert!(!report.is_different(), "{mode:?}: {report:?}");
+        assert_eq!(report.left(), dir.join("left"));
+    }
+
+    let err = dir_diff::DirDiffOptions::new()
+        .snapshot(dir_diff::Snapshot::Copy)
+        .diff("tests/easy/good/dir1", "does_not_exist")
+        .unwrap_err();
+    assert!(matches!(err, dir_diff::Error::RootMissing { .. }));
+    assert!(dir_diff::DirDiffOptions::new()
+        .snapshot(dir_diff::Snapshot::Copy)
+        .is_different("tests/easy/bad/dir1", "tests/easy/bad/dir2")
+        .unwrap());
+}
+
+#[test]
+fn hard_links() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("hard_links");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left")).unwrap();
+    create_dir_all(dir.join("right")).unwrap();
+    std::fs::write(dir.join("left/a.txt"), "shared").unwrap();
+    std::fs::hard_link(dir.join("left/a.txt"), dir.join("right/a.txt")).unwrap();
+
+    assert!(!dir_diff::is_different(dir.join("left"), dir.join("right")).unwrap());
+}
+
+#[test]
+fn walk_threads() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("walk_threads");
+    let _ = std::fs::remove_dir_all(&dir);
+    for side in ["left", "right"] {
+        for i in 0..20 {
+            create_dir_all(dir.join(side).join(format!("{i}/nested"))).unwrap();
+            std::fs::write(dir.join(side).join(format!("{i}/nested/file")), side).unwrap();
+        }
+    }
+    create_dir_all(dir.join("left/only/nested")).unwrap();
+
+    let expected = dir_diff::diff(dir.join("left"), dir.join("right")).unwrap();
+    assert_eq!(expected.differences().len(), 21);
+    let options = dir_diff::DirDiffOptions::new().walk_threads(4);
+    let report = options.diff(dir.join("left"), dir.join("right")).unwrap();
+    assert_eq!(report, expected);
+    assert!(options
+        .is_different(dir.join("left"), dir.join("right"))
+        .unwrap());
+}
+
+#[test]
+fn stable_order() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("stable_order");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left/a")).unwrap();
+    create_dir_all(dir.join("right/a")).unwrap();
+    // Created out of order, so listing order can't be relied on to sort them.
+    for name in ["b", "a.txt", "a/z", "B", "a-"] {
+        std::fs::write(dir.join("left").join(name), name).unwrap();
+    }
+
+    let report = dir_diff::diff(dir.join("left"), dir.join("right")).unwrap();
+    let paths: Vec<_> = report.iter().map(|d| d.path().to_owned()).collect();
+    assert_eq!(
+        paths,
+        ["B", "a/z", "a-", "a.txt", "b"].map(std::path::PathBuf::from)
+    );
+    assert!(paths.windows(2).all(|pair| pair[0] < pair[1]));
+}
+
+#[test]
+fn hooks() {
+    use std::sync::{Arc, Mutex};
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let (enter, skip, compare) = (events.clone(), events.clone(), events.clone());
+    let options = dir_diff::DirDiffOptions::new()
+        .exclude("one.txt")
+        .on_enter_dir(move |path| {
+            enter
+                .lock()
+                .unwrap()
+                .push(format!("enter {}", path.display()));
+            dir_diff::Visit::Skip
+        })
+        .on_skip(move |path, reason| {
+            skip.lock()
+                .unwrap()
+                .push(format!("skip {} {reason:?}", path.display()));
+        })
+        .on_compare_file(move |path, same| {
+            compare
+                .lock()
+                .unwrap()
+                .push(format!("compare {} {same}", path.display()));
+        });
+
+    let report = options
+        .diff("tests/reflexive/dir1", "tests/reflexive/dir2")
+        .unwrap();
+    assert!(!report.is_different());
+    assert_eq!(
+        *events.lock().unwrap(),
+        [
+            "skip one.txt Excluded(Left)",
+            "skip one.txt Excluded(Right)",
+            "enter subdir",
+            "skip subdir Hook",
+        ]
+    );
+
+    events.lock().unwrap().clear();
+    let options = dir_diff::DirDiffOptions::new().on_compare_file({
+        let events = events.clone();
+        move |path, same| {
+            events
+                .lock()
+                .unwrap()
+                .push(format!("compare {} {same}", path.display()));
+        }
+    });
+    options
+        .diff("tests/easy/bad/dir1", "tests/easy/bad/dir2")
+        .unwrap();
+    assert_eq!(*events.lock().unwrap(), ["compare test.txt false"]);
+}
+
+#[test]
+fn metrics() {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Default)]
+    struct Counters {
+        files: AtomicU64,
+        same: AtomicU64,
+        bytes: AtomicU64,
+        hits: AtomicU64,
+        misses: AtomicU64,
+        finished: AtomicU64,
+    }
+
+    impl dir_diff::Metrics for Counters {
+        fn file_compared(&self, same: bool, _elapsed: std::time::Duration) {
+            self.files.fetch_add(1, Ordering::Relaxed);
+            self.same.fetch_add(u64::from(same), Ordering::Relaxed);
+        }
+
+        fn bytes_read(&self, bytes: u64) {
+            self.bytes.fetch_add(bytes, Ordering::Relaxed);
+        }
+
+        fn cache_hit(&self) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn cache_miss(&self) {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+
+        fn finished(&self, _elapsed: std::time::Duration) {
+            self.finished.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("metrics");
+    let _ = std::fs::remove_dir_all(&dir);
+    let counters = Arc::new(Counters::default());
+    let options = dir_diff::DirDiffOptions::new()
+        .cache_dir(&dir)
+        .metrics(counters.clone());
+    for _ in 0..2 {
+        assert!(!options
+            .is_different("tests/easy/good/dir1", "tests/easy/good/dir2")
+            .unwrap());
+    }
+    let len = std::fs::metadata("tests/easy/good/dir1/test.txt")
+        .unwrap()
+        .len();
+
+    let count = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+    assert_eq!(count(&counters.files), 2);
+    assert_eq!(count(&counters.same), 2);
+    assert_eq!(count(&counters.bytes), 4 * len);
+    assert_eq!(count(&counters.misses), 2);
+    assert_eq!(count(&counters.hits), 2);
+    assert_eq!(count(&counters.finished), 2);
+}
+
+#[test]
+fn limits() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("limits");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left/sub")).unwrap();
+    create_dir_all(dir.join("right/sub")).unwrap();
+    for side in ["left", "right"] {
+        std::fs::write(dir.join(side).join("a.txt"), "hello").unwrap();
+        std::fs::write(dir.join(side).join("sub/b.txt"), "world").unwrap();
+    }
+
+    assert!(!dir_diff::DirDiffOptions::new()
+        .max_entries(3)
+        .max_total_bytes(20)
+        .is_different(dir.join("left"), dir.join("right"))
+        .unwrap());
+    assert!(matches!(
+        dir_diff::DirDiffOptions::new()
+            .max_entries(2)
+            .diff(dir.join("left"), dir.join("right")),
+        Err(dir_diff::Error::LimitExceeded {
+            limit: dir_diff::Limit::Entries(2),
+            side: Some(dir_diff::Side::Left),
+        })
+    ));
+    let err = dir_diff::DirDiffOptions::new()
+        .max_total_bytes(19)
+        .diff(dir.join("left"), dir.join("right"))
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Comparison stopped at more than 19 bytes of files to compare"
+    );
+}
+
+#[test]
+fn ignore_hidden() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("ignore_hidden");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left/.git")).unwrap();
+    create_dir_all(dir.join("right/sub")).unwrap();
+    for side in ["left", "right"] {
+        std::fs::write(dir.join(side).join("a.txt"), "hello").unwrap();
+    }
+    std::fs::write(dir.join("left/.DS_Store"), "finder").unwrap();
+    std::fs::write(dir.join("left/.git/HEAD"), "ref").unwrap();
+    std::fs::write(dir.join("right/sub/.directory"), "dolphin").unwrap();
+
+    assert!(dir_diff::is_different(dir.join("left"), dir.join("right")).unwrap());
+    let report = dir_diff::DirDiffOptions::new()
+        .ignore_hidden(true)
+        .diff(dir.join("left"), dir.join("right"))
+        .unwrap();
+    let paths: Vec<_> = report.iter().map(|d| d.path().to_path_buf()).collect();
+    assert_eq!(paths, [std::path::PathBuf::from("sub")]);
+}
+
+#[test]
+fn same_file_system() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("same_file_system");
+    let _ = std::fs::remove_dir_all(&dir);
+    for side in ["left", "right"] {
+        create_dir_all(dir.join(side).join("sub")).unwrap();
+    }
+    std::fs::write(dir.join("left/sub/a.txt"), "hello").unwrap();
+    std::fs::write(dir.join("right/sub/a.txt"), "goodbye").unwrap();
+
+    // Directories on the same file system as the root are still descended into.
+    let report = dir_diff::DirDiffOptions::new()
+        .same_file_system(true)
+        .diff(dir.join("left"), dir.join("right"))
+        .unwrap();
+    let paths: Vec<_> = report.iter().map(|d| d.path().to_path_buf()).collect();
+    assert_eq!(paths, [std::path::PathBuf::from("sub/a.txt")]);
+}
+
+#[test]
+fn empty_files_as_missing() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("empty_files_as_missing");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left/logs")).unwrap();
+    create_dir_all(dir.join("right")).unwrap();
+    std::fs::write(dir.join("left/logs/.keep"), "").unwrap();
+    std::fs::write(dir.join("left/a.txt"), "").unwrap();
+    std::fs::write(dir.join("right/a.txt"), "hello").unwrap();
+    std::fs::write(dir.join("right/placeholder"), "").unwrap();
+
+    let options = dir_diff::DirDiffOptions::new().empty_files_as_missing(true);
+    let report = options.diff(dir.join("left"), dir.join("right")).unwrap();
+    let paths: Vec<_> = report.iter().map(|d| d.path().to_path_buf()).collect();
+    // Only the files are ignored, not the directory holding one.
+    assert_eq!(
+        paths,
+        [
+            std::path::PathBuf::from("a.txt"),
+            std::path::PathBuf::from("logs")
+        ]
+    );
+
+    std::fs::remove_dir_all(dir.join("left/logs")).unwrap();
+    std::fs::write(dir.join("left/a.txt"), "hello").unwrap();
+    assert!(!options
+        .is_different(dir.join("left"), dir.join("right"))
+        .unwrap());
+    assert!(dir_diff::is_different(dir.join("left"), dir.join("right")).unwrap());
+}
+
+#[test]
+fn exclude_preset() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("exclude_preset");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left/src/.git")).unwrap();
+    create_dir_all(dir.join("right/src")).unwrap();
+    for side in ["left", "right"] {
+        std::fs::write(dir.join(side).join("src/main.rs"), "fn main() {}").unwrap();
+    }
+    std::fs::write(dir.join("left/.DS_Store"), "finder").unwrap();
+    std::fs::write(dir.join("right/src/Thumbs.db"), "explorer").unwrap();
+    std::fs::write(dir.join("left/src/.git/HEAD"), "ref").unwrap();
+    std::fs::write(dir.join("right/src/main.rs~"), "fn main() {").unwrap();
+    std::fs::write(dir.join("right/src/.main.rs.swp"), "vim").unwrap();
+
+    let options = dir_diff::DirDiffOptions::new()
+        .exclude_preset(dir_diff::Preset::OsJunk)
+        .exclude_preset(dir_diff::Preset::VcsDirs);
+    let report = options
+        .clone()
+        .diff(dir.join("left"), dir.join("right"))
+        .unwrap();
+    assert_eq!(report.differences().len(), 2);
+    assert!(!options
+        .exclude_preset(dir_diff::Preset::EditorBackups)
+        .is_different(dir.join("left"), dir.join("right"))
+        .unwrap());
+}
+
+#[test]
+fn presets() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("presets");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left/.git")).unwrap();
+    create_dir_all(dir.join("right")).unwrap();
+    std::fs::write(dir.join("left/.git/HEAD"), "ref").unwrap();
+    std::fs::write(dir.join("left/out.txt"), "hello\r\nworld\r\n").unwrap();
+    std::fs::write(dir.join("right/out.txt"), "hello\nworld\n").unwrap();
+    let (left, right) = (dir.join("left"), dir.join("right"));
+
+    assert!(dir_diff::is_different(&left, &right).unwrap());
+    assert!(!dir_diff::DirDiffOptions::new()
+        .preset(dir_diff::Preset::GoldenTest)
+        .is_different(&left, &right)
+        .unwrap());
+    assert_eq!(
+        dir_diff::Preset::GoldenTest.globs().len(),
+        [
+            dir_diff::Preset::OsJunk,
+            dir_diff::Preset::VcsDirs,
+            dir_diff::Preset::EditorBackups
+        ]
+        .iter()
+        .map(|preset| preset.globs().len())
+        .sum::<usize>()
+    );
+
+    // Only the layout: both have `out.txt`, whatever it holds.
+    let layout = dir_diff::DirDiffOptions::new()
+        .preset(dir_diff::Preset::Layout)
+        .exclude("**/.git");
+    assert!(!layout.is_different(&left, &right).unwrap());
+    std::fs::write(dir.join("right/extra.txt"), "").unwrap();
+    assert!(layout.is_different(&left, &right).unwrap());
+    std::fs::remove_file(dir.join("right/extra.txt")).unwrap();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        std::fs::write(dir.join("right/out.txt"), "hello\r\nworld\r\n").unwrap();
+        std::fs::set_permissions(
+            dir.join("right/out.txt"),
+            std::fs::Permissions::from_mode(0o600),
+        )
+        .unwrap();
+        std::fs::set_permissions(
+            dir.join("left/out.txt"),
+            std::fs::Permissions::from_mode(0o644),
+        )
+        .unwrap();
+        for preset in [dir_diff::Preset::Strict, dir_diff::Preset::BackupVerify] {
+            let report = dir_diff::DirDiffOptions::new()
+                .preset(preset)
+                .exclude("**/.git")
+                .diff(&left, &right)
+                .unwrap();
+            assert!(report
+                .differences()
+                .iter()
+                .any(|difference| difference.kind() == dir_diff::DifferenceKind::Permissions));
+        }
+        assert!(!dir_diff::DirDiffOptions::new()
+            .exclude("**/.git")
+            .is_different(&left, &right)
+            .unwrap());
+    }
+}
+
+#[test]
+fn ignore_extra() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("ignore_extra");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("actual")).unwrap();
+    create_dir_all(dir.join("expected")).unwrap();
+    std::fs::write(dir.join("actual/main.py"), "print()").unwrap();
+    std::fs::write(dir.join("actual/main.pyc"), "bytecode").unwrap();
+    std::fs::write(dir.join("expected/main.py"), "print()").unwrap();
+
+    let options = dir_diff::DirDiffOptions::new().ignore_extra(dir_diff::Side::Left, "*.pyc");
+    assert!(!options
+        .clone()
+        .is_different(dir.join("actual"), dir.join("expected"))
+        .unwrap());
+
+    // Only extra entries on the left are ignored.
+    let report = options
+        .clone()
+        .diff(dir.join("expected"), dir.join("actual"))
+        .unwrap();
+    assert_eq!(report.differences().len(), 1);
+    std::fs::write(dir.join("expected/lib.pyc"), "bytecode").unwrap();
+    assert!(options
+        .clone()
+        .is_different(dir.join("actual"), dir.join("expected"))
+        .unwrap());
+    std::fs::write(dir.join("actual/lib.pyc"), "other bytecode").unwrap();
+    assert!(options
+        .is_different(dir.join("actual"), dir.join("expected"))
+        .unwrap());
+}
+
+#[test]
+fn map_path() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("map_path");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left/lib64/python")).unwrap();
+    create_dir_all(dir.join("left/docs")).unwrap();
+    create_dir_all(dir.join("right/lib/python")).unwrap();
+    create_dir_all(dir.join("right/docs")).unwrap();
+    for (file, contents) in [
+        ("left/lib64/libc.so", "libc"),
+        ("left/lib64/python/site.py", "import os"),
+        ("left/docs/index.htm", "<html>"),
+        ("right/lib/libc.so", "libc"),
+        ("right/lib/python/site.py", "import sys"),
+        ("right/docs/index.html", "<html>"),
+    ] {
+        std::fs::write(dir.join(file), contents).unwrap();
+    }
+
+    let report = dir_diff::DirDiffOptions::new()
+        .map_path("lib64/**", "lib/**")
+        .map_path("*.htm", "*.html")
+        .diff(dir.join("left"), dir.join("right"))
+        .unwrap();
+    let differences: Vec<_> = report
+        .iter()
+        .map(|d| (d.path().to_path_buf(), d.kind()))
+        .collect();
+    assert_eq!(
+        differences,
+        [(
+            std::path::PathBuf::from("lib/python/site.py"),
+            dir_diff::DifferenceKind::Content
+        )]
+    );
+
+    assert!(matches!(
+        dir_diff::DirDiffOptions::new()
+            .map_path("*.htm", "*/*")
+            .diff(dir.join("left"), dir.join("right")),
+        Err(dir_diff::Error::PathMap { .. })
+    ));
+    assert!(dir_diff::DirDiffOptions::new()
+        .map_path("lib64/*", "lib")
+        .diff(dir.join("left"), dir.join("right"))
+        .is_err());
+}
+
+#[test]
+fn matches_any() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("matches_any");
+    let _ = std::fs::remove_dir_all(&dir);
+    for (tree, contents) in [
+        ("actual", "a\r\n"),
+        ("unix", "a\n"),
+        ("windows", "a\r\n"),
+        ("mac", "a\r"),
+    ] {
+        create_dir_all(dir.join(tree)).unwrap();
+        std::fs::write(dir.join(tree).join("out.txt"), contents).unwrap();
+    }
+
+    let golden = |trees: &[&str]| -> Vec<_> { trees.iter().map(|tree| dir.join(tree)).collect() };
+    assert_eq!(
+        dir_diff::matches_any(dir.join("actual"), golden(&["unix", "windows", "mac"])).unwrap(),
+        Some(1)
+    );
+    assert_eq!(
+        dir_diff::matches_any(dir.join("actual"), golden(&["unix", "mac"])).unwrap(),
+        None
+    );
+    assert!(dir_diff::matches_any(dir.join("actual"), golden(&["missing", "windows"])).is_err());
+}
+
+#[test]
+#[cfg(unix)]
+fn verify_copy() {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("verify_copy");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("src")).unwrap();
+    for file in ["a.txt", "b.txt", "c.sh", "d.txt"] {
+        std::fs::write(dir.join("src").join(file), file).unwrap();
+    }
+    std::fs::set_permissions(dir.join("src/c.sh"), std::fs::Permissions::from_mode(0o755)).unwrap();
+    let status = std::process::Command::new("cp")
+        .arg("-a")
+        .arg(dir.join("src"))
+        .arg(dir.join("dst"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    std::fs::write(dir.join("dst/extra.txt"), "extra").unwrap();
+    assert!(!dir_diff::verify_copy(dir.join("src"), dir.join("dst"))
+        .unwrap()
+        .is_different());
+
+    std::fs::remove_file(dir.join("dst/a.txt")).unwrap();
+    std::fs::write(dir.join("dst/b.txt"), "b.tx!").unwrap();
+    std::fs::set_permissions(dir.join("dst/c.sh"), std::fs::Permissions::from_mode(0o644)).unwrap();
+    let status = std::process::Command::new("touch")
+        .arg("-d")
+        .arg("@0")
+        .arg(dir.join("dst/d.txt"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let report = dir_diff::verify_copy(dir.join("src"), dir.join("dst")).unwrap();
+    let differences: Vec<_> = report
+        .iter()
+        .map(|d| (d.path().to_str().unwrap().to_owned(), d.kind()))
+        .collect();
+    assert_eq!(
+        differences,
+        [
+            ("a.txt".to_owned(), dir_diff::DifferenceKind::OnlyInLeft),
+            ("b.txt".to_owned(), dir_diff::DifferenceKind::Content),
+            ("c.sh".to_owned(), dir_diff::DifferenceKind::Permissions),
+            ("d.txt".to_owned(), dir_diff::DifferenceKind::Modified),
+        ]
+    );
+    assert!(!dir_diff::DirDiffOptions::new()
+        .allow_different("b.txt")
+        .verify_copy(dir.join("src"), dir.join("dst"))
+        .unwrap()
+        .iter()
+        .any(|d| d.kind() == dir_diff::DifferenceKind::Modified));
+}
+
+#[test]
+#[cfg(unix)]
+fn changed_permissions() {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("changed_permissions");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left")).unwrap();
+    create_dir_all(dir.join("right")).unwrap();
+    std::fs::write(dir.join("left/run.sh"), "run").unwrap();
+    std::fs::write(dir.join("right/run.sh"), "run").unwrap();
+    std::fs::set_permissions(
+        dir.join("left/run.sh"),
+        std::fs::Permissions::from_mode(0o755),
+    )
+    .unwrap();
+    std::fs::set_permissions(
+        dir.join("right/run.sh"),
+        std::fs::Permissions::from_mode(0o644),
+    )
+    .unwrap();
+
+    let report = dir_diff::DirDiffOptions::new()
+        .compare_permissions(true)
+        .diff(dir.join("left"), dir.join("right"))
+        .unwrap();
+    assert!(report.is_different());
+    assert_eq!(report.only_in_left().count(), 0);
+    assert_eq!(report.only_in_right().count(), 0);
+    let changed: Vec<_> = report
+        .changed()
+        .map(|d| (d.path().to_str().unwrap().to_owned(), d.kind()))
+        .collect();
+    assert_eq!(
+        changed,
+        [("run.sh".to_owned(), dir_diff::DifferenceKind::Permissions)]
+    );
+}
+
+#[test]
+fn tree_digest() {
+    assert_eq!(
+        dir_diff::tree_digest("tests/easy/good/dir1").unwrap(),
+        dir_diff::tree_digest("tests/easy/good/dir2").unwrap()
+    );
+    assert_ne!(
+        dir_diff::tree_digest("tests/easy/bad/dir1").unwrap(),
+        dir_diff::tree_digest("tests/easy/bad/dir2").unwrap()
+    );
+    let options = dir_diff::DirDiffOptions::new().exclude("test.txt");
+    assert_eq!(
+        options.tree_digest("tests/easy/bad/dir1").unwrap(),
+        options.tree_digest("tests/easy/bad/dir2").unwrap()
+    );
+    assert!(matches!(
+        dir_diff::tree_digest("tests/does-not-exist"),
+        Err(dir_diff::Error::RootMissing { .. })
+    ));
+}
+
+#[test]
+fn merkle_tree() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("merkle_tree");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("tree/docs")).unwrap();
+    create_dir_all(dir.join("tree/src")).unwrap();
+    std::fs::write(dir.join("tree/docs/index.html"), "<p>hello</p>").unwrap();
+    std::fs::write(dir.join("tree/src/main.rs"), "fn main() {}").unwrap();
+    let tree_dir = dir.join("tree");
+
+    let options = dir_diff::DirDiffOptions::new();
+    let mut tree = dir_diff::merkle_tree(&tree_dir).unwrap();
+    assert_eq!(tree.digest(), dir_diff::tree_digest(&tree_dir).unwrap());
+    let dirs: Vec<_> = tree.dirs().map(|(path, _)| path.to_path_buf()).collect();
+    assert_eq!(
+        dirs,
+        [
+            std::path::PathBuf::new(),
+            std::path::PathBuf::from("docs"),
+            std::path::PathBuf::from("src")
+        ]
+    );
+    let src = tree.get("src").unwrap();
+
+    std::fs::write(dir.join("tree/docs/index.html"), "<p>goodbye</p>").unwrap();
+    create_dir_all(dir.join("tree/docs/api")).unwrap();
+    std::fs::write(dir.join("tree/docs/api/index.html"), "<p>api</p>").unwrap();
+    options
+        .update_merkle_tree(
+            &mut tree,
+            &tree_dir,
+            ["docs/index.html", "docs/api/index.html"],
+        )
+        .unwrap();
+    assert_eq!(tree.digest(), dir_diff::tree_digest(&tree_dir).unwrap());
+    assert_eq!(tree.get("src"), Some(src));
+    assert!(tree.get("docs/api").is_some());
+
+    // The table survives a round trip, and only what changed since is walked again.
+    let mut table = Vec::new();
+    tree.write_to(&mut table).unwrap();
+    let mut tree = dir_diff::MerkleTree::read_from(&table[..]).unwrap();
+    std::fs::remove_dir_all(dir.join("tree/docs")).unwrap();
+    options
+        .update_merkle_tree(&mut tree, &tree_dir, ["docs"])
+        .unwrap();
+    assert_eq!(tree.digest(), dir_diff::tree_digest(&tree_dir).unwrap());
+    assert_eq!(tree.get("docs"), None);
+}
+
+#[test]
+fn diff_incremental() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("diff_incremental");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left")).unwrap();
+    create_dir_all(dir.join("right")).unwrap();
+    std::fs::write(dir.join("left/a.txt"), "hello").unwrap();
+    std::fs::write(dir.join("right/a.txt"), "world").unwrap();
+    std::fs::write(dir.join("left/b.txt"), "same").unwrap();
+    std::fs::write(dir.join("right/b.txt"), "same").unwrap();
+    let (left, right) = (dir.join("left"), dir.join("right"));
+
+    let mut stats = dir_diff::StatCache::default();
+    let first =
+        dir_diff::diff_incremental(&left, &right, &dir_diff::DiffReport::default(), &mut stats)
+            .unwrap();
+    assert_eq!(first, dir_diff::diff(&left, &right).unwrap());
+    assert_eq!(stats.len(), 2);
+
+    let mut table = Vec::new();
+    stats.write_to(&mut table).unwrap();
+    let mut stats = dir_diff::StatCache::read_from(&table[..]).unwrap();
+    let second = dir_diff::diff_incremental(&left, &right, &first, &mut stats).unwrap();
+    assert_eq!(second, first);
+
+    // Unchanged files aren't read again, so a report saying they were the same is trusted.
+    let trusted =
+        dir_diff::diff_incremental(&left, &right, &dir_diff::DiffReport::default(), &mut stats)
+            .unwrap();
+    assert!(!trusted.is_different());
+
+    std::fs::write(dir.join("right/a.txt"), "world!").unwrap();
+    let changed =
+        dir_diff::diff_incremental(&left, &right, &dir_diff::DiffReport::default(), &mut stats)
+            .unwrap();
+    let paths: Vec<_> = changed.iter().map(|d| d.path().to_path_buf()).collect();
+    assert_eq!(paths, [std::path::PathBuf::from("a.txt")]);
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn case_sensitivity() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("case_sensitivity");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left/Docs")).unwrap();
+    create_dir_all(dir.join("right/docs")).unwrap();
+    std::fs::write(dir.join("left/Docs/README.md"), "hello").unwrap();
+    std::fs::write(dir.join("right/docs/readme.md"), "hello").unwrap();
+    std::fs::write(dir.join("left/B.txt"), "b").unwrap();
+    std::fs::write(dir.join("right/b.txt"), "b").unwrap();
+    std::fs::write(dir.join("left/c.txt"), "c").unwrap();
+    std::fs::write(dir.join("right/c.txt"), "c").unwrap();
+    let (left, right) = (dir.join("left"), dir.join("right"));
+
+    assert!(dir_diff::is_case_sensitive(&left).unwrap());
+    assert!(dir_diff::is_different(&left, &right).unwrap());
+    let detect =
+        dir_diff::DirDiffOptions::new().case_sensitivity(dir_diff::CaseSensitivity::Detect);
+    assert!(detect.is_different(&left, &right).unwrap());
+    let insensitive =
+        dir_diff::DirDiffOptions::new().case_sensitivity(dir_diff::CaseSensitivity::Insensitive);
+    assert!(!insensitive.is_different(&left, &right).unwrap());
+
+    std::fs::write(dir.join("right/docs/readme.md"), "world").unwrap();
+    let report = insensitive.diff(&left, &right).unwrap();
+    let paths: Vec<_> = report.iter().map(|d| d.path().to_path_buf()).collect();
+    assert_eq!(paths, [std::path::Path::new("Docs").join("README.md")]);
+
+    // `b.txt` collides with `B.txt`, so is missing from the other tree.
+    std::fs::write(dir.join("left/b.txt"), "b").unwrap();
+    let report = insensitive
+        .clone()
+        .check_portable_names(true)
+        .diff(&left, &right)
+        .unwrap();
+    let differences: Vec<_> = report
+        .iter()
+        .map(|d| (d.path().to_path_buf(), d.kind()))
+        .collect();
+    assert_eq!(
+        differences,
+        [
+            (
+                std::path::PathBuf::from("b.txt"),
+                dir_diff::DifferenceKind::OnlyInLeft
+            ),
+            (
+                std::path::Path::new("Docs").join("README.md"),
+                dir_diff::DifferenceKind::Content
+            ),
+        ]
+    );
+    let issues: Vec<_> = report
+        .name_issues()
+        .iter()
+        .map(|issue| (issue.path().to_path_buf(), issue.problem()))
+        .collect();
+    assert_eq!(
+        issues,
+        [(
+            std::path::PathBuf::from("b.txt"),
+            dir_diff::NameProblem::CaseCollision
+        )]
+    );
+}
+
+#[test]
+fn differences() {
+    fn assert_send<T: Send>(_: &T) {}
+
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("differences");
+    let _ = std::fs::remove_dir_all(&dir);
+    for side in ["left", "right"] {
+        create_dir_all(dir.join(side)).unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            std::fs::write(dir.join(side).join(name), side).unwrap();
+        }
+    }
+
+    let mut differences = dir_diff::differences(dir.join("left"), dir.join("right"));
+    assert_send(&differences);
+    let first = differences.next().unwrap().unwrap();
+    assert_eq!(first.path(), std::path::Path::new("a.txt"));
+    assert_eq!(first.kind(), dir_diff::DifferenceKind::Content);
+    drop(differences);
+
+    let paths: Vec<_> = dir_diff::DirDiffOptions::new()
+        .allow_different("b.txt")
+        .differences(dir.join("left"), dir.join("right"))
+        .map(|difference| difference.unwrap().path().to_owned())
+        .collect();
+    assert_eq!(paths, ["a.txt", "c.txt"].map(std::path::PathBuf::from));
+
+    let mut missing = dir_diff::differences(dir.join("left"), dir.join("missing"));
+    assert!(matches!(
+        missing.next(),
+        Some(Err(dir_diff::Error::RootMissing { .. }))
+    ));
+    assert!(missing.next().is_none());
+}
+
+#[test]
+fn write_patch() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("write_patch");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left/added")).unwrap();
+    create_dir_all(dir.join("right/removed")).unwrap();
+    std::fs::write(dir.join("left/text.txt"), "1\n2\n3\n4\n5\n6\n7\nchanged\n9").unwrap();
+    std::fs::write(dir.join("right/text.txt"), "1\n2\n3\n4\n5\n6\n7\n8\n9").unwrap();
+    std::fs::write(dir.join("left/added/new.txt"), "new\n").unwrap();
+    std::fs::write(dir.join("left/data.bin"), [0, 1, 2]).unwrap();
+    std::fs::write(dir.join("right/data.bin"), [0, 1]).unwrap();
+    std::fs::write(dir.join("right/removed/old.txt"), "old\n").unwrap();
+
+    let report = dir_diff::diff(dir.join("left"), dir.join("right")).unwrap();
+    let mut patch = Vec::new();
+    report.write_patch(&mut patch).unwrap();
+    assert_eq!(
+        String::from_utf8(patch).unwrap(),
+        "dir-diff patch 1
+mkdir added
+--- /dev/null
++++ b/added/new.txt
+@@ -0,0 +1,1 @@
++new
+binary 3 data.bin
+AAEC
+delete removed
+--- a/text.txt
++++ b/text.txt
+@@ -5,5 +5,5 @@
+ 5
+ 6
+ 7
+-8
++changed
+ 9
+\\ No newline at end of file
+"
+    );
+}
+
+#[test]
+fn apply_patch() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("apply_patch");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left/added/deeper")).unwrap();
+    create_dir_all(dir.join("left/was_file")).unwrap();
+    create_dir_all(dir.join("right/removed")).unwrap();
+    std::fs::write(dir.join("left/text.txt"), "a\nb\nc\nd\ne\nf\ng\nh\n").unwrap();
+    std::fs::write(dir.join("right/text.txt"), "a\nB\nc\nd\ne\nf\ng\nh\ni").unwrap();
+    std::fs::write(dir.join("left/added/deeper/new.txt"), "new\n").unwrap();
+    std::fs::write(dir.join("left/added/empty"), "").unwrap();
+    std::fs::write(dir.join("left/was_file/inside"), [0xff, 0]).unwrap();
+    std::fs::write(dir.join("right/was_file"), "file\n").unwrap();
+    std::fs::write(dir.join("left/data.bin"), [0, 1, 2, 3]).unwrap();
+    std::fs::write(dir.join("right/data.bin"), [0, 1]).unwrap();
+    std::fs::write(dir.join("right/removed/old.txt"), "old\n").unwrap();
+
+    let report = dir_diff::diff(dir.join("left"), dir.join("right")).unwrap();
+    let mut bytes = Vec::new();
+    report.write_patch(&mut bytes).unwrap();
+    let patch = dir_diff::Patch::read(bytes.as_slice()).unwrap();
+    assert!(patch
+        .paths()
+        .any(|path| path == std::path::Path::new("added/deeper/new.txt")));
+
+    // A dry run checks the patch applies, and leaves the directory alone.
+    patch.dry_run(dir.join("right")).unwrap();
+    assert!(dir_diff::is_different(dir.join("left"), dir.join("right")).unwrap());
+    let err = patch.dry_run(dir.join("left")).unwrap_err();
+    assert!(matches!(err, dir_diff::Error::PatchConflict { .. }));
+
+    dir_diff::apply_patch(bytes.as_slice(), dir.join("right")).unwrap();
+    assert!(!dir_diff::is_different(dir.join("left"), dir.join("right")).unwrap());
+    // Once applied, it no longer applies.
+    assert!(patch.dry_run(dir.join("right")).is_err());
+
+    assert!(dir_diff::Patch::read(&b"diff -u a b\n"[..]).is_err());
+    assert!(dir_diff::Patch::read(&b"dir-diff patch 1\ndelete ../escape\n"[..]).is_err());
+}
+
+#[test]
+fn merge() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("merge");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left/only_left")).unwrap();
+    create_dir_all(dir.join("right/only_right")).unwrap();
+    create_dir_all(dir.join("right/was_file")).unwrap();
+    std::fs::write(dir.join("left/same.txt"), "same").unwrap();
+    std::fs::write(dir.join("right/same.txt"), "same").unwrap();
+    std::fs::write(dir.join("left/only_left/file"), "left").unwrap();
+    std::fs::write(dir.join("right/only_right/file"), "right").unwrap();
+    std::fs::write(dir.join("left/changed.txt"), "left").unwrap();
+    std::fs::write(dir.join("right/changed.txt"), "right").unwrap();
+    std::fs::write(dir.join("left/was_file"), "left").unwrap();
+    std::fs::write(dir.join("right/was_file/inside"), "right").unwrap();
+    // The left file is older.
+    std::fs::File::options()
+        .write(true)
+        .open(dir.join("left/changed.txt"))
+        .unwrap()
+        .set_modified(std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1000))
+        .unwrap();
+
+    let report = dir_diff::diff(dir.join("left"), dir.join("right")).unwrap();
+    let merged = |policy, name: &str| {
+        let out = dir.join(name);
+        report.merge(&policy, &out).unwrap();
+        let read = |path: &str| std::fs::read_to_string(out.join(path)).ok();
+        assert_eq!(read("same.txt").as_deref(), Some("same"));
+        assert_eq!(read("only_left/file").as_deref(), Some("left"));
+        assert_eq!(read("only_right/file").as_deref(), Some("right"));
+        (read("changed.txt").unwrap(), out.join("was_file").is_dir())
+    };
+    assert_eq!(
+        merged(dir_diff::MergePolicy::PreferLeft, "left_merged"),
+        ("left".to_owned(), false)
+    );
+    assert_eq!(
+        merged(dir_diff::MergePolicy::PreferRight, "right_merged"),
+        ("right".to_owned(), true)
+    );
+    assert_eq!(
+        merged(dir_diff::MergePolicy::PreferNewer, "newer_merged").0,
+        "right"
+    );
+    let callback = dir_diff::MergePolicy::Callback(std::sync::Arc::new(
+        |difference: &dir_diff::Difference| match difference.path().extension() {
+            Some(_) => dir_diff::Side::Left,
+            None => dir_diff::Side::Right,
+        },
+    ));
+    assert_eq!(
+        merged(callback, "callback_merged"),
+        ("left".to_owned(), true)
+    );
+
+    // The trees are left as they were, and an existing directory isn't merged into.
+    assert_eq!(
+        std::fs::read_to_string(dir.join("left/changed.txt")).unwrap(),
+        "left"
+    );
+    assert!(report
+        .merge(&dir_diff::MergePolicy::PreferLeft, dir.join("left_merged"))
+        .is_err());
+    assert!(report
+        .merge(&dir_diff::MergePolicy::PreferLeft, dir.join("left/inside"))
+        .is_err());
+    assert!(!dir.join("left/inside").exists());
+}
+
+#[test]
+fn group_identical() {
+    let dirs: Vec<std::path::PathBuf> = [
+        "tests/easy/good/dir1",
+        "tests/easy/bad/dir1",
+        "tests/easy/good/dir2",
+        "tests/easy/bad/dir2",
+        "tests/easy/good/dir1",
+    ]
+    .iter()
+    .map(std::path::PathBuf::from)
+    .collect();
+    assert_eq!(
+        dir_diff::group_identical(&dirs).unwrap(),
+        vec![vec![0, 1, 2, 4], vec![3]]
+    );
+    assert_eq!(
+        dir_diff::group_identical(&[]).unwrap(),
+        Vec::<Vec<usize>>::new()
+    );
+    assert!(matches!(
+        dir_diff::group_identical(&["tests/missing".into()]),
+        Err(dir_diff::Error::RootMissing { .. })
+    ));
+}
+
+#[test]
+fn tree_similarity() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("tree_similarity");
+    let _ = std::fs::remove_dir_all(&dir);
+    for name in ["out", "small_changed", "large_changed"] {
+        create_dir_all(dir.join(name)).unwrap();
+    }
+    std::fs::write(dir.join("out/large"), [1; 990]).unwrap();
+    std::fs::write(dir.join("out/small"), "0123456789").unwrap();
+    std::fs::write(dir.join("small_changed/large"), [1; 990]).unwrap();
+    std::fs::write(dir.join("small_changed/small"), "9876543210").unwrap();
+    std::fs::write(dir.join("large_changed/large"), [2; 990]).unwrap();
+    std::fs::write(dir.join("large_changed/small"), "0123456789").unwrap();
+
+    let out = dir.join("out");
+    assert_eq!(dir_diff::similarity(&out, &out).unwrap(), 1.0);
+    assert_eq!(
+        dir_diff::similarity(&out, dir.join("small_changed")).unwrap(),
+        0.99
+    );
+    assert_eq!(
+        dir_diff::similarity(&out, dir.join("large_changed")).unwrap(),
+        0.01
+    );
+    assert_eq!(
+        dir_diff::DirDiffOptions::new()
+            .exclude("large")
+            .tree_similarity(&out, dir.join("large_changed"))
+            .unwrap(),
+        1.0
+    );
+}
+
+#[test]
+fn placeholders() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("placeholders");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("out")).unwrap();
+    create_dir_all(dir.join("golden")).unwrap();
+    std::fs::write(
+        dir.join("out/meta.json"),
+        r#"{"version": "1.4.2", "id": "0f8fad5b-d9cb-469f-a165-70867728950e"}"#,
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("golden/meta.json"),
+        r#"{"version": "{{VERSION}}", "id": "[[UUID]]"}"#,
+    )
+    .unwrap();
+    std::fs::write(dir.join("out/notes.txt"), "built 1.4.2").unwrap();
+    std::fs::write(dir.join("golden/notes.txt"), "built {{VERSION}}").unwrap();
+
+    let options = dir_diff::DirDiffOptions::new()
+        .placeholder("**/*.json", "{{VERSION}}", r"\d+\.\d+\.\d+")
+        .placeholder(
+            "**",
+            "[[UUID]]",
+            "[0-9a-f]{8}(-[0-9a-f]{4}){3}-[0-9a-f]{12}",
+        );
+    let report = options.diff(dir.join("out"), dir.join("golden")).unwrap();
+    assert_eq!(
+        report
+            .differences()
+            .iter()
+            .map(|difference| difference.path().as_path())
+            .collect::<Vec<_>>(),
+        [std::path::Path::new("notes.txt")]
+    );
+
+    std::fs::write(dir.join("out/meta.json"), r#"{"version": "1.4"}"#).unwrap();
+    std::fs::write(dir.join("golden/notes.txt"), "built 1.4.2").unwrap();
+    assert!(options
+        .is_different(dir.join("out"), dir.join("golden"))
+        .unwrap());
+
+    assert!(matches!(
+        dir_diff::DirDiffOptions::new()
+            .placeholder("**", "{{X}}", "(")
+            .diff(dir.join("out"), dir.join("golden")),
+        Err(dir_diff::Error::Regex { .. })
+    ));
+}
+
+#[test]
+fn path_variable() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("path_variable");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("out/bin/aarch64-apple-darwin")).unwrap();
+    create_dir_all(dir.join("golden/bin/{{target}}")).unwrap();
+    for (file, contents) in [
+        ("out/bin/aarch64-apple-darwin/app", "app"),
+        ("out/bin/aarch64-apple-darwin/app.d", "deps"),
+        ("golden/bin/{{target}}/app", "app"),
+        ("golden/bin/{{target}}/app.{{ext}}", "deps"),
+    ] {
+        std::fs::write(dir.join(file), contents).unwrap();
+    }
+
+    let options = dir_diff::DirDiffOptions::new()
+        .path_variable("target", "x86_64-unknown-linux-gnu")
+        .path_variable("ext", "d")
+        .path_variable("target", "aarch64-apple-darwin");
+    assert!(!options
+        .is_different(dir.join("out"), dir.join("golden"))
+        .unwrap());
+
+    let report = dir_diff::DirDiffOptions::new()
+        .path_variable("target", "aarch64-apple-darwin")
+        .diff(dir.join("out"), dir.join("golden"))
+        .unwrap();
+    let differences: Vec<_> = report
+        .iter()
+        .map(|d| (d.path().to_path_buf(), d.kind()))
+        .collect();
+    assert_eq!(
+        differences,
+        [
+            (
+                std::path::PathBuf::from("bin/aarch64-apple-darwin/app.d"),
+                dir_diff::DifferenceKind::OnlyInLeft
+            ),
+            (
+                std::path::PathBuf::from("bin/aarch64-apple-darwin/app.{{ext}}"),
+                dir_diff::DifferenceKind::OnlyInRight
+            ),
+        ]
+    );
+}
+
+#[test]
+fn assert_dir_eq() {
+    dir_diff::assert_dir_eq!("tests/easy/good/dir1", "tests/easy/good/dir2");
+    dir_diff::assert_dir_eq!(
+        std::path::Path::new("tests/easy/good/dir1"),
+        String::from("tests/easy/good/dir2"),
+        "with a {}",
+        "message"
+    );
+
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("assert_dir_eq");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("actual")).unwrap();
+    create_dir_all(dir.join("expected")).unwrap();
+    std::fs::write(dir.join("actual/notes.txt"), "one\ntwo\nthree\n").unwrap();
+    std::fs::write(dir.join("expected/notes.txt"), "one\n2\nthree\n").unwrap();
+    std::fs::write(dir.join("expected/extra.txt"), "extra").unwrap();
+
+    let panic = std::panic::catch_unwind(|| {
+        dir_diff::assert_dir_eq!(dir.join("actual"), dir.join("expected"), "case {}", 1);
+    })
+    .unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+    assert!(message.starts_with("assertion failed: directories differ: case 1\n"));
+    assert!(message.contains("+ extra.txt\n"));
+    assert!(message.contains("M notes.txt\n"));
+    assert!(message.contains("@@ -1,3 +1,3 @@\n one\n-two\n+2\n three\n"));
+    assert!(!message.contains("DIR_DIFF_UPDATE"));
+}
+
+#[test]
+fn assert_dir_snapshot() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("assert_dir_snapshot");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("actual/sub")).unwrap();
+    create_dir_all(dir.join("snapshot")).unwrap();
+    std::fs::write(dir.join("actual/sub/new.txt"), "new").unwrap();
+    std::fs::write(dir.join("snapshot/old.txt"), "old").unwrap();
+
+    let panic = std::panic::catch_unwind(|| {
+        dir_diff::assert_dir_snapshot!(dir.join("actual"), dir.join("snapshot"));
+    })
+    .unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+    assert!(message.contains("set DIR_DIFF_UPDATE=1 to update"));
+
+    std::env::set_var("DIR_DIFF_UPDATE", "1");
+    dir_diff::assert_dir_snapshot!(dir.join("actual"), dir.join("snapshot"));
+    std::env::remove_var("DIR_DIFF_UPDATE");
+    dir_diff::assert_dir_snapshot!(dir.join("actual"), dir.join("snapshot"));
+    assert!(!dir.join("snapshot/old.txt").exists());
+}
+
+#[test]
+fn explain() {
+    let dir = std::path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("explain");
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left/sub")).unwrap();
+    create_dir_all(dir.join("right/sub")).unwrap();
+    std::fs::write(dir.join("left/same.txt"), "same").unwrap();
+    std::fs::write(dir.join("right/same.txt"), "same").unwrap();
+    std::fs::write(dir.join("left/sub/size.txt"), "short").unwrap();
+    std::fs::write(dir.join("right/sub/size.txt"), "longer").unwrap();
+    std::fs::write(dir.join("left/only.txt"), "only").unwrap();
+    std::fs::write(dir.join("left/skipped.log"), "left").unwrap();
+
+    let report = dir_diff::DirDiffOptions::new()
+        .exclude("*.log")
+        .explain(true)
+        .diff(dir.join("left"), dir.join("right"))
+        .unwrap();
+    let explanation = report.explanation().unwrap();
+    assert_eq!(
+        explanation.to_string(),
+        "\
+only.txt
+  only in left: different
+same.txt
+  name: same
+  full read: same
+skipped.log
+  skipped (excluded on the left): same
+sub
+  name: same
+sub/size.txt
+  name: same
+  size: different
+"
+    );
+    assert!(explanation.get("same.txt").unwrap().is_same());
+    assert!(!explanation.get("sub/size.txt").unwrap().is_same());
+
+    let report = dir_diff::DirDiffOptions::new()
+        .diff(dir.join("left"), dir.join("right"))
+        .unwrap();
+    assert!(report.explanation().is_none());
+}
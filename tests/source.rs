@@ -0,0 +1,621 @@
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{self, AtomicU32, AtomicU64};
+use std::time::{Duration, SystemTime};
+
+use dir_diff::source::{FileType, FsSource, Metadata, OverlaySource, SourceEntry, TreeSource};
+use dir_diff::{DifferenceKind, DirDiffOptions, RetryPolicy};
+
+/// A tree of files held in memory, keyed by relative path.
+struct MemorySource {
+    root: PathBuf,
+    files: BTreeMap<PathBuf, &'static [u8]>,
+}
+
+impl MemorySource {
+    fn new(files: &[(&str, &'static [u8])]) -> Self {
+        Self {
+            root: PathBuf::from("memory"),
+            files: files.iter().map(|(p, c)| (PathBuf::from(p), *c)).collect(),
+        }
+    }
+
+    fn file_type(&self, path: &Path) -> Option<FileType> {
+        if self.files.contains_key(path) {
+            Some(FileType::File)
+        } else if path.as_os_str().is_empty() || self.files.keys().any(|f| f.starts_with(path)) {
+            Some(FileType::Dir)
+        } else {
+            None
+        }
+    }
+}
+
+fn not_found() -> dir_diff::Error {
+    dir_diff::Error::from(std::io::Error::from(std::io::ErrorKind::NotFound))
+}
+
+impl TreeSource for MemorySource {
+    fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<SourceEntry>, dir_diff::Error> {
+        let mut entries: Vec<SourceEntry> = Vec::new();
+        for file in self.files.keys() {
+            let Ok(rest) = file.strip_prefix(path) else {
+                continue;
+            };
+            let name = rest.iter().next().unwrap();
+            if entries.iter().all(|e| e.name() != name) {
+                let file_type = self.file_type(&path.join(name)).unwrap();
+                entries.push(SourceEntry::new(name, file_type));
+            }
+        }
+        Ok(entries)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, dir_diff::Error> {
+        let file_type = self.file_type(path).ok_or_else(not_found)?;
+        let len = self.files.get(path).map(|c| c.len() as u64).unwrap_or(0);
+        Ok(Metadata::new(file_type, len))
+    }
+
+    fn open(&self, path: &Path) -> Result<Box<dyn Read + '_>, dir_diff::Error> {
+        let contents = self.files.get(path).ok_or_else(not_found)?;
+        Ok(Box::new(*contents))
+    }
+}
+
+#[test]
+fn memory_vs_fs() {
+    let memory = MemorySource::new(&[("one.txt", b"one"), ("subdir/two.txt", b"two")]);
+    let options = DirDiffOptions::new();
+    assert!(!options
+        .is_different_sources(&memory, FsSource::new("tests/reflexive/dir1"))
+        .unwrap());
+
+    let report = options
+        .diff_sources(&memory, FsSource::new("tests/reflexive/dir2"))
+        .unwrap();
+    assert_eq!(report.left(), Path::new("memory"));
+    let differences: Vec<_> = report
+        .differences()
+        .iter()
+        .map(|d| (d.path().to_path_buf(), d.kind()))
+        .collect();
+    assert_eq!(
+        differences,
+        [(
+            PathBuf::from("subdir/three.txt"),
+            DifferenceKind::OnlyInRight
+        )]
+    );
+}
+
+#[test]
+fn memory_contents() {
+    let left = MemorySource::new(&[("a/b.txt", b"same"), ("c.txt", b"left")]);
+    let right = MemorySource::new(&[("a/b.txt", b"same"), ("c.txt", b"rght")]);
+    let report = DirDiffOptions::new().diff_sources(&left, &right).unwrap();
+    let differences: Vec<_> = report
+        .differences()
+        .iter()
+        .map(|d| (d.path().to_path_buf(), d.kind()))
+        .collect();
+    assert_eq!(
+        differences,
+        [(PathBuf::from("c.txt"), DifferenceKind::Content)]
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn memory_command() {
+    let left = MemorySource::new(&[("a/b.txt", b"same"), ("c.txt", b"left")]);
+    let right = MemorySource::new(&[("a/b.txt", b"same"), ("c.txt", b"rght")]);
+    let report = DirDiffOptions::new()
+        .compare_with_command("**", "cmp -s %L %R")
+        .diff_sources(&left, &right)
+        .unwrap();
+    let differences: Vec<_> = report
+        .differences()
+        .iter()
+        .map(|d| (d.path().to_path_buf(), d.kind()))
+        .collect();
+    assert_eq!(
+        differences,
+        [(PathBuf::from("c.txt"), DifferenceKind::Content)]
+    );
+}
+
+#[test]
+fn tree_digest() {
+    let memory = MemorySource::new(&[("one.txt", b"one"), ("subdir/two.txt", b"two")]);
+    let options = DirDiffOptions::new();
+    assert_eq!(
+        options.tree_digest_source(&memory).unwrap(),
+        dir_diff::tree_digest("tests/reflexive/dir1").unwrap()
+    );
+    assert_ne!(
+        options.tree_digest_source(&memory).unwrap(),
+        dir_diff::tree_digest("tests/reflexive/dir2").unwrap()
+    );
+
+    // Moving a file changes the digest, even with the same contents.
+    let moved = MemorySource::new(&[("subdir/one.txt", b"one"), ("two.txt", b"two")]);
+    assert_ne!(
+        options.tree_digest_source(&moved).unwrap(),
+        options.tree_digest_source(&memory).unwrap()
+    );
+}
+
+/// A [`MemorySource`] whose files can't be read.
+struct Unreadable(MemorySource);
+
+impl TreeSource for Unreadable {
+    fn root(&self) -> &Path {
+        self.0.root()
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<SourceEntry>, dir_diff::Error> {
+        self.0.read_dir(path)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, dir_diff::Error> {
+        self.0.metadata(path)
+    }
+
+    fn open(&self, _path: &Path) -> Result<Box<dyn Read + '_>, dir_diff::Error> {
+        Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied).into())
+    }
+}
+
+#[test]
+fn error_context() {
+    let left = MemorySource::new(&[("a/b.txt", b"same")]);
+    let right = Unreadable(MemorySource::new(&[("a/b.txt", b"same")]));
+    let err = DirDiffOptions::new()
+        .diff_sources(&left, &right)
+        .unwrap_err();
+    assert_eq!(err.path(), Some(Path::new("memory/a/b.txt")));
+    assert_eq!(
+        err.phase(),
+        Some(dir_diff::Phase::Reading(dir_diff::Side::Right))
+    );
+    assert_eq!(
+        err.io_error().unwrap().kind(),
+        std::io::ErrorKind::PermissionDenied
+    );
+    assert!(err
+        .to_string()
+        .starts_with("I/O error while reading the right tree at memory/a/b.txt: "));
+}
+
+#[test]
+fn size_tolerance_unread() {
+    let left = Unreadable(MemorySource::new(&[("run.log", b"started\n")]));
+    let right = Unreadable(MemorySource::new(&[("run.log", b"started\nfinished\n")]));
+    let options = DirDiffOptions::new()
+        .placeholder("*.log", "{{STEP}}", "[a-z]+")
+        .similarity(true)
+        .detect_encoding_changes(true);
+    assert!(!options
+        .clone()
+        .size_tolerance("*.log", 16)
+        .diff_sources(&left, &right)
+        .unwrap()
+        .is_different());
+    let report = options
+        .size_tolerance("*.log", 4)
+        .diff_sources(&left, &right)
+        .unwrap();
+    assert_eq!(report.differences()[0].kind(), DifferenceKind::Content);
+}
+
+/// A [`MemorySource`] whose files look modified every time they are looked up.
+struct Changing(MemorySource, AtomicU64);
+
+impl TreeSource for Changing {
+    fn root(&self) -> &Path {
+        self.0.root()
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<SourceEntry>, dir_diff::Error> {
+        self.0.read_dir(path)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, dir_diff::Error> {
+        let secs = self.1.fetch_add(1, atomic::Ordering::Relaxed);
+        Ok(self
+            .0
+            .metadata(path)?
+            .with_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(secs)))
+    }
+
+    fn open(&self, path: &Path) -> Result<Box<dyn Read + '_>, dir_diff::Error> {
+        self.0.open(path)
+    }
+}
+
+#[test]
+fn volatile() {
+    let left = MemorySource::new(&[("a.txt", b"same")]);
+    let right = Changing(MemorySource::new(&[("a.txt", b"same")]), AtomicU64::new(0));
+    assert!(!DirDiffOptions::new()
+        .is_different_sources(&left, &right)
+        .unwrap());
+
+    let report = DirDiffOptions::new()
+        .detect_volatile(true)
+        .diff_sources(&left, &right)
+        .unwrap();
+    let differences: Vec<_> = report
+        .iter()
+        .map(|d| (d.path().to_path_buf(), d.kind()))
+        .collect();
+    assert_eq!(
+        differences,
+        [(PathBuf::from("a.txt"), DifferenceKind::Volatile)]
+    );
+}
+
+/// A [`MemorySource`] counting how many times its files are opened.
+struct Counting(MemorySource, AtomicU32);
+
+impl TreeSource for Counting {
+    fn root(&self) -> &Path {
+        self.0.root()
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<SourceEntry>, dir_diff::Error> {
+        self.0.read_dir(path)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, dir_diff::Error> {
+        self.0.metadata(path)
+    }
+
+    fn open(&self, path: &Path) -> Result<Box<dyn Read + '_>, dir_diff::Error> {
+        self.1.fetch_add(1, atomic::Ordering::Relaxed);
+        self.0.open(path)
+    }
+}
+
+#[test]
+fn exit_early() {
+    let left = MemorySource::new(&[("a.txt", b"left"), ("b.txt", b"left")]);
+    let right = Counting(
+        MemorySource::new(&[("a.txt", b"righ"), ("b.txt", b"righ")]),
+        AtomicU32::new(0),
+    );
+    let options = DirDiffOptions::new().similarity(true);
+    assert!(options.is_different_sources(&left, &right).unwrap());
+    assert_eq!(right.1.swap(0, atomic::Ordering::Relaxed), 1);
+
+    let report = options.diff_sources(&left, &right).unwrap();
+    assert_eq!(report.differences().len(), 2);
+    assert!(report.iter().all(|d| d.similarity().is_some()));
+    assert!(right.1.load(atomic::Ordering::Relaxed) > 2);
+}
+
+/// A [`MemorySource`] that tracks how many files are open at once, along with every other tree
+/// sharing its counters, and the most that ever were.
+struct Crowded<'c>(MemorySource, &'c (AtomicU32, AtomicU32));
+
+impl TreeSource for Crowded<'_> {
+    fn root(&self) -> &Path {
+        self.0.root()
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<SourceEntry>, dir_diff::Error> {
+        self.0.read_dir(path)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, dir_diff::Error> {
+        self.0.metadata(path)
+    }
+
+    fn open(&self, path: &Path) -> Result<Box<dyn Read + '_>, dir_diff::Error> {
+        let (open, most) = self.1;
+        let now = open.fetch_add(1, atomic::Ordering::SeqCst) + 1;
+        most.fetch_max(now, atomic::Ordering::SeqCst);
+        // Give the other comparison time to open its files too.
+        std::thread::sleep(Duration::from_millis(20));
+        Ok(Box::new(Opened(self.0.open(path)?, open)))
+    }
+}
+
+/// A file opened from a [`Crowded`] tree, counted as open until dropped.
+struct Opened<'r>(Box<dyn Read + 'r>, &'r AtomicU32);
+
+impl Read for Opened<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.0.read(buf)
+    }
+}
+
+impl Drop for Opened<'_> {
+    fn drop(&mut self) {
+        self.1.fetch_sub(1, atomic::Ordering::SeqCst);
+    }
+}
+
+#[test]
+fn max_open_files_shared() {
+    let counters = (AtomicU32::new(0), AtomicU32::new(0));
+    let files: &[(&str, &[u8])] = &[("a.txt", b"same"), ("b.txt", b"same"), ("c.txt", b"same")];
+    let options = DirDiffOptions::new().max_open_files(2);
+    std::thread::scope(|scope| {
+        for _ in 0..2 {
+            let options = options.clone();
+            let counters = &counters;
+            scope.spawn(move || {
+                let left = Crowded(MemorySource::new(files), counters);
+                let right = Crowded(MemorySource::new(files), counters);
+                assert!(!options.is_different_sources(&left, &right).unwrap());
+            });
+        }
+    });
+    assert_eq!(counters.1.load(atomic::Ordering::SeqCst), 2);
+}
+
+/// A [`MemorySource`] whose files time out the first few times they are opened.
+struct Flaky(MemorySource, AtomicU32);
+
+impl TreeSource for Flaky {
+    fn root(&self) -> &Path {
+        self.0.root()
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<SourceEntry>, dir_diff::Error> {
+        self.0.read_dir(path)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, dir_diff::Error> {
+        self.0.metadata(path)
+    }
+
+    fn open(&self, path: &Path) -> Result<Box<dyn Read + '_>, dir_diff::Error> {
+        let failures_left = self.1.load(atomic::Ordering::Relaxed);
+        if failures_left > 0 {
+            self.1.store(failures_left - 1, atomic::Ordering::Relaxed);
+            return Err(std::io::Error::from(std::io::ErrorKind::TimedOut).into());
+        }
+        self.0.open(path)
+    }
+}
+
+#[test]
+fn retry() {
+    let left = MemorySource::new(&[("a.txt", b"same")]);
+    let right = Flaky(MemorySource::new(&[("a.txt", b"same")]), AtomicU32::new(2));
+    let report = DirDiffOptions::new()
+        .retry(RetryPolicy::new(2).backoff(Duration::from_millis(1)))
+        .diff_sources(&left, &right)
+        .unwrap();
+    assert!(!report.is_different());
+    assert_eq!(report.stats().retries(), 2);
+
+    right.1.store(3, atomic::Ordering::Relaxed);
+    let err = DirDiffOptions::new()
+        .retry(RetryPolicy::new(2).backoff(Duration::from_millis(1)))
+        .diff_sources(&left, &right)
+        .unwrap_err();
+    assert_eq!(err.io_error().unwrap().kind(), std::io::ErrorKind::TimedOut);
+}
+
+/// A [`MemorySource`] whose files are all a FIFO on the local filesystem, which hangs when opened
+/// for reading as nothing writes to it.
+struct Hanging(MemorySource, PathBuf);
+
+impl TreeSource for Hanging {
+    fn root(&self) -> &Path {
+        self.0.root()
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<SourceEntry>, dir_diff::Error> {
+        self.0.read_dir(path)
+    }
+
+    fn metadata(&self, path: &Path) -> Result<Metadata, dir_diff::Error> {
+        self.0.metadata(path)
+    }
+
+    fn open(&self, path: &Path) -> Result<Box<dyn Read + '_>, dir_diff::Error> {
+        self.0.open(path)
+    }
+
+    fn local_path(&self, path: &Path) -> Option<PathBuf> {
+        (!path.as_os_str().is_empty()).then(|| self.1.clone())
+    }
+}
+
+#[test]
+#[cfg(unix)]
+fn read_timeout() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("read_timeout");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    let fifo = dir.join("fifo");
+    assert!(std::process::Command::new("mkfifo")
+        .arg(&fifo)
+        .status()
+        .unwrap()
+        .success());
+    let files: &[(&str, &[u8])] = &[("a.txt", b"same"), ("b.txt", b"same")];
+    let left = Hanging(MemorySource::new(files), fifo.clone());
+    let right = Hanging(MemorySource::new(files), fifo);
+
+    let report = DirDiffOptions::new()
+        .read_timeout(Duration::from_millis(50))
+        .diff_sources(&left, &right)
+        .unwrap();
+    let differences: Vec<_> = report
+        .iter()
+        .map(|d| (d.path().to_path_buf(), d.kind()))
+        .collect();
+    assert_eq!(
+        differences,
+        [
+            (PathBuf::from("a.txt"), DifferenceKind::Timeout),
+            (PathBuf::from("b.txt"), DifferenceKind::Timeout),
+        ]
+    );
+    assert!(report
+        .render(dir_diff::ColorChoice::Never)
+        .contains("! a.txt"));
+
+    let err = DirDiffOptions::new()
+        .deadline(Duration::from_millis(50))
+        .diff_sources(&left, &right)
+        .unwrap_err();
+    assert!(matches!(err, dir_diff::Error::DeadlineExceeded));
+}
+
+#[test]
+fn overlay() {
+    let base = MemorySource::new(&[
+        ("one.txt", b"old"),
+        ("subdir/two.txt", b"two"),
+        ("config", b"file"),
+    ]);
+    let patch = MemorySource::new(&[
+        ("one.txt", b"one"),
+        ("subdir/three.txt", b"three"),
+        ("config/app.toml", b"dir"),
+    ]);
+    let flattened = MemorySource::new(&[
+        ("one.txt", b"one"),
+        ("subdir/two.txt", b"two"),
+        ("subdir/three.txt", b"three"),
+        ("config/app.toml", b"dir"),
+    ]);
+    let layered = OverlaySource::new([&base, &patch]);
+    assert!(!DirDiffOptions::new()
+        .is_different_sources(&layered, &flattened)
+        .unwrap());
+
+    let reversed = OverlaySource::new([&patch, &base]);
+    let report = DirDiffOptions::new()
+        .diff_sources(&reversed, &flattened)
+        .unwrap();
+    let differences: Vec<_> = report
+        .differences()
+        .iter()
+        .map(|d| (d.path().to_path_buf(), d.kind()))
+        .collect();
+    assert_eq!(
+        differences,
+        [
+            (PathBuf::from("config"), DifferenceKind::FileType),
+            (PathBuf::from("one.txt"), DifferenceKind::Content),
+        ]
+    );
+}
+
+#[test]
+fn overlay_walk_threads() {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("overlay_walk_threads");
+    let _ = std::fs::remove_dir_all(&dir);
+    for (path, contents) in [
+        ("base/sub/a", "a"),
+        ("patch/sub/b", "b"),
+        ("flat/sub/a", "a"),
+        ("flat/sub/b", "b"),
+    ] {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+
+    let layered = OverlaySource::new([
+        FsSource::new(dir.join("base")),
+        FsSource::new(dir.join("patch")),
+    ]);
+    for threads in [1, 4] {
+        let report = DirDiffOptions::new()
+            .walk_threads(threads)
+            .diff_sources(&layered, FsSource::new(dir.join("flat")))
+            .unwrap();
+        assert!(!report.is_different(), "{threads} threads: {report:?}");
+    }
+}
+
+#[test]
+#[cfg(target_os = "linux")]
+fn dir_fd() {
+    use dir_diff::source::DirFdSource;
+
+    let source = DirFdSource::open("tests/binary/good/dir1").unwrap();
+    assert!(!DirDiffOptions::new()
+        .compare_permissions(true)
+        .compare_modified(true)
+        .is_different_sources(&source, FsSource::new("tests/binary/good/dir1"))
+        .unwrap());
+
+    let options = DirDiffOptions::new().fd_traversal(true);
+    assert!(!options
+        .is_different("tests/binary/good/dir1", "tests/binary/good/dir2")
+        .unwrap());
+    assert!(options
+        .is_different("tests/binary/bad/dir1", "tests/binary/bad/dir2")
+        .unwrap());
+    assert!(matches!(
+        options.diff("tests/binary/missing", "tests/binary/good/dir2"),
+        Err(dir_diff::Error::RootMissing { .. })
+    ));
+
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("dir_fd");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("tree")).unwrap();
+    std::fs::create_dir_all(dir.join("outside")).unwrap();
+    std::fs::write(dir.join("outside/secret"), "secret").unwrap();
+    std::os::unix::fs::symlink("../outside", dir.join("tree/sub")).unwrap();
+    let source = DirFdSource::open(dir.join("tree")).unwrap();
+    assert_eq!(
+        source.metadata(Path::new("sub")).unwrap().file_type(),
+        FileType::Symlink
+    );
+    assert!(source.read_dir(Path::new("sub")).is_err());
+    assert!(source.open(Path::new("sub/secret")).is_err());
+    assert!(source.open(Path::new("../outside/secret")).is_err());
+}
+
+#[test]
+fn memory_source() {
+    let left = dir_diff::source::MemorySource::new("left")
+        .file("a/b.txt", "same")
+        .file("c.txt", "left")
+        .dir("empty")
+        .symlink("link", "c.txt");
+    let right = dir_diff::source::MemorySource::new("right")
+        .file("a/b.txt", "same")
+        .file("c.txt", "rght")
+        .file("empty", "now a file")
+        .file("link", "not a link")
+        .remove("link");
+    assert_eq!(
+        left.paths().collect::<Vec<_>>(),
+        ["a", "a/b.txt", "c.txt", "empty", "link"].map(Path::new)
+    );
+    let report = DirDiffOptions::new().diff_sources(&left, &right).unwrap();
+    let differences: Vec<_> = report
+        .differences()
+        .iter()
+        .map(|d| (d.path().to_path_buf(), d.kind()))
+        .collect();
+    assert_eq!(
+        differences,
+        [
+            (PathBuf::from("c.txt"), DifferenceKind::Content),
+            (PathBuf::from("empty"), DifferenceKind::FileType),
+            (PathBuf::from("link"), DifferenceKind::OnlyInLeft),
+        ]
+    );
+    assert_eq!(
+        left.read_link(Path::new("link")).unwrap(),
+        Path::new("c.txt")
+    );
+}
@@ -0,0 +1,177 @@
+#![cfg(unix)]
+
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+
+fn fixture(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    for side in ["left", "right"] {
+        std::fs::create_dir_all(dir.join(side).join("real")).unwrap();
+        std::fs::write(dir.join(side).join("real/file"), "contents").unwrap();
+    }
+    dir
+}
+
+#[test]
+fn follows_links_into_the_tree() {
+    let dir = fixture("follows_links_into_the_tree");
+    symlink("real", dir.join("left/linked")).unwrap();
+    std::fs::create_dir_all(dir.join("right/linked")).unwrap();
+    std::fs::write(dir.join("right/linked/file"), "contents").unwrap();
+
+    let options = dir_diff::DirDiffOptions::new().follow_symlinks(true);
+    assert!(!options
+        .is_different(dir.join("left"), dir.join("right"))
+        .unwrap());
+
+    std::fs::write(dir.join("left/real/file"), "changed").unwrap();
+    let report = options.diff(dir.join("left"), dir.join("right")).unwrap();
+    let paths: Vec<_> = report.differences().iter().map(|d| d.path()).collect();
+    assert_eq!(paths, [Path::new("linked/file"), Path::new("real/file")]);
+}
+
+#[test]
+fn reports_escaping_links() {
+    let dir = fixture("reports_escaping_links");
+    for side in ["left", "right"] {
+        symlink("../../outside", dir.join(side).join("real/escape")).unwrap();
+    }
+
+    let options = dir_diff::DirDiffOptions::new().follow_symlinks(true);
+    let report = options.diff(dir.join("left"), dir.join("right")).unwrap();
+    assert_eq!(report.differences().len(), 1);
+    assert_eq!(report.differences()[0].path(), Path::new("real/escape"));
+    assert_eq!(
+        report.differences()[0].kind(),
+        dir_diff::DifferenceKind::SymlinkEscape
+    );
+
+    assert!(!dir_diff::is_different(dir.join("left"), dir.join("right")).unwrap());
+}
+
+#[test]
+fn errors_on_loops() {
+    let dir = fixture("errors_on_loops");
+    for side in ["left", "right"] {
+        symlink("..", dir.join(side).join("real/parent")).unwrap();
+    }
+
+    let options = dir_diff::DirDiffOptions::new().follow_symlinks(true);
+    match options.diff(dir.join("left"), dir.join("right")) {
+        Err(dir_diff::Error::SymlinkLoop { side, path }) => {
+            assert_eq!(side, dir_diff::Side::Left);
+            assert_eq!(path, dir.join("left/real/parent"));
+        }
+        other => panic!("expected a symlink loop, got {other:?}"),
+    }
+
+    let dir = fixture("errors_on_link_chains");
+    for side in ["left", "right"] {
+        symlink("b", dir.join(side).join("a")).unwrap();
+        symlink("a", dir.join(side).join("b")).unwrap();
+    }
+    assert!(matches!(
+        options.diff(dir.join("left"), dir.join("right")),
+        Err(dir_diff::Error::SymlinkLoop { .. })
+    ));
+}
+
+#[test]
+fn strict_rejects_dangling_links() {
+    let dir = fixture("strict_rejects_dangling_links");
+    for side in ["left", "right"] {
+        symlink("missing", dir.join(side).join("real/dangling")).unwrap();
+    }
+    assert!(!dir_diff::is_different(dir.join("left"), dir.join("right")).unwrap());
+
+    for follow in [false, true] {
+        let options = dir_diff::DirDiffOptions::new()
+            .follow_symlinks(follow)
+            .strict(true);
+        let err = options
+            .diff(dir.join("left"), dir.join("right"))
+            .unwrap_err();
+        assert!(matches!(
+            err,
+            dir_diff::Error::UnsupportedEntry {
+                side: dir_diff::Side::Left,
+                file_type: dir_diff::source::FileType::Symlink,
+                ..
+            }
+        ));
+        assert_eq!(err.path(), Some(dir.join("left/real/dangling").as_path()));
+
+        assert!(!options
+            .clone()
+            .exclude("**/dangling")
+            .is_different(dir.join("left"), dir.join("right"))
+            .unwrap());
+    }
+}
+
+#[test]
+fn strict_rejects_sockets() {
+    let dir = fixture("strict_rejects_sockets");
+    let _socket = std::os::unix::net::UnixListener::bind(dir.join("right/real/socket")).unwrap();
+    std::fs::write(dir.join("left/real/socket"), "").unwrap();
+    symlink("file", dir.join("left/real/link")).unwrap();
+    symlink("file", dir.join("right/real/link")).unwrap();
+
+    let report = dir_diff::diff(dir.join("left"), dir.join("right")).unwrap();
+    assert_eq!(
+        report.differences()[0].kind(),
+        dir_diff::DifferenceKind::FileType
+    );
+
+    let err = dir_diff::DirDiffOptions::new()
+        .strict(true)
+        .diff(dir.join("left"), dir.join("right"))
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Unsupported entry in the right tree at {}: not a file, directory or symlink",
+            dir.join("right/real/socket").display()
+        )
+    );
+}
+
+#[test]
+fn reports_broken_links() {
+    let dir = fixture("reports_broken_links");
+    symlink("missing", dir.join("left/real/broken")).unwrap();
+    std::fs::write(dir.join("right/real/broken"), "contents").unwrap();
+    symlink("missing", dir.join("left/real/both")).unwrap();
+    symlink("gone", dir.join("right/real/both")).unwrap();
+    symlink("nowhere", dir.join("left/real/dir")).unwrap();
+    std::fs::create_dir_all(dir.join("right/real/dir")).unwrap();
+    std::fs::write(dir.join("right/real/dir/inner"), "contents").unwrap();
+
+    let options = dir_diff::DirDiffOptions::new().follow_symlinks(true);
+    let report = options.diff(dir.join("left"), dir.join("right")).unwrap();
+    let differences: Vec<_> = report
+        .differences()
+        .iter()
+        .map(|d| (d.path().as_path(), d.kind()))
+        .collect();
+    let broken = dir_diff::DifferenceKind::BrokenSymlink {
+        side: dir_diff::Side::Left,
+    };
+    assert_eq!(
+        differences,
+        [
+            (Path::new("real/broken"), broken),
+            (Path::new("real/dir"), broken),
+        ]
+    );
+    assert_eq!(report.changed().count(), 2);
+
+    let report = options.diff(dir.join("right"), dir.join("left")).unwrap();
+    assert_eq!(
+        report.differences()[0].kind(),
+        dir_diff::DifferenceKind::BrokenSymlink {
+            side: dir_diff::Side::Right
+        }
+    );
+}
@@ -0,0 +1,61 @@
+#![cfg(any(feature = "tempfile", feature = "assert_fs"))]
+
+use std::path::Path;
+
+#[test]
+#[cfg(feature = "tempfile")]
+fn diff_temp_tempfile() {
+    let temp = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(temp.path().join("out/sub")).unwrap();
+    std::fs::write(temp.path().join("out/sub/a.txt"), "a").unwrap();
+    std::fs::write(temp.path().join("out/b.txt"), "b").unwrap();
+
+    // Relative paths are taken from the temporary directory.
+    let err = dir_diff::diff_temp(&temp, "out", "tests/easy/good/dir1").unwrap_err();
+    assert!(matches!(
+        err,
+        dir_diff::Error::RootMissing { ref path, .. } if path == Path::new("tests/easy/good/dir1")
+    ));
+
+    let golden = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/easy/good/dir1");
+    let report = dir_diff::diff_temp(&temp, "out", &golden).unwrap();
+    assert_eq!(report.left(), Path::new("out"));
+    assert_eq!(report.right(), golden);
+    assert!(report
+        .to_string()
+        .starts_with(&format!("out (-) vs {} (+)\n", golden.display())));
+    assert_eq!(
+        report.resolve(
+            &report.differences()[0].path().clone(),
+            dir_diff::Side::Left
+        ),
+        Path::new("out/b.txt")
+    );
+
+    std::fs::create_dir_all(temp.path().join("expected/sub")).unwrap();
+    std::fs::write(temp.path().join("expected/sub/a.txt"), "a").unwrap();
+    std::fs::write(temp.path().join("expected/b.txt"), "b").unwrap();
+    assert!(!dir_diff::DirDiffOptions::new()
+        .is_different_temp(&temp, "out", temp.path().join("expected"))
+        .unwrap());
+}
+
+#[test]
+#[cfg(feature = "assert_fs")]
+fn diff_temp_assert_fs() {
+    use assert_fs::prelude::*;
+
+    let temp = assert_fs::TempDir::new().unwrap();
+    temp.child("out/a.txt").write_str("a").unwrap();
+    temp.child("expected/a.txt").write_str("b").unwrap();
+
+    let report = dir_diff::diff_temp(&temp, temp.child("out"), "expected").unwrap();
+    assert_eq!(report.left(), Path::new("out"));
+    assert_eq!(report.right(), Path::new("expected"));
+    assert_eq!(report.differences().len(), 1);
+
+    match dir_diff::diff_temp(&temp, "out", "missing") {
+        Err(dir_diff::Error::RootMissing { path, .. }) => assert_eq!(path, Path::new("missing")),
+        other => panic!("unexpected result: {other:?}"),
+    }
+}
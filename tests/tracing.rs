@@ -0,0 +1,95 @@
+#![cfg(feature = "tracing")]
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Metadata, Subscriber};
+
+/// Collects the message of every event, and the names of spans.
+#[derive(Default)]
+struct Collect {
+    events: Arc<Mutex<Vec<String>>>,
+    spans: Arc<Mutex<Vec<String>>>,
+    next_id: AtomicU64,
+}
+
+struct Message<'a>(&'a mut String);
+
+impl Visit for Message<'_> {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            *self.0 = format!("{value:?}");
+        }
+    }
+}
+
+impl Subscriber for Collect {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, span: &Attributes<'_>) -> Id {
+        self.spans
+            .lock()
+            .unwrap()
+            .push(span.metadata().name().to_owned());
+        Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed) + 1)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut message = String::new();
+        event.record(&mut Message(&mut message));
+        self.events.lock().unwrap().push(message);
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+#[test]
+fn events() {
+    let collect = Collect::default();
+    let events = collect.events.clone();
+    let spans = collect.spans.clone();
+    tracing::subscriber::with_default(collect, || {
+        let report = dir_diff::diff("tests/easy/good/dir1", "tests/easy/good/dir2").unwrap();
+        assert!(report.is_empty());
+    });
+
+    let events = events.lock().unwrap();
+    for message in [
+        "visited entry",
+        "compared file",
+        "reading files",
+        "finished walking",
+        "compared trees",
+    ] {
+        assert!(events.iter().any(|event| event == message), "{message}");
+    }
+    assert_eq!(*spans.lock().unwrap(), ["diff"]);
+}
+
+#[test]
+fn skipped() {
+    let collect = Collect::default();
+    let events = collect.events.clone();
+    tracing::subscriber::with_default(collect, || {
+        dir_diff::DirDiffOptions::new()
+            .exclude("test.txt")
+            .diff("tests/easy/bad/dir1", "tests/easy/bad/dir2")
+            .unwrap();
+    });
+
+    assert!(events
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|event| event == "skipped entry"));
+}
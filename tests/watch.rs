@@ -0,0 +1,50 @@
+#![cfg(feature = "watch")]
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+fn fixture(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    for side in ["left", "right"] {
+        std::fs::create_dir_all(dir.join(side).join("sub")).unwrap();
+        std::fs::write(dir.join(side).join("sub/file.txt"), "same").unwrap();
+    }
+    dir
+}
+
+fn paths(report: &dir_diff::DiffReport) -> Vec<PathBuf> {
+    report
+        .differences()
+        .iter()
+        .map(|d| d.path().to_path_buf())
+        .collect()
+}
+
+#[test]
+fn rediffs_on_change() {
+    let dir = fixture("rediffs_on_change");
+    let (sender, receiver) = mpsc::channel();
+    let _watch = dir_diff::watch(dir.join("left"), dir.join("right"), move |report| {
+        let _ = sender.send(paths(report.unwrap()));
+    })
+    .unwrap();
+    let timeout = Duration::from_secs(10);
+    assert_eq!(
+        receiver.recv_timeout(timeout).unwrap(),
+        Vec::<PathBuf>::new()
+    );
+
+    std::fs::write(dir.join("left/sub/file.txt"), "changed").unwrap();
+    let mut differences = receiver.recv_timeout(timeout).unwrap();
+    while differences.is_empty() {
+        differences = receiver.recv_timeout(timeout).unwrap();
+    }
+    assert_eq!(differences, [Path::new("sub/file.txt")]);
+
+    std::fs::write(dir.join("right/sub/file.txt"), "changed").unwrap();
+    while !differences.is_empty() {
+        differences = receiver.recv_timeout(timeout).unwrap();
+    }
+}
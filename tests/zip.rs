@@ -0,0 +1,102 @@
+#![cfg(feature = "zip")]
+
+use std::fs::create_dir_all;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+use dir_diff::source::{FsSource, ZipSource};
+use dir_diff::{DifferenceKind, DirDiffOptions};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, DateTime, ZipWriter};
+
+/// Write a jar with `class` as its one class, with every member stamped `second` seconds past
+/// midnight.
+fn write_jar(path: &Path, class: &[u8], second: u8) {
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .last_modified_time(DateTime::from_date_and_time(2024, 1, 1, 0, 0, second).unwrap());
+    let mut jar = ZipWriter::new(std::fs::File::create(path).unwrap());
+    jar.add_directory("META-INF/", options).unwrap();
+    jar.start_file("META-INF/MANIFEST.MF", options).unwrap();
+    jar.write_all(b"Manifest-Version: 1.0\n").unwrap();
+    jar.start_file("com/example/Main.class", options).unwrap();
+    jar.write_all(class).unwrap();
+    jar.finish().unwrap();
+}
+
+fn dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    create_dir_all(dir.join("left")).unwrap();
+    create_dir_all(dir.join("right")).unwrap();
+    dir
+}
+
+#[test]
+fn compare_archives() {
+    let dir = dir("compare_archives");
+    write_jar(&dir.join("left/app.jar"), b"\xca\xfe\xba\xbe", 0);
+    write_jar(&dir.join("right/app.jar"), b"\xca\xfe\xba\xbe", 30);
+    write_jar(&dir.join("left/lib.jar"), b"\xca\xfe\xba\xbe", 0);
+    write_jar(&dir.join("right/lib.jar"), b"\xca\xfe\xba\xbf", 0);
+
+    let report = DirDiffOptions::new()
+        .diff(dir.join("left"), dir.join("right"))
+        .unwrap();
+    assert_eq!(report.differences().len(), 2);
+
+    let report = DirDiffOptions::new()
+        .compare_archives("*.jar")
+        .diff(dir.join("left"), dir.join("right"))
+        .unwrap();
+    let differences: Vec<_> = report
+        .iter()
+        .map(|d| (d.path().to_path_buf(), d.kind()))
+        .collect();
+    assert_eq!(
+        differences,
+        [(PathBuf::from("lib.jar"), DifferenceKind::Content)]
+    );
+}
+
+#[test]
+fn not_an_archive() {
+    let dir = dir("not_an_archive");
+    std::fs::write(dir.join("left/app.jar"), "not a zip").unwrap();
+    std::fs::write(dir.join("right/app.jar"), "not a zip either").unwrap();
+    assert!(DirDiffOptions::new()
+        .compare_archives("*.jar")
+        .is_different(dir.join("left"), dir.join("right"))
+        .unwrap());
+}
+
+#[test]
+fn zip_source() {
+    let dir = dir("zip_source");
+    write_jar(&dir.join("left/app.jar"), b"\xca\xfe\xba\xbe", 0);
+    write_jar(&dir.join("right/app.jar"), b"\xca\xfe\xba\xbe", 30);
+    let left = ZipSource::open(dir.join("left/app.jar")).unwrap();
+    let right = ZipSource::open(dir.join("right/app.jar")).unwrap();
+    assert!(!DirDiffOptions::new()
+        .is_different_sources(&left, &right)
+        .unwrap());
+
+    let extracted = dir.join("extracted");
+    create_dir_all(extracted.join("META-INF")).unwrap();
+    create_dir_all(extracted.join("com/example")).unwrap();
+    std::fs::write(
+        extracted.join("META-INF/MANIFEST.MF"),
+        "Manifest-Version: 1.0\n",
+    )
+    .unwrap();
+    std::fs::write(
+        extracted.join("com/example/Main.class"),
+        b"\xca\xfe\xba\xbe",
+    )
+    .unwrap();
+    assert!(!DirDiffOptions::new()
+        .is_different_sources(&left, FsSource::new(extracted))
+        .unwrap());
+
+    assert!(ZipSource::open(dir.join("left/missing.jar")).is_err());
+}